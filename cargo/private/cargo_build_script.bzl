@@ -357,6 +357,9 @@ def _cargo_build_script_impl(ctx):
     flags_out = ctx.actions.declare_file(ctx.label.name + ".flags")
     link_flags = ctx.actions.declare_file(ctx.label.name + ".linkflags")
     link_search_paths = ctx.actions.declare_file(ctx.label.name + ".linksearchpaths")  # rustc-link-search, propagated from transitive dependencies
+    cdylib_link_flags = ctx.actions.declare_file(ctx.label.name + ".cdylib_linkflags")  # rustc-cdylib-link-arg
+    bin_link_flags = ctx.actions.declare_file(ctx.label.name + ".bin_linkflags")  # rustc-link-arg-bin and rustc-link-arg-bins
+    rerun_manifest = ctx.actions.declare_file(ctx.label.name + ".rerun_manifest")  # rerun-if-changed and rerun-if-env-changed inputs
     compilation_mode_opt_level = get_compilation_mode_opts(ctx, toolchain).opt_level
 
     script_tools = []
@@ -383,14 +386,16 @@ def _cargo_build_script_impl(ctx):
     incompatible_runfiles_cargo_manifest_dir = ctx.attr._incompatible_runfiles_cargo_manifest_dir[BuildSettingInfo].value
     if not incompatible_runfiles_cargo_manifest_dir:
         script_data.append(ctx.attr.script[DefaultInfo].default_runfiles.files)
-        manifest_dir = "{}.runfiles/{}/{}".format(script.path, workspace_name, ctx.label.package)
+        runfiles_root = "{}.runfiles".format(script.path)
+        manifest_dir = "{}/{}/{}".format(runfiles_root, workspace_name, ctx.label.package)
     else:
         runfiles_dir, runfiles_inputs, runfiles_args = _create_runfiles_dir(
             ctx = ctx,
             script = ctx.attr.script,
             retain_list = ctx.attr._cargo_manifest_dir_filename_suffixes_to_retain[BuildSettingInfo].value,
         )
-        manifest_dir = "{}/{}/{}".format(runfiles_dir.path, workspace_name, ctx.label.package)
+        runfiles_root = runfiles_dir.path
+        manifest_dir = "{}/{}/{}".format(runfiles_root, workspace_name, ctx.label.package)
         extra_args.append(runfiles_args)
         extra_inputs.append(runfiles_inputs)
         extra_output = [runfiles_dir]
@@ -582,11 +587,15 @@ def _cargo_build_script_impl(ctx):
     args.add(flags_out, format = "--flags_out=%s")
     args.add(link_flags, format = "--link_flags=%s")
     args.add(link_search_paths, format = "--link_search_paths=%s")
+    args.add(cdylib_link_flags, format = "--cdylib_link_flags=%s")
+    args.add(bin_link_flags, format = "--bin_link_flags=%s")
     args.add(dep_env_out, format = "--dep_env_out=%s")
+    args.add(rerun_manifest, format = "--rerun_manifest_out=%s")
     args.add(ctx.attr.rundir, format = "--rundir=%s")
 
     output_groups = {
         "out_dir": depset([out_dir]),
+        "rerun_manifest": depset([rerun_manifest]),
     }
 
     debug_std_streams_output_group = ctx.attr._debug_std_streams_output_group[BuildSettingInfo].value
@@ -633,6 +642,47 @@ def _cargo_build_script_impl(ctx):
     if experimental_symlink_execroot:
         env["RULES_RUST_SYMLINK_EXEC_ROOT"] = "1"
 
+    execution_requirements = {}
+    build_script_cache_dir = ctx.attr._experimental_build_script_cache_dir[BuildSettingInfo].value
+    if build_script_cache_dir:
+        if not paths.is_absolute(build_script_cache_dir):
+            fail("//cargo/settings:experimental_build_script_cache_dir must be an absolute path, got {}".format(build_script_cache_dir))
+        args.add(build_script_cache_dir, format = "--rerun_cache_dir=%s")
+
+        # Earlier runs are only available on the machine which stored them.
+        execution_requirements["no-remote-exec"] = "1"
+
+        # A reused run is only as fresh as the inputs the script declared, so its
+        # outputs must not be shared through the disk or remote cache.
+        execution_requirements["no-cache"] = "1"
+
+    # Outputs of the action which are not passed on to the crate.
+    build_script_outputs = []
+    build_script_audit = ctx.attr._build_script_audit[BuildSettingInfo].value
+    if build_script_audit != "off":
+        # Everything in the runfiles of the script is an input of the action.
+        audit_inputs = ctx.actions.args()
+        audit_inputs.set_param_file_format("multiline")
+        audit_inputs.add(runfiles_root, format = "file\t%s")
+        audit_inputs.add_all(
+            depset(build_script_inputs, transitive = extra_inputs + [tools]),
+            format_each = "file\t%s",
+            expand_directories = False,
+        )
+        audit_inputs.add_all(sorted(env.keys()), format_each = "env\t%s")
+        audit_declared_inputs = ctx.actions.declare_file(ctx.label.name + ".audit_inputs")
+        ctx.actions.write(
+            output = audit_declared_inputs,
+            content = audit_inputs,
+        )
+        audit_report = ctx.actions.declare_file(ctx.label.name + ".audit_report")
+        args.add(build_script_audit, format = "--audit=%s")
+        args.add(audit_declared_inputs, format = "--audit_declared_inputs=%s")
+        args.add(audit_report, format = "--audit_report=%s")
+        build_script_inputs.append(audit_declared_inputs)
+        build_script_outputs.append(audit_report)
+        output_groups["build_script_audit"] = depset([audit_report])
+
     ctx.actions.run(
         executable = ctx.executable._cargo_build_script_runner,
         arguments = [args] + extra_args,
@@ -642,13 +692,17 @@ def _cargo_build_script_impl(ctx):
             flags_out,
             link_flags,
             link_search_paths,
+            cdylib_link_flags,
+            bin_link_flags,
             dep_env_out,
-        ] + extra_output,
+            rerun_manifest,
+        ] + extra_output + build_script_outputs,
         tools = tools,
         inputs = depset(build_script_inputs, transitive = extra_inputs),
         mnemonic = "CargoBuildScriptRun",
         progress_message = "Running Cargo build script {}".format(pkg_name),
         env = env,
+        execution_requirements = execution_requirements,
         toolchain = None,
         use_default_shell_env = use_default_shell_env,
     )
@@ -659,6 +713,8 @@ def _cargo_build_script_impl(ctx):
         # since bazel is lazy.
         DefaultInfo(files = depset([out_dir])),
         BuildInfo(
+            bin_linker_flags = bin_link_flags,
+            cdylib_linker_flags = cdylib_link_flags,
             out_dir = out_dir,
             rustc_env = env_out,
             dep_env = dep_env_out,
@@ -778,12 +834,18 @@ cargo_build_script = rule(
         "_cargo_manifest_dir_filename_suffixes_to_retain": attr.label(
             default = Label("//cargo/settings:cargo_manifest_dir_filename_suffixes_to_retain"),
         ),
+        "_build_script_audit": attr.label(
+            default = Label("//cargo/settings:build_script_audit"),
+        ),
         "_debug_std_streams_output_group": attr.label(
             default = Label("//cargo/settings:debug_std_streams_output_group"),
         ),
         "_default_use_default_shell_env": attr.label(
             default = Label("//cargo/settings:use_default_shell_env"),
         ),
+        "_experimental_build_script_cache_dir": attr.label(
+            default = Label("//cargo/settings:experimental_build_script_cache_dir"),
+        ),
         "_experimental_symlink_execroot": attr.label(
             default = Label("//cargo/settings:experimental_symlink_execroot"),
         ),
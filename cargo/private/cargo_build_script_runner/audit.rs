@@ -0,0 +1,304 @@
+//! An opt-in audit of the inputs a build script accesses.
+//!
+//! Build scripts which read files or environment variables Bazel doesn't know
+//! about are not hermetic, which usually shows up as remote cache misses or
+//! flaky builds. Without tracing the script, the runner can't observe every
+//! read it makes, so the audit relies on two signals:
+//!
+//! - The `cargo::rerun-if-changed` and `cargo::rerun-if-env-changed`
+//!   directives, which list what the script itself reports depending on. These
+//!   are compared against the inputs and environment of the Bazel action.
+//! - A snapshot of `CARGO_MANIFEST_DIR` taken before and after the script ran,
+//!   to catch scripts writing anywhere other than `OUT_DIR`.
+//!
+//! Reads the script doesn't declare, and writes outside of
+//! `CARGO_MANIFEST_DIR`, are not detected.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::BuildScriptOutput;
+
+/// What to do when the audit finds violations.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AuditMode {
+    /// Report violations as warnings.
+    Warn,
+    /// Fail the build script action.
+    Error,
+}
+
+impl AuditMode {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "warn" => Ok(AuditMode::Warn),
+            "error" => Ok(AuditMode::Error),
+            _ => Err(format!("Unknown build script audit mode: {:?}", value)),
+        }
+    }
+}
+
+/// A hermeticity problem found by the audit.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Violation {
+    /// A `rerun-if-changed` path within the exec root which isn't an input of the action.
+    UndeclaredFile(String),
+    /// A `rerun-if-changed` path outside of the exec root.
+    FileOutsideExecRoot(String),
+    /// A `rerun-if-env-changed` variable which isn't in the environment of the action.
+    UndeclaredEnv(String),
+    /// A file the build script created or modified outside of `OUT_DIR`.
+    WriteOutsideOutDir(String),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::UndeclaredFile(path) => write!(
+                f,
+                "depends on `{}`, which is not in `data` or `compile_data`",
+                path
+            ),
+            Violation::FileOutsideExecRoot(path) => {
+                write!(
+                    f,
+                    "depends on `{}`, which is outside of the exec root",
+                    path
+                )
+            }
+            Violation::UndeclaredEnv(name) => write!(
+                f,
+                "depends on the environment variable `{}`, which is not in the environment of the action",
+                name
+            ),
+            Violation::WriteOutsideOutDir(path) => {
+                write!(f, "wrote to `{}`, which is outside of `OUT_DIR`", path)
+            }
+        }
+    }
+}
+
+/// The inputs and environment variables of a build script action.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DeclaredInputs {
+    /// Paths relative to the exec root. Directories cover everything within them.
+    pub paths: Vec<PathBuf>,
+    pub env: BTreeSet<String>,
+}
+
+impl DeclaredInputs {
+    /// Parses `file\tPATH` and `env\tNAME` lines, as written by `cargo_build_script`.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut declared = DeclaredInputs::default();
+        for line in contents.lines().filter(|line| !line.is_empty()) {
+            match line.split_once('\t') {
+                Some(("file", path)) => declared.paths.push(PathBuf::from(path)),
+                Some(("env", name)) => {
+                    declared.env.insert(name.to_owned());
+                }
+                _ => return Err(format!("Malformed declared inputs line: {:?}", line)),
+            }
+        }
+        Ok(declared)
+    }
+
+    fn contains_path(&self, path: &Path) -> bool {
+        self.paths.iter().any(|declared| path.starts_with(declared))
+    }
+
+    /// Checks the `rerun-if-*` directives of `outputs` against the declared inputs.
+    ///
+    /// `is_in_env` reports whether a variable is in the environment the build
+    /// script ran with. This covers variables set by the runner, e.g. `OUT_DIR`
+    /// or `CARGO_CFG_*`, and those inherited from the action, e.g. `PATH` with
+    /// `use_default_shell_env`. Bazel only passes the environment of the action
+    /// to the runner, so these are all part of the action key.
+    pub fn check(
+        &self,
+        outputs: &[BuildScriptOutput],
+        manifest_dir: &Path,
+        exec_root: &Path,
+        is_in_env: impl Fn(&str) -> bool,
+    ) -> Vec<Violation> {
+        let mut violations = BTreeSet::new();
+        for output in outputs {
+            match output {
+                BuildScriptOutput::RerunIfChanged(path) => {
+                    let resolved = normalize(&manifest_dir.join(path));
+                    match resolved.strip_prefix(exec_root) {
+                        Ok(relative) if self.contains_path(relative) => {}
+                        Ok(relative) => {
+                            violations.insert(Violation::UndeclaredFile(
+                                relative.to_string_lossy().into_owned(),
+                            ));
+                        }
+                        Err(_) => {
+                            violations.insert(Violation::FileOutsideExecRoot(
+                                resolved.to_string_lossy().into_owned(),
+                            ));
+                        }
+                    }
+                }
+                BuildScriptOutput::RerunIfEnvChanged(name)
+                    if !self.env.contains(name) && !is_in_env(name) =>
+                {
+                    violations.insert(Violation::UndeclaredEnv(name.clone()));
+                }
+                _ => {}
+            }
+        }
+        violations.into_iter().collect()
+    }
+}
+
+/// Lexically resolves `.` and `..` components, without following symlinks.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The size and modification time of every file within a directory.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FileSnapshot {
+    files: BTreeMap<PathBuf, (u64, Option<SystemTime>)>,
+}
+
+impl FileSnapshot {
+    /// Records the files within `dir`.
+    ///
+    /// Symlinked files are followed so that writes to sources through the
+    /// sandbox are caught, but symlinked directories aren't descended into.
+    pub fn take(dir: &Path) -> Self {
+        fn visit(dir: &Path, files: &mut BTreeMap<PathBuf, (u64, Option<SystemTime>)>) {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => return,
+            };
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let path = entry.path();
+                let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                if is_dir {
+                    visit(&path, files);
+                } else if let Ok(metadata) = fs::metadata(&path) {
+                    if metadata.is_file() {
+                        files.insert(path, (metadata.len(), metadata.modified().ok()));
+                    }
+                }
+            }
+        }
+
+        let mut files = BTreeMap::new();
+        visit(dir, &mut files);
+        FileSnapshot { files }
+    }
+
+    /// Returns the files which were created or modified in `after`.
+    pub fn changed_files<'a>(&self, after: &'a FileSnapshot) -> Vec<&'a Path> {
+        after
+            .files
+            .iter()
+            .filter(|(path, state)| self.files.get(*path) != Some(state))
+            .map(|(path, _)| path.as_path())
+            .collect()
+    }
+}
+
+/// Formats violations as `KIND\tVALUE` lines for the audit report.
+pub fn format_report(violations: &[Violation]) -> String {
+    violations
+        .iter()
+        .map(|violation| match violation {
+            Violation::UndeclaredFile(path) => format!("undeclared-file\t{}", path),
+            Violation::FileOutsideExecRoot(path) => format!("outside-exec-root\t{}", path),
+            Violation::UndeclaredEnv(name) => format!("undeclared-env\t{}", name),
+            Violation::WriteOutsideOutDir(path) => format!("write-outside-out-dir\t{}", path),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_rerun_directives() {
+        let declared = DeclaredInputs::parse(
+            "file\texternal/foo/build.rs\n\
+             file\tbazel-out/k8-fastbuild/bin/external/foo/_bs.runfiles\n\
+             env\tOPENSSL_DIR\n",
+        )
+        .unwrap();
+        assert_eq!(declared.paths.len(), 2);
+        assert!(DeclaredInputs::parse("external/foo/build.rs").is_err());
+
+        let exec_root = Path::new("/execroot/_main");
+        let manifest_dir =
+            exec_root.join("bazel-out/k8-fastbuild/bin/external/foo/_bs.runfiles/foo");
+        let outputs = [
+            BuildScriptOutput::RerunIfChanged("build.rs".to_owned()),
+            BuildScriptOutput::RerunIfChanged(
+                "../../../../../../../external/foo/build.rs".to_owned(),
+            ),
+            BuildScriptOutput::RerunIfChanged("../../../../../../../external/bar/lib.h".to_owned()),
+            BuildScriptOutput::RerunIfChanged("/usr/include/openssl/ssl.h".to_owned()),
+            BuildScriptOutput::RerunIfEnvChanged("OPENSSL_DIR".to_owned()),
+            BuildScriptOutput::RerunIfEnvChanged("OUT_DIR".to_owned()),
+            BuildScriptOutput::RerunIfEnvChanged("PATH".to_owned()),
+            BuildScriptOutput::RerunIfEnvChanged("OPENSSL_STATIC".to_owned()),
+            BuildScriptOutput::Cfg("ossl300".to_owned()),
+        ];
+        assert_eq!(
+            declared.check(&outputs, &manifest_dir, exec_root, |name| {
+                ["OUT_DIR", "PATH"].contains(&name)
+            }),
+            vec![
+                Violation::UndeclaredFile("external/bar/lib.h".to_owned()),
+                Violation::FileOutsideExecRoot("/usr/include/openssl/ssl.h".to_owned()),
+                Violation::UndeclaredEnv("OPENSSL_STATIC".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_changed_files() {
+        let dir = PathBuf::from(std::env::var("TEST_TMPDIR").unwrap()).join("audit_snapshot");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/lib.rs"), "").unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]").unwrap();
+
+        let before = FileSnapshot::take(&dir);
+        fs::write(dir.join("src/generated.rs"), "pub fn f() {}").unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]\nname = \"foo\"").unwrap();
+        let after = FileSnapshot::take(&dir);
+
+        assert_eq!(
+            before.changed_files(&after),
+            vec![dir.join("Cargo.toml"), dir.join("src/generated.rs")]
+        );
+        assert!(after.changed_files(&FileSnapshot::take(&dir)).is_empty());
+    }
+
+    #[test]
+    fn report_format() {
+        assert_eq!(
+            format_report(&[
+                Violation::UndeclaredEnv("CC_FOO".to_owned()),
+                Violation::WriteOutsideOutDir("external/foo/src/gen.rs".to_owned()),
+            ]),
+            "undeclared-env\tCC_FOO\nwrite-outside-out-dir\texternal/foo/src/gen.rs"
+        );
+    }
+}
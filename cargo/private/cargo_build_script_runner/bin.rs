@@ -21,7 +21,11 @@ use std::fs::{create_dir_all, read_to_string, write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use cargo_build_script_runner::audit::{
+    format_report, AuditMode, DeclaredInputs, FileSnapshot, Violation,
+};
 use cargo_build_script_runner::cargo_manifest_dir::{remove_symlink, symlink, RunfilesMaker};
+use cargo_build_script_runner::rerun_manifest::{RerunCache, RerunManifest};
 use cargo_build_script_runner::{BuildScriptOutput, CompileAndLinkFlags};
 
 fn run_buildrs() -> Result<(), String> {
@@ -43,13 +47,18 @@ fn run_buildrs() -> Result<(), String> {
         compile_flags_file,
         link_flags_file,
         link_search_paths_file,
+        cdylib_link_flags_file,
+        bin_link_flags_file,
         output_dep_env_path,
         stdout_path,
         stderr_path,
+        rerun_manifest_path,
+        rerun_cache_dir,
+        audit,
         rundir,
         input_dep_env_paths,
         cargo_manifest_maker,
-    } = Args::parse();
+    } = Args::parse()?;
 
     if let Some(cargo_manifest_maker) = &cargo_manifest_maker {
         cargo_manifest_maker.create_runfiles_dir().unwrap()
@@ -89,12 +98,13 @@ fn run_buildrs() -> Result<(), String> {
 
     let working_directory = resolve_rundir(&rundir, &exec_root, &manifest_dir)?;
 
-    let mut command = Command::new(exec_root.join(progname));
+    let script = exec_root.join(progname);
+    let mut command = Command::new(&script);
     command
         .current_dir(&working_directory)
         .envs(target_env_vars)
         .env("OUT_DIR", &out_dir_abs)
-        .env("CARGO_MANIFEST_DIR", manifest_dir)
+        .env("CARGO_MANIFEST_DIR", &manifest_dir)
         .env("RUSTC", rustc)
         .env("RUST_BACKTRACE", "full");
 
@@ -160,21 +170,94 @@ fn run_buildrs() -> Result<(), String> {
         );
     }
 
-    let (buildrs_outputs, process_output) = BuildScriptOutput::outputs_from_command(&mut command)
-        .map_err(|process_output| {
-        format!(
-            "Build script process failed{}\n--stdout:\n{}\n--stderr:\n{}",
-            if let Some(exit_code) = process_output.status.code() {
-                format!(" with exit code {exit_code}")
-            } else {
-                String::new()
-            },
-            String::from_utf8(process_output.stdout)
-                .expect("Failed to parse stdout of child process"),
-            String::from_utf8(process_output.stderr)
-                .expect("Failed to parse stdout of child process"),
-        )
-    })?;
+    // The audit needs to observe the build script, so it always runs when auditing.
+    let rerun_cache = match (&rerun_cache_dir, &audit) {
+        (Some(dir), None) => RerunCache::key(&script, &command_envs(&command), &exec_root)
+            .map(|key| RerunCache::new(Path::new(dir), &key)),
+        _ => None,
+    };
+    let cached_run = rerun_cache.as_ref().and_then(|cache| {
+        cache.restore(&out_dir_abs, &exec_root, |name| {
+            command_env_var(&command, name)
+        })
+    });
+    let ran_script = cached_run.is_none();
+
+    let audit_snapshot = audit.as_ref().map(|_| FileSnapshot::take(&manifest_dir));
+
+    let (buildrs_outputs, stdout, stderr) = match cached_run {
+        Some(run) => (
+            BuildScriptOutput::outputs_from_stdout(&run.stdout),
+            run.stdout,
+            run.stderr,
+        ),
+        None => {
+            let (buildrs_outputs, process_output) = BuildScriptOutput::outputs_from_command(
+                &mut command,
+            )
+            .map_err(|process_output| {
+                format!(
+                    "Build script process failed{}\n--stdout:\n{}\n--stderr:\n{}",
+                    if let Some(exit_code) = process_output.status.code() {
+                        format!(" with exit code {exit_code}")
+                    } else {
+                        String::new()
+                    },
+                    String::from_utf8(process_output.stdout)
+                        .expect("Failed to parse stdout of child process"),
+                    String::from_utf8(process_output.stderr)
+                        .expect("Failed to parse stdout of child process"),
+                )
+            })?;
+            (
+                buildrs_outputs,
+                process_output.stdout,
+                process_output.stderr,
+            )
+        }
+    };
+
+    if let (Some(audit), Some(snapshot)) = (&audit, &audit_snapshot) {
+        let declared = read_to_string(&audit.declared_inputs_path)
+            .map_err(|e| format!("Failed to read {:?}: {:?}", audit.declared_inputs_path, e))
+            .and_then(|contents| DeclaredInputs::parse(&contents))?;
+        let mut violations = declared.check(&buildrs_outputs, &manifest_dir, &exec_root, |name| {
+            command_env_var(&command, name).is_some()
+        });
+        violations.extend(
+            snapshot
+                .changed_files(&FileSnapshot::take(&manifest_dir))
+                .into_iter()
+                .map(|path| {
+                    Violation::WriteOutsideOutDir(
+                        path.strip_prefix(&exec_root)
+                            .unwrap_or(path)
+                            .to_string_lossy()
+                            .into_owned(),
+                    )
+                }),
+        );
+
+        write(&audit.report_path, format_report(&violations))
+            .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", audit.report_path, e));
+        if !violations.is_empty() {
+            let message = format!(
+                "Build script of {} is not hermetic:\n{}\n\
+                 Note: only `rerun-if-*` directives and writes to CARGO_MANIFEST_DIR are audited, \
+                 not every file the build script reads.",
+                env::var("CARGO_PKG_NAME").unwrap_or_default(),
+                violations
+                    .iter()
+                    .map(|violation| format!("  - {violation}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+            match audit.mode {
+                AuditMode::Warn => eprintln!("Warning: {message}"),
+                AuditMode::Error => return Err(format!("error: {message}")),
+            }
+        }
+    }
 
     write(
         &env_file,
@@ -194,18 +277,35 @@ fn run_buildrs() -> Result<(), String> {
     .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", output_dep_env_path, e));
 
     if let Some(path) = &stdout_path {
-        write(path, process_output.stdout)
+        write(path, &stdout)
             .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", path, e));
     }
     if let Some(path) = &stderr_path {
-        write(path, process_output.stderr)
+        write(path, &stderr)
+            .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", path, e));
+    }
+
+    let manifest =
+        RerunManifest::from_outputs(&buildrs_outputs, &manifest_dir, &exec_root, |name| {
+            command_env_var(&command, name)
+        });
+    if let Some(path) = &rerun_manifest_path {
+        write(path, manifest.to_string())
             .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", path, e));
     }
+    if let (Some(cache), true) = (&rerun_cache, ran_script) {
+        // The cache only saves time, so failing to update it isn't an error.
+        if let Err(e) = cache.store(&manifest, &stdout, &stderr, &out_dir_abs, &exec_root) {
+            eprintln!("Warning: failed to cache build script outputs: {e}");
+        }
+    }
 
     let CompileAndLinkFlags {
         compile_flags,
         link_flags,
         link_search_paths,
+        cdylib_link_flags,
+        bin_link_flags,
     } = BuildScriptOutput::outputs_to_flags(&buildrs_outputs, &exec_root.to_string_lossy());
 
     write(&compile_flags_file, compile_flags.as_bytes())
@@ -218,6 +318,14 @@ fn run_buildrs() -> Result<(), String> {
             link_search_paths_file, e
         )
     });
+    if let Some(path) = &cdylib_link_flags_file {
+        write(path, cdylib_link_flags.as_bytes())
+            .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", path, e));
+    }
+    if let Some(path) = &bin_link_flags_file {
+        write(path, bin_link_flags.as_bytes())
+            .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", path, e));
+    }
 
     if !exec_root_links.is_empty() {
         for link in exec_root_links {
@@ -240,6 +348,31 @@ fn run_buildrs() -> Result<(), String> {
     Ok(())
 }
 
+/// Returns the environment of the process `command` spawns.
+fn command_envs(command: &Command) -> BTreeMap<String, String> {
+    let mut envs = env::vars().collect::<BTreeMap<_, _>>();
+    for (key, value) in command.get_envs() {
+        let key = key.to_string_lossy().into_owned();
+        match value {
+            Some(value) => {
+                envs.insert(key, value.to_string_lossy().into_owned());
+            }
+            None => {
+                envs.remove(&key);
+            }
+        }
+    }
+    envs
+}
+
+/// Looks up the value of an environment variable as seen by the process `command` spawns.
+fn command_env_var(command: &Command, name: &str) -> Option<String> {
+    match command.get_envs().find(|(key, _)| *key == name) {
+        Some((_, value)) => value.map(|value| value.to_string_lossy().into_owned()),
+        None => env::var(name).ok(),
+    }
+}
+
 fn should_symlink_exec_root() -> bool {
     env::var("RULES_RUST_SYMLINK_EXEC_ROOT")
         .map(|s| s == "1")
@@ -278,6 +411,13 @@ fn swallow_already_exists(err: std::io::Error) -> std::io::Result<()> {
     }
 }
 
+/// Arguments of the opt-in hermeticity audit, see [cargo_build_script_runner::audit].
+struct Audit {
+    mode: AuditMode,
+    declared_inputs_path: String,
+    report_path: String,
+}
+
 /// A representation of expected command line arguments.
 struct Args {
     progname: String,
@@ -287,16 +427,21 @@ struct Args {
     compile_flags_file: String,
     link_flags_file: String,
     link_search_paths_file: String,
+    cdylib_link_flags_file: Option<String>,
+    bin_link_flags_file: Option<String>,
     output_dep_env_path: String,
     stdout_path: Option<String>,
     stderr_path: Option<String>,
+    rerun_manifest_path: Option<String>,
+    rerun_cache_dir: Option<String>,
+    audit: Option<Audit>,
     rundir: String,
     input_dep_env_paths: Vec<String>,
     cargo_manifest_maker: Option<RunfilesMaker>,
 }
 
 impl Args {
-    fn parse() -> Self {
+    fn parse() -> Result<Self, String> {
         let mut progname: Result<String, String> =
             Err("Argument `progname` not provided".to_owned());
         let mut crate_links: Result<String, String> =
@@ -310,10 +455,17 @@ impl Args {
             Err("Argument `link_flags_file` not provided".to_owned());
         let mut link_search_paths_file: Result<String, String> =
             Err("Argument `link_search_paths_file` not provided".to_owned());
+        let mut cdylib_link_flags_file = None;
+        let mut bin_link_flags_file = None;
         let mut output_dep_env_path: Result<String, String> =
             Err("Argument `output_dep_env_path` not provided".to_owned());
         let mut stdout_path = None;
         let mut stderr_path = None;
+        let mut rerun_manifest_path = None;
+        let mut rerun_cache_dir = None;
+        let mut audit_mode = None;
+        let mut audit_declared_inputs = None;
+        let mut audit_report = None;
         let mut rundir: Result<String, String> = Err("Argument `rundir` not provided".to_owned());
         let mut input_dep_env_paths = Vec::new();
         let mut cargo_manifest_maker = None;
@@ -333,12 +485,26 @@ impl Args {
                 link_flags_file = Ok(arg.split_off("--link_flags=".len()));
             } else if arg.starts_with("--link_search_paths=") {
                 link_search_paths_file = Ok(arg.split_off("--link_search_paths=".len()));
+            } else if arg.starts_with("--cdylib_link_flags=") {
+                cdylib_link_flags_file = Some(arg.split_off("--cdylib_link_flags=".len()));
+            } else if arg.starts_with("--bin_link_flags=") {
+                bin_link_flags_file = Some(arg.split_off("--bin_link_flags=".len()));
             } else if arg.starts_with("--dep_env_out=") {
                 output_dep_env_path = Ok(arg.split_off("--dep_env_out=".len()));
             } else if arg.starts_with("--stdout=") {
                 stdout_path = Some(arg.split_off("--stdout=".len()));
             } else if arg.starts_with("--stderr=") {
                 stderr_path = Some(arg.split_off("--stderr=".len()));
+            } else if arg.starts_with("--rerun_manifest_out=") {
+                rerun_manifest_path = Some(arg.split_off("--rerun_manifest_out=".len()));
+            } else if arg.starts_with("--rerun_cache_dir=") {
+                rerun_cache_dir = Some(arg.split_off("--rerun_cache_dir=".len()));
+            } else if arg.starts_with("--audit=") {
+                audit_mode = Some(AuditMode::parse(&arg.split_off("--audit=".len()))?);
+            } else if arg.starts_with("--audit_declared_inputs=") {
+                audit_declared_inputs = Some(arg.split_off("--audit_declared_inputs=".len()));
+            } else if arg.starts_with("--audit_report=") {
+                audit_report = Some(arg.split_off("--audit_report=".len()));
             } else if arg.starts_with("--rundir=") {
                 rundir = Ok(arg.split_off("--rundir=".len()))
             } else if arg.starts_with("--input_dep_env_path=") {
@@ -350,21 +516,35 @@ impl Args {
             }
         }
 
-        Args {
-            progname: progname.unwrap(),
-            crate_links: crate_links.unwrap(),
-            out_dir: out_dir.unwrap(),
-            env_file: env_file.unwrap(),
-            compile_flags_file: compile_flags_file.unwrap(),
-            link_flags_file: link_flags_file.unwrap(),
-            link_search_paths_file: link_search_paths_file.unwrap(),
-            output_dep_env_path: output_dep_env_path.unwrap(),
+        Ok(Args {
+            progname: progname?,
+            crate_links: crate_links?,
+            out_dir: out_dir?,
+            env_file: env_file?,
+            compile_flags_file: compile_flags_file?,
+            link_flags_file: link_flags_file?,
+            link_search_paths_file: link_search_paths_file?,
+            cdylib_link_flags_file,
+            bin_link_flags_file,
+            output_dep_env_path: output_dep_env_path?,
             stdout_path,
             stderr_path,
-            rundir: rundir.unwrap(),
+            rerun_manifest_path,
+            rerun_cache_dir,
+            audit: audit_mode
+                .map(|mode| -> Result<Audit, String> {
+                    Ok(Audit {
+                        mode,
+                        declared_inputs_path: audit_declared_inputs
+                            .ok_or("Argument `audit_declared_inputs` not provided")?,
+                        report_path: audit_report.ok_or("Argument `audit_report` not provided")?,
+                    })
+                })
+                .transpose()?,
+            rundir: rundir?,
             input_dep_env_paths,
             cargo_manifest_maker,
-        }
+        })
     }
 }
 
@@ -17,13 +17,18 @@
 use std::io::{BufRead, BufReader, Read};
 use std::process::{Command, Output};
 
+pub mod audit;
 pub mod cargo_manifest_dir;
+pub mod rerun_manifest;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct CompileAndLinkFlags {
     pub compile_flags: String,
     pub link_flags: String,
     pub link_search_paths: String,
+    pub cdylib_link_flags: String,
+    /// `KEY=FLAG` lines, where `KEY` is the crate name of a binary or `*` for all binaries.
+    pub bin_link_flags: String,
 }
 
 /// Enum containing all the considered return value from the script
@@ -43,6 +48,16 @@ pub enum BuildScriptOutput {
     Env(String),
     /// cargo::VAR=VALUE
     DepEnv(String),
+    /// cargo::rerun-if-changed
+    RerunIfChanged(String),
+    /// cargo::rerun-if-env-changed
+    RerunIfEnvChanged(String),
+    /// cargo::rustc-cdylib-link-arg
+    CdylibLinkArg(String),
+    /// cargo::rustc-link-arg-bin, as the binary name and the argument
+    LinkArgBin(String, String),
+    /// cargo::rustc-link-arg-bins
+    LinkArgBins(String),
 }
 
 impl BuildScriptOutput {
@@ -77,11 +92,10 @@ impl BuildScriptOutput {
             "rustc-flags" => Some(BuildScriptOutput::Flags(param)),
             "rustc-link-arg" => Some(BuildScriptOutput::LinkArg(param)),
             "rustc-env" => Some(BuildScriptOutput::Env(param)),
-            "rerun-if-changed" | "rerun-if-env-changed" =>
-            // Ignored because Bazel will re-run if those change all the time.
-            {
-                None
-            }
+            // Bazel reruns the script whenever its inputs change regardless, but these are
+            // recorded in the rerun manifest. See [rerun_manifest].
+            "rerun-if-changed" => Some(BuildScriptOutput::RerunIfChanged(param)),
+            "rerun-if-env-changed" => Some(BuildScriptOutput::RerunIfEnvChanged(param)),
             "warning" => {
                 eprint!("Build Script Warning: {}", split[1]);
                 None
@@ -100,16 +114,23 @@ impl BuildScriptOutput {
                     Some(BuildScriptOutput::DepEnv(format!("METADATA={}", param)))
                 }
             }
-            "rustc-cdylib-link-arg" | "rustc-link-arg-bin" | "rustc-link-arg-bins" => {
-                // cargo::rustc-cdylib-link-arg=FLAG — Passes custom flags to a linker for cdylib crates.
+            "rustc-cdylib-link-arg" => Some(BuildScriptOutput::CdylibLinkArg(param)),
+            "rustc-link-arg-bin" => {
                 // cargo::rustc-link-arg-bin=BIN=FLAG – Passes custom flags to a linker for the binary BIN.
-                // cargo::rustc-link-arg-bins=FLAG – Passes custom flags to a linker for binaries.
-                eprint!(
-                    "Warning: build script returned unsupported directive `{}`",
-                    split[0]
-                );
-                None
+                if let Some((bin, flag)) = param.split_once('=') {
+                    Some(BuildScriptOutput::LinkArgBin(
+                        bin.to_owned(),
+                        flag.trim().to_owned(),
+                    ))
+                } else {
+                    eprint!(
+                        "Warning: build script returned malformed directive `{}`",
+                        line
+                    );
+                    None
+                }
             }
+            "rustc-link-arg-bins" => Some(BuildScriptOutput::LinkArgBins(param)),
             _ => {
                 // cargo::KEY=VALUE — Metadata, used by links scripts.
                 Some(BuildScriptOutput::DepEnv(format!(
@@ -121,6 +142,11 @@ impl BuildScriptOutput {
         }
     }
 
+    /// Parses the captured stdout of a build script into a vector of [BuildScriptOutput] enums.
+    pub fn outputs_from_stdout(stdout: &[u8]) -> Vec<BuildScriptOutput> {
+        Self::outputs_from_reader(BufReader::new(stdout))
+    }
+
     /// Converts a [BufReader] into a vector of [BuildScriptOutput] enums.
     fn outputs_from_reader<T: Read>(mut reader: BufReader<T>) -> Vec<BuildScriptOutput> {
         let mut result = Vec::<BuildScriptOutput>::new();
@@ -149,8 +175,7 @@ impl BuildScriptOutput {
             .output()
             .unwrap_or_else(|e| panic!("Unable to start command:\n{:#?}\n{:?}", cmd, e));
         if child_output.status.success() {
-            let reader = BufReader::new(child_output.stdout.as_slice());
-            let output = Self::outputs_from_reader(reader);
+            let output = Self::outputs_from_stdout(&child_output.stdout);
             Ok((output, child_output))
         } else {
             Err(child_output)
@@ -203,6 +228,8 @@ impl BuildScriptOutput {
         let mut compile_flags = Vec::new();
         let mut link_flags = Vec::new();
         let mut link_search_paths = Vec::new();
+        let mut cdylib_link_flags = Vec::new();
+        let mut bin_link_flags = Vec::new();
 
         for flag in outputs {
             match flag {
@@ -211,6 +238,16 @@ impl BuildScriptOutput {
                 BuildScriptOutput::LinkArg(e) => compile_flags.push(format!("-Clink-arg={e}")),
                 BuildScriptOutput::LinkLib(e) => link_flags.push(format!("-l{e}")),
                 BuildScriptOutput::LinkSearch(e) => link_search_paths.push(format!("-L{e}")),
+                BuildScriptOutput::CdylibLinkArg(e) => {
+                    cdylib_link_flags.push(format!("-Clink-arg={e}"))
+                }
+                // Binaries are keyed by crate name, which can't contain dashes.
+                BuildScriptOutput::LinkArgBin(bin, e) => {
+                    bin_link_flags.push(format!("{}=-Clink-arg={e}", bin.replace('-', "_")))
+                }
+                BuildScriptOutput::LinkArgBins(e) => {
+                    bin_link_flags.push(format!("*=-Clink-arg={e}"))
+                }
                 _ => {}
             }
         }
@@ -219,6 +256,8 @@ impl BuildScriptOutput {
             compile_flags: compile_flags.join("\n"),
             link_flags: Self::redact_exec_root(&link_flags.join("\n"), exec_root),
             link_search_paths: Self::redact_exec_root(&link_search_paths.join("\n"), exec_root),
+            cdylib_link_flags: cdylib_link_flags.join("\n"),
+            bin_link_flags: bin_link_flags.join("\n"),
         }
     }
 
@@ -248,7 +287,7 @@ mod tests {
     fn from_read_buffer_to_env_and_flags_test_impl(buff: Cursor<&str>) {
         let reader = BufReader::new(buff);
         let result = BuildScriptOutput::outputs_from_reader(reader);
-        assert_eq!(result.len(), 13);
+        assert_eq!(result.len(), 14);
         assert_eq!(result[0], BuildScriptOutput::LinkLib("sdfsdf".to_owned()));
         assert_eq!(result[1], BuildScriptOutput::Env("FOO=BAR".to_owned()));
         assert_eq!(
@@ -259,27 +298,31 @@ mod tests {
         assert_eq!(result[4], BuildScriptOutput::Flags("-Lblah".to_owned()));
         assert_eq!(
             result[5],
-            BuildScriptOutput::Cfg("feature=awesome".to_owned())
+            BuildScriptOutput::RerunIfChanged("wrapper.h".to_owned())
         );
         assert_eq!(
             result[6],
-            BuildScriptOutput::DepEnv("VERSION=123".to_owned())
+            BuildScriptOutput::Cfg("feature=awesome".to_owned())
         );
         assert_eq!(
             result[7],
+            BuildScriptOutput::DepEnv("VERSION=123".to_owned())
+        );
+        assert_eq!(
+            result[8],
             BuildScriptOutput::DepEnv("VERSION_NUMBER=1010107f".to_owned())
         );
         assert_eq!(
-            result[9],
+            result[10],
             BuildScriptOutput::Env("SOME_PATH=/some/absolute/path/beep".to_owned())
         );
         assert_eq!(
-            result[10],
+            result[11],
             BuildScriptOutput::LinkArg("-weak_framework".to_owned())
         );
-        assert_eq!(result[11], BuildScriptOutput::LinkArg("Metal".to_owned()));
+        assert_eq!(result[12], BuildScriptOutput::LinkArg("Metal".to_owned()));
         assert_eq!(
-            result[12],
+            result[13],
             BuildScriptOutput::Env("no_trailing_newline=true".to_owned())
         );
         assert_eq!(
@@ -300,6 +343,8 @@ mod tests {
                         .to_owned(),
                 link_flags: "-lsdfsdf".to_owned(),
                 link_search_paths: "-L${pwd}/bleh".to_owned(),
+                cdylib_link_flags: String::new(),
+                bin_link_flags: String::new(),
             }
         );
     }
@@ -313,7 +358,7 @@ cargo::rustc-env=FOO=BAR
 cargo::rustc-link-search=/some/absolute/path/bleh
 cargo::rustc-env=BAR=FOO
 cargo::rustc-flags=-Lblah
-cargo::rerun-if-changed=ignored
+cargo::rerun-if-changed=wrapper.h
 cargo::rustc-cfg=feature=awesome
 cargo::version=123
 cargo::version_number=1010107f
@@ -338,7 +383,7 @@ cargo:rustc-env=FOO=BAR
 cargo:rustc-link-search=/some/absolute/path/bleh
 cargo:rustc-env=BAR=FOO
 cargo:rustc-flags=-Lblah
-cargo:rerun-if-changed=ignored
+cargo:rerun-if-changed=wrapper.h
 cargo:rustc-cfg=feature=awesome
 cargo:version=123
 cargo:version_number=1010107f
@@ -399,4 +444,55 @@ cargo:rustc-env=valid2=2
             vec![BuildScriptOutput::DepEnv("VERSION_1_10_0=1".to_owned())]
         );
     }
+
+    #[test]
+    fn rerun_if_directives_are_recorded() {
+        let reader = BufReader::new(Cursor::new(
+            "cargo::rerun-if-changed=src/wrapper.h\ncargo:rerun-if-env-changed=OPENSSL_DIR\n",
+        ));
+        let result = BuildScriptOutput::outputs_from_reader(reader);
+        assert_eq!(
+            result,
+            vec![
+                BuildScriptOutput::RerunIfChanged("src/wrapper.h".to_owned()),
+                BuildScriptOutput::RerunIfEnvChanged("OPENSSL_DIR".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn per_target_link_args() {
+        let reader = BufReader::new(Cursor::new(
+            "
+cargo::rustc-cdylib-link-arg=-Wl,-soname,libfoo.so
+cargo::rustc-link-arg-bins=-Wl,--as-needed
+cargo::rustc-link-arg-bin=foo-cli=-Wl,--defsym=main_cli=main
+cargo:rustc-link-arg-bin=malformed
+",
+        ));
+        let result = BuildScriptOutput::outputs_from_reader(reader);
+        assert_eq!(
+            result,
+            vec![
+                BuildScriptOutput::CdylibLinkArg("-Wl,-soname,libfoo.so".to_owned()),
+                BuildScriptOutput::LinkArgBins("-Wl,--as-needed".to_owned()),
+                BuildScriptOutput::LinkArgBin(
+                    "foo-cli".to_owned(),
+                    "-Wl,--defsym=main_cli=main".to_owned()
+                ),
+            ]
+        );
+        assert_eq!(
+            BuildScriptOutput::outputs_to_flags(&result, "/some/absolute/path"),
+            CompileAndLinkFlags {
+                compile_flags: String::new(),
+                link_flags: String::new(),
+                link_search_paths: String::new(),
+                cdylib_link_flags: "-Clink-arg=-Wl,-soname,libfoo.so".to_owned(),
+                bin_link_flags: "*=-Clink-arg=-Wl,--as-needed\n\
+                                 foo_cli=-Clink-arg=-Wl,--defsym=main_cli=main"
+                    .to_owned(),
+            }
+        );
+    }
 }
@@ -0,0 +1,561 @@
+//! Tracking of the inputs declared by `cargo::rerun-if-changed` and
+//! `cargo::rerun-if-env-changed` directives.
+//!
+//! Bazel decides when to rerun a build script based on the action inputs, so
+//! these directives don't affect scheduling. They are still recorded, together
+//! with a digest of each declared input, in a manifest next to the other build
+//! script outputs. A later run can compare the manifest against the current
+//! state of the inputs to tell whether the previous outputs are still valid,
+//! and tooling can use it to flag build scripts which read undeclared inputs.
+//!
+//! [RerunCache] uses the manifest to reuse the outputs of an earlier run of the
+//! same build script, as long as none of its declared inputs changed.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::audit::normalize;
+use crate::BuildScriptOutput;
+
+/// The version of the manifest format, written as the first line.
+const MANIFEST_HEADER: &str = "# rerun-manifest v1";
+
+/// Marker used in place of a digest for inputs which don't exist.
+const MISSING: &str = "-";
+
+/// Stands in for the exec root in cache keys and cached output streams.
+const EXEC_ROOT_PLACEHOLDER: &str = "${pwd}";
+
+/// An input declared by a `rerun-if-*` directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RerunInput {
+    /// cargo::rerun-if-changed=PATH
+    ///
+    /// The path is relative to the exec root.
+    File {
+        path: String,
+        digest: Option<String>,
+    },
+    /// cargo::rerun-if-env-changed=VAR
+    Env {
+        name: String,
+        digest: Option<String>,
+    },
+}
+
+impl RerunInput {
+    fn digest(&self) -> &Option<String> {
+        match self {
+            RerunInput::File { digest, .. } | RerunInput::Env { digest, .. } => digest,
+        }
+    }
+}
+
+/// The inputs a build script declared, and their state when it ran.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RerunManifest {
+    pub inputs: Vec<RerunInput>,
+}
+
+impl RerunManifest {
+    /// Collects the `rerun-if-*` directives from `outputs`, digesting each input.
+    ///
+    /// Relative paths are resolved against `manifest_dir`, as Cargo does. Paths
+    /// outside of `exec_root` are dropped: they can't be inputs of the action,
+    /// and would tie the manifest to the machine it was written on. `env` looks
+    /// up variables in the environment the build script ran with.
+    pub fn from_outputs(
+        outputs: &[BuildScriptOutput],
+        manifest_dir: &Path,
+        exec_root: &Path,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Self {
+        let mut inputs = Vec::new();
+        for output in outputs {
+            let input = match output {
+                BuildScriptOutput::RerunIfChanged(path) => {
+                    let path = normalize(&manifest_dir.join(path));
+                    let relative = match path.strip_prefix(exec_root) {
+                        Ok(relative) => relative.to_string_lossy().into_owned(),
+                        Err(_) => continue,
+                    };
+                    RerunInput::File {
+                        path: relative,
+                        digest: digest_path(&path),
+                    }
+                }
+                BuildScriptOutput::RerunIfEnvChanged(name) => RerunInput::Env {
+                    name: name.clone(),
+                    digest: env(name).map(|value| digest_bytes(value.as_bytes())),
+                },
+                _ => continue,
+            };
+            if !inputs.contains(&input) {
+                inputs.push(input);
+            }
+        }
+        Self { inputs }
+    }
+
+    /// Parses a manifest previously written with [RerunManifest::to_string].
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut lines = contents.lines();
+        if lines.next() != Some(MANIFEST_HEADER) {
+            return Err(format!(
+                "Rerun manifest does not start with `{}`",
+                MANIFEST_HEADER
+            ));
+        }
+
+        let mut inputs = Vec::new();
+        for line in lines.filter(|line| !line.is_empty()) {
+            let fields = line.splitn(3, '\t').collect::<Vec<_>>();
+            let (kind, digest, key) = match fields.as_slice() {
+                [kind, digest, key] => (*kind, *digest, (*key).to_owned()),
+                _ => return Err(format!("Malformed rerun manifest line: {:?}", line)),
+            };
+            let digest = if digest == MISSING {
+                None
+            } else {
+                Some(digest.to_owned())
+            };
+            inputs.push(match kind {
+                "file" => RerunInput::File { path: key, digest },
+                "env" => RerunInput::Env { name: key, digest },
+                _ => return Err(format!("Unknown rerun manifest entry kind: {:?}", kind)),
+            });
+        }
+        Ok(Self { inputs })
+    }
+
+    /// Returns the inputs which changed since the manifest was recorded.
+    ///
+    /// If this is empty, the outputs of the run which produced the manifest
+    /// are still valid. Note that like Cargo, a build script which declared no
+    /// inputs at all should be treated as depending on its entire package.
+    pub fn stale_inputs(
+        &self,
+        exec_root: &Path,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Vec<&RerunInput> {
+        self.inputs
+            .iter()
+            .filter(|input| {
+                let current = match input {
+                    RerunInput::File { path, .. } => digest_path(&exec_root.join(path)),
+                    RerunInput::Env { name, .. } => {
+                        env(name).map(|value| digest_bytes(value.as_bytes()))
+                    }
+                };
+                &current != input.digest()
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for RerunManifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", MANIFEST_HEADER)?;
+        for input in &self.inputs {
+            let (kind, key) = match input {
+                RerunInput::File { path, .. } => ("file", path),
+                RerunInput::Env { name, .. } => ("env", name),
+            };
+            let digest = input.digest().as_deref().unwrap_or(MISSING);
+            writeln!(f, "{}\t{}\t{}", kind, digest, key)?;
+        }
+        Ok(())
+    }
+}
+
+/// A directory of earlier build script runs, which are reused while none of
+/// the inputs recorded in their [RerunManifest] changed.
+///
+/// Each run is stored under a key identifying the build script and the
+/// environment it ran with, see [RerunCache::key]. Only runs which declared
+/// at least one input are stored, since like Cargo, a build script without
+/// `rerun-if-*` directives depends on its entire package.
+///
+/// The exec root differs between sandboxes, so it is replaced with a
+/// placeholder in the stored output streams and expanded again to the exec
+/// root of the run restoring them. That way paths such as
+/// `cargo::rustc-link-search=native=<OUT_DIR>` point into the current sandbox.
+pub struct RerunCache {
+    entry: PathBuf,
+}
+
+/// The captured output streams of a build script run restored from a [RerunCache].
+pub struct CachedRun {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl RerunCache {
+    pub fn new(cache_dir: &Path, key: &str) -> Self {
+        Self {
+            entry: cache_dir.join(key),
+        }
+    }
+
+    /// Computes the key of a run of `script` in the environment `env`.
+    ///
+    /// Occurrences of `exec_root` in `env` are ignored, since it differs
+    /// between sandboxes. Returns `None` if `script` can't be read.
+    pub fn key(script: &Path, env: &BTreeMap<String, String>, exec_root: &Path) -> Option<String> {
+        let exec_root = exec_root.to_string_lossy();
+        let mut hash = fnv1a(FNV_OFFSET_BASIS, &fs::read(script).ok()?);
+        for (key, value) in env {
+            let value = value.replace(exec_root.as_ref(), EXEC_ROOT_PLACEHOLDER);
+            for field in [key.as_bytes(), b"=", value.as_bytes(), b"\0"] {
+                hash = fnv1a(hash, field);
+            }
+        }
+        Some(format!("{:016x}", hash))
+    }
+
+    /// Restores the contents of `OUT_DIR` from an earlier run, if none of its
+    /// inputs changed.
+    ///
+    /// `env` looks up variables in the environment the build script would run with.
+    pub fn restore(
+        &self,
+        out_dir: &Path,
+        exec_root: &Path,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Option<CachedRun> {
+        let manifest = fs::read_to_string(self.entry.join("rerun_manifest")).ok()?;
+        let manifest = RerunManifest::parse(&manifest).ok()?;
+        if manifest.inputs.is_empty() || !manifest.stale_inputs(exec_root, env).is_empty() {
+            return None;
+        }
+        let exec_root = exec_root.to_string_lossy();
+        let run = CachedRun {
+            stdout: replace_bytes(
+                &fs::read(self.entry.join("stdout")).ok()?,
+                EXEC_ROOT_PLACEHOLDER,
+                &exec_root,
+            ),
+            stderr: replace_bytes(
+                &fs::read(self.entry.join("stderr")).ok()?,
+                EXEC_ROOT_PLACEHOLDER,
+                &exec_root,
+            ),
+        };
+        copy_dir(&self.entry.join("out_dir"), out_dir).ok()?;
+        Some(run)
+    }
+
+    /// Stores a run of the build script in `exec_root`, replacing any earlier one.
+    pub fn store(
+        &self,
+        manifest: &RerunManifest,
+        stdout: &[u8],
+        stderr: &[u8],
+        out_dir: &Path,
+        exec_root: &Path,
+    ) -> io::Result<()> {
+        if manifest.inputs.is_empty() {
+            return Ok(());
+        }
+
+        // Write the run next to the entry and move it in place, so concurrent
+        // builds never see a partially written entry.
+        let staging = self.entry.with_extension(format!("tmp-{}", process::id()));
+        let _ = fs::remove_dir_all(&staging);
+        fs::create_dir_all(&staging)?;
+        fs::write(staging.join("rerun_manifest"), manifest.to_string())?;
+        let exec_root = exec_root.to_string_lossy();
+        fs::write(
+            staging.join("stdout"),
+            replace_bytes(stdout, &exec_root, EXEC_ROOT_PLACEHOLDER),
+        )?;
+        fs::write(
+            staging.join("stderr"),
+            replace_bytes(stderr, &exec_root, EXEC_ROOT_PLACEHOLDER),
+        )?;
+        copy_dir(out_dir, &staging.join("out_dir"))?;
+
+        let _ = fs::remove_dir_all(&self.entry);
+        fs::rename(&staging, &self.entry).or_else(|e| {
+            let _ = fs::remove_dir_all(&staging);
+            // Another build stored the same run in the meantime.
+            if self.entry.exists() {
+                Ok(())
+            } else {
+                Err(e)
+            }
+        })
+    }
+}
+
+/// Replaces every occurrence of `from` in `bytes`, which need not be UTF-8.
+fn replace_bytes(bytes: &[u8], from: &str, to: &str) -> Vec<u8> {
+    let from = from.as_bytes();
+    if from.is_empty() {
+        return bytes.to_vec();
+    }
+    let mut replaced = Vec::with_capacity(bytes.len());
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        if rest.starts_with(from) {
+            replaced.extend_from_slice(to.as_bytes());
+            rest = &rest[from.len()..];
+        } else {
+            replaced.push(rest[0]);
+            rest = &rest[1..];
+        }
+    }
+    replaced
+}
+
+/// Recursively copies the contents of `from` into `to`, following symlinks.
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            copy_dir(&path, &to.join(entry.file_name()))?;
+        } else {
+            fs::copy(&path, to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+/// Computes a 64 bit FNV-1a digest of `bytes`, formatted as hex.
+fn digest_bytes(bytes: &[u8]) -> String {
+    format!("{:016x}", fnv1a(FNV_OFFSET_BASIS, bytes))
+}
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Digests the contents of a file, or of every file in a directory, returning
+/// `None` if `path` doesn't exist.
+///
+/// Like Cargo, a directory is considered changed if any file within it changed.
+pub fn digest_path(path: &Path) -> Option<String> {
+    fn visit(path: &Path, relative: &str, hash: u64) -> Option<u64> {
+        if path.is_dir() {
+            let mut entries = fs::read_dir(path)
+                .ok()?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            entries.sort();
+            entries.into_iter().try_fold(hash, |hash, name| {
+                let relative = format!("{}/{}", relative, name);
+                let hash = fnv1a(hash, relative.as_bytes());
+                visit(&path.join(&name), &relative, hash)
+            })
+        } else {
+            Some(fnv1a(hash, &fs::read(path).ok()?))
+        }
+    }
+
+    if !path.exists() {
+        return None;
+    }
+    visit(path, "", FNV_OFFSET_BASIS).map(|hash| format!("{:016x}", hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn manifest_round_trip() {
+        let manifest = RerunManifest {
+            inputs: vec![
+                RerunInput::File {
+                    path: "external/foo/build.rs".to_owned(),
+                    digest: Some("0123456789abcdef".to_owned()),
+                },
+                RerunInput::File {
+                    path: "external/foo/missing with spaces.h".to_owned(),
+                    digest: None,
+                },
+                RerunInput::Env {
+                    name: "OPENSSL_DIR".to_owned(),
+                    digest: None,
+                },
+            ],
+        };
+        assert_eq!(
+            manifest.to_string(),
+            "# rerun-manifest v1\n\
+             file\t0123456789abcdef\texternal/foo/build.rs\n\
+             file\t-\texternal/foo/missing with spaces.h\n\
+             env\t-\tOPENSSL_DIR\n"
+        );
+        assert_eq!(
+            RerunManifest::parse(&manifest.to_string()).unwrap(),
+            manifest
+        );
+        assert!(RerunManifest::parse("file\t-\tfoo").is_err());
+    }
+
+    #[test]
+    fn stale_inputs() {
+        let exec_root = PathBuf::from(std::env::var("TEST_TMPDIR").unwrap()).join("rerun");
+        let manifest_dir = exec_root.join("external/foo");
+        fs::create_dir_all(manifest_dir.join("src")).unwrap();
+        fs::write(manifest_dir.join("wrapper.h"), "int foo();").unwrap();
+        fs::write(manifest_dir.join("src/lib.c"), "int foo() { return 1; }").unwrap();
+
+        let outputs = [
+            BuildScriptOutput::RerunIfChanged("wrapper.h".to_owned()),
+            BuildScriptOutput::RerunIfChanged("src".to_owned()),
+            BuildScriptOutput::RerunIfEnvChanged("CC".to_owned()),
+            BuildScriptOutput::RerunIfEnvChanged("CFLAGS".to_owned()),
+            BuildScriptOutput::Cfg("feature=awesome".to_owned()),
+        ];
+        let env = |name: &str| (name == "CC").then(|| "clang".to_owned());
+        let manifest = RerunManifest::from_outputs(&outputs, &manifest_dir, &exec_root, env);
+        assert_eq!(manifest.inputs.len(), 4);
+        assert_eq!(
+            manifest.inputs[0],
+            RerunInput::File {
+                path: "external/foo/wrapper.h".to_owned(),
+                digest: Some(digest_bytes(b"int foo();")),
+            }
+        );
+        assert!(manifest.stale_inputs(&exec_root, env).is_empty());
+
+        fs::write(manifest_dir.join("src/lib.c"), "int foo() { return 2; }").unwrap();
+        let env = |name: &str| Some(format!("{}-value", name));
+        assert_eq!(
+            manifest.stale_inputs(&exec_root, env),
+            vec![
+                &manifest.inputs[1],
+                &manifest.inputs[2],
+                &manifest.inputs[3]
+            ]
+        );
+    }
+
+    #[test]
+    fn paths_outside_of_exec_root_are_dropped() {
+        let exec_root = PathBuf::from("/exec_root");
+        let manifest_dir = exec_root.join("external/foo");
+        let outputs = [
+            BuildScriptOutput::RerunIfChanged("../bar/include/bar.h".to_owned()),
+            BuildScriptOutput::RerunIfChanged("/usr/include/zlib.h".to_owned()),
+            BuildScriptOutput::RerunIfChanged("../../../etc/passwd".to_owned()),
+        ];
+        let manifest = RerunManifest::from_outputs(&outputs, &manifest_dir, &exec_root, |_| None);
+        assert_eq!(
+            manifest.inputs,
+            vec![RerunInput::File {
+                path: "external/bar/include/bar.h".to_owned(),
+                digest: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn rerun_cache() {
+        let root = PathBuf::from(std::env::var("TEST_TMPDIR").unwrap()).join("rerun_cache");
+        let exec_root = root.join("exec_root");
+        let manifest_dir = exec_root.join("external/foo");
+        let out_dir = exec_root.join("out_dir");
+        fs::create_dir_all(&manifest_dir).unwrap();
+        fs::create_dir_all(out_dir.join("include")).unwrap();
+        fs::write(manifest_dir.join("wrapper.h"), "int foo();").unwrap();
+        fs::write(out_dir.join("include/foo.h"), "int foo();").unwrap();
+
+        let script = manifest_dir.join("build_script");
+        fs::write(&script, "script").unwrap();
+        let env = BTreeMap::from([("OUT_DIR".to_owned(), out_dir.to_string_lossy().into_owned())]);
+        let key = RerunCache::key(&script, &env, &exec_root).unwrap();
+        let cache = RerunCache::new(&root.join("cache"), &key);
+
+        // A different sandbox for the same run has the same key.
+        let moved_env = BTreeMap::from([("OUT_DIR".to_owned(), "/sandbox/1/out_dir".to_owned())]);
+        assert_eq!(
+            RerunCache::key(&script, &moved_env, Path::new("/sandbox/1")).unwrap(),
+            key
+        );
+        let features = BTreeMap::from([("CARGO_FEATURE_STD".to_owned(), "1".to_owned())]);
+        assert_ne!(
+            RerunCache::key(&script, &features, &exec_root).unwrap(),
+            key
+        );
+
+        let stdout = format!(
+            "cargo::rerun-if-changed=wrapper.h\ncargo::rustc-cfg=foo\n\
+             cargo::rustc-link-search=native={}\n",
+            out_dir.display()
+        );
+        let outputs = BuildScriptOutput::outputs_from_stdout(stdout.as_bytes());
+        let manifest = RerunManifest::from_outputs(&outputs, &manifest_dir, &exec_root, |_| None);
+        cache
+            .store(
+                &manifest,
+                stdout.as_bytes(),
+                b"warning",
+                &out_dir,
+                &exec_root,
+            )
+            .unwrap();
+
+        let restored = root.join("restored");
+        let run = cache.restore(&restored, &exec_root, |_| None).unwrap();
+        assert_eq!(run.stdout, stdout.as_bytes());
+        assert_eq!(run.stderr, b"warning");
+        assert_eq!(
+            fs::read_to_string(restored.join("include/foo.h")).unwrap(),
+            "int foo();"
+        );
+
+        // Paths into the exec root of the stored run point into the exec root
+        // of the run restoring it.
+        let sandbox = root.join("sandbox");
+        fs::create_dir_all(sandbox.join("external/foo")).unwrap();
+        fs::copy(
+            manifest_dir.join("wrapper.h"),
+            sandbox.join("external/foo/wrapper.h"),
+        )
+        .unwrap();
+        let run = cache
+            .restore(&sandbox.join("out_dir"), &sandbox, |_| None)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(run.stdout).unwrap(),
+            format!(
+                "cargo::rerun-if-changed=wrapper.h\ncargo::rustc-cfg=foo\n\
+                 cargo::rustc-link-search=native={}\n",
+                sandbox.join("out_dir").display()
+            )
+        );
+
+        // Changing a declared input invalidates the run.
+        fs::write(manifest_dir.join("wrapper.h"), "int foo(int);").unwrap();
+        assert!(cache
+            .restore(&root.join("stale"), &exec_root, |_| None)
+            .is_none());
+
+        // Runs which declared no inputs are never reused.
+        let cache = RerunCache::new(&root.join("cache"), "no_inputs");
+        cache
+            .store(&RerunManifest::default(), b"", b"", &out_dir, &exec_root)
+            .unwrap();
+        assert!(cache
+            .restore(&root.join("none"), &exec_root, |_| None)
+            .is_none());
+    }
+}
@@ -3,7 +3,7 @@
 Definitions for all `@rules_rust//cargo` settings
 """
 
-load("@bazel_skylib//rules:common_settings.bzl", "bool_flag", "string_list_flag")
+load("@bazel_skylib//rules:common_settings.bzl", "bool_flag", "string_flag", "string_list_flag")
 
 def experimental_symlink_execroot():
     """A flag for which causes `cargo_build_script` to symlink the execroot of the action to \
@@ -38,6 +38,47 @@ def cargo_manifest_dir_filename_suffixes_to_retain():
         ],
     )
 
+def build_script_audit():
+    """A flag which audits `cargo_build_script` targets for hermeticity problems.
+
+    The audit reports `cargo::rerun-if-changed` paths which are not inputs of the action,
+    `cargo::rerun-if-env-changed` variables which are not in the environment of the action,
+    and files written to `CARGO_MANIFEST_DIR`. With `warn`, problems are printed and
+    collected in the `build_script_audit` output group. With `error`, they fail the build.
+
+    The audit does not trace the build script: files and environment variables it reads
+    without declaring them in `rerun-if-*` directives, and writes outside of
+    `CARGO_MANIFEST_DIR`, are not detected.
+    """
+    string_flag(
+        name = "build_script_audit",
+        build_setting_default = "off",
+        values = [
+            "error",
+            "off",
+            "warn",
+        ],
+    )
+
+def experimental_build_script_cache_dir():
+    """A flag naming an absolute directory in which `cargo_build_script` reuses earlier runs.
+
+    A build script is rerun whenever any input of its action changes. With this flag, the
+    runner stores each run together with the `cargo::rerun-if-changed` and
+    `cargo::rerun-if-env-changed` inputs it declared, and restores the outputs of an
+    earlier run of the same script with the same environment as long as none of those
+    inputs changed. Build scripts which declare no inputs always run.
+
+    Files and variables a build script reads without declaring them are not tracked, so a
+    reused run may be stale. Build script actions therefore run locally and their outputs
+    are never stored in the disk or remote cache when this is set. With sandboxing, the
+    directory must also be passed to `--sandbox_writable_path`.
+    """
+    string_flag(
+        name = "experimental_build_script_cache_dir",
+        build_setting_default = "",
+    )
+
 def debug_std_streams_output_group():
     """A flag which adds a `streams` output group to `cargo_build_script` targets that contain \
     the raw `stderr` and `stdout` streams from the build script.
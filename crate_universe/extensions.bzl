@@ -196,6 +196,7 @@ You could use the syntax specified in the above document to place it in `Cargo.t
 This method has the following consequences:
 * if you use shared dependency tree with your project these binary dependencies will interfere with yours (may conflict)
 * you have to use  nightly `host_tools` to generate dependencies because
+* artifacts can only be built for the host or, with `target = "target"`, for the platform of the crate depending on them. Explicit target triples (e.g. `target = "wasm32-unknown-unknown"`) are rejected.
 
 Alternatively you can specify this in a separate `repo` with `cargo.from_specs` syntax:
 
@@ -394,6 +395,7 @@ load(
     "GENERATOR_ENV_VARS",
     "determine_repin",
     "execute_generator",
+    "read_target_specs",
     generate_render_config = "render_config",
 )
 load("//crate_universe/private:local_crate_mirror.bzl", "local_crate_mirror")
@@ -604,6 +606,8 @@ def _generate_hub_and_spokes(
             workspace_name = cfg.name,
             generate_binaries = cfg.generate_binaries,
             render_config = render_config,
+            platform_cfgs = cfg.platform_cfgs,
+            target_specs = read_target_specs(module_ctx, cfg.custom_target_specs),
             repository_ctx = module_ctx,
         ),
     )
@@ -632,6 +636,7 @@ def _generate_hub_and_spokes(
         lockfile_path = lockfile,
         config = config_file,
         splicing_manifest = splicing_manifest,
+        cargo_lockfile = cargo_lockfile,
     )
 
     # The workspace root when one is explicitly provided.
@@ -744,7 +749,7 @@ def _generate_hub_and_spokes(
                 remote_patch_strip = 1,
                 sha256 = repo.get("sha256", None),
                 type = "tar.gz",
-                urls = [repo["url"]],
+                urls = _crate_mirror_urls(render_config, name, version, repo.get("sha256", None)) + [repo["url"]],
                 strip_prefix = "%s-%s" % (crate["name"], crate["version"]),
                 build_file_content = build_file_content,
             )
@@ -775,7 +780,7 @@ def _generate_hub_and_spokes(
                 "config": render_config,
                 "crate_context": crate,
                 "platform_conditions": contents["conditions"],
-                "supported_platform_triples": cfg.supported_platform_triples,
+                "supported_platform_triples": cfg.supported_platform_triples + cfg.custom_target_specs.values(),
             }
             kwargs = {}
             if len(CARGO_BAZEL_URLS) == 0:
@@ -789,6 +794,30 @@ def _generate_hub_and_spokes(
         else:
             fail("Invalid repo: expected Http or Git to exist for crate %s-%s, got %s" % (name, version, repo))
 
+def _crate_mirror_urls(render_config, name, version, sha256):
+    """Render the mirror URLs of a crate.
+
+    Replicates `render_crate_mirror_urls` in `cargo-bazel`.
+
+    Args:
+        render_config (dict): The render config in use.
+        name (str): The name of the crate.
+        version (str): The version of the crate.
+        sha256 (str): The checksum of the crate's archive, if known.
+
+    Returns:
+        list: The URLs of the crate's archive on each mirror.
+    """
+    urls = []
+    for template in render_config.get("crate_mirror_url_templates", []):
+        url = template.replace("{name}", name).replace("{version}", version)
+        if sha256:
+            url = url.replace("{sha256}", sha256)
+        elif "{sha256}" in url:
+            continue
+        urls.append(url)
+    return urls
+
 def _package_to_json(p):
     # Avoid adding unspecified properties.
     # If we add them as empty strings, cargo-bazel will be unhappy.
@@ -1189,6 +1218,7 @@ def _crate_impl(module_ctx):
 _FROM_COMMON_ATTRS = {
     "cargo_config": CRATES_VENDOR_ATTRS["cargo_config"],
     "cargo_lockfile": CRATES_VENDOR_ATTRS["cargo_lockfile"],
+    "custom_target_specs": CRATES_VENDOR_ATTRS["custom_target_specs"],
     "generate_binaries": CRATES_VENDOR_ATTRS["generate_binaries"],
     "generate_build_scripts": CRATES_VENDOR_ATTRS["generate_build_scripts"],
     "host_tools": attr.label(
@@ -1211,6 +1241,14 @@ _FROM_COMMON_ATTRS = {
             "If set, this file must exist within the workspace (but can be empty) before this rule will work."
         ),
     ),
+    "platform_cfgs": attr.string_list_dict(
+        doc = (
+            "Extra `cfg` options enabled for platforms in `supported_platform_triples`, such as `--cfg` flags passed to " +
+            "rustc through `rustflags`. Keys are platform triples and values are lists of options in the syntax of " +
+            "`rustc --cfg`, e.g. `tokio_unstable` or `foo=\"bar\"`. `target_feature=\"...\"` options enable target features. " +
+            "These are used both when resolving features with Cargo and when evaluating `cfg(...)` dependencies."
+        ),
+    ),
     "skip_cargo_lockfile_overwrite": attr.bool(
         doc = (
             "Whether to skip writing the cargo lockfile back after resolving. " +
@@ -1306,6 +1344,15 @@ _ANNOTATION_NORMAL_ATTRS = {
         values = _OPT_BOOL_VALUES.keys(),
         default = "auto",
     ),
+    "gen_cdylib": attr.bool(
+        doc = "If true, produces a `rust_shared_library` target named `<crate>__cdylib` for crates declaring a `cdylib` crate type. It is aliased in the hub repository as `<package>__cdylib`.",
+    ),
+    "gen_staticlib": attr.bool(
+        doc = "If true, produces a `rust_static_library` target named `<crate>__staticlib` for crates declaring a `staticlib` crate type. It is aliased in the hub repository as `<package>__staticlib`.",
+    ),
+    "gen_tests": attr.bool(
+        doc = "If true, produces `rust_test` targets for the crate's library unit tests (`<crate>__lib_test`) and integration tests (`<test>__test`). Dev-dependencies are added to these targets. Cargo only resolves the dev-dependencies of workspace members, so for other crates which declare any, a warning is printed and no tests are generated.",
+    ),
     "override_target_bin": attr.label(
         doc = "An optional alternate target to use when something depends on this crate to allow the parent repo to provide its own version of this dependency.",
     ),
@@ -1483,6 +1530,7 @@ can be found below where the supported keys for each template can be found in th
 | `version` | The crate version. Eg `1.2.3` |
 | `target` | The library or binary target of the crate |
 | `file` | The basename of a file |
+| `sha256` | The sha256 checksum of a crate's archive |
 """,
     attrs = {
         "repositories": attr.string_list(
@@ -1502,6 +1550,10 @@ can be found below where the supported keys for each template can be found in th
             doc = "The base template to use for crate labels. The available format keys are [`{repository}`, `{name}`, `{version}`, `{target}`].",
             default = "@{repository}__{name}-{version}//:{target}",
         ),
+        "crate_mirror_url_templates": attr.string_list(
+            doc = "Templates of mirror URLs to download crates from before falling back to the registry, such as a server for a directory populated by `cargo-bazel mirror`. The available format keys are [`{name}`, `{version}`, `{sha256}`].",
+            default = [],
+        ),
         "crate_repository_template": attr.string(
             doc = "The base template to use for Crate label repository names. The available format keys are [`{repository}`, `{name}`, `{version}`].",
             default = "{repository}__{name}-{version}",
@@ -1572,6 +1624,7 @@ Environment Variables:
 | `CARGO_BAZEL_REPIN` | An indicator that the dependencies represented by the rule should be regenerated. `REPIN` may also be used. See [Repinning / Updating Dependencies](crate_universe_workspace.html#repinning--updating-dependencies) for more details. |
 | `CARGO_BAZEL_REPIN_ONLY` | A comma-delimited allowlist for rules to execute repinning. Can be useful if multiple instances of the repository rule are used in a Bazel workspace, but repinning should be limited to one of them. |
 | `CARGO_BAZEL_TIMEOUT` | An integer value to override the default timeout setting when running the cargo-bazel binary. This value must be in seconds. |
+| `CARGO_BAZEL_TREE_RESOLVER_CACHE_DIR` | A directory in which the per-platform output of `cargo tree` is cached between repins. Cached outputs are reused as long as the `Cargo.lock` file, manifests, toolchain, Cargo config and `CARGO_*` environment variables are unchanged. Any change to these re-resolves every platform, as entries are keyed on the whole workspace rather than individual members. Outputs which haven't been used for 30 days are removed. |
 
 """,
     implementation = _crate_impl,
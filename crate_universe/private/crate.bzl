@@ -114,6 +114,9 @@ def _annotation(
         gen_binaries = None,
         disable_pipelining = False,
         gen_build_script = None,
+        gen_cdylib = False,
+        gen_staticlib = False,
+        gen_tests = False,
         patch_args = None,
         patch_tool = None,
         patches = None,
@@ -166,6 +169,13 @@ def _annotation(
         disable_pipelining (bool, optional): If True, disables pipelining for library targets for this crate.
         gen_build_script (bool, optional): An authoritative flag to determine whether or not to produce
             `cargo_build_script` targets for the current crate.
+        gen_cdylib (bool, optional): If True, produces a `rust_shared_library` target named `<crate>__cdylib` for
+            crates declaring a `cdylib` crate type. It is aliased in the hub repository as `<package>__cdylib`.
+        gen_staticlib (bool, optional): If True, produces a `rust_static_library` target named `<crate>__staticlib`
+            for crates declaring a `staticlib` crate type. It is aliased in the hub repository as `<package>__staticlib`.
+        gen_tests (bool, optional): If True, produces `rust_test` targets for the crate's library unit tests
+            (`<crate>__lib_test`) and integration tests (`<test>__test`). Dev-dependencies are added to these targets.
+            Cargo only resolves the dev-dependencies of workspace members, so for other crates which declare any, a warning is printed and no tests are generated.
         patch_args (list, optional): The `patch_args` attribute of a Bazel repository rule. See
             [http_archive.patch_args](https://docs.bazel.build/versions/main/repo/http.html#http_archive-patch_args)
         patch_tool (string, optional): The `patch_tool` attribute of a Bazel repository rule. See
@@ -222,6 +232,9 @@ def _annotation(
             gen_binaries = gen_binaries,
             disable_pipelining = disable_pipelining,
             gen_build_script = gen_build_script,
+            gen_cdylib = gen_cdylib,
+            gen_staticlib = gen_staticlib,
+            gen_tests = gen_tests,
             patch_args = patch_args,
             patch_tool = patch_tool,
             patches = _stringify_list(patches),
@@ -8,6 +8,7 @@ load(
 load(
     "//crate_universe/private:generate_utils.bzl",
     "CRATES_REPOSITORY_ENVIRON",
+    "CUSTOM_TARGET_SPECS_ATTR",
     "determine_repin",
     "execute_generator",
     "generate_config",
@@ -73,6 +74,7 @@ def _crates_repository_impl(repository_ctx):
         lockfile_path = lockfiles.bazel,
         config = config_path,
         splicing_manifest = splicing_manifest,
+        cargo_lockfile = lockfiles.cargo,
         repin_instructions = repository_ctx.attr.repin_instructions,
     )
 
@@ -171,6 +173,7 @@ Environment Variables:
 | `CARGO_BAZEL_REPIN` | An indicator that the dependencies represented by the rule should be regenerated. `REPIN` may also be used. See [Repinning / Updating Dependencies](#repinning--updating-dependencies) for more details. |
 | `CARGO_BAZEL_REPIN_ONLY` | A comma-delimited allowlist for rules to execute repinning. Can be useful if multiple instances of the repository rule are used in a Bazel workspace, but repinning should be limited to one of them. |
 | `CARGO_BAZEL_TIMEOUT` | An integer value to override the default timeout setting when running the cargo-bazel binary. This value must be in seconds. |
+| `CARGO_BAZEL_TREE_RESOLVER_CACHE_DIR` | A directory in which the per-platform output of `cargo tree` is cached between repins. Cached outputs are reused as long as the `Cargo.lock` file, manifests, toolchain, Cargo config and `CARGO_*` environment variables are unchanged. Any change to these re-resolves every platform, as entries are keyed on the whole workspace rather than individual members. Outputs which haven't been used for 30 days are removed. |
 
 Example:
 
@@ -271,6 +274,7 @@ CARGO_BAZEL_REPIN=1 CARGO_BAZEL_REPIN_ONLY=crate_index bazel sync --only=crate_i
             doc = "Whether or not the toolchain names of windows toolchains are expected to be in a `compressed` format.",
             default = True,
         ),
+        "custom_target_specs": CUSTOM_TARGET_SPECS_ATTR,
         "generate_binaries": attr.bool(
             doc = (
                 "Whether to generate `rust_binary` targets for all the binary crates in every package. " +
@@ -330,6 +334,14 @@ CARGO_BAZEL_REPIN=1 CARGO_BAZEL_REPIN_ONLY=crate_index bazel sync --only=crate_i
         "packages": attr.string_dict(
             doc = "A set of crates (packages) specifications to depend on. See [crate.spec](#crate.spec).",
         ),
+        "platform_cfgs": attr.string_list_dict(
+            doc = (
+                "Extra `cfg` options enabled for platforms in `supported_platform_triples`, such as `--cfg` flags passed to " +
+                "rustc through `rustflags`. Keys are platform triples and values are lists of options in the syntax of " +
+                "`rustc --cfg`, e.g. `tokio_unstable` or `foo=\"bar\"`. `target_feature=\"...\"` options enable target features. " +
+                "These are used both when resolving features with Cargo and when evaluating `cfg(...)` dependencies."
+            ),
+        ),
         "quiet": attr.bool(
             doc = "If stdout and stderr should not be printed to the terminal.",
             default = True,
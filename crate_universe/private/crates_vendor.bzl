@@ -1,6 +1,6 @@
 """Rules for vendoring Bazel targets into existing workspaces"""
 
-load("//crate_universe/private:generate_utils.bzl", "compile_config", generate_render_config = "render_config")
+load("//crate_universe/private:generate_utils.bzl", "CUSTOM_TARGET_SPECS_ATTR", "compile_config", generate_render_config = "render_config")
 load("//crate_universe/private:splicing_utils.bzl", "kebab_case_keys", generate_splicing_config = "splicing_config")
 load("//crate_universe/private:urls.bzl", "CARGO_BAZEL_LABEL")
 load("//rust/platform:triple_mappings.bzl", "SUPPORTED_PLATFORM_TRIPLES")
@@ -42,6 +42,16 @@ fi
 # Pass on CARGO_REGISTRIES_* and CARGO_REGISTRY*
 while IFS= read -r line; do _ENVIRON+=("${{line}}"); done < <(env | grep ^CARGO_REGISTER)
 
+# `add` and `remove` edit the manifests of this target and repin its lockfiles.
+if [[ "${{1:-}}" == "add" || "${{1:-}}" == "remove" ]]; then
+    exec env - \\
+    "${{_ENVIRON[@]}}" \\
+        "${{_BIN}}" \\
+        "$@" \\
+        {repin_args} \\
+        --nonhermetic-root-bazel-workspace-dir="${{BUILD_WORKSPACE_DIRECTORY}}"
+fi
+
 # The path needs to be preserved to prevent bazel from starting with different
 # startup options (requiring a restart of bazel).
 # If you provide an empty path, bazel starts itself with
@@ -121,8 +131,15 @@ set RUNTIME_PWD=%CD%
 
 call :rlocation "{bin}" _BIN
 
+if "%~1"=="add" goto :repin
+if "%~1"=="remove" goto :repin
+
 %_BIN% {args} --nonhermetic-root-bazel-workspace-dir=%BUILD_WORKSPACE_DIRECTORY% %*
 exit %ERRORLEVEL%
+
+:repin
+%_BIN% %* {repin_args} --nonhermetic-root-bazel-workspace-dir=%BUILD_WORKSPACE_DIRECTORY%
+exit %ERRORLEVEL%
 """
 
 CARGO_BAZEL_GENERATOR_PATH = "CARGO_BAZEL_GENERATOR_PATH"
@@ -274,11 +291,12 @@ def _write_config_file(ctx):
             generate_binaries = ctx.attr.generate_binaries,
             generate_build_scripts = ctx.attr.generate_build_scripts,
             generate_target_compatible_with = ctx.attr.generate_target_compatible_with,
-            supported_platform_triples = ctx.attr.supported_platform_triples,
+            supported_platform_triples = ctx.attr.supported_platform_triples + ctx.attr.custom_target_specs.values(),
             repository_name = ctx.attr.repository_name,
             output_pkg = _get_output_package(ctx),
             workspace_name = workspace_name,
             render_config = dict(json.decode(ctx.attr.render_config)) if ctx.attr.render_config else None,
+            platform_cfgs = ctx.attr.platform_cfgs,
         ),
     )
 
@@ -286,6 +304,14 @@ def _write_config_file(ctx):
     env = [_sys_runfile_env(ctx, "CONFIG", config, is_windows)]
     args = ["--config", _expand_env("CONFIG", is_windows)]
     runfiles = [config] + ctx.files.manifests
+
+    # Custom target specifications can't be read during analysis, so `cargo-bazel` reads them from runfiles.
+    for i, (target, triple) in enumerate(ctx.attr.custom_target_specs.items()):
+        spec = target.files.to_list()[0]
+        env.append(_sys_runfile_env(ctx, "TARGET_SPEC_{}".format(i), spec, is_windows))
+        args.extend(["--target-spec", "{}={}".format(triple, _expand_env("TARGET_SPEC_{}".format(i), is_windows))])
+        runfiles.append(spec)
+
     return args, env, runfiles
 
 def generate_config_file(
@@ -300,6 +326,8 @@ def generate_config_file(
         output_pkg,
         workspace_name,
         render_config,
+        platform_cfgs = {},
+        target_specs = {},
         repository_ctx = None):
     """Writes the rendering config to cargo-bazel-config.json.
 
@@ -316,6 +344,10 @@ def generate_config_file(
         output_pkg: The path to the package containing the build files.
         workspace_name (str): The name of the workspace.
         render_config: The render config to use.
+        platform_cfgs (dict, optional): Extra `cfg` options enabled for each
+            platform triple.
+        target_specs (dict, optional): Custom target specifications keyed by
+            the name of their platform triple.
         repository_ctx (repository_ctx, optional): A repository context object
             used for enabling certain functionality.
 
@@ -391,6 +423,8 @@ def generate_config_file(
         render_config = render_config,
         supported_platform_triples = supported_platform_triples,
         repository_name = repository_name or ctx.label.name,
+        platform_cfgs = platform_cfgs,
+        target_specs = target_specs,
         repository_ctx = repository_ctx,
     )
 
@@ -424,22 +458,25 @@ def _crates_vendor_impl(ctx):
             CARGO_BAZEL_GENERATOR_PATH,
         ))
 
+    # Arguments shared with the `add` and `remove` subcommands.
+    repin_args = []
+
     # Generate config file
     config_args, config_env, config_runfiles = _write_config_file(ctx)
     environ.extend(config_env)
-    args.extend(config_args)
+    repin_args.extend(config_args)
     cargo_bazel_runfiles.extend(config_runfiles)
 
     # Generate splicing manifest
     splicing_manifest_args, splicing_manifest_env, splicing_manifest_runfiles = _write_splicing_manifest(ctx)
     environ.extend(splicing_manifest_env)
-    args.extend(splicing_manifest_args)
+    repin_args.extend(splicing_manifest_args)
     cargo_bazel_runfiles.extend(splicing_manifest_runfiles)
 
     # Add an optional `Cargo.lock` file.
     if ctx.attr.cargo_lockfile:
         environ.append(_sys_runfile_env(ctx, "CARGO_LOCK", ctx.file.cargo_lockfile, is_windows))
-        args.extend(["--cargo-lockfile", _expand_env("CARGO_LOCK", is_windows)])
+        repin_args.extend(["--cargo-lockfile", _expand_env("CARGO_LOCK", is_windows)])
         cargo_bazel_runfiles.extend([ctx.file.cargo_lockfile])
 
     # Optionally include buildifier
@@ -457,9 +494,11 @@ def _crates_vendor_impl(ctx):
     # Optionally write the rendering lockfile.
     if ctx.attr.lockfile:
         environ.append(_sys_runfile_env(ctx, "BAZEL_LOCK", ctx.file.lockfile, is_windows))
-        args.extend(["--lockfile", _expand_env("BAZEL_LOCK", is_windows)])
+        repin_args.extend(["--lockfile", _expand_env("BAZEL_LOCK", is_windows)])
         cargo_bazel_runfiles.extend([ctx.file.lockfile])
 
+    args.extend(repin_args)
+
     # Determine platform specific settings
     if is_windows:
         extension = ".bat"
@@ -476,6 +515,7 @@ def _crates_vendor_impl(ctx):
             env = "\n".join(environ),
             bin = bin_path,
             args = " ".join(args),
+            repin_args = " ".join(repin_args),
         ),
         is_executable = True,
     )
@@ -525,6 +565,7 @@ CRATES_VENDOR_ATTRS = {
         doc = "The path to an existing `Cargo.lock` file",
         allow_single_file = True,
     ),
+    "custom_target_specs": CUSTOM_TARGET_SPECS_ATTR,
     "generate_binaries": attr.bool(
         doc = (
             "Whether to generate `rust_binary` targets for all the binary crates in every package. " +
@@ -574,6 +615,14 @@ CRATES_VENDOR_ATTRS = {
     "packages": attr.string_dict(
         doc = "A set of crates (packages) specifications to depend on. See [crate.spec](#crate.spec).",
     ),
+    "platform_cfgs": attr.string_list_dict(
+        doc = (
+            "Extra `cfg` options enabled for platforms in `supported_platform_triples`, such as `--cfg` flags passed to " +
+            "rustc through `rustflags`. Keys are platform triples and values are lists of options in the syntax of " +
+            "`rustc --cfg`, e.g. `tokio_unstable` or `foo=\"bar\"`. `target_feature=\"...\"` options enable target features. " +
+            "These are used both when resolving features with Cargo and when evaluating `cfg(...)` dependencies."
+        ),
+    ),
     "render_config": attr.string(
         doc = (
             "The configuration flags to use for rendering. Use `//crate_universe:defs.bzl\\%render_config` to " +
@@ -677,6 +726,24 @@ call against the generated workspace. The following table describes how to contr
 | `package_name` | `cargo upgrade --package package_name` |
 | `package_name@1.2.3` | `cargo upgrade --package package_name --precise 1.2.3` |
 
+### Adding and removing dependencies
+
+The `add` and `remove` commands of the `crates_vendor` binary edit one of its `manifests` the way
+`cargo add` and `cargo remove` do and update `cargo_lockfile` and `lockfile` to match. Run
+`bazel run //3rdparty:crates_vendor -- add --help` for all options.
+
+```shell
+bazel run //3rdparty:crates_vendor -- add serde@1 --features derive
+bazel run //3rdparty:crates_vendor -- add tempfile --dev
+bazel run //3rdparty:crates_vendor -- add libc --target 'cfg(unix)'
+bazel run //3rdparty:crates_vendor -- remove serde
+```
+
+Use `--manifest` to select the manifest to edit if there is more than one, and `--dry-run` to print
+the edited files, each under a header with its path, instead of writing them. Only Cargo manifests are edited: dependencies declared
+with `packages` (or `crate.spec`) need to be edited manually. Run the `crates_vendor` target
+afterwards to regenerate the `BUILD` files.
+
 """,
     attrs = CRATES_VENDOR_ATTRS,
     executable = True,
@@ -25,6 +25,17 @@ CRATES_REPOSITORY_ENVIRON = GENERATOR_ENV_VARS + REPIN_ENV_VARS + [
     CARGO_BAZEL_TIMEOUT,
 ]
 
+# The `custom_target_specs` attribute shared by `crates_repository`, `crates_vendor` and the `crate` module extension.
+CUSTOM_TARGET_SPECS_ATTR = attr.label_keyed_string_dict(
+    doc = (
+        "[Custom target specifications](https://doc.rust-lang.org/rustc/targets/custom.html) to consider in addition " +
+        "to `supported_platform_triples`, mapped to the name of their platform triple. `render_config.platforms_template` " +
+        "must map each name to a platform constraint. Custom targets are unstable in Cargo and `rustc`, so " +
+        "`RUSTC_BOOTSTRAP=1` is set when resolving features for them."
+    ),
+    allow_files = [".json"],
+)
+
 def get_generator(repository_ctx, host_triple):
     """Query network resources to locate a `cargo-bazel` binary
 
@@ -94,6 +105,7 @@ def render_config(
         crate_label_template = "@{repository}__{name}-{version}//:{target}",
         crate_alias_template = "//:{name}-{version}",
         crate_repository_template = "{repository}__{name}-{version}",
+        crate_mirror_url_templates = [],
         crates_module_template = "//:{file}",
         default_alias_rule = "alias",
         default_package_name = None,
@@ -116,6 +128,7 @@ def render_config(
     | `version` | The crate version. Eg `1.2.3` |
     | `target` | The library or binary target of the crate |
     | `file` | The basename of a file |
+    | `sha256` | The sha256 checksum of a crate's archive |
 
     Args:
         build_file_template (str, optional): The base template to use for BUILD file names. The available format keys
@@ -124,6 +137,9 @@ def render_config(
             are [`{repository}`, `{name}`, `{version}`, `{target}`].
         crate_repository_template (str, optional): The base template to use for Crate label repository names. The
             available format keys are [`{repository}`, `{name}`, `{version}`].
+        crate_mirror_url_templates (list, optional): Templates of mirror URLs to download crates from before falling
+            back to the registry, such as a server for a directory populated by `cargo-bazel mirror`. The available
+            format keys are [`{name}`, `{version}`, `{sha256}`].
         crate_alias_template (str, optional): The template to use when referring to generated aliases within the external
             repository. The available format keys are [`{repository}`, `{name}`, `{version}`].
         crates_module_template (str, optional): The pattern to use for the `defs.bzl` and `BUILD.bazel`
@@ -153,6 +169,7 @@ def render_config(
         build_file_template = build_file_template,
         crate_alias_template = crate_alias_template,
         crate_label_template = crate_label_template,
+        crate_mirror_url_templates = crate_mirror_url_templates,
         crate_repository_template = crate_repository_template,
         crates_module_template = crates_module_template,
         default_alias_rule = parse_alias_rule(default_alias_rule),
@@ -240,6 +257,21 @@ def _get_render_config(repository_ctx):
 
     return config
 
+def read_target_specs(ctx, custom_target_specs):
+    """Load custom target specifications for `compile_config`.
+
+    Args:
+        ctx (repository_ctx or module_ctx): The context used to read the specifications.
+        custom_target_specs (dict): A mapping of specification file labels to the name of their platform triple.
+
+    Returns:
+        dict: The decoded specifications, keyed by the name of their platform triple.
+    """
+    return {
+        triple: json.decode(ctx.read(label))
+        for label, triple in custom_target_specs.items()
+    }
+
 def compile_config(
         crate_annotations,
         generate_binaries,
@@ -249,6 +281,8 @@ def compile_config(
         render_config,
         supported_platform_triples,
         repository_name,
+        platform_cfgs = {},
+        target_specs = {},
         repository_ctx = None):
     """Create a config file for generating crate targets
 
@@ -264,6 +298,10 @@ def compile_config(
         render_config (dict): The deserialized dict of the `render_config` function.
         supported_platform_triples (list): A list of platform triples
         repository_name (str): The name of the repository being generated
+        platform_cfgs (dict, optional): Extra `cfg` options, in the syntax of `rustc --cfg`, enabled
+            for each entry in `supported_platform_triples`.
+        target_specs (dict, optional): Custom target specifications from `read_target_specs`. Their
+            platform triples are supported in addition to `supported_platform_triples`.
         repository_ctx (repository_ctx, optional): A repository context object used for enabling
             certain functionality.
 
@@ -289,6 +327,11 @@ def compile_config(
     if unexpected:
         fail("The following annotations use `additive_build_file` which is not supported for {}: {}".format(repository_name, unexpected))
 
+    triples = list(supported_platform_triples)
+    for triple in target_specs:
+        if triple not in triples:
+            triples.append(triple)
+
     # Deprecated: Apply `generate_target_compatible_with` to `render_config`.
     if not generate_target_compatible_with:
         # buildifier: disable=print
@@ -304,7 +347,9 @@ def compile_config(
             config = render_config,
             repository_name = repository_name,
         ),
-        supported_platform_triples = supported_platform_triples,
+        supported_platform_triples = triples,
+        target_specs = target_specs,
+        platform_cfgs = platform_cfgs,
     )
 
     return config
@@ -328,6 +373,8 @@ def generate_config(repository_ctx):
         render_config = _get_render_config(repository_ctx),
         supported_platform_triples = repository_ctx.attr.supported_platform_triples,
         repository_name = repository_ctx.name,
+        platform_cfgs = repository_ctx.attr.platform_cfgs,
+        target_specs = read_target_specs(repository_ctx, repository_ctx.attr.custom_target_specs),
         repository_ctx = repository_ctx,
     )
 
@@ -361,6 +408,7 @@ def determine_repin(
         lockfile_path,
         config,
         splicing_manifest,
+        cargo_lockfile = None,
         repin_instructions = None):
     """Use the `cargo-bazel` binary to determine whether or not dependencies need to be re-pinned
 
@@ -371,6 +419,7 @@ def determine_repin(
         config (path): The path to a `cargo-bazel` config file. See `generate_config`.
         splicing_manifest (path): The path to a `cargo-bazel` splicing manifest. See `create_splicing_manifest`
         lockfile_path (path): The path to a "lock" file for reproducible outputs.
+        cargo_lockfile (path, optional): The path to a Cargo.lock file, used to report packages which changed since the last repin.
         repin_instructions (optional string): Instructions to re-pin dependencies in your repository. Will be shown when re-pinning is required.
 
     Returns:
@@ -392,17 +441,21 @@ def determine_repin(
     if not lockfile_path:
         return True
 
+    args = [
+        "query",
+        "--lockfile",
+        lockfile_path,
+        "--config",
+        config,
+        "--splicing-manifest",
+        splicing_manifest,
+    ]
+    if cargo_lockfile:
+        args.extend(["--cargo-lockfile", cargo_lockfile])
+
     # Run the binary to check if a repin is needed
     result = cargo_bazel_fn(
-        args = [
-            "query",
-            "--lockfile",
-            lockfile_path,
-            "--config",
-            config,
-            "--splicing-manifest",
-            splicing_manifest,
-        ],
+        args = args,
         allow_fail = True,
     )
 
@@ -0,0 +1,516 @@
+//! The diff module compares two lockfiles, reporting the dependency changes between them in a form suitable for review.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::lockfile::{CargoBazelLockfile, CrateInfo};
+use crate::config::CrateId;
+use crate::context::crate_context::CrateDependency;
+use crate::select::{Select, SelectableOrderedValue};
+
+/// The differences between two lockfiles.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockfileDiff {
+    /// Crates which are only present in the new lockfile.
+    pub added: BTreeSet<CrateId>,
+
+    /// Crates which are only present in the old lockfile.
+    pub removed: BTreeSet<CrateId>,
+
+    /// Crates whose version changed between the two lockfiles.
+    pub version_changes: Vec<VersionChange>,
+
+    /// Crates in the new lockfile which have a build script they did not have before,
+    /// including added crates with build scripts.
+    pub new_build_scripts: BTreeSet<CrateId>,
+
+    /// Changes to crates present in both lockfiles, keyed by their id in the new lockfile.
+    pub changed: BTreeMap<CrateId, CrateChanges>,
+}
+
+/// A crate whose version changed between two lockfiles.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionChange {
+    pub name: String,
+    pub old: semver::Version,
+    pub new: semver::Version,
+}
+
+/// A value which may only apply to a given configuration (e.g. `cfg(unix)`).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Conditional {
+    pub configuration: Option<String>,
+    pub value: String,
+}
+
+/// The changes to a single crate present in both lockfiles.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrateChanges {
+    #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
+    pub features_added: BTreeSet<Conditional>,
+
+    #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
+    pub features_removed: BTreeSet<Conditional>,
+
+    /// Platform-conditional normal and proc-macro dependencies, by crate name.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
+    pub conditional_deps_added: BTreeSet<Conditional>,
+
+    #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
+    pub conditional_deps_removed: BTreeSet<Conditional>,
+
+    /// Configurations added to the `select` of an attribute, keyed by attribute name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub select_branches_added: BTreeMap<String, BTreeSet<String>>,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub select_branches_removed: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl CrateChanges {
+    fn new(old: &CrateInfo, new: &CrateInfo) -> Self {
+        let features = |info: &CrateInfo| conditionals(&info.crate_features(), |f| f.clone());
+        let conditional_deps = |info: &CrateInfo| {
+            let name = |dep: &CrateDependency| dep.id.name.clone();
+            conditionals(&info.normal_deps(), name)
+                .into_iter()
+                .chain(conditionals(&info.proc_macro_deps(), name))
+                .filter(|dep| dep.configuration.is_some())
+                .collect::<BTreeSet<_>>()
+        };
+        let branches = |info: &CrateInfo| {
+            BTreeMap::from([
+                ("crate_features", info.crate_features().configurations()),
+                ("deps", info.normal_deps().configurations()),
+                ("deps_dev", info.dev_deps().configurations()),
+                ("proc_macro_deps", info.proc_macro_deps().configurations()),
+                (
+                    "proc_macro_deps_dev",
+                    info.proc_macro_dev_deps().configurations(),
+                ),
+            ])
+        };
+
+        let (old_features, new_features) = (features(old), features(new));
+        let (old_deps, new_deps) = (conditional_deps(old), conditional_deps(new));
+        let (old_branches, new_branches) = (branches(old), branches(new));
+
+        let branch_difference =
+            |lhs: &BTreeMap<&str, BTreeSet<String>>, rhs: &BTreeMap<&str, BTreeSet<String>>| {
+                lhs.iter()
+                    .map(|(attr, configurations)| {
+                        (
+                            attr.to_string(),
+                            configurations.difference(&rhs[attr]).cloned().collect(),
+                        )
+                    })
+                    .filter(|(_, configurations): &(String, BTreeSet<String>)| {
+                        !configurations.is_empty()
+                    })
+                    .collect()
+            };
+
+        Self {
+            features_added: new_features.difference(&old_features).cloned().collect(),
+            features_removed: old_features.difference(&new_features).cloned().collect(),
+            conditional_deps_added: new_deps.difference(&old_deps).cloned().collect(),
+            conditional_deps_removed: old_deps.difference(&new_deps).cloned().collect(),
+            select_branches_added: branch_difference(&new_branches, &old_branches),
+            select_branches_removed: branch_difference(&old_branches, &new_branches),
+        }
+    }
+
+    /// Whether there were no changes.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+fn conditionals<T, F>(select: &Select<BTreeSet<T>>, value: F) -> BTreeSet<Conditional>
+where
+    T: SelectableOrderedValue,
+    F: Fn(&T) -> String,
+{
+    select
+        .items()
+        .into_iter()
+        .map(|(configuration, item)| Conditional {
+            configuration,
+            value: value(&item),
+        })
+        .collect()
+}
+
+/// Compare two lockfiles.
+pub fn diff(old: &impl CargoBazelLockfile, new: &impl CargoBazelLockfile) -> LockfileDiff {
+    let old_crates = old.crates();
+    let new_crates = new.crates();
+
+    let mut diff = LockfileDiff::default();
+
+    // Group the crates which aren't in both lockfiles by name so that version
+    // changes can be told apart from crates being added or removed.
+    let mut only_old: BTreeMap<&str, Vec<&CrateId>> = BTreeMap::new();
+    for id in old_crates.difference(&new_crates) {
+        only_old.entry(&id.name).or_default().push(id);
+    }
+    let mut only_new: BTreeMap<&str, Vec<&CrateId>> = BTreeMap::new();
+    for id in new_crates.difference(&old_crates) {
+        only_new.entry(&id.name).or_default().push(id);
+    }
+
+    // Crates present in both lockfiles, as pairs of old and new ids.
+    let mut pairs: Vec<(&CrateId, &CrateId)> = old_crates
+        .intersection(&new_crates)
+        .map(|id| (id, id))
+        .collect();
+
+    for (name, new_ids) in &only_new {
+        let mut old_ids = only_old.remove(name).unwrap_or_default();
+
+        // Versions are paired with a semver compatible version first, so that
+        // e.g. `2.0.48 -> 2.0.50` isn't reported as `1.0.100 -> 2.0.50` when
+        // both 1.x and 2.x were locked. The remaining versions are paired up in
+        // order, and anything left over is an addition or removal.
+        let mut version_pairs = Vec::new();
+        let mut unpaired_new_ids = Vec::new();
+        for new_id in new_ids {
+            match old_ids
+                .iter()
+                .position(|old_id| semver_compatible(&old_id.version, &new_id.version))
+            {
+                Some(index) => version_pairs.push((old_ids.remove(index), *new_id)),
+                None => unpaired_new_ids.push(*new_id),
+            }
+        }
+        version_pairs.extend(
+            old_ids
+                .iter()
+                .copied()
+                .zip(unpaired_new_ids.iter().copied()),
+        );
+
+        for (old_id, new_id) in version_pairs {
+            diff.version_changes.push(VersionChange {
+                name: name.to_string(),
+                old: old_id.version.clone(),
+                new: new_id.version.clone(),
+            });
+            pairs.push((old_id, new_id));
+        }
+        diff.added.extend(
+            unpaired_new_ids
+                .iter()
+                .skip(old_ids.len())
+                .map(|id| (*id).clone()),
+        );
+        diff.removed.extend(
+            old_ids
+                .iter()
+                .skip(unpaired_new_ids.len())
+                .map(|id| (*id).clone()),
+        );
+    }
+    diff.removed
+        .extend(only_old.into_values().flatten().cloned());
+
+    for id in &diff.added {
+        if new
+            .crate_info(id)
+            .is_some_and(|info| info.has_build_script())
+        {
+            diff.new_build_scripts.insert(id.clone());
+        }
+    }
+
+    for (old_id, new_id) in pairs {
+        let (Some(old_info), Some(new_info)) = (old.crate_info(old_id), new.crate_info(new_id))
+        else {
+            continue;
+        };
+
+        if new_info.has_build_script() && !old_info.has_build_script() {
+            diff.new_build_scripts.insert(new_id.clone());
+        }
+
+        let changes = CrateChanges::new(&old_info, &new_info);
+        if !changes.is_empty() {
+            diff.changed.insert(new_id.clone(), changes);
+        }
+    }
+
+    diff
+}
+
+/// Whether Cargo considers two versions compatible, i.e. they share the same
+/// leftmost non-zero component.
+fn semver_compatible(lhs: &semver::Version, rhs: &semver::Version) -> bool {
+    match (lhs.major, rhs.major) {
+        (0, 0) => match (lhs.minor, rhs.minor) {
+            (0, 0) => lhs.patch == rhs.patch,
+            (lhs_minor, rhs_minor) => lhs_minor == rhs_minor,
+        },
+        (lhs_major, rhs_major) => lhs_major == rhs_major,
+    }
+}
+
+impl fmt::Display for LockfileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self == &Self::default() {
+            return writeln!(f, "No dependency changes.");
+        }
+
+        fn conditional(value: &Conditional) -> String {
+            match &value.configuration {
+                Some(configuration) => format!("{} ({})", value.value, configuration),
+                None => value.value.clone(),
+            }
+        }
+
+        if !self.added.is_empty() {
+            writeln!(f, "Added crates:")?;
+            for id in &self.added {
+                writeln!(f, "  + {id}")?;
+            }
+        }
+        if !self.removed.is_empty() {
+            writeln!(f, "Removed crates:")?;
+            for id in &self.removed {
+                writeln!(f, "  - {id}")?;
+            }
+        }
+        if !self.version_changes.is_empty() {
+            writeln!(f, "Version changes:")?;
+            for change in &self.version_changes {
+                let direction = if change.new > change.old {
+                    "upgraded"
+                } else {
+                    "downgraded"
+                };
+                writeln!(
+                    f,
+                    "  ~ {} {} -> {} ({direction})",
+                    change.name, change.old, change.new
+                )?;
+            }
+        }
+        if !self.new_build_scripts.is_empty() {
+            writeln!(f, "New build scripts:")?;
+            for id in &self.new_build_scripts {
+                writeln!(f, "  ! {id}")?;
+            }
+        }
+        if !self.changed.is_empty() {
+            writeln!(f, "Changed crates:")?;
+            for (id, changes) in &self.changed {
+                writeln!(f, "  {id}:")?;
+                for (label, values) in [
+                    ("feature added", &changes.features_added),
+                    ("feature removed", &changes.features_removed),
+                    ("conditional dep added", &changes.conditional_deps_added),
+                    ("conditional dep removed", &changes.conditional_deps_removed),
+                ] {
+                    for value in values {
+                        writeln!(f, "    {label}: {}", conditional(value))?;
+                    }
+                }
+                for (label, branches) in [
+                    ("select branch added", &changes.select_branches_added),
+                    ("select branch removed", &changes.select_branches_removed),
+                ] {
+                    for (attr, configurations) in branches {
+                        for configuration in configurations {
+                            writeln!(f, "    {label}: {attr} {configuration}")?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use semver::Version;
+
+    use crate::test::cargo_bazel_lockfile::{MockCrate, MockLockfile};
+
+    #[test]
+    fn diff_lockfiles() {
+        let old = MockLockfile::new([
+            MockCrate::new("anyhow", "1.0.69")
+                .feature(None, "std")
+                .build(),
+            MockCrate::new("syn", "1.0.109").build(),
+            MockCrate::new("libc", "0.2.100").build(),
+            MockCrate::new("rand", "0.8.5")
+                .feature(None, "std")
+                .dep(Some("cfg(unix)"), "libc", "0.2.100")
+                .build(),
+        ])
+        .build();
+        let new = MockLockfile::new([
+            MockCrate::new("anyhow", "1.0.69")
+                .feature(None, "std")
+                .build(),
+            MockCrate::new("syn", "2.0.48").build_script().build(),
+            MockCrate::new("ring", "0.17.8").build_script().build(),
+            MockCrate::new("rand", "0.8.5")
+                .feature(None, "std")
+                .feature(Some("cfg(unix)"), "getrandom")
+                .dep(Some("cfg(windows)"), "windows-sys", "0.52.0")
+                .build(),
+        ])
+        .build();
+
+        let diff = diff(&old, &new);
+        assert_eq!(
+            diff.added,
+            BTreeSet::from([CrateId::new("ring".to_owned(), Version::new(0, 17, 8))])
+        );
+        assert_eq!(
+            diff.removed,
+            BTreeSet::from([CrateId::new("libc".to_owned(), Version::new(0, 2, 100))])
+        );
+        assert_eq!(
+            diff.version_changes,
+            vec![VersionChange {
+                name: "syn".to_owned(),
+                old: Version::new(1, 0, 109),
+                new: Version::new(2, 0, 48),
+            }]
+        );
+        assert_eq!(
+            diff.new_build_scripts,
+            BTreeSet::from([
+                CrateId::new("ring".to_owned(), Version::new(0, 17, 8)),
+                CrateId::new("syn".to_owned(), Version::new(2, 0, 48)),
+            ])
+        );
+
+        let rand = CrateId::new("rand".to_owned(), Version::new(0, 8, 5));
+        assert_eq!(diff.changed.keys().collect::<Vec<_>>(), vec![&rand]);
+        assert_eq!(
+            diff.changed[&rand],
+            CrateChanges {
+                features_added: BTreeSet::from([Conditional {
+                    configuration: Some("cfg(unix)".to_owned()),
+                    value: "getrandom".to_owned(),
+                }]),
+                features_removed: BTreeSet::new(),
+                conditional_deps_added: BTreeSet::from([Conditional {
+                    configuration: Some("cfg(windows)".to_owned()),
+                    value: "windows-sys".to_owned(),
+                }]),
+                conditional_deps_removed: BTreeSet::from([Conditional {
+                    configuration: Some("cfg(unix)".to_owned()),
+                    value: "libc".to_owned(),
+                }]),
+                select_branches_added: BTreeMap::from([
+                    (
+                        "crate_features".to_owned(),
+                        BTreeSet::from(["cfg(unix)".to_owned()])
+                    ),
+                    (
+                        "deps".to_owned(),
+                        BTreeSet::from(["cfg(windows)".to_owned()])
+                    ),
+                ]),
+                select_branches_removed: BTreeMap::from([(
+                    "deps".to_owned(),
+                    BTreeSet::from(["cfg(unix)".to_owned()])
+                )]),
+            }
+        );
+
+        assert_eq!(
+            diff.to_string(),
+            "\
+Added crates:
+  + ring 0.17.8
+Removed crates:
+  - libc 0.2.100
+Version changes:
+  ~ syn 1.0.109 -> 2.0.48 (upgraded)
+New build scripts:
+  ! ring 0.17.8
+  ! syn 2.0.48
+Changed crates:
+  rand 0.8.5:
+    feature added: getrandom (cfg(unix))
+    conditional dep added: windows-sys (cfg(windows))
+    conditional dep removed: libc (cfg(unix))
+    select branch added: crate_features cfg(unix)
+    select branch added: deps cfg(windows)
+    select branch removed: deps cfg(unix)
+"
+        );
+    }
+
+    #[test]
+    fn diff_pairs_semver_compatible_versions() {
+        let old = MockLockfile::new([
+            MockCrate::new("syn", "1.0.100").build(),
+            MockCrate::new("syn", "2.0.48").build(),
+        ])
+        .build();
+        let new = MockLockfile::new([MockCrate::new("syn", "2.0.50").build()]).build();
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.added, BTreeSet::new());
+        assert_eq!(
+            changes.removed,
+            BTreeSet::from([CrateId::new("syn".to_owned(), Version::new(1, 0, 100))])
+        );
+        assert_eq!(
+            changes.version_changes,
+            vec![VersionChange {
+                name: "syn".to_owned(),
+                old: Version::new(2, 0, 48),
+                new: Version::new(2, 0, 50),
+            }]
+        );
+
+        // Pre-1.0 versions are compatible within a minor version.
+        let old = MockLockfile::new([
+            MockCrate::new("rand", "0.7.3").build(),
+            MockCrate::new("rand", "0.8.4").build(),
+        ])
+        .build();
+        let new = MockLockfile::new([
+            MockCrate::new("rand", "0.8.5").build(),
+            MockCrate::new("rand", "0.9.0").build(),
+        ])
+        .build();
+        assert_eq!(
+            diff(&old, &new).version_changes,
+            vec![
+                VersionChange {
+                    name: "rand".to_owned(),
+                    old: Version::new(0, 8, 4),
+                    new: Version::new(0, 8, 5),
+                },
+                VersionChange {
+                    name: "rand".to_owned(),
+                    old: Version::new(0, 7, 3),
+                    new: Version::new(0, 9, 0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_identical_lockfiles() {
+        let lockfile =
+            MockLockfile::new([MockCrate::new("anyhow", "1.0.69").build_script().build()]).build();
+        let diff = diff(&lockfile, &lockfile);
+        assert_eq!(diff, LockfileDiff::default());
+        assert_eq!(diff.to_string(), "No dependency changes.\n");
+    }
+}
@@ -0,0 +1,688 @@
+//! The duplicates module finds crates resolved at multiple versions and features which are only
+//! enabled on some platforms, similar to `cargo tree --duplicates` for the per-platform graph.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::lockfile::{CargoBazelLockfile, DependencyKind};
+use crate::config::CrateId;
+use crate::metadata::TreeResolverMetadata;
+
+/// Crates resolved at multiple versions, and features enabled only on some platforms.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicatesReport {
+    /// Every platform the lockfile was resolved for.
+    pub platforms: BTreeSet<String>,
+
+    /// The versions of each crate resolved at more than one version, keyed by crate name.
+    pub duplicates: BTreeMap<String, Vec<DuplicateVersion>>,
+
+    /// Features enabled only on some platforms, keyed by crate, then by feature.
+    /// The values are the platforms each feature is enabled on.
+    pub platform_features: BTreeMap<CrateId, BTreeMap<String, BTreeSet<String>>>,
+}
+
+/// One of the versions of a duplicated crate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateVersion {
+    /// The version of the crate.
+    pub version: semver::Version,
+
+    /// The platforms the version is resolved on alongside another version of the crate,
+    /// or `None` if it is duplicated on every platform.
+    pub platforms: Option<BTreeSet<String>>,
+
+    /// For each direct dependency of a workspace member which pulls in this version, the
+    /// shortest path from the workspace member to it.
+    pub paths: Vec<Vec<CrateId>>,
+
+    /// Direct dependencies of workspace members which could be bumped to remove this version.
+    /// Empty for the newest version.
+    pub bump: BTreeSet<CrateId>,
+}
+
+/// Find the crates in a lockfile which are resolved at multiple versions, following `kinds` of dependency.
+pub fn duplicates(
+    lockfile: &impl CargoBazelLockfile,
+    kinds: &[DependencyKind],
+) -> DuplicatesReport {
+    find_duplicates(lockfile, kinds, None)
+}
+
+/// Find the crates in a lockfile which are resolved at multiple versions, following the
+/// dependencies `cargo tree` resolved for each platform (see [crate::metadata::TreeResolver])
+/// rather than those recorded in the lockfile. Only dependencies the lockfile records through
+/// `kinds` of dependency, on any platform, are followed.
+pub(crate) fn duplicates_with_tree_metadata(
+    lockfile: &impl CargoBazelLockfile,
+    kinds: &[DependencyKind],
+    tree_metadata: &TreeResolverMetadata,
+) -> DuplicatesReport {
+    find_duplicates(lockfile, kinds, Some(tree_metadata))
+}
+
+fn find_duplicates(
+    lockfile: &impl CargoBazelLockfile,
+    kinds: &[DependencyKind],
+    tree_metadata: Option<&TreeResolverMetadata>,
+) -> DuplicatesReport {
+    let workspace_members = lockfile.workspace_members();
+    let platforms: BTreeSet<String> = lockfile.conditions().into_values().flatten().collect();
+
+    // Without any conditions every dependency applies everywhere, so one graph covers all
+    // platforms.
+    let triples: Vec<Option<&str>> = if platforms.is_empty() {
+        vec![None]
+    } else {
+        platforms
+            .iter()
+            .map(|triple| Some(triple.as_str()))
+            .collect()
+    };
+    let graphs: Vec<(Option<&str>, Graph)> = triples
+        .into_iter()
+        .map(|triple| {
+            (
+                triple,
+                Graph::new(lockfile, &workspace_members, kinds, triple, tree_metadata),
+            )
+        })
+        .collect();
+
+    // Only crates reachable from a workspace member are considered, so crates from other
+    // kinds of dependency don't show up as duplicates. Versions which are only reachable
+    // through contradicting configurations, such as a Windows only dependency of a unix only
+    // crate, aren't built on any platform, so they aren't reachable in any graph.
+    let reachable: Vec<BTreeSet<&CrateId>> =
+        graphs.iter().map(|(_, graph)| graph.reachable()).collect();
+
+    let dependencies: BTreeSet<&CrateId> = reachable
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|crate_id| !workspace_members.contains(*crate_id))
+        .collect();
+
+    // A crate is only duplicated if multiple versions of it are resolved on the same
+    // platform. Versions which are each only resolved on different platforms are never
+    // built together.
+    let mut by_name: BTreeMap<&str, BTreeSet<&CrateId>> = BTreeMap::new();
+    let mut duplicated_on: BTreeMap<&CrateId, BTreeSet<String>> = BTreeMap::new();
+    for ((triple, _), reachable) in graphs.iter().zip(&reachable) {
+        let mut versions: BTreeMap<&str, Vec<&CrateId>> = BTreeMap::new();
+        for crate_id in reachable {
+            if dependencies.contains(crate_id) {
+                versions.entry(&crate_id.name).or_default().push(crate_id);
+            }
+        }
+        for (name, crate_ids) in versions {
+            if crate_ids.len() < 2 {
+                continue;
+            }
+            for crate_id in crate_ids {
+                by_name.entry(name).or_default().insert(crate_id);
+                duplicated_on
+                    .entry(crate_id)
+                    .or_default()
+                    .extend(triple.map(str::to_owned));
+            }
+        }
+    }
+
+    let mut report = DuplicatesReport {
+        platforms: platforms.clone(),
+        ..DuplicatesReport::default()
+    };
+    for (name, crate_ids) in &by_name {
+        let newest = crate_ids.iter().map(|id| &id.version).max().cloned();
+
+        let versions = crate_ids
+            .iter()
+            .map(|crate_id| {
+                let present_on = &duplicated_on[crate_id];
+                // The shortest path on any platform the version is duplicated on, for each
+                // direct dependency of a workspace member.
+                let mut shortest: BTreeMap<(&CrateId, &CrateId), Vec<CrateId>> = BTreeMap::new();
+                for (triple, graph) in &graphs {
+                    if triple.is_some_and(|triple| !present_on.contains(triple)) {
+                        continue;
+                    }
+
+                    let distances = graph.distances_to(crate_id);
+                    for (member, direct_deps) in &graph.roots {
+                        for direct in direct_deps {
+                            let Some(path) = graph.path(direct, &distances) else {
+                                continue;
+                            };
+                            let path = [vec![member.clone()], path].concat();
+                            shortest
+                                .entry((member, direct))
+                                .and_modify(|existing| {
+                                    if path.len() < existing.len() {
+                                        *existing = path.clone();
+                                    }
+                                })
+                                .or_insert(path);
+                        }
+                    }
+                }
+                let paths: Vec<Vec<CrateId>> = shortest.into_values().collect();
+
+                let bump = if Some(&crate_id.version) == newest.as_ref() {
+                    BTreeSet::new()
+                } else {
+                    paths.iter().map(|path| path[1].clone()).collect()
+                };
+
+                DuplicateVersion {
+                    version: crate_id.version.clone(),
+                    platforms: (present_on != &platforms).then(|| present_on.clone()),
+                    paths,
+                    bump,
+                }
+            })
+            .collect();
+        report.duplicates.insert((*name).to_owned(), versions);
+    }
+
+    let conditions = lockfile.conditions();
+    for crate_id in dependencies {
+        let Some(info) = lockfile.crate_info(crate_id) else {
+            continue;
+        };
+        let features = info.crate_features();
+        let common: BTreeSet<String> = features
+            .items()
+            .into_iter()
+            .filter_map(|(configuration, feature)| configuration.is_none().then_some(feature))
+            .collect();
+
+        let mut feature_platforms: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for (configuration, feature) in features.items() {
+            let Some(configuration) = configuration else {
+                continue;
+            };
+            if common.contains(&feature) {
+                continue;
+            }
+            feature_platforms
+                .entry(feature)
+                .or_default()
+                .extend(conditions.get(&configuration).cloned().unwrap_or_default());
+        }
+        feature_platforms.retain(|_, enabled_on| enabled_on != &platforms);
+
+        if !feature_platforms.is_empty() {
+            report
+                .platform_features
+                .insert(crate_id.clone(), feature_platforms);
+        }
+    }
+
+    report
+}
+
+/// The dependency graph of a lockfile on a single platform, or on every platform at once if
+/// it was built without a triple.
+///
+/// Without `cargo tree`'s output for each platform, the graph is built from the dependencies
+/// recorded in the lockfile, which only reflect its feature resolution for optional
+/// dependencies.
+struct Graph {
+    /// The direct dependencies of each workspace member, through every requested kind of
+    /// dependency.
+    roots: BTreeMap<CrateId, BTreeSet<CrateId>>,
+
+    /// The direct dependencies of each crate which are needed to build it.
+    deps: BTreeMap<CrateId, BTreeSet<CrateId>>,
+
+    /// The crates which directly depend on each crate, the inverse of `deps`.
+    dependents: BTreeMap<CrateId, BTreeSet<CrateId>>,
+}
+
+impl Graph {
+    fn new(
+        lockfile: &impl CargoBazelLockfile,
+        workspace_members: &BTreeSet<CrateId>,
+        kinds: &[DependencyKind],
+        triple: Option<&str>,
+        tree_metadata: Option<&TreeResolverMetadata>,
+    ) -> Self {
+        // Dev dependencies are only followed from workspace members, as those of their
+        // dependencies aren't needed to build them.
+        let transitive_kinds: Vec<DependencyKind> = kinds
+            .iter()
+            .copied()
+            .filter(|kind| !kind.is_dev())
+            .collect();
+
+        let dependencies = |crate_id: &CrateId, kinds: &[DependencyKind]| match tree_metadata {
+            Some(tree_metadata) => {
+                let deps = lockfile.dependencies(crate_id, kinds, None);
+                tree_metadata
+                    .get(crate_id)
+                    .map(|select| select.items())
+                    .into_iter()
+                    .flatten()
+                    .filter(|(configuration, _)| match (configuration, triple) {
+                        (Some(configuration), Some(triple)) => configuration == triple,
+                        _ => true,
+                    })
+                    .flat_map(|(_, entry)| entry.deps)
+                    .filter(|dep| deps.contains(dep))
+                    .collect()
+            }
+            None => lockfile.dependencies(crate_id, kinds, triple),
+        };
+
+        let roots = workspace_members
+            .iter()
+            .map(|member| (member.clone(), dependencies(member, kinds)))
+            .collect();
+        let deps: BTreeMap<CrateId, BTreeSet<CrateId>> = lockfile
+            .crates()
+            .into_iter()
+            .map(|crate_id| {
+                let deps = dependencies(&crate_id, &transitive_kinds);
+                (crate_id, deps)
+            })
+            .collect();
+        let mut dependents: BTreeMap<CrateId, BTreeSet<CrateId>> = BTreeMap::new();
+        for (crate_id, deps) in &deps {
+            for dep in deps {
+                dependents
+                    .entry(dep.clone())
+                    .or_default()
+                    .insert(crate_id.clone());
+            }
+        }
+
+        Self {
+            roots,
+            deps,
+            dependents,
+        }
+    }
+
+    /// Every crate reachable from a workspace member, not including the members themselves
+    /// unless another member depends on them.
+    fn reachable(&self) -> BTreeSet<&CrateId> {
+        let mut reachable = BTreeSet::new();
+        let mut queue: Vec<&CrateId> = self.roots.values().flatten().collect();
+        while let Some(crate_id) = queue.pop() {
+            if reachable.insert(crate_id) {
+                queue.extend(self.deps.get(crate_id).into_iter().flatten());
+            }
+        }
+        reachable
+    }
+
+    /// The length of the shortest dependency path from each crate which depends on `to`,
+    /// directly or transitively.
+    fn distances_to(&self, to: &CrateId) -> BTreeMap<&CrateId, usize> {
+        let Some((to, _)) = self.deps.get_key_value(to) else {
+            return BTreeMap::new();
+        };
+        let mut distances = BTreeMap::from([(to, 0)]);
+        let mut queue = VecDeque::from([to]);
+        while let Some(current) = queue.pop_front() {
+            let distance = distances[current] + 1;
+            for dependent in self.dependents.get(current).into_iter().flatten() {
+                distances.entry(dependent).or_insert_with(|| {
+                    queue.push_back(dependent);
+                    distance
+                });
+            }
+        }
+        distances
+    }
+
+    /// The shortest dependency path from `from` to the crate `distances` were computed for,
+    /// including both ends.
+    fn path(&self, from: &CrateId, distances: &BTreeMap<&CrateId, usize>) -> Option<Vec<CrateId>> {
+        let mut distance = *distances.get(from)?;
+        let mut path = vec![from.clone()];
+        while distance > 0 {
+            distance -= 1;
+            let next = self.deps[path.last().unwrap()]
+                .iter()
+                .find(|dep| distances.get(dep) == Some(&distance))?;
+            path.push(next.clone());
+        }
+        Some(path)
+    }
+}
+
+impl DuplicatesReport {
+    /// Whether no duplicates or platform specific features were found.
+    pub fn is_empty(&self) -> bool {
+        self.duplicates.is_empty() && self.platform_features.is_empty()
+    }
+
+    /// Describe a subset of all platforms, by whichever of its members or the platforms it
+    /// excludes is shorter.
+    fn describe_platforms(&self, platforms: &BTreeSet<String>) -> String {
+        let join = |platforms: Vec<&String>| {
+            platforms
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if platforms.len() * 2 > self.platforms.len() {
+            format!(
+                "on all platforms except {}",
+                join(self.platforms.difference(platforms).collect())
+            )
+        } else {
+            format!("only on {}", join(platforms.iter().collect()))
+        }
+    }
+}
+
+impl fmt::Display for DuplicatesReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(
+                f,
+                "No duplicate crates or platform specific features were found."
+            );
+        }
+
+        fn path(path: &[CrateId]) -> String {
+            path.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        }
+
+        if !self.duplicates.is_empty() {
+            writeln!(f, "Duplicate crates:")?;
+        }
+        for (name, versions) in &self.duplicates {
+            writeln!(f, "  {name}:")?;
+            for version in versions {
+                match &version.platforms {
+                    Some(platforms) => writeln!(
+                        f,
+                        "    {} ({})",
+                        version.version,
+                        self.describe_platforms(platforms)
+                    )?,
+                    None => writeln!(f, "    {}", version.version)?,
+                }
+                for dependency_path in &version.paths {
+                    writeln!(f, "      {}", path(dependency_path))?;
+                }
+                for direct in &version.bump {
+                    writeln!(f, "      consider bumping {direct}")?;
+                }
+            }
+        }
+
+        if !self.platform_features.is_empty() {
+            writeln!(f, "Features enabled only on some platforms:")?;
+        }
+        for (crate_id, features) in &self.platform_features {
+            writeln!(f, "  {crate_id}:")?;
+            for (feature, platforms) in features {
+                writeln!(f, "    {feature}: {}", self.describe_platforms(platforms))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use semver::Version;
+
+    use crate::api::lockfile::CargoBazelLockfileImpl;
+    use crate::metadata::CargoTreeEntry;
+    use crate::select::Select;
+    use crate::test::cargo_bazel_lockfile::{crate_id, MockCrate, MockLockfile};
+
+    const LINUX: &str = "x86_64-unknown-linux-gnu";
+    const WINDOWS: &str = "x86_64-pc-windows-msvc";
+
+    fn mock_lockfile() -> CargoBazelLockfileImpl {
+        MockLockfile::new([
+            MockCrate::new("app", "0.1.0")
+                .workspace_member()
+                .dep(None, "serde", "1.0.0")
+                .dep(None, "old_derive", "0.1.0")
+                .dep(Some("cfg(windows)"), "windows-sys", "0.52.0")
+                .dep(None, "mio", "0.8.0")
+                .dep(Some("cfg(unix)"), "nix", "0.1.0")
+                .dep(Some("cfg(unix)"), "libc", "0.2.0")
+                .dep(Some("cfg(windows)"), "libc", "0.1.0")
+                .build(),
+            MockCrate::new("serde", "1.0.0")
+                .dep(None, "syn", "2.0.0")
+                .build(),
+            MockCrate::new("old_derive", "0.1.0")
+                .dep(None, "syn", "1.0.0")
+                .build(),
+            MockCrate::new("syn", "1.0.0").build(),
+            MockCrate::new("syn", "2.0.0").feature(None, "full").build(),
+            MockCrate::new("windows-sys", "0.52.0")
+                .feature(Some("cfg(windows)"), "Win32_Foundation")
+                .build(),
+            MockCrate::new("mio", "0.8.0")
+                .dep(Some("cfg(windows)"), "windows-sys", "0.48.0")
+                .feature(None, "os-poll")
+                .feature(Some("cfg(unix)"), "net")
+                .feature(Some("cfg(windows)"), "net")
+                .build(),
+            MockCrate::new("windows-sys", "0.48.0").build(),
+            MockCrate::new("nix", "0.1.0")
+                .dep(Some("cfg(windows)"), "windows-sys", "0.45.0")
+                .dep(Some("cfg(windows)"), "windows-sys", "0.48.0")
+                .build(),
+            MockCrate::new("windows-sys", "0.45.0").build(),
+            MockCrate::new("libc", "0.1.0").build(),
+            MockCrate::new("libc", "0.2.0").build(),
+        ])
+        .condition("cfg(unix)", &[LINUX])
+        .condition("cfg(windows)", &[WINDOWS])
+        .build()
+    }
+
+    #[test]
+    fn find_duplicates() {
+        let report = duplicates(&mock_lockfile(), &DependencyKind::BUILD);
+
+        assert_eq!(
+            report.duplicates["syn"],
+            vec![
+                DuplicateVersion {
+                    version: Version::new(1, 0, 0),
+                    platforms: None,
+                    paths: vec![vec![
+                        crate_id("app", "0.1.0"),
+                        crate_id("old_derive", "0.1.0"),
+                        crate_id("syn", "1.0.0"),
+                    ]],
+                    bump: BTreeSet::from([crate_id("old_derive", "0.1.0")]),
+                },
+                DuplicateVersion {
+                    version: Version::new(2, 0, 0),
+                    platforms: None,
+                    paths: vec![vec![
+                        crate_id("app", "0.1.0"),
+                        crate_id("serde", "1.0.0"),
+                        crate_id("syn", "2.0.0"),
+                    ]],
+                    bump: BTreeSet::new(),
+                },
+            ]
+        );
+
+        // `nix` only depends on `windows-sys` on Windows, but `nix` is itself only a
+        // dependency on unix, so 0.45.0 is never built and `nix` isn't a path to 0.48.0.
+        let windows = Some(BTreeSet::from([WINDOWS.to_owned()]));
+        assert_eq!(
+            report.duplicates["windows-sys"],
+            vec![
+                DuplicateVersion {
+                    version: Version::new(0, 48, 0),
+                    platforms: windows.clone(),
+                    paths: vec![vec![
+                        crate_id("app", "0.1.0"),
+                        crate_id("mio", "0.8.0"),
+                        crate_id("windows-sys", "0.48.0"),
+                    ]],
+                    bump: BTreeSet::from([crate_id("mio", "0.8.0")]),
+                },
+                DuplicateVersion {
+                    version: Version::new(0, 52, 0),
+                    platforms: windows,
+                    paths: vec![vec![
+                        crate_id("app", "0.1.0"),
+                        crate_id("windows-sys", "0.52.0"),
+                    ]],
+                    bump: BTreeSet::new(),
+                },
+            ]
+        );
+
+        // Each version of `libc` is only resolved on one platform, so they're never built
+        // together.
+        assert!(!report.duplicates.contains_key("libc"));
+        assert_eq!(report.duplicates.len(), 2);
+    }
+
+    #[test]
+    fn find_duplicates_with_tree_metadata() {
+        let entry = |deps: &[(&str, &str)]| CargoTreeEntry {
+            features: BTreeSet::new(),
+            deps: deps
+                .iter()
+                .map(|(name, version)| crate_id(name, version))
+                .collect(),
+        };
+        let select = |items: Vec<(Option<&str>, CargoTreeEntry)>| {
+            let mut select = Select::new();
+            for (configuration, entry) in items {
+                select.insert(entry, configuration.map(str::to_owned));
+            }
+            select
+        };
+
+        // `cargo tree` didn't resolve `mio`'s dependency on `windows-sys` 0.48.0, but did
+        // resolve both versions of `libc` on Linux, unlike the lockfile.
+        let tree_metadata = TreeResolverMetadata::from([
+            (
+                crate_id("app", "0.1.0"),
+                select(vec![
+                    (
+                        None,
+                        entry(&[
+                            ("serde", "1.0.0"),
+                            ("old_derive", "0.1.0"),
+                            ("mio", "0.8.0"),
+                        ]),
+                    ),
+                    (
+                        Some(LINUX),
+                        entry(&[("nix", "0.1.0"), ("libc", "0.1.0"), ("libc", "0.2.0")]),
+                    ),
+                    (
+                        Some(WINDOWS),
+                        entry(&[("windows-sys", "0.52.0"), ("libc", "0.1.0")]),
+                    ),
+                ]),
+            ),
+            (
+                crate_id("serde", "1.0.0"),
+                select(vec![(None, entry(&[("syn", "2.0.0")]))]),
+            ),
+            (
+                crate_id("old_derive", "0.1.0"),
+                select(vec![(None, entry(&[("syn", "1.0.0")]))]),
+            ),
+        ]);
+
+        let report =
+            duplicates_with_tree_metadata(&mock_lockfile(), &DependencyKind::BUILD, &tree_metadata);
+        assert_eq!(
+            report.duplicates.keys().collect::<Vec<_>>(),
+            vec!["libc", "syn"]
+        );
+        let linux = Some(BTreeSet::from([LINUX.to_owned()]));
+        assert_eq!(
+            report.duplicates["libc"],
+            vec![
+                DuplicateVersion {
+                    version: Version::new(0, 1, 0),
+                    platforms: linux.clone(),
+                    paths: vec![vec![crate_id("app", "0.1.0"), crate_id("libc", "0.1.0")]],
+                    bump: BTreeSet::from([crate_id("libc", "0.1.0")]),
+                },
+                DuplicateVersion {
+                    version: Version::new(0, 2, 0),
+                    platforms: linux,
+                    paths: vec![vec![crate_id("app", "0.1.0"), crate_id("libc", "0.2.0")]],
+                    bump: BTreeSet::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn find_platform_features() {
+        let report = duplicates(&mock_lockfile(), &DependencyKind::BUILD);
+
+        assert_eq!(
+            report.platform_features,
+            BTreeMap::from([(
+                crate_id("windows-sys", "0.52.0"),
+                BTreeMap::from([(
+                    "Win32_Foundation".to_owned(),
+                    BTreeSet::from([WINDOWS.to_owned()])
+                )]),
+            )])
+        );
+    }
+
+    #[test]
+    fn display_duplicates() {
+        let report = duplicates(&mock_lockfile(), &DependencyKind::BUILD);
+        let text = report.to_string();
+
+        assert!(text.starts_with("Duplicate crates:\n  syn:\n    1.0.0\n      app 0.1.0 -> old_derive 0.1.0 -> syn 1.0.0\n      consider bumping old_derive 0.1.0\n"), "{text}");
+        assert!(
+            text.contains("    0.52.0 (only on x86_64-pc-windows-msvc)\n"),
+            "{text}"
+        );
+        assert!(text.contains("Features enabled only on some platforms:\n  windows-sys 0.52.0:\n    Win32_Foundation: only on x86_64-pc-windows-msvc\n"), "{text}");
+
+        let report = DuplicatesReport {
+            platforms: BTreeSet::from(["a".to_owned(), "b".to_owned(), "c".to_owned()]),
+            duplicates: BTreeMap::new(),
+            platform_features: BTreeMap::from([(
+                crate_id("tokio", "1.0.0"),
+                BTreeMap::from([(
+                    "net".to_owned(),
+                    BTreeSet::from(["a".to_owned(), "b".to_owned()]),
+                )]),
+            )]),
+        };
+        assert!(
+            report
+                .to_string()
+                .contains("    net: on all platforms except c\n"),
+            "{report}"
+        );
+
+        assert_eq!(
+            DuplicatesReport::default().to_string(),
+            "No duplicate crates or platform specific features were found.\n"
+        );
+    }
+}
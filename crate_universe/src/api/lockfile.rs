@@ -1,17 +1,21 @@
 //! The lockfile::public module represents a reasonable stable API for inspecting the contents of a lockfile which others can code against.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 
 use anyhow::Result;
-use serde::Deserialize;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::config::Commitish;
 pub use crate::config::CrateId;
-use crate::context::crate_context::{CrateDependency, Rule};
+pub use crate::context::crate_context::CrateDependency;
+use crate::context::crate_context::{BuildScriptAttributes, CrateContext, Rule};
 use crate::context::{CommonAttributes, Context};
-use crate::select::Select;
+use crate::metadata::SourceAnnotation;
+pub use crate::select::{Select, Selectable};
 
 /// Parse a lockfile at a path on disk.
 pub fn parse(path: &Path) -> Result<impl CargoBazelLockfile> {
@@ -20,6 +24,13 @@ pub fn parse(path: &Path) -> Result<impl CargoBazelLockfile> {
     Ok(lockfile)
 }
 
+/// Parse a [LockfileSnapshot] at a path on disk.
+pub fn parse_snapshot(path: &Path) -> Result<LockfileSnapshot> {
+    let reader = BufReader::new(File::open(path)?);
+    let snapshot: LockfileSnapshot = serde_json::from_reader(reader)?;
+    Ok(snapshot)
+}
+
 /// `CargoBazelLockfile` provides a view over `cargo-bazel`'s lockfile format.
 ///
 /// This trait provides information about the third-party dependencies of a workspace.
@@ -32,43 +43,575 @@ pub trait CargoBazelLockfile {
     /// These are typically not very interesting on their own, but can be used as roots for navigating what dependencies these crates have.
     fn workspace_members(&self) -> BTreeSet<CrateId>;
 
+    /// Get every crate in the lockfile, including both workspace members and external dependencies.
+    fn crates(&self) -> BTreeSet<CrateId>;
+
     /// Get information about a specific crate (which may be in the local workspace, or an external dependency).
     fn crate_info(&self, crate_id: &CrateId) -> Option<CrateInfo>;
+
+    /// Get the platform triples each configuration of a [Select] applies to.
+    fn conditions(&self) -> BTreeMap<String, BTreeSet<String>>;
+
+    /// Get the values of a [Select] which apply to the platform `triple`: the common values
+    /// followed by the values of every configuration matching the platform.
+    fn resolve_select<T>(&self, select: &Select<T>, triple: &str) -> Vec<T::ItemType>
+    where
+        Self: Sized,
+        T: Selectable,
+    {
+        let conditions = self.conditions();
+        select
+            .items()
+            .into_iter()
+            .filter(|(configuration, _)| match configuration {
+                None => true,
+                Some(configuration) => conditions
+                    .get(configuration)
+                    .is_some_and(|triples| triples.contains(triple)),
+            })
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    /// Get the crates `crate_id` directly depends on through any of `kinds` of dependency.
+    /// If `triple` is set, only dependencies which apply to that platform are included.
+    fn dependencies(
+        &self,
+        crate_id: &CrateId,
+        kinds: &[DependencyKind],
+        triple: Option<&str>,
+    ) -> BTreeSet<CrateId>
+    where
+        Self: Sized,
+    {
+        let Some(info) = self.crate_info(crate_id) else {
+            return BTreeSet::new();
+        };
+        kinds
+            .iter()
+            .flat_map(|kind| {
+                let deps = info.dependencies(*kind);
+                match triple {
+                    Some(triple) => self.resolve_select(&deps, triple),
+                    None => deps.values(),
+                }
+            })
+            .map(|dep| dep.id)
+            .collect()
+    }
+
+    /// Get every crate `crate_id` transitively depends on through any of `kinds` of dependency,
+    /// not including `crate_id` itself. Dev dependencies are only followed from `crate_id`, as
+    /// those of its dependencies aren't needed to build it.
+    /// If `triple` is set, only dependencies which apply to that platform are included.
+    fn transitive_dependencies(
+        &self,
+        crate_id: &CrateId,
+        kinds: &[DependencyKind],
+        triple: Option<&str>,
+    ) -> BTreeSet<CrateId>
+    where
+        Self: Sized,
+    {
+        let transitive_kinds: Vec<DependencyKind> = kinds
+            .iter()
+            .copied()
+            .filter(|kind| !kind.is_dev())
+            .collect();
+
+        let mut closure = BTreeSet::new();
+        let mut queue: Vec<CrateId> = self
+            .dependencies(crate_id, kinds, triple)
+            .into_iter()
+            .collect();
+        while let Some(dep) = queue.pop() {
+            if closure.insert(dep.clone()) {
+                queue.extend(self.dependencies(&dep, &transitive_kinds, triple));
+            }
+        }
+        closure
+    }
+
+    /// Get the crates which directly depend on `crate_id` through any of `kinds` of dependency.
+    /// If `triple` is set, only dependencies which apply to that platform are considered.
+    fn reverse_dependencies(
+        &self,
+        crate_id: &CrateId,
+        kinds: &[DependencyKind],
+        triple: Option<&str>,
+    ) -> BTreeSet<CrateId>
+    where
+        Self: Sized,
+    {
+        self.crates()
+            .into_iter()
+            .filter(|dependent| {
+                self.dependencies(dependent, kinds, triple)
+                    .contains(crate_id)
+            })
+            .collect()
+    }
 }
 
 #[derive(Deserialize)]
 #[serde(transparent)]
-struct CargoBazelLockfileImpl(Context);
+pub(crate) struct CargoBazelLockfileImpl(pub(crate) Context);
 
 impl CargoBazelLockfile for CargoBazelLockfileImpl {
     fn workspace_members(&self) -> BTreeSet<CrateId> {
         self.0.workspace_members.keys().cloned().collect()
     }
 
+    fn crates(&self) -> BTreeSet<CrateId> {
+        self.0.crates.keys().cloned().collect()
+    }
+
     fn crate_info(&self, crate_id: &CrateId) -> Option<CrateInfo> {
-        let crate_context = self.0.crates.get(crate_id)?;
-        Some(CrateInfo {
-            name: crate_context.name.clone(),
-            version: crate_context.version.clone(),
-            library_target_name: crate_context.library_target_name.clone(),
-            is_proc_macro: crate_context
-                .targets
-                .iter()
-                .any(|t| matches!(t, Rule::ProcMacro(_))),
-            common_attributes: crate_context.common_attrs.clone(),
+        self.0.crates.get(crate_id).map(CrateInfo::from)
+    }
+
+    fn conditions(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.0
+            .conditions
+            .iter()
+            .map(|(configuration, triples)| {
+                (
+                    configuration.clone(),
+                    triples.iter().map(|triple| triple.to_bazel()).collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// A kind of dependency between crates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyKind {
+    /// Dependencies required to compile the crate, without procedural macro dependencies.
+    Normal,
+
+    /// Dependencies only required to compile the crate's tests, without procedural macro dependencies.
+    Dev,
+
+    /// Procedural macro dependencies required to compile the crate.
+    ProcMacro,
+
+    /// Procedural macro dependencies only required to compile the crate's tests.
+    ProcMacroDev,
+
+    /// Dependencies of the crate's build script, including procedural macros and `links` dependencies.
+    Build,
+}
+
+impl DependencyKind {
+    /// Every kind of dependency.
+    pub const ALL: [DependencyKind; 5] = [
+        DependencyKind::Normal,
+        DependencyKind::Dev,
+        DependencyKind::ProcMacro,
+        DependencyKind::ProcMacroDev,
+        DependencyKind::Build,
+    ];
+
+    /// The kinds of dependency needed to build a crate, but not its tests.
+    pub const BUILD: [DependencyKind; 3] = [
+        DependencyKind::Normal,
+        DependencyKind::ProcMacro,
+        DependencyKind::Build,
+    ];
+
+    /// Whether the dependency is only needed by tests.
+    pub fn is_dev(&self) -> bool {
+        matches!(self, DependencyKind::Dev | DependencyKind::ProcMacroDev)
+    }
+}
+
+/// The version of the [LockfileSnapshot] format. It is incremented whenever a change is made
+/// to the format which older readers would misinterpret.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A serializable copy of everything the [CargoBazelLockfile] API exposes about a lockfile.
+///
+/// Unlike the lockfile itself, whose format changes freely between releases, the snapshot
+/// format is versioned: snapshots with a different [SNAPSHOT_VERSION] fail to deserialize.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockfileSnapshot {
+    #[serde(deserialize_with = "deserialize_snapshot_version")]
+    version: u32,
+    workspace_members: BTreeSet<CrateId>,
+    conditions: BTreeMap<String, BTreeSet<String>>,
+    crates: BTreeMap<CrateId, CrateSnapshot>,
+}
+
+impl LockfileSnapshot {
+    /// Take a snapshot of a lockfile.
+    pub fn new(lockfile: &impl CargoBazelLockfile) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            workspace_members: lockfile.workspace_members(),
+            conditions: lockfile.conditions(),
+            crates: lockfile
+                .crates()
+                .into_iter()
+                .filter_map(|crate_id| {
+                    let info = lockfile.crate_info(&crate_id)?;
+                    Some((crate_id, CrateSnapshot::from(&info)))
+                })
+                .collect(),
+        }
+    }
+
+    /// Get the snapshot of a specific crate, exactly as it is serialized.
+    pub fn crate_snapshot(&self, crate_id: &CrateId) -> Option<&CrateSnapshot> {
+        self.crates.get(crate_id)
+    }
+}
+
+fn deserialize_snapshot_version<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let version = u32::deserialize(deserializer)?;
+    if version != SNAPSHOT_VERSION {
+        return Err(D::Error::custom(format!(
+            "Unsupported lockfile snapshot version {version}, expected {SNAPSHOT_VERSION}"
+        )));
+    }
+    Ok(version)
+}
+
+impl CargoBazelLockfile for LockfileSnapshot {
+    fn workspace_members(&self) -> BTreeSet<CrateId> {
+        self.workspace_members.clone()
+    }
+
+    fn crates(&self) -> BTreeSet<CrateId> {
+        self.crates.keys().cloned().collect()
+    }
+
+    fn crate_info(&self, crate_id: &CrateId) -> Option<CrateInfo> {
+        self.crates.get(crate_id).map(CrateInfo::from)
+    }
+
+    fn conditions(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.conditions.clone()
+    }
+}
+
+/// A value recorded in a [LockfileSnapshot], along with the configuration it is limited to.
+/// Values without a configuration apply to every platform.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotValue<T> {
+    pub value: T,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub configuration: Option<String>,
+}
+
+/// A dependency edge recorded in a [LockfileSnapshot].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencySnapshot {
+    /// The crate depended on.
+    pub id: CrateId,
+
+    /// The name of the target of the crate depended on.
+    pub target: String,
+
+    /// The name the dependent refers to the dependency by, if it's renamed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+}
+
+/// Everything a [LockfileSnapshot] records about a crate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrateSnapshot {
+    pub name: String,
+    pub version: semver::Version,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub library_target_name: Option<String>,
+
+    pub is_proc_macro: bool,
+    pub has_build_script: bool,
+
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub binaries: BTreeSet<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub crate_features: Vec<SnapshotValue<String>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deps: Vec<SnapshotValue<DependencySnapshot>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deps_dev: Vec<SnapshotValue<DependencySnapshot>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proc_macro_deps: Vec<SnapshotValue<DependencySnapshot>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proc_macro_deps_dev: Vec<SnapshotValue<DependencySnapshot>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_script: Option<BuildScriptSnapshot>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<CrateSource>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub license_ids: BTreeSet<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package_url: Option<String>,
+}
+
+/// Everything a [LockfileSnapshot] records about a crate's build script.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildScriptSnapshot {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deps: Vec<SnapshotValue<DependencySnapshot>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proc_macro_deps: Vec<SnapshotValue<DependencySnapshot>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub link_deps: Vec<SnapshotValue<DependencySnapshot>>,
+
+    /// Environment variables as `(name, value)` pairs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<SnapshotValue<(String, String)>>,
+
+    /// Environment variables as `(name, value)` pairs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rustc_env: Vec<SnapshotValue<(String, String)>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rustc_flags: Vec<SnapshotValue<String>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<String>,
+}
+
+fn snapshot_values<T, U>(select: &Select<T>, f: impl Fn(T::ItemType) -> U) -> Vec<SnapshotValue<U>>
+where
+    T: Selectable,
+{
+    select
+        .items()
+        .into_iter()
+        .map(|(configuration, value)| SnapshotValue {
+            value: f(value),
+            configuration,
         })
+        .collect()
+}
+
+fn select_values<T, U>(values: &[SnapshotValue<U>], f: impl Fn(&U) -> T::ItemType) -> Select<T>
+where
+    T: Selectable,
+{
+    let mut select = Select::new();
+    for value in values {
+        select.insert(f(&value.value), value.configuration.clone());
+    }
+    select
+}
+
+impl From<CrateDependency> for DependencySnapshot {
+    fn from(dep: CrateDependency) -> Self {
+        DependencySnapshot {
+            id: dep.id,
+            target: dep.target,
+            alias: dep.alias,
+        }
+    }
+}
+
+impl From<&DependencySnapshot> for CrateDependency {
+    fn from(dep: &DependencySnapshot) -> Self {
+        CrateDependency {
+            id: dep.id.clone(),
+            target: dep.target.clone(),
+            alias: dep.alias.clone(),
+            local_path: None,
+        }
+    }
+}
+
+impl From<&CrateInfo> for CrateSnapshot {
+    fn from(info: &CrateInfo) -> Self {
+        let deps = |select: &Select<BTreeSet<CrateDependency>>| {
+            snapshot_values(select, DependencySnapshot::from)
+        };
+        let attrs = &info.common_attributes;
+        CrateSnapshot {
+            name: info.name.clone(),
+            version: info.version.clone(),
+            library_target_name: info.library_target_name.clone(),
+            is_proc_macro: info.is_proc_macro,
+            has_build_script: info.has_build_script,
+            binaries: info.binaries.clone(),
+            crate_features: snapshot_values(&attrs.crate_features, |feature| feature),
+            deps: deps(&attrs.deps),
+            deps_dev: deps(&attrs.deps_dev),
+            proc_macro_deps: deps(&attrs.proc_macro_deps),
+            proc_macro_deps_dev: deps(&attrs.proc_macro_deps_dev),
+            build_script: info
+                .build_script
+                .as_ref()
+                .map(|build_script| BuildScriptSnapshot {
+                    deps: deps(&build_script.deps),
+                    proc_macro_deps: deps(&build_script.proc_macro_deps),
+                    link_deps: deps(&build_script.link_deps),
+                    env: snapshot_values(&build_script.env, |entry| entry),
+                    rustc_env: snapshot_values(&build_script.rustc_env, |entry| entry),
+                    rustc_flags: snapshot_values(&build_script.rustc_flags, |flag| flag),
+                    links: build_script.links.clone(),
+                }),
+            source: info.source.clone(),
+            license: info.license.clone(),
+            license_ids: info.license_ids.clone(),
+            package_url: info.package_url.clone(),
+        }
+    }
+}
+
+impl From<&CrateSnapshot> for CrateInfo {
+    fn from(snapshot: &CrateSnapshot) -> Self {
+        let deps = |values: &[SnapshotValue<DependencySnapshot>]| {
+            select_values::<BTreeSet<CrateDependency>, _>(values, |dep| CrateDependency::from(dep))
+        };
+        let build_script = snapshot
+            .build_script
+            .as_ref()
+            .map(|build_script| BuildScriptInfo {
+                deps: deps(&build_script.deps),
+                proc_macro_deps: deps(&build_script.proc_macro_deps),
+                link_deps: deps(&build_script.link_deps),
+                env: select_values(&build_script.env, Clone::clone),
+                rustc_env: select_values(&build_script.rustc_env, Clone::clone),
+                rustc_flags: select_values(&build_script.rustc_flags, Clone::clone),
+                links: build_script.links.clone(),
+            });
+        CrateInfo {
+            name: snapshot.name.clone(),
+            version: snapshot.version.clone(),
+            library_target_name: snapshot.library_target_name.clone(),
+            is_proc_macro: snapshot.is_proc_macro,
+            has_build_script: snapshot.has_build_script,
+            binaries: snapshot.binaries.clone(),
+            common_attributes: CommonAttributes {
+                crate_features: select_values(&snapshot.crate_features, Clone::clone),
+                deps: deps(&snapshot.deps),
+                deps_dev: deps(&snapshot.deps_dev),
+                proc_macro_deps: deps(&snapshot.proc_macro_deps),
+                proc_macro_deps_dev: deps(&snapshot.proc_macro_deps_dev),
+                ..CommonAttributes::default()
+            },
+            build_deps: build_script
+                .as_ref()
+                .map(BuildScriptInfo::all_deps)
+                .unwrap_or_default(),
+            build_script,
+            download_url: match &snapshot.source {
+                Some(CrateSource::Http { url, .. }) => Some(url.clone()),
+                _ => None,
+            },
+            sha256: match &snapshot.source {
+                Some(CrateSource::Http { sha256, .. }) => sha256.clone(),
+                _ => None,
+            },
+            source: snapshot.source.clone(),
+            license: snapshot.license.clone(),
+            license_ids: snapshot.license_ids.clone(),
+            package_url: snapshot.package_url.clone(),
+        }
     }
 }
 
 /// Information about a crate (which may be in-workspace or a dependency).
-#[derive(Deserialize, PartialEq, Eq, Debug)]
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct CrateInfo {
     name: String,
     version: semver::Version,
     library_target_name: Option<String>,
     is_proc_macro: bool,
+    has_build_script: bool,
+
+    #[serde(default)]
+    binaries: BTreeSet<String>,
 
     common_attributes: CommonAttributes,
+
+    #[serde(default)]
+    build_deps: Select<BTreeSet<CrateDependency>>,
+
+    #[serde(default)]
+    build_script: Option<BuildScriptInfo>,
+
+    #[serde(default)]
+    source: Option<CrateSource>,
+
+    license: Option<String>,
+
+    #[serde(default)]
+    license_ids: BTreeSet<String>,
+
+    package_url: Option<String>,
+    download_url: Option<String>,
+    sha256: Option<String>,
+}
+
+impl From<&CrateContext> for CrateInfo {
+    fn from(crate_context: &CrateContext) -> Self {
+        let build_script = crate_context
+            .build_script_attrs
+            .as_ref()
+            .map(BuildScriptInfo::from);
+        CrateInfo {
+            name: crate_context.name.clone(),
+            version: crate_context.version.clone(),
+            library_target_name: crate_context.library_target_name.clone(),
+            is_proc_macro: crate_context
+                .targets
+                .iter()
+                .any(|t| matches!(t, Rule::ProcMacro(_))),
+            has_build_script: crate_context
+                .targets
+                .iter()
+                .any(|t| matches!(t, Rule::BuildScript(_))),
+            binaries: crate_context
+                .targets
+                .iter()
+                .filter_map(|t| match t {
+                    Rule::Binary(attrs) => Some(attrs.crate_name.clone()),
+                    _ => None,
+                })
+                .collect(),
+            common_attributes: crate_context.common_attrs.clone(),
+            build_deps: build_script
+                .as_ref()
+                .map(BuildScriptInfo::all_deps)
+                .unwrap_or_default(),
+            build_script,
+            source: crate_context.repository.as_ref().map(CrateSource::from),
+            license: crate_context.license.clone(),
+            license_ids: crate_context.license_ids.clone(),
+            package_url: crate_context.package_url.clone(),
+            download_url: match &crate_context.repository {
+                Some(SourceAnnotation::Http { url, .. }) => Some(url.clone()),
+                _ => None,
+            },
+            sha256: match &crate_context.repository {
+                Some(SourceAnnotation::Http { sha256, .. }) => sha256.clone(),
+                _ => None,
+            },
+        }
+    }
 }
 
 impl CrateInfo {
@@ -93,6 +636,21 @@ impl CrateInfo {
         self.is_proc_macro
     }
 
+    /// Whether the crate has a build script.
+    pub fn has_build_script(&self) -> bool {
+        self.has_build_script
+    }
+
+    /// The crate names of the binaries the crate provides.
+    pub fn binaries(&self) -> &BTreeSet<String> {
+        &self.binaries
+    }
+
+    /// The features enabled for the crate.
+    pub fn crate_features(&self) -> Select<BTreeSet<String>> {
+        self.common_attributes.crate_features.clone()
+    }
+
     /// Dependencies required to compile the crate, without procedural macro dependencies.
     pub fn normal_deps(&self) -> Select<BTreeSet<CrateDependency>> {
         self.common_attributes.deps.clone()
@@ -112,15 +670,202 @@ impl CrateInfo {
     pub fn proc_macro_dev_deps(&self) -> Select<BTreeSet<CrateDependency>> {
         self.common_attributes.proc_macro_deps_dev.clone()
     }
+
+    /// Dependencies required to compile and run the crate's build script, including procedural macros.
+    pub fn build_deps(&self) -> Select<BTreeSet<CrateDependency>> {
+        self.build_deps.clone()
+    }
+
+    /// The dependencies of the crate of a given kind.
+    pub fn dependencies(&self, kind: DependencyKind) -> Select<BTreeSet<CrateDependency>> {
+        match kind {
+            DependencyKind::Normal => self.normal_deps(),
+            DependencyKind::Dev => self.dev_deps(),
+            DependencyKind::ProcMacro => self.proc_macro_deps(),
+            DependencyKind::ProcMacroDev => self.proc_macro_dev_deps(),
+            DependencyKind::Build => self.build_deps(),
+        }
+    }
+
+    /// The attributes of the crate's build script, if it has one.
+    pub fn build_script(&self) -> Option<&BuildScriptInfo> {
+        self.build_script.as_ref()
+    }
+
+    /// Where the crate's sources are fetched from. Workspace members have no source.
+    pub fn source(&self) -> Option<&CrateSource> {
+        self.source.as_ref()
+    }
+
+    /// The license expression of the crate, as written in its `Cargo.toml`.
+    pub fn license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+
+    /// The SPDX license identifiers referenced by the crate's license expression.
+    pub fn license_ids(&self) -> &BTreeSet<String> {
+        &self.license_ids
+    }
+
+    /// The url of the crate's source repository, as written in its `Cargo.toml`.
+    pub fn package_url(&self) -> Option<&str> {
+        self.package_url.as_deref()
+    }
+
+    /// The url the crate's archive is downloaded from, if it's fetched over http.
+    pub fn download_url(&self) -> Option<&str> {
+        self.download_url.as_deref()
+    }
+
+    /// The sha256 checksum of the crate's archive, if it's fetched over http and the checksum is known.
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+}
+
+/// Information about a crate's build script.
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct BuildScriptInfo {
+    #[serde(default)]
+    deps: Select<BTreeSet<CrateDependency>>,
+
+    #[serde(default)]
+    proc_macro_deps: Select<BTreeSet<CrateDependency>>,
+
+    #[serde(default)]
+    link_deps: Select<BTreeSet<CrateDependency>>,
+
+    #[serde(default)]
+    env: Select<BTreeMap<String, String>>,
+
+    #[serde(default)]
+    rustc_env: Select<BTreeMap<String, String>>,
+
+    #[serde(default)]
+    rustc_flags: Select<Vec<String>>,
+
+    #[serde(default)]
+    links: Option<String>,
+}
+
+impl From<&BuildScriptAttributes> for BuildScriptInfo {
+    fn from(attrs: &BuildScriptAttributes) -> Self {
+        BuildScriptInfo {
+            deps: attrs.deps.clone(),
+            proc_macro_deps: attrs.proc_macro_deps.clone(),
+            link_deps: attrs.link_deps.clone(),
+            env: attrs.build_script_env.clone(),
+            rustc_env: attrs.rustc_env.clone(),
+            rustc_flags: attrs.rustc_flags.clone(),
+            links: attrs.links.clone(),
+        }
+    }
+}
+
+impl BuildScriptInfo {
+    fn all_deps(&self) -> Select<BTreeSet<CrateDependency>> {
+        Select::merge(
+            Select::merge(self.deps.clone(), self.proc_macro_deps.clone()),
+            self.link_deps.clone(),
+        )
+    }
+
+    /// Dependencies required to compile the build script, without procedural macro dependencies.
+    pub fn deps(&self) -> Select<BTreeSet<CrateDependency>> {
+        self.deps.clone()
+    }
+
+    /// Procedural macro dependencies required to compile the build script.
+    pub fn proc_macro_deps(&self) -> Select<BTreeSet<CrateDependency>> {
+        self.proc_macro_deps.clone()
+    }
+
+    /// Dependencies whose `links` metadata is made available to the build script.
+    pub fn link_deps(&self) -> Select<BTreeSet<CrateDependency>> {
+        self.link_deps.clone()
+    }
+
+    /// Environment variables set when running the build script.
+    pub fn env(&self) -> Select<BTreeMap<String, String>> {
+        self.env.clone()
+    }
+
+    /// Environment variables set when compiling the build script.
+    pub fn rustc_env(&self) -> Select<BTreeMap<String, String>> {
+        self.rustc_env.clone()
+    }
+
+    /// Flags passed to `rustc` when compiling the build script.
+    pub fn rustc_flags(&self) -> Select<Vec<String>> {
+        self.rustc_flags.clone()
+    }
+
+    /// The value of the crate's `links` key, naming the native library it links to.
+    pub fn links(&self) -> Option<&str> {
+        self.links.as_deref()
+    }
+}
+
+/// Where a crate's sources are fetched from.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum CrateSource {
+    /// An archive downloaded over http, typically from a registry.
+    Http { url: String, sha256: Option<String> },
+
+    /// A git repository.
+    Git {
+        remote: String,
+        reference: GitReference,
+    },
+
+    /// A local directory.
+    Path { path: String },
+}
+
+/// The revision of a git repository a crate is fetched from.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum GitReference {
+    /// A tag.
+    Tag(String),
+
+    /// The HEAD of a branch.
+    Branch(String),
+
+    /// A specific revision.
+    Rev(String),
+}
+
+impl From<&SourceAnnotation> for CrateSource {
+    fn from(annotation: &SourceAnnotation) -> Self {
+        match annotation {
+            SourceAnnotation::Http { url, sha256, .. } => CrateSource::Http {
+                url: url.clone(),
+                sha256: sha256.clone(),
+            },
+            SourceAnnotation::Git {
+                remote, commitish, ..
+            } => CrateSource::Git {
+                remote: remote.clone(),
+                reference: match commitish {
+                    Commitish::Tag(tag) => GitReference::Tag(tag.clone()),
+                    Commitish::Branch(branch) => GitReference::Branch(branch.clone()),
+                    Commitish::Rev(rev) => GitReference::Rev(rev.clone()),
+                },
+            },
+            SourceAnnotation::Path { path } => CrateSource::Path {
+                path: path.to_string(),
+            },
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{parse, CargoBazelLockfile};
-    use crate::config::CrateId;
-    use crate::context::crate_context::CrateDependency;
+    use super::*;
+    use crate::test::cargo_bazel_lockfile::{self, dependency, target, MockCrate, MockLockfile};
     use semver::Version;
-    use std::collections::BTreeSet;
 
     #[test]
     fn exercise_public_lockfile_api() {
@@ -165,6 +910,12 @@ mod test {
         assert_eq!(got_serde_derive.version(), &Version::new(1, 0, 152));
         assert_eq!(got_serde_derive.library_target_name(), Some("serde_derive"));
         assert!(got_serde_derive.is_proc_macro);
+        assert_eq!(got_serde_derive.license(), Some("MIT OR Apache-2.0"));
+        assert_eq!(
+            got_serde_derive.sha256(),
+            Some("af487d118eecd09402d70a5d72551860e788df87b464af30e5ea6a38c75c541e")
+        );
+        assert_eq!(got_pkg_a.sha256(), None);
 
         assert_eq!(
             got_pkg_a.normal_deps().values(),
@@ -220,4 +971,273 @@ mod test {
             .collect::<BTreeSet<_>>(),
         );
     }
+
+    const LINUX: &str = "x86_64-unknown-linux-gnu";
+
+    fn crate_id(name: &str) -> CrateId {
+        cargo_bazel_lockfile::crate_id(name, "1.0.0")
+    }
+
+    fn mock_crate(name: &str) -> MockCrate {
+        MockCrate::new(name, "1.0.0")
+    }
+
+    fn mock_lockfile() -> CargoBazelLockfileImpl {
+        let mut app = mock_crate("app")
+            .workspace_member()
+            .dep(None, "serde", "1.0.0")
+            .dep(Some("cfg(unix)"), "libc", "1.0.0")
+            .dep(Some("cfg(windows)"), "winapi", "1.0.0")
+            .feature(None, "std")
+            .build();
+        app.targets.insert(Rule::Binary(target("app_cli")));
+        app.common_attrs
+            .proc_macro_deps
+            .insert(dependency("serde_derive", "1.0.0"), None);
+        app.common_attrs
+            .deps_dev
+            .insert(dependency("tempfile", "1.0.0"), None);
+
+        let mut libc = mock_crate("libc").build_script().build();
+        let mut build_script_attrs = BuildScriptAttributes::default();
+        build_script_attrs
+            .deps
+            .insert(dependency("cc", "1.0.0"), None);
+        build_script_attrs
+            .build_script_env
+            .insert(("LIBC_CHECK_CFG".to_owned(), "1".to_owned()), None);
+        build_script_attrs.build_script_env.insert(
+            ("LIBC_WINDOWS".to_owned(), "1".to_owned()),
+            Some("cfg(windows)".to_owned()),
+        );
+        build_script_attrs.links = Some("c".to_owned());
+        libc.build_script_attrs = Some(build_script_attrs);
+
+        MockLockfile::new([
+            app,
+            libc,
+            mock_crate("serde").build(),
+            mock_crate("serde_derive").dep(None, "syn", "1.0.0").build(),
+            mock_crate("syn").build(),
+            mock_crate("winapi").build(),
+            mock_crate("cc").build(),
+            mock_crate("tempfile")
+                .dep(None, "fastrand", "1.0.0")
+                .build(),
+            mock_crate("fastrand").build(),
+        ])
+        .condition("cfg(unix)", &[LINUX])
+        .condition("cfg(windows)", &["x86_64-pc-windows-msvc"])
+        .build()
+    }
+
+    fn crate_ids(names: &[&str]) -> BTreeSet<CrateId> {
+        names.iter().map(|name| crate_id(name)).collect()
+    }
+
+    #[test]
+    fn lockfile_graph_queries() {
+        let lockfile = mock_lockfile();
+        let app = crate_id("app");
+
+        assert_eq!(
+            lockfile.dependencies(&app, &DependencyKind::ALL, None),
+            crate_ids(&["libc", "serde", "serde_derive", "tempfile", "winapi"])
+        );
+        assert_eq!(
+            lockfile.dependencies(&app, &[DependencyKind::Normal], Some(LINUX)),
+            crate_ids(&["libc", "serde"])
+        );
+        assert_eq!(
+            lockfile.transitive_dependencies(&app, &DependencyKind::BUILD, Some(LINUX)),
+            crate_ids(&["cc", "libc", "serde", "serde_derive", "syn"])
+        );
+        assert_eq!(
+            lockfile.transitive_dependencies(&app, &DependencyKind::ALL, Some(LINUX)),
+            crate_ids(&[
+                "cc",
+                "fastrand",
+                "libc",
+                "serde",
+                "serde_derive",
+                "syn",
+                "tempfile"
+            ])
+        );
+        assert_eq!(
+            lockfile.reverse_dependencies(&crate_id("cc"), &DependencyKind::ALL, None),
+            crate_ids(&["libc"])
+        );
+        assert_eq!(
+            lockfile.reverse_dependencies(&crate_id("winapi"), &DependencyKind::ALL, Some(LINUX)),
+            BTreeSet::new()
+        );
+    }
+
+    #[test]
+    fn lockfile_crate_details() {
+        let lockfile = mock_lockfile();
+
+        let app = lockfile.crate_info(&crate_id("app")).unwrap();
+        assert_eq!(app.binaries(), &BTreeSet::from(["app_cli".to_owned()]));
+        assert_eq!(app.crate_features().values(), vec!["std".to_owned()]);
+        assert_eq!(app.source(), None);
+
+        let libc = lockfile.crate_info(&crate_id("libc")).unwrap();
+        assert_eq!(
+            libc.source(),
+            Some(&CrateSource::Http {
+                url: "https://static.crates.io/crates/libc/libc-1.0.0.crate".to_owned(),
+                sha256: None,
+            })
+        );
+        let build_script = libc.build_script().unwrap();
+        assert_eq!(build_script.links(), Some("c"));
+        assert_eq!(
+            lockfile.resolve_select(&build_script.env(), LINUX),
+            vec![("LIBC_CHECK_CFG".to_owned(), "1".to_owned())]
+        );
+        assert_eq!(
+            lockfile.resolve_select(&build_script.env(), "x86_64-pc-windows-msvc"),
+            vec![
+                ("LIBC_CHECK_CFG".to_owned(), "1".to_owned()),
+                ("LIBC_WINDOWS".to_owned(), "1".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lockfile_snapshot_round_trip() {
+        let lockfile = mock_lockfile();
+        let snapshot = LockfileSnapshot::new(&lockfile);
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["version"], SNAPSHOT_VERSION);
+        let parsed: LockfileSnapshot = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(parsed, snapshot);
+
+        assert_eq!(parsed.workspace_members(), lockfile.workspace_members());
+        assert_eq!(parsed.conditions(), lockfile.conditions());
+        assert_eq!(parsed.crates(), lockfile.crates());
+        for crate_id in lockfile.crates() {
+            assert_same_crate_info(
+                &parsed.crate_info(&crate_id).unwrap(),
+                &lockfile.crate_info(&crate_id).unwrap(),
+            );
+        }
+        assert_eq!(
+            parsed.transitive_dependencies(&crate_id("app"), &DependencyKind::BUILD, None),
+            lockfile.transitive_dependencies(&crate_id("app"), &DependencyKind::BUILD, None),
+        );
+
+        let mut future = json;
+        future["version"] = (SNAPSHOT_VERSION + 1).into();
+        let err = serde_json::from_value::<LockfileSnapshot>(future).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("Unsupported lockfile snapshot version 2"),
+            "{err}"
+        );
+    }
+
+    fn assert_same_crate_info(actual: &CrateInfo, expected: &CrateInfo) {
+        assert_eq!(actual.name(), expected.name());
+        assert_eq!(actual.version(), expected.version());
+        assert_eq!(actual.library_target_name(), expected.library_target_name());
+        assert_eq!(actual.is_proc_macro(), expected.is_proc_macro());
+        assert_eq!(actual.has_build_script(), expected.has_build_script());
+        assert_eq!(actual.binaries(), expected.binaries());
+        assert_eq!(actual.crate_features(), expected.crate_features());
+        for kind in DependencyKind::ALL {
+            let strip_local_path = |select: Select<BTreeSet<CrateDependency>>| {
+                select
+                    .items()
+                    .into_iter()
+                    .map(|(configuration, dep)| {
+                        (configuration, dep.id.clone(), dep.target.clone(), dep.alias)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                strip_local_path(actual.dependencies(kind)),
+                strip_local_path(expected.dependencies(kind)),
+                "{kind:?}"
+            );
+        }
+        assert_eq!(actual.build_script(), expected.build_script());
+        assert_eq!(actual.source(), expected.source());
+        assert_eq!(actual.license(), expected.license());
+        assert_eq!(actual.license_ids(), expected.license_ids());
+        assert_eq!(actual.package_url(), expected.package_url());
+        assert_eq!(actual.download_url(), expected.download_url());
+        assert_eq!(actual.sha256(), expected.sha256());
+    }
+
+    #[test]
+    fn lockfile_snapshot_golden() {
+        let snapshot = LockfileSnapshot::new(&mock_lockfile());
+
+        let golden = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/test_data/cargo_bazel_lockfile/snapshot-v1.json"
+        ));
+        assert_eq!(
+            serde_json::to_string_pretty(&snapshot).unwrap(),
+            golden.trim_end(),
+            "The snapshot format changed. If this is intended, bump SNAPSHOT_VERSION and add a new golden file."
+        );
+        let parsed: LockfileSnapshot = serde_json::from_str(golden).unwrap();
+        assert_eq!(parsed, snapshot);
+    }
+
+    #[test]
+    fn crate_info_deserialize() {
+        let crate_info: CrateInfo = serde_json::from_value(serde_json::json!({
+            "name": "libc",
+            "version": "1.0.0",
+            "library_target_name": "libc",
+            "is_proc_macro": false,
+            "has_build_script": true,
+            "common_attributes": {
+                "crate_features": {
+                    "common": ["std"],
+                    "selects": {},
+                },
+                "deps": {
+                    "common": [],
+                    "selects": {
+                        "cfg(unix)": [{ "id": "cc 1.0.0", "target": "cc" }],
+                    },
+                },
+            },
+            "build_deps": {
+                "common": [{ "id": "cc 1.0.0", "target": "cc" }],
+                "selects": {},
+            },
+            "license": "MIT",
+            "package_url": null,
+            "download_url": "https://static.crates.io/crates/libc/libc-1.0.0.crate",
+            "sha256": "abc",
+        }))
+        .unwrap();
+
+        assert_eq!(crate_info.crate_features().values(), vec!["std".to_owned()]);
+        assert_eq!(
+            crate_info
+                .normal_deps()
+                .items()
+                .into_iter()
+                .map(|(configuration, dep)| (configuration, dep.id))
+                .collect::<Vec<_>>(),
+            vec![(Some("cfg(unix)".to_owned()), crate_id("cc"))]
+        );
+        assert_eq!(crate_info.build_deps().values().len(), 1);
+        assert_eq!(
+            crate_info.download_url(),
+            Some("https://static.crates.io/crates/libc/libc-1.0.0.crate")
+        );
+        assert_eq!(crate_info.sha256(), Some("abc"));
+        assert_eq!(crate_info.build_script(), None);
+        assert_eq!(crate_info.source(), None);
+    }
 }
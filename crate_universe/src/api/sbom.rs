@@ -0,0 +1,491 @@
+//! The sbom module builds software bills of materials for the crates in a lockfile, in the
+//! [CycloneDX](https://cyclonedx.org/) and [SPDX](https://spdx.dev/) json formats.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+
+use crate::api::lockfile::{CargoBazelLockfile, CrateInfo, DependencyKind};
+use crate::config::CrateId;
+
+/// The version of `cargo-bazel` recorded as the tool which created a bill of materials.
+const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The crates of a lockfile and the dependencies between them.
+#[derive(Debug)]
+pub struct Sbom {
+    /// The workspace members the bill of materials describes.
+    roots: BTreeSet<CrateId>,
+
+    /// Every crate in the bill of materials, including the roots.
+    crates: BTreeMap<CrateId, CrateInfo>,
+
+    /// The direct dependencies of each crate, across all platforms.
+    dependencies: BTreeMap<CrateId, BTreeSet<CrateId>>,
+}
+
+impl Sbom {
+    /// Collect the crates of a lockfile into a bill of materials. If `member` is set, only it
+    /// and the crates it transitively depends on are included, otherwise every workspace member
+    /// and its dependencies are.
+    ///
+    /// Normal, procedural macro, and build script dependencies are included for every platform.
+    /// Dev dependencies are not, as they are not part of what's shipped.
+    pub fn new(lockfile: &impl CargoBazelLockfile, member: Option<&CrateId>) -> Result<Self> {
+        let workspace_members = lockfile.workspace_members();
+        let roots = match member {
+            Some(member) if workspace_members.contains(member) => BTreeSet::from([member.clone()]),
+            Some(member) => bail!("{member} is not a member of the workspace"),
+            None => workspace_members,
+        };
+
+        let mut crates = BTreeMap::new();
+        let mut dependencies = BTreeMap::new();
+        let mut queue: Vec<CrateId> = roots.iter().cloned().collect();
+        while let Some(crate_id) = queue.pop() {
+            if crates.contains_key(&crate_id) {
+                continue;
+            }
+
+            let info = lockfile
+                .crate_info(&crate_id)
+                .ok_or_else(|| anyhow!("The lockfile has no entry for {crate_id}"))?;
+
+            let deps = lockfile.dependencies(&crate_id, &DependencyKind::BUILD, None);
+
+            queue.extend(deps.iter().cloned());
+            dependencies.insert(crate_id.clone(), deps);
+            crates.insert(crate_id, info);
+        }
+
+        Ok(Self {
+            roots,
+            crates,
+            dependencies,
+        })
+    }
+
+    /// The crates included in the bill of materials.
+    pub fn crates(&self) -> BTreeSet<CrateId> {
+        self.crates.keys().cloned().collect()
+    }
+
+    /// Render the bill of materials as a [CycloneDX 1.5](https://cyclonedx.org/docs/1.5/json/) document.
+    pub fn to_cyclonedx(&self, timestamp: SystemTime) -> Value {
+        let component = |crate_id: &CrateId, info: &CrateInfo| {
+            let mut component = json!({
+                "type": if self.roots.contains(crate_id) { "application" } else { "library" },
+                "bom-ref": purl(crate_id),
+                "name": crate_id.name,
+                "version": crate_id.version.to_string(),
+                "purl": purl(crate_id),
+            });
+            if let Some(sha256) = info.sha256() {
+                component["hashes"] = json!([{ "alg": "SHA-256", "content": sha256 }]);
+            }
+            if let Some(license) = info.license() {
+                component["licenses"] = match spdx_expression(license) {
+                    Some(expression) => json!([{ "expression": expression }]),
+                    None => json!([{ "license": { "name": license } }]),
+                };
+            }
+            let mut references = Vec::new();
+            if let Some(url) = info.download_url() {
+                references.push(json!({ "type": "distribution", "url": url }));
+            }
+            if let Some(url) = info.package_url() {
+                references.push(json!({ "type": "vcs", "url": url }));
+            }
+            if !references.is_empty() {
+                component["externalReferences"] = Value::Array(references);
+            }
+            component
+        };
+
+        let mut metadata = json!({
+            "timestamp": rfc3339(timestamp),
+            "tools": {
+                "components": [{
+                    "type": "application",
+                    "name": "cargo-bazel",
+                    "version": TOOL_VERSION,
+                }],
+            },
+        });
+
+        // A bill of materials for a single member describes that member, so it's recorded as the
+        // subject of the document rather than one of its components.
+        let single_root = match self.roots.len() {
+            1 => self.roots.first(),
+            _ => None,
+        };
+        if let Some(root) = single_root {
+            metadata["component"] = component(root, &self.crates[root]);
+        }
+
+        let components: Vec<Value> = self
+            .crates
+            .iter()
+            .filter(|(crate_id, _)| Some(*crate_id) != single_root)
+            .map(|(crate_id, info)| component(crate_id, info))
+            .collect();
+
+        let dependencies: Vec<Value> = self
+            .dependencies
+            .iter()
+            .map(|(crate_id, deps)| {
+                json!({
+                    "ref": purl(crate_id),
+                    "dependsOn": deps.iter().map(purl).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": metadata,
+            "components": components,
+            "dependencies": dependencies,
+        })
+    }
+
+    /// Render the bill of materials as an [SPDX 2.3](https://spdx.github.io/spdx-spec/v2.3/) document.
+    /// `namespace` is the unique uri SPDX requires of each document.
+    pub fn to_spdx(&self, namespace: &str, timestamp: SystemTime) -> Value {
+        let packages: Vec<Value> = self
+            .crates
+            .iter()
+            .map(|(crate_id, info)| {
+                let mut package = json!({
+                    "SPDXID": spdx_id(crate_id),
+                    "name": crate_id.name,
+                    "versionInfo": crate_id.version.to_string(),
+                    "downloadLocation": info.download_url().unwrap_or("NOASSERTION"),
+                    "filesAnalyzed": false,
+                    "licenseConcluded": "NOASSERTION",
+                    "licenseDeclared": info
+                        .license()
+                        .and_then(spdx_expression)
+                        .unwrap_or_else(|| "NOASSERTION".to_owned()),
+                    "copyrightText": "NOASSERTION",
+                    "externalRefs": [{
+                        "referenceCategory": "PACKAGE-MANAGER",
+                        "referenceType": "purl",
+                        "referenceLocator": purl(crate_id),
+                    }],
+                });
+                if let Some(sha256) = info.sha256() {
+                    package["checksums"] =
+                        json!([{ "algorithm": "SHA256", "checksumValue": sha256 }]);
+                }
+                if let Some(url) = info.package_url() {
+                    package["homepage"] = json!(url);
+                }
+                package
+            })
+            .collect();
+
+        let describes = self.roots.iter().map(|root| {
+            json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": spdx_id(root),
+            })
+        });
+        let depends_on = self.dependencies.iter().flat_map(|(crate_id, deps)| {
+            deps.iter().map(move |dep| {
+                json!({
+                    "spdxElementId": spdx_id(crate_id),
+                    "relationshipType": "DEPENDS_ON",
+                    "relatedSpdxElement": spdx_id(dep),
+                })
+            })
+        });
+
+        let name = match (self.roots.len(), self.roots.first()) {
+            (1, Some(root)) => format!("{}-{}", root.name, root.version),
+            _ => "cargo-bazel".to_owned(),
+        };
+
+        json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": name,
+            "documentNamespace": namespace,
+            "creationInfo": {
+                "created": rfc3339(timestamp),
+                "creators": [format!("Tool: cargo-bazel-{TOOL_VERSION}")],
+            },
+            "packages": packages,
+            "relationships": describes.chain(depends_on).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// The [package url](https://github.com/package-url/purl-spec) of a crate.
+fn purl(crate_id: &CrateId) -> String {
+    // Build metadata is the only part of a crate name or version which needs escaping.
+    format!(
+        "pkg:cargo/{}@{}",
+        crate_id.name,
+        crate_id.version.to_string().replace('+', "%2B")
+    )
+}
+
+/// A unique SPDX identifier for a crate, which may only contain letters, numbers, `.` and `-`.
+fn spdx_id(crate_id: &CrateId) -> String {
+    format!("SPDXRef-Package-{}-{}", crate_id.name, crate_id.version)
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '-',
+        })
+        .collect()
+}
+
+/// Convert a license from a `Cargo.toml` into a valid SPDX expression, if possible. Older crates
+/// separate alternatives with `/`, which isn't valid SPDX.
+fn spdx_expression(license: &str) -> Option<String> {
+    let expression = license.replace('/', " OR ");
+    spdx::Expression::parse(&expression).ok()?;
+    Some(expression)
+}
+
+/// Format a time as an RFC 3339 UTC timestamp, with second precision.
+fn rfc3339(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Convert days since the epoch into a civil date, from
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    use semver::Version;
+
+    use crate::api::lockfile::CargoBazelLockfileImpl;
+    use crate::context::crate_context::BuildScriptAttributes;
+    use crate::test::cargo_bazel_lockfile::{self, dependency, MockCrate, MockLockfile};
+
+    fn mock_crate(name: &str) -> MockCrate {
+        MockCrate::new(name, "1.0.0")
+            .package_url(&format!("https://github.com/example/{name}"))
+            .sha256(&format!("{name}-sha256"))
+            .license("MIT/Apache-2.0", &["Apache-2.0", "MIT"])
+    }
+
+    fn mock_lockfile() -> CargoBazelLockfileImpl {
+        let mut app = mock_crate("app")
+            .workspace_member()
+            .dep(None, "serde", "1.0.0")
+            .dep(Some("cfg(unix)"), "libc", "1.0.0")
+            .build();
+        app.license = None;
+        let tool = mock_crate("tool")
+            .workspace_member()
+            .dep(None, "anyhow", "1.0.0")
+            .build();
+
+        let mut libc = mock_crate("libc").build();
+        let mut build_script_attrs = BuildScriptAttributes::default();
+        build_script_attrs
+            .deps
+            .insert(dependency("cc", "1.0.0"), None);
+        libc.build_script_attrs = Some(build_script_attrs);
+
+        MockLockfile::new([
+            app,
+            tool,
+            libc,
+            mock_crate("serde").build(),
+            mock_crate("anyhow").build(),
+            mock_crate("cc").build(),
+        ])
+        .build()
+    }
+
+    fn crate_id(name: &str) -> CrateId {
+        cargo_bazel_lockfile::crate_id(name, "1.0.0")
+    }
+
+    #[test]
+    fn sbom_transitive_closure() {
+        let lockfile = mock_lockfile();
+
+        let sbom = Sbom::new(&lockfile, None).unwrap();
+        assert_eq!(sbom.crates().len(), 6);
+
+        let sbom = Sbom::new(&lockfile, Some(&crate_id("app"))).unwrap();
+        assert_eq!(
+            sbom.crates(),
+            BTreeSet::from([
+                crate_id("app"),
+                crate_id("cc"),
+                crate_id("libc"),
+                crate_id("serde")
+            ])
+        );
+
+        let err = Sbom::new(&lockfile, Some(&crate_id("serde"))).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "serde 1.0.0 is not a member of the workspace"
+        );
+    }
+
+    #[test]
+    fn sbom_cyclonedx() {
+        let sbom = Sbom::new(&mock_lockfile(), Some(&crate_id("app"))).unwrap();
+        let bom = sbom.to_cyclonedx(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+
+        assert_eq!(bom["specVersion"], "1.5");
+        assert_eq!(bom["metadata"]["timestamp"], "2023-11-14T22:13:20Z");
+        assert_eq!(bom["metadata"]["component"]["purl"], "pkg:cargo/app@1.0.0");
+        assert_eq!(bom["metadata"]["component"]["type"], "application");
+        assert!(bom["metadata"]["component"].get("licenses").is_none());
+
+        let components = bom["components"].as_array().unwrap();
+        assert_eq!(components.len(), 3);
+        assert_eq!(
+            components[0],
+            json!({
+                "type": "library",
+                "bom-ref": "pkg:cargo/cc@1.0.0",
+                "name": "cc",
+                "version": "1.0.0",
+                "purl": "pkg:cargo/cc@1.0.0",
+                "hashes": [{ "alg": "SHA-256", "content": "cc-sha256" }],
+                "licenses": [{ "expression": "MIT OR Apache-2.0" }],
+                "externalReferences": [
+                    {
+                        "type": "distribution",
+                        "url": "https://static.crates.io/crates/cc/cc-1.0.0.crate",
+                    },
+                    { "type": "vcs", "url": "https://github.com/example/cc" },
+                ],
+            })
+        );
+
+        assert_eq!(
+            bom["dependencies"],
+            json!([
+                {
+                    "ref": "pkg:cargo/app@1.0.0",
+                    "dependsOn": ["pkg:cargo/libc@1.0.0", "pkg:cargo/serde@1.0.0"],
+                },
+                { "ref": "pkg:cargo/cc@1.0.0", "dependsOn": [] },
+                { "ref": "pkg:cargo/libc@1.0.0", "dependsOn": ["pkg:cargo/cc@1.0.0"] },
+                { "ref": "pkg:cargo/serde@1.0.0", "dependsOn": [] },
+            ])
+        );
+    }
+
+    #[test]
+    fn sbom_spdx() {
+        let sbom = Sbom::new(&mock_lockfile(), None).unwrap();
+        let doc = sbom.to_spdx("https://example.com/sbom", UNIX_EPOCH);
+
+        assert_eq!(doc["name"], "cargo-bazel");
+        assert_eq!(doc["creationInfo"]["created"], "1970-01-01T00:00:00Z");
+
+        let packages = doc["packages"].as_array().unwrap();
+        assert_eq!(packages.len(), 6);
+        let app = &packages[1];
+        assert_eq!(app["SPDXID"], "SPDXRef-Package-app-1.0.0");
+        assert_eq!(app["downloadLocation"], "NOASSERTION");
+        assert_eq!(app["licenseDeclared"], "NOASSERTION");
+        assert!(app.get("checksums").is_none());
+        let libc = &packages[3];
+        assert_eq!(libc["licenseDeclared"], "MIT OR Apache-2.0");
+        assert_eq!(
+            libc["checksums"],
+            json!([{ "algorithm": "SHA256", "checksumValue": "libc-sha256" }])
+        );
+
+        let relationships: BTreeSet<(String, String, String)> = doc["relationships"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                (
+                    r["spdxElementId"].as_str().unwrap().to_owned(),
+                    r["relationshipType"].as_str().unwrap().to_owned(),
+                    r["relatedSpdxElement"].as_str().unwrap().to_owned(),
+                )
+            })
+            .collect();
+        let relationship =
+            |from: &str, kind: &str, to: &str| (from.to_owned(), kind.to_owned(), to.to_owned());
+        assert_eq!(
+            relationships,
+            BTreeSet::from([
+                relationship("SPDXRef-DOCUMENT", "DESCRIBES", "SPDXRef-Package-app-1.0.0"),
+                relationship(
+                    "SPDXRef-DOCUMENT",
+                    "DESCRIBES",
+                    "SPDXRef-Package-tool-1.0.0"
+                ),
+                relationship(
+                    "SPDXRef-Package-app-1.0.0",
+                    "DEPENDS_ON",
+                    "SPDXRef-Package-libc-1.0.0"
+                ),
+                relationship(
+                    "SPDXRef-Package-app-1.0.0",
+                    "DEPENDS_ON",
+                    "SPDXRef-Package-serde-1.0.0"
+                ),
+                relationship(
+                    "SPDXRef-Package-libc-1.0.0",
+                    "DEPENDS_ON",
+                    "SPDXRef-Package-cc-1.0.0"
+                ),
+                relationship(
+                    "SPDXRef-Package-tool-1.0.0",
+                    "DEPENDS_ON",
+                    "SPDXRef-Package-anyhow-1.0.0"
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn purl_escapes_build_metadata() {
+        assert_eq!(
+            purl(&CrateId::new(
+                "wasi".to_owned(),
+                Version::parse("0.11.0+wasi-snapshot-preview1").unwrap()
+            )),
+            "pkg:cargo/wasi@0.11.0%2Bwasi-snapshot-preview1"
+        );
+    }
+}
@@ -1,8 +1,16 @@
 //! Command line interface entry points and utilities
 
+mod add;
+mod diff;
+mod duplicates;
 mod generate;
+mod mirror;
 mod query;
+mod remove;
 mod render;
+mod repin;
+mod report;
+mod sbom;
 mod splice;
 mod vendor;
 
@@ -16,16 +24,31 @@ use tracing_subscriber::FmtSubscriber;
 
 pub use tracing::Level as LogLevel;
 
+pub use self::add::AddOptions;
+pub use self::diff::{DiffFormat, DiffOptions};
+pub use self::duplicates::{DuplicatesFormat, DuplicatesOptions};
 pub use self::generate::GenerateOptions;
+pub use self::mirror::MirrorOptions;
 pub use self::query::QueryOptions;
+pub use self::remove::RemoveOptions;
 pub use self::render::RenderOptions;
+pub use self::repin::RepinOptions;
+pub use self::report::{ReportFormat, ReportOptions};
+pub use self::sbom::{SbomFormat, SbomOptions};
 pub use self::splice::SpliceOptions;
 pub use self::vendor::VendorOptions;
 
 // Entrypoints
+pub use add::add;
+pub use diff::diff;
+pub use duplicates::duplicates;
 pub use generate::generate;
+pub use mirror::mirror;
 pub use query::query;
+pub use remove::remove;
 pub use render::render;
+pub use report::report;
+pub use sbom::sbom;
 pub use splice::splice;
 pub use vendor::vendor;
 
@@ -50,6 +73,27 @@ pub enum Options {
 
     /// Render a BUILD file for a single crate.
     Render(RenderOptions),
+
+    /// Report the dependency changes between two lockfiles.
+    Diff(DiffOptions),
+
+    /// Download the crates in a lockfile into a directory for use as a mirror.
+    Mirror(MirrorOptions),
+
+    /// Report the licenses, sources and advisories of the crates in a lockfile, optionally checking them against a policy.
+    Report(ReportOptions),
+
+    /// Generate a CycloneDX or SPDX software bill of materials for the crates in a lockfile.
+    Sbom(SbomOptions),
+
+    /// Report the crates in a lockfile which are resolved at multiple versions, and why.
+    Duplicates(DuplicatesOptions),
+
+    /// Add dependencies to a Cargo manifest and repin the lockfiles.
+    Add(AddOptions),
+
+    /// Remove dependencies from a Cargo manifest and repin the lockfiles.
+    Remove(RemoveOptions),
 }
 
 // Convenience wrappers to avoid dependencies in the binary
@@ -59,7 +103,20 @@ pub fn parse_args() -> Options {
     Options::parse()
 }
 
-const EXPECTED_LOGGER_NAMES: [&str; 5] = ["Generate", "Splice", "Query", "Vendor", "Render"];
+const EXPECTED_LOGGER_NAMES: [&str; 12] = [
+    "Generate",
+    "Splice",
+    "Query",
+    "Vendor",
+    "Render",
+    "Diff",
+    "Mirror",
+    "Report",
+    "Sbom",
+    "Duplicates",
+    "Add",
+    "Remove",
+];
 
 /// A wrapper for the tracing-subscriber default [FormatEvent]
 /// that prepends the name of the active CLI option.
@@ -0,0 +1,279 @@
+//! The cli entrypoint for the `add` subcommand
+
+use std::fs;
+
+use anyhow::{bail, Context as AnyhowContext, Result};
+use clap::Parser;
+use semver::VersionReq;
+
+use crate::cli::repin::{edit_manifest, RepinOptions};
+use crate::metadata::CargoUpdateRequest;
+use crate::splicing::manifest_editor::{
+    add_dependency, has_dependency, DependencyKind, DependencySpec, DependencyTable,
+};
+
+/// Command line options for the `add` subcommand
+#[derive(Parser, Debug)]
+#[clap(about = "Command line options for the `add` subcommand", version)]
+pub struct AddOptions {
+    /// The crates to add in the form `name[@version]`. Crates already in the manifest are
+    /// updated in place like `cargo add` does. If no version is given, the version of an
+    /// existing entry is kept and new entries use the version the crate resolves to.
+    #[clap(required = true)]
+    pub crates: Vec<String>,
+
+    /// Features to enable on the added crates, in addition to any they already have.
+    #[clap(long, short = 'F', value_delimiter = ',')]
+    pub features: Vec<String>,
+
+    /// Disable the default features of the added crates.
+    #[clap(long)]
+    pub no_default_features: bool,
+
+    /// Add the crates as `[dev-dependencies]`.
+    #[clap(long, conflicts_with = "build")]
+    pub dev: bool,
+
+    /// Add the crates as `[build-dependencies]`.
+    #[clap(long)]
+    pub build: bool,
+
+    /// Add the crates as dependencies of a platform, e.g. `cfg(unix)` for
+    /// `[target.'cfg(unix)'.dependencies]`.
+    #[clap(long)]
+    pub target: Option<String>,
+
+    #[clap(flatten)]
+    pub repin: RepinOptions,
+}
+
+/// Split a `name[@version]` argument.
+fn parse_crate_arg(arg: &str) -> Result<(String, Option<String>)> {
+    let (name, version) = match arg.split_once('@') {
+        Some((name, version)) => (name, Some(version.to_owned())),
+        None => (arg, None),
+    };
+    if name.is_empty() || version.as_deref() == Some("") {
+        bail!("Invalid crate `{arg}`, expected `name[@version]`");
+    }
+    Ok((name.to_owned(), version))
+}
+
+/// `cargo update` requests for the added crates which the existing lockfile didn't satisfy.
+///
+/// Crates already locked at a version matching their requirement keep it, so adding a crate
+/// which is already a transitive dependency doesn't change the lockfile. Others are updated to
+/// the newest version matching their requirement. They're identified as `name@version` since
+/// other versions of the same crate may also be locked.
+fn update_requests<'a>(
+    dependencies: impl IntoIterator<Item = &'a DependencySpec>,
+    existing_lock: Option<&cargo_lock::Lockfile>,
+    resolved_lock: &cargo_lock::Lockfile,
+) -> Result<Vec<CargoUpdateRequest>> {
+    let mut requests = Vec::new();
+    for dependency in dependencies {
+        let requirement = dependency.version.as_deref().unwrap_or("*");
+        let requirement = VersionReq::parse(requirement).with_context(|| {
+            format!(
+                "Invalid version requirement `{}` for `{}`",
+                requirement, dependency.name
+            )
+        })?;
+        let locked_version = |lockfile: &cargo_lock::Lockfile| {
+            lockfile
+                .packages
+                .iter()
+                .filter(|pkg| pkg.name.as_str() == dependency.name)
+                .map(|pkg| pkg.version.clone())
+                .filter(|version| requirement.matches(version))
+                .max()
+        };
+
+        if existing_lock.and_then(locked_version).is_some() {
+            continue;
+        }
+        if let Some(version) = locked_version(resolved_lock) {
+            requests.push(CargoUpdateRequest::Package {
+                name: format!("{}@{}", dependency.name, version),
+                version: None,
+            });
+        }
+    }
+    Ok(requests)
+}
+
+/// Add dependencies to a Cargo manifest and update the lockfiles
+pub fn add(opt: AddOptions) -> Result<()> {
+    let table = DependencyTable {
+        kind: if opt.dev {
+            DependencyKind::Dev
+        } else if opt.build {
+            DependencyKind::Build
+        } else {
+            DependencyKind::Normal
+        },
+        target: opt.target.clone(),
+    };
+
+    let mut dependencies = opt
+        .crates
+        .iter()
+        .map(|arg| {
+            let (name, version) = parse_crate_arg(arg)?;
+            Ok(DependencySpec {
+                name,
+                version,
+                features: opt.features.clone(),
+                default_features: !opt.no_default_features,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let splicing_manifest = opt.repin.splicing_manifest()?;
+    let manifest_path = opt.repin.target_manifest(&splicing_manifest)?;
+
+    let existing_lock = match &opt.repin.cargo_lockfile {
+        Some(path) if path.exists() => Some(
+            cargo_lock::Lockfile::load(path)
+                .with_context(|| format!("Failed to load lockfile: {}", path.display()))?,
+        ),
+        _ => None,
+    };
+    let existing_lock_path = opt
+        .repin
+        .cargo_lockfile
+        .clone()
+        .filter(|_| existing_lock.is_some());
+
+    edit_manifest(&manifest_path, opt.repin.dry_run, |original| {
+        // Like `cargo add`, new crates added without a version are pinned to the version they
+        // resolve to. Crates already in the manifest keep their version requirement.
+        let unpinned: Vec<bool> = dependencies
+            .iter()
+            .map(|dependency| {
+                dependency.version.is_none() && !has_dependency(original, &table, &dependency.name)
+            })
+            .collect();
+
+        let mut content = original.to_owned();
+        for dependency in &dependencies {
+            content = add_dependency(&content, &table, dependency)?;
+        }
+        fs::write(&manifest_path, &content)?;
+
+        let mut workspace = opt.repin.resolve(&splicing_manifest, &existing_lock_path)?;
+        let update_requests = update_requests(
+            &dependencies,
+            existing_lock.as_ref(),
+            &workspace.cargo_lockfile,
+        )?;
+        workspace.update(&update_requests, &opt.repin.cargo_bin())?;
+
+        if unpinned.contains(&true) {
+            for (dependency, unpinned) in dependencies.iter_mut().zip(unpinned) {
+                if !unpinned {
+                    continue;
+                }
+                let version = workspace
+                    .cargo_lockfile
+                    .packages
+                    .iter()
+                    .filter(|pkg| pkg.name.as_str() == dependency.name)
+                    .map(|pkg| &pkg.version)
+                    .max()
+                    .with_context(|| format!("`{}` was not resolved", dependency.name))?;
+                dependency.version = Some(version.to_string());
+                content = add_dependency(&content, &table, dependency)?;
+            }
+            fs::write(&manifest_path, &content)?;
+
+            let lockfile_path = workspace.cargo_lockfile_path();
+            workspace = opt
+                .repin
+                .resolve(&splicing_manifest, &Some(lockfile_path))?;
+        }
+
+        opt.repin.write_lockfiles(&splicing_manifest, workspace)?;
+        Ok(content)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_crate_args() {
+        assert_eq!(
+            parse_crate_arg("serde").unwrap(),
+            ("serde".to_owned(), None)
+        );
+        assert_eq!(
+            parse_crate_arg("serde@1.0.219").unwrap(),
+            ("serde".to_owned(), Some("1.0.219".to_owned()))
+        );
+        assert!(parse_crate_arg("serde@").is_err());
+        assert!(parse_crate_arg("@1.0").is_err());
+    }
+
+    fn lockfile(packages: &[(&str, &str)]) -> cargo_lock::Lockfile {
+        let mut content = "version = 4\n".to_owned();
+        for (name, version) in packages {
+            content.push_str(&format!(
+                "\n[[package]]\nname = \"{name}\"\nversion = \"{version}\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n"
+            ));
+        }
+        content.parse().unwrap()
+    }
+
+    fn dependency(name: &str, version: &str) -> DependencySpec {
+        DependencySpec {
+            name: name.to_owned(),
+            version: Some(version.to_owned()),
+            features: Vec::new(),
+            default_features: true,
+        }
+    }
+
+    #[test]
+    fn update_requests_for_unsatisfied_crates() {
+        // `foo` 1.x is a transitive dependency and `foo@2` is added.
+        let existing_lock = lockfile(&[("bar", "0.1.0"), ("foo", "1.0.100")]);
+        let resolved_lock = lockfile(&[("bar", "0.1.0"), ("foo", "1.0.100"), ("foo", "2.0.48")]);
+
+        assert_eq!(
+            update_requests(
+                &[dependency("foo", "2")],
+                Some(&existing_lock),
+                &resolved_lock
+            )
+            .unwrap(),
+            vec![CargoUpdateRequest::Package {
+                name: "foo@2.0.48".to_owned(),
+                version: None,
+            }]
+        );
+
+        // Crates which are already locked at a matching version are left alone.
+        assert_eq!(
+            update_requests(
+                &[dependency("foo", "1.0"), dependency("bar", "*")],
+                Some(&existing_lock),
+                &existing_lock
+            )
+            .unwrap(),
+            Vec::new()
+        );
+
+        // Without an existing lockfile every crate is updated.
+        assert_eq!(
+            update_requests(&[dependency("bar", "0.1")], None, &resolved_lock).unwrap(),
+            vec![CargoUpdateRequest::Package {
+                name: "bar@0.1.0".to_owned(),
+                version: None,
+            }]
+        );
+
+        assert!(update_requests(&[dependency("foo", "two")], None, &resolved_lock).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+//! The cli entrypoint for the `diff` subcommand
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+
+use crate::api::diff::diff as diff_lockfiles;
+use crate::api::lockfile::parse;
+
+/// The format of the `diff` report.
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    /// A human-readable summary.
+    #[default]
+    Text,
+
+    /// A machine-readable `json` report.
+    Json,
+}
+
+/// Command line options for the `diff` subcommand
+#[derive(Parser, Debug)]
+#[clap(about = "Command line options for the `diff` subcommand", version)]
+pub struct DiffOptions {
+    /// The lockfile to compare against
+    #[clap(long)]
+    pub old: PathBuf,
+
+    /// The lockfile containing the changes
+    #[clap(long)]
+    pub new: PathBuf,
+
+    /// The format of the report
+    #[clap(long, value_enum, default_value_t)]
+    pub format: DiffFormat,
+
+    /// The path to write the report to. If unset, it is printed to stdout.
+    #[clap(long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Report the dependency changes between two lockfiles
+pub fn diff(opt: DiffOptions) -> Result<()> {
+    let old = parse(&opt.old)
+        .with_context(|| format!("Failed to parse lockfile `{}`", opt.old.display()))?;
+    let new = parse(&opt.new)
+        .with_context(|| format!("Failed to parse lockfile `{}`", opt.new.display()))?;
+
+    let diff = diff_lockfiles(&old, &new);
+    let report = match opt.format {
+        DiffFormat::Text => diff.to_string(),
+        DiffFormat::Json => serde_json::to_string_pretty(&diff)? + "\n",
+    };
+
+    match &opt.output {
+        Some(path) => fs::write(path, report)
+            .with_context(|| format!("Failed to write report to `{}`", path.display())),
+        None => {
+            print!("{report}");
+            Ok(())
+        }
+    }
+}
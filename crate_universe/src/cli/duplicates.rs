@@ -0,0 +1,91 @@
+//! The cli entrypoint for the `duplicates` subcommand
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+
+use crate::api::duplicates::{duplicates as find_duplicates, duplicates_with_tree_metadata};
+use crate::api::lockfile::{parse, DependencyKind};
+use crate::splicing::WorkspaceMetadata;
+
+/// The format of the `duplicates` report.
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatesFormat {
+    /// A human-readable summary.
+    #[default]
+    Text,
+
+    /// A machine-readable `json` report.
+    Json,
+}
+
+/// Command line options for the `duplicates` subcommand
+#[derive(Parser, Debug)]
+#[clap(
+    about = "Command line options for the `duplicates` subcommand",
+    version
+)]
+pub struct DuplicatesOptions {
+    /// The path to a Cargo Bazel lockfile.
+    #[clap(long)]
+    pub lockfile: PathBuf,
+
+    /// The path to the Cargo metadata `json` file written by `cargo-bazel splice` for the
+    /// lockfile. If set, the dependencies `cargo tree` resolved for each platform are followed
+    /// instead of those recorded in the lockfile.
+    #[clap(long)]
+    pub metadata: Option<PathBuf>,
+
+    /// Whether to include the dev dependencies of workspace members.
+    #[clap(long)]
+    pub dev: bool,
+
+    /// The format of the report
+    #[clap(long, value_enum, default_value_t)]
+    pub format: DuplicatesFormat,
+
+    /// The path to write the report to. If unset, it is printed to stdout.
+    #[clap(long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Report the crates in a lockfile which are resolved at multiple versions
+pub fn duplicates(opt: DuplicatesOptions) -> Result<()> {
+    let lockfile = parse(&opt.lockfile)
+        .with_context(|| format!("Failed to parse lockfile `{}`", opt.lockfile.display()))?;
+
+    let kinds: &[DependencyKind] = if opt.dev {
+        &DependencyKind::ALL
+    } else {
+        &DependencyKind::BUILD
+    };
+
+    let duplicates = match &opt.metadata {
+        Some(path) => {
+            let workspace_metadata = load_workspace_metadata(path)
+                .with_context(|| format!("Failed to parse metadata `{}`", path.display()))?;
+            duplicates_with_tree_metadata(&lockfile, kinds, &workspace_metadata.tree_metadata)
+        }
+        None => find_duplicates(&lockfile, kinds),
+    };
+    let report = match opt.format {
+        DuplicatesFormat::Text => duplicates.to_string(),
+        DuplicatesFormat::Json => serde_json::to_string_pretty(&duplicates)? + "\n",
+    };
+
+    match &opt.output {
+        Some(path) => fs::write(path, report)
+            .with_context(|| format!("Failed to write report to `{}`", path.display())),
+        None => {
+            print!("{report}");
+            Ok(())
+        }
+    }
+}
+
+fn load_workspace_metadata(path: &Path) -> Result<WorkspaceMetadata> {
+    let metadata: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    WorkspaceMetadata::try_from(metadata["metadata"].clone())
+}
@@ -0,0 +1,233 @@
+//! The cli entrypoint for the `mirror` subcommand
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context as AnyhowContext, Result};
+use clap::Parser;
+use sha2::{Digest as Sha2Digest, Sha256};
+
+use crate::context::Context;
+use crate::metadata::SourceAnnotation;
+
+/// Command line options for the `mirror` subcommand
+#[derive(Parser, Debug)]
+#[clap(about = "Command line options for the `mirror` subcommand", version)]
+pub struct MirrorOptions {
+    /// The path to a Cargo Bazel lockfile whose crates should be mirrored.
+    #[clap(long)]
+    pub lockfile: PathBuf,
+
+    /// The directory to download crates into. Each crate is written to `sha256/{sha256}`,
+    /// so a server for this directory can be used in `render_config.crate_mirror_url_templates`.
+    #[clap(long)]
+    pub output_dir: PathBuf,
+
+    /// The path to a `curl` binary used to download crates.
+    #[clap(long, default_value = "curl")]
+    pub curl: PathBuf,
+}
+
+/// Whether a crate was downloaded or was already in the mirror.
+#[derive(Debug, PartialEq, Eq)]
+enum MirrorStatus {
+    Downloaded,
+    Present,
+}
+
+/// Download every crate archive in a lockfile into a content-addressed directory
+pub fn mirror(opt: MirrorOptions) -> Result<()> {
+    let context = Context::try_from_path(&opt.lockfile)
+        .with_context(|| format!("Failed to parse lockfile `{}`", opt.lockfile.display()))?;
+
+    let mut downloaded = 0;
+    let mut present = 0;
+    for (crate_id, crate_context) in &context.crates {
+        let Some(SourceAnnotation::Http { url, sha256, .. }) = &crate_context.repository else {
+            continue;
+        };
+
+        let status = mirror_crate(url, sha256.as_deref(), &opt.output_dir, |url, output| {
+            download(&opt.curl, url, output)
+        })
+        .with_context(|| format!("Failed to mirror {crate_id}"))?;
+
+        match status {
+            MirrorStatus::Downloaded => downloaded += 1,
+            MirrorStatus::Present => present += 1,
+        }
+    }
+
+    println!(
+        "Mirrored {} crates to `{}` ({} downloaded, {} already present)",
+        downloaded + present,
+        opt.output_dir.display(),
+        downloaded,
+        present,
+    );
+
+    Ok(())
+}
+
+/// Fetch the archive at `url` into `output_dir`, named after its sha256 checksum. Archives
+/// already in the mirror are not downloaded again. If `sha256` is unknown, the archive is
+/// named after the checksum of what was downloaded.
+fn mirror_crate(
+    url: &str,
+    sha256: Option<&str>,
+    output_dir: &Path,
+    fetch: impl Fn(&str, &Path) -> Result<()>,
+) -> Result<MirrorStatus> {
+    let sha256_dir = output_dir.join("sha256");
+    let sha256 = sha256.map(str::to_ascii_lowercase);
+
+    if let Some(expected) = &sha256 {
+        let path = sha256_dir.join(expected);
+        if path.exists() && &file_sha256(&path)? == expected {
+            return Ok(MirrorStatus::Present);
+        }
+    }
+
+    fs::create_dir_all(&sha256_dir)
+        .with_context(|| format!("Failed to create `{}`", sha256_dir.display()))?;
+
+    // Download within the output directory so the archive can be moved into place
+    // once it's verified, without ever exposing a partial download.
+    let tempdir = tempfile::TempDir::new_in(output_dir)
+        .context("Failed to create a temporary download directory")?;
+    let download_path = tempdir.path().join("download");
+    fetch(url, &download_path)?;
+
+    let actual = file_sha256(&download_path)?;
+    match &sha256 {
+        Some(expected) if expected != &actual => {
+            bail!("Checksum mismatch for {url}: expected {expected}, got {actual}")
+        }
+        Some(_) => {}
+        None => tracing::warn!(
+            "No checksum is known for {}, it was mirrored without verification as {}",
+            url,
+            actual
+        ),
+    }
+
+    fs::rename(&download_path, sha256_dir.join(&actual))
+        .with_context(|| format!("Failed to move {url} into the mirror"))?;
+
+    Ok(MirrorStatus::Downloaded)
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let content = fs::read(path).with_context(|| format!("Failed to read `{}`", path.display()))?;
+    Ok(hex::encode(Sha256::digest(content)))
+}
+
+fn download(curl: &Path, url: &str, output: &Path) -> Result<()> {
+    let result = Command::new(curl)
+        .arg("--fail")
+        .arg("--silent")
+        .arg("--show-error")
+        .arg("--location")
+        .arg("--output")
+        .arg(output)
+        .arg(url)
+        .output()
+        .with_context(|| format!("Failed to spawn `{}`", curl.display()))?;
+
+    if !result.status.success() {
+        bail!(
+            "Failed to download {}: {}",
+            url,
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::Cell;
+
+    const CONTENT: &[u8] = b"crate archive";
+
+    #[test]
+    fn mirror_crate_is_content_addressed() {
+        let (_tempdir, output_dir) = crate::test::test_tempdir("mirror_crate_is_content_addressed");
+        let sha256 = hex::encode(Sha256::digest(CONTENT));
+
+        let fetches = Cell::new(0);
+        let fetch = |url: &str, output: &Path| {
+            assert_eq!(url, "https://example.com/foo-0.1.0.crate");
+            fetches.set(fetches.get() + 1);
+            fs::write(output, CONTENT).map_err(Into::into)
+        };
+
+        assert_eq!(
+            mirror_crate(
+                "https://example.com/foo-0.1.0.crate",
+                Some(&sha256.to_ascii_uppercase()),
+                &output_dir,
+                fetch
+            )
+            .unwrap(),
+            MirrorStatus::Downloaded
+        );
+        assert_eq!(
+            fs::read(output_dir.join("sha256").join(&sha256)).unwrap(),
+            CONTENT
+        );
+
+        // Archives already in the mirror are not downloaded again.
+        assert_eq!(
+            mirror_crate(
+                "https://example.com/foo-0.1.0.crate",
+                Some(&sha256),
+                &output_dir,
+                fetch
+            )
+            .unwrap(),
+            MirrorStatus::Present
+        );
+        assert_eq!(fetches.get(), 1);
+
+        // Corrupt archives are replaced.
+        fs::write(output_dir.join("sha256").join(&sha256), b"corrupt").unwrap();
+        assert_eq!(
+            mirror_crate(
+                "https://example.com/foo-0.1.0.crate",
+                Some(&sha256),
+                &output_dir,
+                fetch
+            )
+            .unwrap(),
+            MirrorStatus::Downloaded
+        );
+        assert_eq!(fetches.get(), 2);
+    }
+
+    #[test]
+    fn mirror_crate_verifies_checksum() {
+        let (_tempdir, output_dir) = crate::test::test_tempdir("mirror_crate_verifies_checksum");
+        let fetch = |_: &str, output: &Path| fs::write(output, CONTENT).map_err(Into::into);
+
+        let err = mirror_crate("https://example.com/foo", Some("0000"), &output_dir, fetch)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Checksum mismatch"), "{err}");
+        assert!(!output_dir.join("sha256").join("0000").exists());
+
+        // Without a checksum, the archive is named after its contents.
+        assert_eq!(
+            mirror_crate("https://example.com/foo", None, &output_dir, fetch).unwrap(),
+            MirrorStatus::Downloaded
+        );
+        assert!(output_dir
+            .join("sha256")
+            .join(hex::encode(Sha256::digest(CONTENT)))
+            .exists());
+    }
+}
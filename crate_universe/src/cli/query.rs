@@ -1,15 +1,17 @@
 //! The cli entrypoint for the `query` subcommand
 
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context as AnyhowContext, Result};
 use clap::Parser;
+use hex::ToHex;
 
-use crate::config::Config;
+use crate::config::{Commitish, Config};
 use crate::context::Context;
 use crate::lockfile::Digest;
-use crate::metadata::Cargo;
+use crate::metadata::{Cargo, SourceAnnotation};
 use crate::splicing::SplicingManifest;
 
 /// Command line options for the `query` subcommand
@@ -28,6 +30,11 @@ pub struct QueryOptions {
     #[clap(long)]
     pub splicing_manifest: PathBuf,
 
+    /// The path to a Cargo lockfile. When provided, packages which were added or
+    /// removed since the last repin are reported.
+    #[clap(long)]
+    pub cargo_lockfile: Option<PathBuf>,
+
     /// The path to a Cargo binary to use for gathering metadata
     #[clap(long, env = "CARGO")]
     pub cargo: PathBuf,
@@ -67,7 +74,7 @@ pub fn query(opt: QueryOptions) -> Result<()> {
     let splicing_manifest = SplicingManifest::try_from_path(&opt.splicing_manifest)?;
 
     // Generate a new digest so we can compare it with the one in the lockfile
-    let expected = Digest::new(
+    let (expected, inputs) = Digest::new(
         &lockfile,
         &config,
         &splicing_manifest,
@@ -76,9 +83,230 @@ pub fn query(opt: QueryOptions) -> Result<()> {
     )?;
 
     if digest != expected {
-        bail!("Digests do not match: Current {digest:?} != Expected {expected:?}");
+        let mut reasons = match &lockfile.digest_inputs {
+            Some(recorded) => recorded.changes(&inputs),
+            None => vec![
+                "The lockfile does not record the inputs of its digest. Repin to record them."
+                    .to_owned(),
+            ],
+        };
+        if let Some(cargo_lockfile) = &opt.cargo_lockfile {
+            reasons.extend(cargo_lockfile_changes(&lockfile, cargo_lockfile)?);
+        }
+
+        let mut message =
+            format!("Digests do not match: Current {digest:?} != Expected {expected:?}");
+        for reason in reasons {
+            message.push_str(&format!("\n  - {reason}"));
+        }
+        bail!(message);
     }
 
     // There is no need to repin
     Ok(())
 }
+
+/// Describes the registry and git packages in a Cargo lockfile which were
+/// added, removed or changed compared to the crates in a cargo-bazel lockfile.
+fn cargo_lockfile_changes(lockfile: &Context, cargo_lockfile: &Path) -> Result<Vec<String>> {
+    // A missing Cargo lockfile is reported by the repin itself.
+    if !cargo_lockfile.exists() {
+        return Ok(Vec::new());
+    }
+
+    let cargo_lockfile = cargo_lock::Lockfile::load(cargo_lockfile).with_context(|| {
+        format!(
+            "Failed to load Cargo lockfile `{}`",
+            cargo_lockfile.display()
+        )
+    })?;
+
+    let pinned = lockfile
+        .crates
+        .values()
+        .filter_map(|ctx| {
+            ctx.repository.as_ref().map(|repository| {
+                (
+                    (ctx.name.clone(), ctx.version.to_string()),
+                    annotation_pin(repository),
+                )
+            })
+        })
+        .collect();
+
+    Ok(package_changes(&locked_packages(&cargo_lockfile), &pinned))
+}
+
+/// Identifies a package by its name and version.
+type PackageKey = (String, String);
+
+/// Maps the registry and git packages of a Cargo lockfile to their [locked_pin].
+fn locked_packages(cargo_lockfile: &cargo_lock::Lockfile) -> BTreeMap<PackageKey, Option<String>> {
+    cargo_lockfile
+        .packages
+        .iter()
+        .filter(|pkg| pkg.source.is_some())
+        .map(|pkg| {
+            (
+                (pkg.name.to_string(), pkg.version.to_string()),
+                locked_pin(pkg),
+            )
+        })
+        .collect()
+}
+
+/// Describes where the sources of a package in a Cargo lockfile come from: the
+/// checksum of a registry package or the revision of a git package. This
+/// changes when e.g. a git dependency moves to a new commit without changing
+/// its version.
+fn locked_pin(pkg: &cargo_lock::Package) -> Option<String> {
+    let source = pkg.source.as_ref()?;
+    if source.is_git() {
+        let rev = source.precise().map(str::to_owned).or_else(|| {
+            source.git_reference().map(|git_ref| match git_ref {
+                cargo_lock::package::GitReference::Tag(tag) => tag.clone(),
+                cargo_lock::package::GitReference::Branch(branch) => branch.clone(),
+                cargo_lock::package::GitReference::Rev(rev) => rev.clone(),
+            })
+        })?;
+        return Some(format!("git {}#{}", source.url(), rev));
+    }
+    let checksum = pkg.checksum.as_ref()?;
+    checksum
+        .as_sha256()
+        .map(|sum| format!("sha256 {}", sum.encode_hex::<String>()))
+}
+
+/// The [locked_pin] equivalent of the source of a crate in a cargo-bazel lockfile.
+fn annotation_pin(repository: &SourceAnnotation) -> Option<String> {
+    match repository {
+        SourceAnnotation::Git {
+            remote, commitish, ..
+        } => {
+            let rev = match commitish {
+                Commitish::Tag(rev) | Commitish::Branch(rev) | Commitish::Rev(rev) => rev,
+            };
+            Some(format!("git {remote}#{rev}"))
+        }
+        SourceAnnotation::Http { sha256, .. } => {
+            sha256.as_ref().map(|sha256| format!("sha256 {sha256}"))
+        }
+        SourceAnnotation::Path { .. } => None,
+    }
+}
+
+/// Compares the packages of a Cargo lockfile with those of a cargo-bazel lockfile.
+///
+/// Pins are only compared if both are known.
+fn package_changes(
+    locked: &BTreeMap<PackageKey, Option<String>>,
+    pinned: &BTreeMap<PackageKey, Option<String>>,
+) -> Vec<String> {
+    let mut changes = Vec::new();
+    for ((name, version), pin) in locked {
+        match pinned.get(&(name.clone(), version.clone())) {
+            None => changes.push(format!(
+                "Cargo lockfile package `{name} {version}` was added"
+            )),
+            Some(Some(pinned)) if matches!(pin, Some(pin) if pin != pinned) => changes.push(
+                format!("Cargo lockfile package `{name} {version}` changed source or checksum"),
+            ),
+            Some(_) => {}
+        }
+    }
+    for (name, version) in pinned.keys() {
+        if !locked.contains_key(&(name.clone(), version.clone())) {
+            changes.push(format!(
+                "Cargo lockfile package `{name} {version}` was removed"
+            ));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cargo_lockfile_package_changes() {
+        let cargo_lockfile: cargo_lock::Lockfile = textwrap::dedent(
+            r#"
+            version = 3
+
+            [[package]]
+            name = "anyhow"
+            version = "1.0.69"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            checksum = "224afbd727c3d6e4b90103ece64b8d1b67fbb1973b1046c2281eed3f3803f800"
+
+            [[package]]
+            name = "log"
+            version = "0.4.20"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            checksum = "b5e6163cb8c49088c2c36f57875e58ccd8c87c7427f7fbd50ea6710b2f3f2e8f"
+
+            [[package]]
+            name = "tracing"
+            version = "0.1.37"
+            source = "git+https://github.com/tokio-rs/tracing.git?branch=v0.1.x#1e09e50e8d15580b5929adbade9c782a6833e4a0"
+
+            [[package]]
+            name = "local"
+            version = "0.1.0"
+            "#,
+        )
+        .parse()
+        .unwrap();
+
+        let locked = locked_packages(&cargo_lockfile);
+        assert_eq!(
+            locked[&("tracing".to_owned(), "0.1.37".to_owned())],
+            Some(
+                "git https://github.com/tokio-rs/tracing.git#1e09e50e8d15580b5929adbade9c782a6833e4a0"
+                    .to_owned()
+            )
+        );
+        assert!(!locked.contains_key(&("local".to_owned(), "0.1.0".to_owned())));
+
+        let pinned = BTreeMap::from([
+            (
+                ("anyhow".to_owned(), "1.0.69".to_owned()),
+                annotation_pin(&SourceAnnotation::Http {
+                    url: "https://static.crates.io/crates/anyhow/1.0.69/download".to_owned(),
+                    sha256: Some(
+                        "224afbd727c3d6e4b90103ece64b8d1b67fbb1973b1046c2281eed3f3803f800"
+                            .to_owned(),
+                    ),
+                    patch_args: None,
+                    patch_tool: None,
+                    patches: None,
+                }),
+            ),
+            (
+                ("tracing".to_owned(), "0.1.37".to_owned()),
+                annotation_pin(&SourceAnnotation::Git {
+                    remote: "https://github.com/tokio-rs/tracing.git".to_owned(),
+                    commitish: Commitish::Rev(
+                        "0b2a2a7a4d3e29cb1d0b1a9f5e5d1d4f1e1c4b2a".to_owned(),
+                    ),
+                    shallow_since: None,
+                    strip_prefix: None,
+                    patch_args: None,
+                    patch_tool: None,
+                    patches: None,
+                }),
+            ),
+            (("rand".to_owned(), "0.8.5".to_owned()), None),
+        ]);
+
+        assert_eq!(
+            package_changes(&locked, &pinned),
+            vec![
+                "Cargo lockfile package `log 0.4.20` was added",
+                "Cargo lockfile package `tracing 0.1.37` changed source or checksum",
+                "Cargo lockfile package `rand 0.8.5` was removed",
+            ]
+        );
+    }
+}
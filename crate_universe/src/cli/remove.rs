@@ -0,0 +1,66 @@
+//! The cli entrypoint for the `remove` subcommand
+
+use std::fs;
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::cli::repin::{edit_manifest, RepinOptions};
+use crate::splicing::manifest_editor::{remove_dependency, DependencyKind, DependencyTable};
+
+/// Command line options for the `remove` subcommand
+#[derive(Parser, Debug)]
+#[clap(about = "Command line options for the `remove` subcommand", version)]
+pub struct RemoveOptions {
+    /// The names of the crates to remove.
+    #[clap(required = true)]
+    pub crates: Vec<String>,
+
+    /// Remove the crates from `[dev-dependencies]`.
+    #[clap(long, conflicts_with = "build")]
+    pub dev: bool,
+
+    /// Remove the crates from `[build-dependencies]`.
+    #[clap(long)]
+    pub build: bool,
+
+    /// Remove the crates from the dependencies of a platform, e.g. `cfg(unix)` for
+    /// `[target.'cfg(unix)'.dependencies]`.
+    #[clap(long)]
+    pub target: Option<String>,
+
+    #[clap(flatten)]
+    pub repin: RepinOptions,
+}
+
+/// Remove dependencies from a Cargo manifest and update the lockfiles
+pub fn remove(opt: RemoveOptions) -> Result<()> {
+    let table = DependencyTable {
+        kind: if opt.dev {
+            DependencyKind::Dev
+        } else if opt.build {
+            DependencyKind::Build
+        } else {
+            DependencyKind::Normal
+        },
+        target: opt.target.clone(),
+    };
+
+    let splicing_manifest = opt.repin.splicing_manifest()?;
+    let manifest_path = opt.repin.target_manifest(&splicing_manifest)?;
+
+    edit_manifest(&manifest_path, opt.repin.dry_run, |original| {
+        let mut content = original.to_owned();
+        for name in &opt.crates {
+            content = remove_dependency(&content, &table, name)?;
+        }
+        fs::write(&manifest_path, &content)?;
+
+        // Cargo drops packages which are no longer used when regenerating the lockfile.
+        let workspace = opt
+            .repin
+            .resolve(&splicing_manifest, &opt.repin.cargo_lockfile)?;
+        opt.repin.write_lockfiles(&splicing_manifest, workspace)?;
+        Ok(content)
+    })
+}
@@ -0,0 +1,405 @@
+//! Utilities shared by the subcommands which edit a Cargo manifest and repin its lockfiles.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context as AnyhowContext, Result};
+use camino::Utf8PathBuf;
+use clap::Args;
+use tempfile::TempDir;
+
+use crate::cli::vendor::{add_target_specs, parse_target_spec};
+use crate::config::Config;
+use crate::context::Context;
+use crate::lockfile::lock_context;
+use crate::metadata::{Annotations, Cargo, CargoUpdateRequest, TreeResolver};
+use crate::rendering::write_outputs;
+use crate::splicing::{
+    generate_lockfile, SplicedManifest, Splicer, SplicingManifest, WorkspaceMetadata,
+};
+
+/// Options shared by subcommands which edit a manifest and repin its lockfiles.
+#[derive(Args, Debug)]
+pub struct RepinOptions {
+    /// A generated manifest of splicing inputs
+    #[clap(long)]
+    pub splicing_manifest: PathBuf,
+
+    /// The config file with information about the Bazel and Cargo workspace
+    #[clap(long)]
+    pub config: PathBuf,
+
+    /// The path of the manifest to edit. Required if the splicing manifest contains more than one.
+    #[clap(long)]
+    pub manifest: Option<PathBuf>,
+
+    /// The path to a [Cargo.lock](https://doc.rust-lang.org/cargo/guide/cargo-toml-vs-cargo-lock.html) file to update.
+    #[clap(long)]
+    pub cargo_lockfile: Option<PathBuf>,
+
+    /// The path to a Bazel lockfile to update.
+    #[clap(long)]
+    pub lockfile: Option<PathBuf>,
+
+    /// The path to a Cargo binary to use for gathering metadata
+    #[clap(long, env = "CARGO")]
+    pub cargo: PathBuf,
+
+    /// The path to a rustc binary for use with Cargo
+    #[clap(long, env = "RUSTC")]
+    pub rustc: PathBuf,
+
+    /// The root of the Bazel workspace, used to resolve `${build_workspace_directory}` in the splicing manifest.
+    #[clap(long, env = "BUILD_WORKSPACE_DIRECTORY")]
+    pub workspace_dir: PathBuf,
+
+    /// The Bazel output base, used to resolve `${output_base}` in the splicing manifest.
+    #[clap(long, env = "OUTPUT_BASE")]
+    pub output_base: Option<PathBuf>,
+
+    /// The path to the Bazel root workspace (i.e. the directory containing the WORKSPACE.bazel file or similar).
+    /// BE CAREFUL with this value. We never want to include it in a lockfile hash (to keep lockfiles portable),
+    /// which means you also should not use it anywhere that _should_ be guarded by a lockfile hash.
+    /// You basically never want to use this value.
+    #[clap(long)]
+    pub nonhermetic_root_bazel_workspace_dir: Utf8PathBuf,
+
+    /// A directory in which to cache the output of `cargo tree` for each platform.
+    #[clap(long, env = "CARGO_BAZEL_TREE_RESOLVER_CACHE_DIR")]
+    pub tree_resolver_cache_dir: Option<PathBuf>,
+
+    /// A [custom target specification](https://doc.rust-lang.org/rustc/targets/custom.html)
+    /// to support in addition to those in the config, in the form `<triple>=<path>`.
+    #[clap(long = "target-spec", value_parser = parse_target_spec)]
+    pub target_specs: Vec<(String, PathBuf)>,
+
+    /// If true, the edited manifest and lockfiles are printed instead of written to disk. Each
+    /// one is printed under a header with its path.
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+/// A spliced Cargo workspace and its resolved lockfile.
+pub(crate) struct ResolvedWorkspace {
+    /// The directory containing the spliced workspace. It's deleted when dropped.
+    _temp_dir: TempDir,
+
+    /// The root manifest of the spliced workspace.
+    manifest_path: SplicedManifest,
+
+    /// The lockfile of the spliced workspace.
+    pub(crate) cargo_lockfile: cargo_lock::Lockfile,
+}
+
+impl ResolvedWorkspace {
+    /// The path of the spliced workspace's Cargo.lock file.
+    pub(crate) fn cargo_lockfile_path(&self) -> PathBuf {
+        self.manifest_path
+            .as_path_buf()
+            .parent()
+            .expect("Every manifest should be contained in a parent directory")
+            .join("Cargo.lock")
+            .into_std_path_buf()
+    }
+
+    /// Run `cargo update` for each request and reload the lockfile.
+    pub(crate) fn update(
+        &mut self,
+        update_requests: &[CargoUpdateRequest],
+        cargo: &Cargo,
+    ) -> Result<()> {
+        if update_requests.is_empty() {
+            return Ok(());
+        }
+        for request in update_requests {
+            request.update(self.manifest_path.as_path_buf().as_std_path(), cargo)?;
+        }
+        let lockfile_path = self.cargo_lockfile_path();
+        self.cargo_lockfile = cargo_lock::Lockfile::load(&lockfile_path)
+            .with_context(|| format!("Failed to load lockfile: {}", lockfile_path.display()))?;
+        Ok(())
+    }
+}
+
+impl RepinOptions {
+    pub(crate) fn splicing_manifest(&self) -> Result<SplicingManifest> {
+        Ok(
+            SplicingManifest::try_from_path(&self.splicing_manifest)?.resolve(
+                &self.workspace_dir,
+                self.output_base.as_ref().unwrap_or(&self.workspace_dir),
+            ),
+        )
+    }
+
+    /// Locate the manifest to edit from the manifests in the splicing manifest.
+    ///
+    /// Only Cargo manifests can be edited. Dependencies declared in Starlark (`packages` or
+    /// `crate.spec`) are out of scope and have to be edited manually.
+    pub(crate) fn target_manifest(&self, splicing_manifest: &SplicingManifest) -> Result<PathBuf> {
+        let manifests: Vec<&Utf8PathBuf> = splicing_manifest.manifests.keys().collect();
+        if manifests.is_empty() {
+            bail!(
+                "No Cargo manifests are used by this repository. Only Cargo manifests can be edited, dependencies declared in Starlark (e.g. `crate.spec`) must be edited manually."
+            );
+        }
+
+        match &self.manifest {
+            Some(manifest) => {
+                let manifest = self.workspace_dir.join(manifest);
+                manifests
+                    .into_iter()
+                    .find(|path| path.as_std_path() == manifest)
+                    .map(|path| path.clone().into_std_path_buf())
+                    .with_context(|| {
+                        format!(
+                            "`{}` is not one of the manifests in the splicing manifest",
+                            manifest.display()
+                        )
+                    })
+            }
+            None => match manifests.as_slice() {
+                [manifest] => Ok(manifest.as_std_path().to_owned()),
+                _ => bail!(
+                    "Multiple manifests are available, use `--manifest` to select one of: {}",
+                    manifests
+                        .iter()
+                        .map(|path| path.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            },
+        }
+    }
+
+    /// Splice the workspace and resolve its lockfile, starting from `existing_lock`.
+    pub(crate) fn resolve(
+        &self,
+        splicing_manifest: &SplicingManifest,
+        existing_lock: &Option<PathBuf>,
+    ) -> Result<ResolvedWorkspace> {
+        let temp_dir = tempfile::tempdir().context("Failed to create temporary directory")?;
+        let temp_dir_path = Utf8PathBuf::from_path_buf(temp_dir.as_ref().to_path_buf())
+            .unwrap_or_else(|path| panic!("Temporary directory wasn't valid UTF-8: {:?}", path));
+
+        let splicer = Splicer::new(temp_dir_path, splicing_manifest.clone())
+            .context("Failed to create splicer")?;
+        let manifest_path = splicer
+            .splice_workspace(&self.nonhermetic_root_bazel_workspace_dir)
+            .context("Failed to splice workspace")?;
+
+        let cargo_lockfile =
+            generate_lockfile(&manifest_path, existing_lock, self.cargo_bin(), &None)?;
+
+        Ok(ResolvedWorkspace {
+            _temp_dir: temp_dir,
+            manifest_path,
+            cargo_lockfile,
+        })
+    }
+
+    /// Regenerate the Cargo and Bazel lockfiles for a resolved workspace.
+    pub(crate) fn write_lockfiles(
+        &self,
+        splicing_manifest: &SplicingManifest,
+        workspace: ResolvedWorkspace,
+    ) -> Result<()> {
+        let mut config = Config::try_from_path(&self.config).context("Failed to parse config")?;
+        add_target_specs(&mut config, &self.target_specs)?;
+        let cargo = self.cargo_bin();
+        let manifest_path = workspace.manifest_path.as_path_buf();
+
+        let resolver_data = TreeResolver::new(cargo.clone())
+            .with_cache_dir(self.tree_resolver_cache_dir.clone())
+            .generate(
+                manifest_path,
+                &config.supported_platform_triples,
+                &config.target_specs,
+                &config.platform_cfgs,
+            )
+            .context("Failed to generate features")?;
+
+        WorkspaceMetadata::write_registry_urls_and_feature_map(
+            &cargo,
+            &workspace.cargo_lockfile,
+            resolver_data,
+            manifest_path,
+            manifest_path,
+        )?;
+
+        let cargo_metadata = cargo
+            .metadata_command_with_options(manifest_path.as_ref(), vec!["--locked".to_owned()])?
+            .exec()?;
+
+        let annotations = Annotations::new(
+            cargo_metadata,
+            &self.cargo_lockfile,
+            workspace.cargo_lockfile.clone(),
+            config.clone(),
+            &self.nonhermetic_root_bazel_workspace_dir,
+        )?;
+        let context = Context::new(annotations, config.rendering.are_sources_present())?;
+
+        // Both lockfiles are rendered before either is written so a failure doesn't leave
+        // them out of sync.
+        let mut outputs = BTreeMap::new();
+        if let Some(path) = &self.cargo_lockfile {
+            outputs.insert(path.clone(), workspace.cargo_lockfile.to_string());
+        }
+        if let Some(lockfile) = &self.lockfile {
+            let lock_content =
+                lock_context(context, &config, splicing_manifest, &cargo, &self.rustc)?;
+            outputs.insert(
+                lockfile.clone(),
+                serde_json::to_string_pretty(&lock_content)? + "\n",
+            );
+        }
+
+        write_outputs(outputs, self.dry_run).context("Failed to write lockfiles")
+    }
+
+    pub(crate) fn cargo_bin(&self) -> Cargo {
+        Cargo::new(self.cargo.clone(), self.rustc.clone())
+    }
+}
+
+/// Run `update` with the content of the manifest at `path`. It writes its edits to `path`,
+/// repins and returns the edited content. The manifest is restored if `update` fails, or if
+/// this is a dry run in which case the edited content is printed after the lockfiles, each
+/// under a header with its path.
+pub(crate) fn edit_manifest<F>(path: &Path, dry_run: bool, update: F) -> Result<()>
+where
+    F: FnOnce(&str) -> Result<String>,
+{
+    let original = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read manifest {}", path.display()))?;
+    let edited = update(&original);
+
+    if dry_run || edited.is_err() {
+        fs::write(path, &original)
+            .with_context(|| format!("Failed to restore manifest {}", path.display()))?;
+    }
+
+    let edited = edited?;
+    if dry_run {
+        write_outputs(BTreeMap::from([(path.to_owned(), edited)]), dry_run)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::str::FromStr;
+
+    use crate::utils::starlark::Label;
+
+    fn repin_options(workspace_dir: &Path, manifest: Option<&str>) -> RepinOptions {
+        RepinOptions {
+            splicing_manifest: PathBuf::new(),
+            config: PathBuf::new(),
+            manifest: manifest.map(PathBuf::from),
+            cargo_lockfile: None,
+            lockfile: None,
+            cargo: PathBuf::from("cargo"),
+            rustc: PathBuf::from("rustc"),
+            workspace_dir: workspace_dir.to_owned(),
+            output_base: None,
+            nonhermetic_root_bazel_workspace_dir: Utf8PathBuf::new(),
+            tree_resolver_cache_dir: None,
+            target_specs: Vec::new(),
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn target_manifest_selection() {
+        let splicing_manifest = SplicingManifest {
+            manifests: BTreeMap::from([
+                (
+                    Utf8PathBuf::from("/workspace/Cargo.toml"),
+                    Label::from_str("//:Cargo.toml").unwrap(),
+                ),
+                (
+                    Utf8PathBuf::from("/workspace/tools/Cargo.toml"),
+                    Label::from_str("//tools:Cargo.toml").unwrap(),
+                ),
+            ]),
+            ..SplicingManifest::default()
+        };
+        let workspace_dir = Path::new("/workspace");
+
+        assert_eq!(
+            repin_options(workspace_dir, Some("tools/Cargo.toml"))
+                .target_manifest(&splicing_manifest)
+                .unwrap(),
+            PathBuf::from("/workspace/tools/Cargo.toml")
+        );
+        assert!(repin_options(workspace_dir, Some("other/Cargo.toml"))
+            .target_manifest(&splicing_manifest)
+            .is_err());
+
+        let err = repin_options(workspace_dir, None)
+            .target_manifest(&splicing_manifest)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Multiple manifests are available, use `--manifest` to select one of: /workspace/Cargo.toml, /workspace/tools/Cargo.toml"
+        );
+
+        // Dependencies declared in Starlark can't be edited.
+        assert!(repin_options(workspace_dir, None)
+            .target_manifest(&SplicingManifest::default())
+            .unwrap_err()
+            .to_string()
+            .contains("`crate.spec`"));
+    }
+
+    #[test]
+    fn edit_manifest_writes_edits() {
+        let (_tempdir, dir) = crate::test::test_tempdir("edit_manifest_writes_edits");
+        let path = dir.join("Cargo.toml");
+        fs::write(&path, "original").unwrap();
+
+        edit_manifest(&path, false, |original| {
+            assert_eq!(original, "original");
+            fs::write(&path, "edited")?;
+            Ok("edited".to_owned())
+        })
+        .unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "edited");
+    }
+
+    #[test]
+    fn edit_manifest_restores_on_failure() {
+        let (_tempdir, dir) = crate::test::test_tempdir("edit_manifest_restores_on_failure");
+        let path = dir.join("Cargo.toml");
+        fs::write(&path, "original").unwrap();
+
+        let err = edit_manifest(&path, false, |_| {
+            fs::write(&path, "edited")?;
+            bail!("Failed to repin")
+        })
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "Failed to repin");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+    }
+
+    #[test]
+    fn edit_manifest_dry_run() {
+        let (_tempdir, dir) = crate::test::test_tempdir("edit_manifest_dry_run");
+        let path = dir.join("Cargo.toml");
+        fs::write(&path, "original").unwrap();
+
+        edit_manifest(&path, true, |_| {
+            fs::write(&path, "edited")?;
+            Ok("edited".to_owned())
+        })
+        .unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+    }
+}
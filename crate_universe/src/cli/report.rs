@@ -0,0 +1,1010 @@
+//! The cli entrypoint for the `report` subcommand
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context as AnyhowContext, Result};
+use clap::{Parser, ValueEnum};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::config::CrateId;
+use crate::context::crate_context::{CrateContext, Rule};
+use crate::context::Context;
+use crate::metadata::SourceAnnotation;
+
+/// The format of the `report` output.
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A human-readable table.
+    #[default]
+    Text,
+
+    /// A machine-readable `json` report.
+    Json,
+}
+
+/// Command line options for the `report` subcommand
+#[derive(Parser, Debug)]
+#[clap(about = "Command line options for the `report` subcommand", version)]
+pub struct ReportOptions {
+    /// The path to a Cargo Bazel lockfile to report on.
+    #[clap(long)]
+    pub lockfile: PathBuf,
+
+    /// The path to a `json` policy file to check the dependencies against.
+    #[clap(long)]
+    pub policy: Option<PathBuf>,
+
+    /// The root of the Bazel workspace, used to inspect the sources of crates with local paths.
+    #[clap(long)]
+    pub workspace_dir: Option<PathBuf>,
+
+    /// The path to a local checkout of the RustSec advisory database
+    /// (<https://github.com/rustsec/advisory-db>) to check the crates against.
+    #[clap(long)]
+    pub advisory_db: Option<PathBuf>,
+
+    /// The format of the report.
+    #[clap(long, value_enum, default_value_t)]
+    pub format: ReportFormat,
+
+    /// The path to write the report to. If unset, it is printed to stdout.
+    #[clap(long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Where the sources of a crate are fetched from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SourceKind {
+    Http,
+    Git,
+    Path,
+}
+
+impl fmt::Display for SourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SourceKind::Http => "http",
+            SourceKind::Git => "git",
+            SourceKind::Path => "path",
+        })
+    }
+}
+
+/// An entry in the report for a single external crate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct CrateReport {
+    name: String,
+    version: semver::Version,
+    license: Option<String>,
+    repository: Option<String>,
+    source: SourceKind,
+    build_script: bool,
+    proc_macro: bool,
+
+    /// Whether the crate root has an inner `#![forbid(...)]` attribute listing
+    /// `unsafe_code`. This is only known for crates whose sources are available locally.
+    #[serde(serialize_with = "serialize_or_unknown")]
+    forbids_unsafe_code: Option<bool>,
+
+    /// The advisories which affect this version of the crate. This is only known for
+    /// crates fetched over http, which are assumed to come from crates.io, and only if
+    /// an advisory database was given.
+    #[serde(serialize_with = "serialize_or_unknown")]
+    advisories: Option<Vec<AdvisoryReport>>,
+}
+
+/// Serialize values which could not be determined as `"unknown"`.
+fn serialize_or_unknown<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    match value {
+        Some(value) => value.serialize(serializer),
+        None => serializer.serialize_str("unknown"),
+    }
+}
+
+/// An advisory which affects a crate in the report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct AdvisoryReport {
+    id: String,
+
+    /// The kind of an informational advisory, e.g. `unmaintained`, which doesn't
+    /// describe a vulnerability.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    informational: Option<String>,
+}
+
+impl fmt::Display for AdvisoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.informational {
+            Some(informational) => write!(f, "{} ({informational})", self.id),
+            None => f.write_str(&self.id),
+        }
+    }
+}
+
+/// The front matter of an advisory in the RustSec advisory database.
+#[derive(Debug, Deserialize)]
+struct Advisory {
+    advisory: AdvisoryMetadata,
+
+    #[serde(default)]
+    versions: AdvisoryVersions,
+}
+
+#[derive(Debug, Deserialize)]
+struct AdvisoryMetadata {
+    id: String,
+    package: String,
+    informational: Option<String>,
+    withdrawn: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AdvisoryVersions {
+    #[serde(default)]
+    patched: Vec<semver::VersionReq>,
+
+    #[serde(default)]
+    unaffected: Vec<semver::VersionReq>,
+}
+
+impl Advisory {
+    fn affects(&self, version: &semver::Version) -> bool {
+        !self
+            .versions
+            .patched
+            .iter()
+            .chain(&self.versions.unaffected)
+            .any(|req| req.matches(version))
+    }
+}
+
+/// The advisories of a local checkout of the RustSec advisory database, by package name.
+#[derive(Debug, Default)]
+struct AdvisoryDatabase {
+    advisories: BTreeMap<String, Vec<Advisory>>,
+}
+
+impl AdvisoryDatabase {
+    /// Load the advisories for crates.io packages, which are stored as `crates/<package>/<id>.md`
+    /// with `toml` front matter. Withdrawn advisories are skipped.
+    fn try_from_path(path: &Path) -> Result<Self> {
+        let mut advisories: BTreeMap<String, Vec<Advisory>> = BTreeMap::new();
+        for entry in walkdir::WalkDir::new(path.join("crates"))
+            .min_depth(2)
+            .max_depth(2)
+            .sort_by_file_name()
+        {
+            let entry = entry?;
+            if entry.path().extension().and_then(|ext| ext.to_str()) != Some("md") {
+                continue;
+            }
+            let advisory =
+                Self::parse_advisory(&fs::read_to_string(entry.path())?).with_context(|| {
+                    format!("Failed to parse advisory `{}`", entry.path().display())
+                })?;
+            if advisory.advisory.withdrawn.is_none() {
+                advisories
+                    .entry(advisory.advisory.package.clone())
+                    .or_default()
+                    .push(advisory);
+            }
+        }
+        Ok(Self { advisories })
+    }
+
+    fn parse_advisory(content: &str) -> Result<Advisory> {
+        let Some(front_matter) = content
+            .trim_start()
+            .strip_prefix("```toml")
+            .and_then(|rest| rest.split_once("\n```"))
+            .map(|(front_matter, _)| front_matter)
+        else {
+            bail!("The advisory has no `toml` front matter");
+        };
+        Ok(toml::from_str(front_matter)?)
+    }
+
+    fn check(&self, name: &str, version: &semver::Version) -> Vec<AdvisoryReport> {
+        self.advisories
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|advisory| advisory.affects(version))
+            .map(|advisory| AdvisoryReport {
+                id: advisory.advisory.id.clone(),
+                informational: advisory.advisory.informational.clone(),
+            })
+            .collect()
+    }
+}
+
+/// A dependency which doesn't satisfy the policy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Violation {
+    #[serde(rename = "crate")]
+    crate_id: CrateId,
+    reason: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    crates: Vec<CrateReport>,
+    violations: Vec<Violation>,
+}
+
+/// The rules external crates are checked against.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Policy {
+    /// If set, the license expression of every crate must be satisfiable using only these licenses.
+    #[serde(default, deserialize_with = "deserialize_optional_licensees")]
+    allow_licenses: Option<Vec<spdx::Licensee>>,
+
+    /// Licenses which may not be used to satisfy the license expression of a crate.
+    #[serde(default, deserialize_with = "deserialize_licensees")]
+    deny_licenses: Vec<spdx::Licensee>,
+
+    /// The names of crates which are exempt from license checks, e.g. for
+    /// crates with non-standard license expressions which have been reviewed.
+    #[serde(default)]
+    license_exceptions: BTreeSet<String>,
+
+    /// The names of crates which may not be depended on.
+    #[serde(default)]
+    deny_crates: BTreeSet<String>,
+
+    /// The kinds of sources crates may not be fetched from.
+    #[serde(default)]
+    deny_sources: BTreeSet<SourceKind>,
+
+    /// Whether crates affected by a vulnerability advisory are denied. Informational
+    /// advisories, e.g. for unmaintained crates, are reported but never denied.
+    #[serde(default)]
+    deny_advisories: bool,
+
+    /// The IDs of advisories which have been reviewed and are not denied.
+    #[serde(default)]
+    ignore_advisories: BTreeSet<String>,
+}
+
+impl Policy {
+    fn try_from_path(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    fn check(&self, crate_report: &CrateReport) -> Vec<String> {
+        let mut reasons = Vec::new();
+
+        if self.deny_crates.contains(&crate_report.name) {
+            reasons.push("the crate is denied".to_owned());
+        }
+
+        if self.deny_sources.contains(&crate_report.source) {
+            reasons.push(format!(
+                "crates from `{}` sources are denied",
+                crate_report.source
+            ));
+        }
+
+        if self.deny_advisories {
+            for advisory in crate_report.advisories.iter().flatten() {
+                if advisory.informational.is_none()
+                    && !self.ignore_advisories.contains(&advisory.id)
+                {
+                    reasons.push(format!(
+                        "the crate is affected by advisory `{}`",
+                        advisory.id
+                    ));
+                }
+            }
+        }
+
+        let checks_licenses = self.allow_licenses.is_some() || !self.deny_licenses.is_empty();
+        if checks_licenses && !self.license_exceptions.contains(&crate_report.name) {
+            let is_denied = |licensee: &spdx::Licensee| self.deny_licenses.contains(licensee);
+            let is_allowed = |req: &spdx::LicenseReq| match &self.allow_licenses {
+                Some(allowed) => allowed
+                    .iter()
+                    .any(|licensee| !is_denied(licensee) && licensee.satisfies(req)),
+                None => !self
+                    .deny_licenses
+                    .iter()
+                    .any(|licensee| licensee.satisfies(req)),
+            };
+            match &crate_report.license {
+                None if self.allow_licenses.is_some() => {
+                    reasons.push("the crate has no license".to_owned());
+                }
+                None => {}
+                Some(license) => {
+                    match spdx::Expression::parse_mode(license, spdx::ParseMode::LAX) {
+                        Ok(expression) => {
+                            if !expression.evaluate(is_allowed) {
+                                reasons.push(format!("the license `{license}` is not allowed"));
+                            }
+                        }
+                        Err(_) => {
+                            reasons.push(format!("the license `{license}` could not be parsed"));
+                        }
+                    }
+                }
+            }
+        }
+
+        reasons
+    }
+}
+
+/// Parse the name of a license in a policy. GNU licenses may be named with their `-only`
+/// and `-or-later` suffixes, which are dropped as they only apply to license holders, so
+/// that e.g. `GPL-3.0` matches both `GPL-3.0-only` and `GPL-3.0-or-later`.
+fn parse_licensee(name: &str) -> Result<spdx::Licensee> {
+    let root = name
+        .strip_suffix("-or-later")
+        .or_else(|| name.strip_suffix("-only"))
+        .or_else(|| name.strip_suffix('+'))
+        .unwrap_or(name);
+    spdx::Licensee::parse(root).with_context(|| format!("Invalid license `{name}`"))
+}
+
+fn deserialize_licensees<'de, D>(deserializer: D) -> Result<Vec<spdx::Licensee>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    BTreeSet::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| parse_licensee(name).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+fn deserialize_optional_licensees<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<spdx::Licensee>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_licensees(deserializer).map(Some)
+}
+
+/// Report on the external crates in a lockfile, optionally checking them against a policy
+pub fn report(opt: ReportOptions) -> Result<()> {
+    let context = Context::try_from_path(&opt.lockfile)
+        .with_context(|| format!("Failed to parse lockfile `{}`", opt.lockfile.display()))?;
+    let policy = match &opt.policy {
+        Some(path) => Policy::try_from_path(path)
+            .with_context(|| format!("Failed to parse policy `{}`", path.display()))?,
+        None => Policy::default(),
+    };
+    let advisory_db =
+        match &opt.advisory_db {
+            Some(path) => Some(AdvisoryDatabase::try_from_path(path).with_context(|| {
+                format!("Failed to load advisory database `{}`", path.display())
+            })?),
+            None if policy.deny_advisories => {
+                bail!("The policy denies advisories, but no `--advisory-db` was given")
+            }
+            None => None,
+        };
+
+    let report = generate_report(
+        &context,
+        &policy,
+        opt.workspace_dir.as_deref(),
+        advisory_db.as_ref(),
+    );
+    let output = match opt.format {
+        ReportFormat::Text => report.to_string(),
+        ReportFormat::Json => serde_json::to_string_pretty(&report)? + "\n",
+    };
+
+    match &opt.output {
+        Some(path) => fs::write(path, output)
+            .with_context(|| format!("Failed to write report to `{}`", path.display()))?,
+        None => print!("{output}"),
+    }
+
+    if !report.violations.is_empty() {
+        bail!(
+            "{} dependencies do not satisfy the policy",
+            report
+                .violations
+                .iter()
+                .map(|violation| &violation.crate_id)
+                .collect::<BTreeSet<_>>()
+                .len()
+        );
+    }
+
+    Ok(())
+}
+
+fn generate_report(
+    context: &Context,
+    policy: &Policy,
+    workspace_dir: Option<&Path>,
+    advisory_db: Option<&AdvisoryDatabase>,
+) -> Report {
+    let mut crates = Vec::new();
+    let mut violations = Vec::new();
+
+    for (crate_id, crate_context) in &context.crates {
+        let Some(repository) = &crate_context.repository else {
+            // Workspace members are not external dependencies.
+            continue;
+        };
+
+        let source = match repository {
+            SourceAnnotation::Http { .. } => SourceKind::Http,
+            SourceAnnotation::Git { .. } => SourceKind::Git,
+            SourceAnnotation::Path { .. } => SourceKind::Path,
+        };
+        let forbids_unsafe_code = match (repository, workspace_dir) {
+            (SourceAnnotation::Path { path }, Some(workspace_dir)) => {
+                forbids_unsafe_code(crate_context, &workspace_dir.join(path))
+            }
+            _ => None,
+        };
+
+        let crate_report = CrateReport {
+            name: crate_context.name.clone(),
+            version: crate_context.version.clone(),
+            license: crate_context.license.clone(),
+            repository: crate_context.package_url.clone(),
+            source,
+            build_script: crate_context
+                .targets
+                .iter()
+                .any(|t| matches!(t, Rule::BuildScript(_))),
+            proc_macro: crate_context
+                .targets
+                .iter()
+                .any(|t| matches!(t, Rule::ProcMacro(_))),
+            forbids_unsafe_code,
+            advisories: advisory_db
+                .filter(|_| source == SourceKind::Http)
+                .map(|db| db.check(&crate_context.name, &crate_context.version)),
+        };
+
+        violations.extend(
+            policy
+                .check(&crate_report)
+                .into_iter()
+                .map(|reason| Violation {
+                    crate_id: crate_id.clone(),
+                    reason,
+                }),
+        );
+        crates.push(crate_report);
+    }
+
+    Report { crates, violations }
+}
+
+/// Determine whether the library of a crate forbids unsafe code, given the directory
+/// containing its sources. Returns `None` if the crate root can't be read.
+fn forbids_unsafe_code(crate_context: &CrateContext, crate_dir: &Path) -> Option<bool> {
+    let crate_root = crate_context.targets.iter().find_map(|t| match t {
+        Rule::Library(attrs) | Rule::ProcMacro(attrs) => attrs.crate_root.as_ref(),
+        _ => None,
+    })?;
+    let content = fs::read_to_string(crate_dir.join(crate_root)).ok()?;
+    Some(has_forbid_unsafe_code(&content))
+}
+
+/// Whether the source of a crate root has an inner `#![forbid(...)]` attribute listing `unsafe_code`.
+fn has_forbid_unsafe_code(source: &str) -> bool {
+    inner_attributes(source).iter().any(|attribute| {
+        attribute
+            .strip_prefix("forbid(")
+            .and_then(|lints| lints.strip_suffix(')'))
+            .is_some_and(|lints| lints.split(',').any(|lint| lint == "unsafe_code"))
+    })
+}
+
+/// Collect the inner attributes (`#![...]`) at the start of a crate root, with comments
+/// and whitespace removed. Attributes after the first item don't apply to the crate and
+/// aren't returned. This is not a full Rust parser: attributes in `cfg_attr` are ignored.
+fn inner_attributes(source: &str) -> Vec<String> {
+    let source = strip_comments(source);
+    // A shebang line is not an attribute.
+    let mut rest = match source.strip_prefix("#!") {
+        Some(shebang) if !shebang.trim_start().starts_with('[') => {
+            shebang.split_once('\n').map_or("", |(_, rest)| rest)
+        }
+        _ => source.as_str(),
+    };
+
+    let mut attributes = Vec::new();
+    'attributes: while let Some(attribute) = rest
+        .trim_start()
+        .strip_prefix("#!")
+        .and_then(|attribute| attribute.trim_start().strip_prefix('['))
+    {
+        let mut depth = 1;
+        let mut in_string = false;
+        let mut escaped = false;
+        for (i, c) in attribute.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_string => escaped = true,
+                '"' => in_string = !in_string,
+                '[' | '(' | '{' if !in_string => depth += 1,
+                ']' | ')' | '}' if !in_string => {
+                    depth -= 1;
+                    if depth == 0 {
+                        attributes.push(attribute[..i].split_whitespace().collect());
+                        rest = &attribute[i + 1..];
+                        continue 'attributes;
+                    }
+                }
+                _ => {}
+            }
+        }
+        break;
+    }
+    attributes
+}
+
+/// Replace the comments in Rust source with whitespace, leaving string literals intact.
+fn strip_comments(source: &str) -> String {
+    let mut output = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_string = !in_string,
+            '\\' if in_string => {
+                output.push(c);
+                if let Some(escaped) = chars.next() {
+                    output.push(escaped);
+                }
+                continue;
+            }
+            '/' if !in_string && chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                output.push('\n');
+                continue;
+            }
+            '/' if !in_string && chars.peek() == Some(&'*') => {
+                chars.next();
+                // Block comments nest.
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some('/') if chars.peek() == Some(&'*') => {
+                            chars.next();
+                            depth += 1;
+                        }
+                        Some('*') if chars.peek() == Some(&'/') => {
+                            chars.next();
+                            depth -= 1;
+                        }
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+                output.push(' ');
+                continue;
+            }
+            _ => {}
+        }
+        output.push(c);
+    }
+    output
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+        let rows: Vec<[String; 8]> = self
+            .crates
+            .iter()
+            .map(|c| {
+                [
+                    format!("{} {}", c.name, c.version),
+                    c.license.clone().unwrap_or_else(|| "-".to_owned()),
+                    c.source.to_string(),
+                    yes_no(c.build_script).to_owned(),
+                    yes_no(c.proc_macro).to_owned(),
+                    c.forbids_unsafe_code.map_or("unknown", yes_no).to_owned(),
+                    match &c.advisories {
+                        None => "unknown".to_owned(),
+                        Some(advisories) if advisories.is_empty() => "-".to_owned(),
+                        Some(advisories) => advisories.iter().join(", "),
+                    },
+                    c.repository.clone().unwrap_or_else(|| "-".to_owned()),
+                ]
+            })
+            .collect();
+
+        let header = [
+            "Crate",
+            "License",
+            "Source",
+            "Build script",
+            "Proc macro",
+            "Forbids unsafe",
+            "Advisories",
+            "Repository",
+        ];
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let header = header.map(str::to_owned);
+        for row in std::iter::once(&header).chain(&rows) {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(f, "{}", line.trim_end())?;
+        }
+
+        if !self.violations.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Policy violations:")?;
+            for violation in &self.violations {
+                writeln!(f, "  {}: {}", violation.crate_id, violation.reason)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::context::crate_context::TargetAttributes;
+    use crate::test::cargo_bazel_lockfile::{MockCrate, MockLockfile};
+    use crate::utils::starlark::Glob;
+
+    fn mock_crate(name: &str) -> MockCrate {
+        MockCrate::new(name, "0.1.0").package_url(&format!("https://github.com/example/{name}"))
+    }
+
+    fn target(crate_root: &str) -> TargetAttributes {
+        TargetAttributes {
+            crate_name: "mock".to_owned(),
+            crate_root: Some(crate_root.to_owned()),
+            srcs: Glob::new_rust_srcs(false),
+        }
+    }
+
+    fn mock_context() -> Context {
+        MockLockfile::new([
+            mock_crate("dual")
+                .license("MIT OR Apache-2.0", &["Apache-2.0", "MIT"])
+                .targets([
+                    Rule::Library(target("src/lib.rs")),
+                    Rule::BuildScript(target("build.rs")),
+                ])
+                .build(),
+            mock_crate("copyleft")
+                .license("GPL-3.0-only", &["GPL-3.0-only"])
+                .targets([Rule::ProcMacro(target("src/lib.rs"))])
+                .build(),
+            mock_crate("local")
+                .repository(SourceAnnotation::Path {
+                    path: "third_party/local".into(),
+                })
+                .targets([Rule::Library(target("src/lib.rs"))])
+                .build(),
+            mock_crate("workspace_member")
+                .workspace_member()
+                .targets([])
+                .build(),
+        ])
+        .context()
+    }
+
+    #[test]
+    fn report_crates() {
+        let (_tempdir, workspace_dir) = crate::test::test_tempdir("report_crates");
+        let src = workspace_dir.join("third_party/local/src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(
+            src.join("lib.rs"),
+            "#![forbid( unsafe_code )]\npub fn f() {}\n",
+        )
+        .unwrap();
+
+        let report = generate_report(
+            &mock_context(),
+            &Policy::default(),
+            Some(&workspace_dir),
+            None,
+        );
+        assert!(report.violations.is_empty());
+        assert_eq!(
+            report
+                .crates
+                .iter()
+                .map(|c| (c.name.as_str(), c.source, c.build_script, c.proc_macro))
+                .collect::<Vec<_>>(),
+            vec![
+                ("copyleft", SourceKind::Http, false, true),
+                ("dual", SourceKind::Http, true, false),
+                ("local", SourceKind::Path, false, false),
+            ]
+        );
+        assert_eq!(
+            report
+                .crates
+                .iter()
+                .map(|c| c.forbids_unsafe_code)
+                .collect::<Vec<_>>(),
+            vec![None, None, Some(true)]
+        );
+
+        assert_eq!(
+            report.to_string(),
+            [
+                "Crate           License            Source  Build script  Proc macro  Forbids unsafe  Advisories  Repository",
+                "copyleft 0.1.0  GPL-3.0-only       http    no            yes         unknown         unknown     https://github.com/example/copyleft",
+                "dual 0.1.0      MIT OR Apache-2.0  http    yes           no          unknown         unknown     https://github.com/example/dual",
+                "local 0.1.0     -                  path    no            no          yes             unknown     https://github.com/example/local",
+                "",
+            ]
+            .join("\n")
+        );
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            json["crates"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|c| (&c["forbids_unsafe_code"], &c["advisories"]))
+                .collect::<Vec<_>>(),
+            vec![
+                (&json!("unknown"), &json!("unknown")),
+                (&json!("unknown"), &json!("unknown")),
+                (&json!(true), &json!("unknown")),
+            ]
+        );
+    }
+
+    fn write_advisory(db: &Path, package: &str, id: &str, front_matter: &str) {
+        let dir = db.join("crates").join(package);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(format!("{id}.md")),
+            format!(
+                "```toml\n[advisory]\nid = \"{id}\"\npackage = \"{package}\"\n{front_matter}```\n\n# Title\n\nDescription\n"
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn report_advisories() {
+        let (_tempdir, db) = crate::test::test_tempdir("report_advisories");
+        write_advisory(
+            &db,
+            "dual",
+            "RUSTSEC-2024-0001",
+            "[versions]\npatched = [\">= 0.1.1\"]\n",
+        );
+        write_advisory(
+            &db,
+            "dual",
+            "RUSTSEC-2024-0002",
+            "[versions]\npatched = [\">= 0.2.0\"]\nunaffected = [\"< 0.1.0\"]\n",
+        );
+        write_advisory(
+            &db,
+            "dual",
+            "RUSTSEC-2024-0003",
+            "[versions]\npatched = [\">= 0.0.2, < 0.1.0\", \">= 0.1.0\"]\n",
+        );
+        write_advisory(
+            &db,
+            "dual",
+            "RUSTSEC-2024-0004",
+            "withdrawn = \"2024-01-02\"\n[versions]\npatched = []\n",
+        );
+        write_advisory(
+            &db,
+            "copyleft",
+            "RUSTSEC-2024-0005",
+            "informational = \"unmaintained\"\n[versions]\npatched = []\n",
+        );
+        write_advisory(
+            &db,
+            "local",
+            "RUSTSEC-2024-0006",
+            "[versions]\npatched = []\n",
+        );
+        let db = AdvisoryDatabase::try_from_path(&db).unwrap();
+
+        let report = generate_report(&mock_context(), &Policy::default(), None, Some(&db));
+        assert!(report.violations.is_empty());
+        assert_eq!(
+            report
+                .crates
+                .iter()
+                .map(|c| (
+                    c.name.as_str(),
+                    c.advisories
+                        .as_ref()
+                        .map(|advisories| advisories.iter().join(", "))
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "copyleft",
+                    Some("RUSTSEC-2024-0005 (unmaintained)".to_owned())
+                ),
+                (
+                    "dual",
+                    Some("RUSTSEC-2024-0001, RUSTSEC-2024-0002".to_owned())
+                ),
+                // Advisories only apply to crates from crates.io.
+                ("local", None),
+            ]
+        );
+
+        let policy: Policy = serde_json::from_str(
+            r#"{"deny_advisories": true, "ignore_advisories": ["RUSTSEC-2024-0002"]}"#,
+        )
+        .unwrap();
+        let report = generate_report(&mock_context(), &policy, None, Some(&db));
+        assert_eq!(
+            report.violations,
+            vec![Violation {
+                crate_id: CrateId::new("dual".to_owned(), semver::Version::new(0, 1, 0)),
+                reason: "the crate is affected by advisory `RUSTSEC-2024-0001`".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_advisory_without_front_matter() {
+        assert!(AdvisoryDatabase::parse_advisory("# RUSTSEC-2024-0001\n").is_err());
+    }
+
+    #[test]
+    fn report_policy_violations() {
+        let policy: Policy = serde_json::from_str(
+            r#"{
+                "allow_licenses": ["MIT", "GPL-3.0"],
+                "deny_licenses": ["GPL-3.0"],
+                "deny_sources": ["path"]
+            }"#,
+        )
+        .unwrap();
+
+        let report = generate_report(&mock_context(), &policy, None, None);
+        assert_eq!(
+            report
+                .violations
+                .iter()
+                .map(|v| format!("{}: {}", v.crate_id, v.reason))
+                .collect::<Vec<_>>(),
+            vec![
+                "copyleft 0.1.0: the license `GPL-3.0-only` is not allowed",
+                "local 0.1.0: crates from `path` sources are denied",
+                "local 0.1.0: the crate has no license",
+            ]
+        );
+
+        let policy = Policy {
+            deny_crates: BTreeSet::from(["dual".to_owned()]),
+            license_exceptions: BTreeSet::from(["copyleft".to_owned()]),
+            deny_licenses: vec![parse_licensee("GPL-3.0").unwrap()],
+            ..Policy::default()
+        };
+        let report = generate_report(&mock_context(), &policy, None, None);
+        assert_eq!(
+            report.violations,
+            vec![Violation {
+                crate_id: CrateId::new("dual".to_owned(), semver::Version::new(0, 1, 0)),
+                reason: "the crate is denied".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn report_denied_licenses() {
+        // GNU licenses match regardless of their `-only` and `-or-later` suffixes.
+        for denied in ["GPL-3.0", "GPL-3.0-only", "GPL-3.0-or-later"] {
+            let policy: Policy =
+                serde_json::from_str(&format!(r#"{{"deny_licenses": ["{denied}"]}}"#)).unwrap();
+            let report = generate_report(&mock_context(), &policy, None, None);
+            assert_eq!(
+                report
+                    .violations
+                    .iter()
+                    .map(|v| format!("{}: {}", v.crate_id, v.reason))
+                    .collect::<Vec<_>>(),
+                vec!["copyleft 0.1.0: the license `GPL-3.0-only` is not allowed"],
+                "{denied}"
+            );
+        }
+
+        // A crate may use any license of an `OR` expression which isn't denied.
+        let policy: Policy = serde_json::from_str(r#"{"deny_licenses": ["MIT"]}"#).unwrap();
+        let report = generate_report(&mock_context(), &policy, None, None);
+        assert!(report.violations.is_empty(), "{:?}", report.violations);
+
+        let policy: Policy =
+            serde_json::from_str(r#"{"deny_licenses": ["MIT", "Apache-2.0"]}"#).unwrap();
+        let report = generate_report(&mock_context(), &policy, None, None);
+        assert_eq!(
+            report.violations,
+            vec![Violation {
+                crate_id: CrateId::new("dual".to_owned(), semver::Version::new(0, 1, 0)),
+                reason: "the license `MIT OR Apache-2.0` is not allowed".to_owned(),
+            }]
+        );
+
+        let policy: Policy = serde_json::from_str(
+            r#"{"allow_licenses": ["GPL-3.0-or-later", "MIT"], "deny_sources": ["path"]}"#,
+        )
+        .unwrap();
+        let report = generate_report(&mock_context(), &policy, None, None);
+        assert_eq!(
+            report
+                .violations
+                .iter()
+                .map(|v| format!("{}: {}", v.crate_id, v.reason))
+                .collect::<Vec<_>>(),
+            vec![
+                "local 0.1.0: crates from `path` sources are denied",
+                "local 0.1.0: the crate has no license",
+            ]
+        );
+
+        assert!(serde_json::from_str::<Policy>(r#"{"deny_licenses": ["Not a license"]}"#).is_err());
+    }
+
+    #[test]
+    fn forbid_unsafe_code_attributes() {
+        assert!(has_forbid_unsafe_code("#![forbid(unsafe_code)]\n"));
+        assert!(has_forbid_unsafe_code(
+            "#![forbid(missing_docs, unsafe_code)]\n"
+        ));
+        assert!(has_forbid_unsafe_code(
+            "#!/usr/bin/env run-cargo-script\n//! Docs\n#![doc = \"]\"]\n#![forbid(\n    unsafe_code, // Reviewed\n)]\n"
+        ));
+        assert!(has_forbid_unsafe_code(
+            "/* /* nested */ */ #![forbid(unsafe_code)]"
+        ));
+
+        assert!(!has_forbid_unsafe_code("// #![forbid(unsafe_code)]\n"));
+        assert!(!has_forbid_unsafe_code("/* #![forbid(unsafe_code)] */\n"));
+        assert!(!has_forbid_unsafe_code("#![deny(unsafe_code)]\n"));
+        assert!(!has_forbid_unsafe_code(
+            "#![forbid(unsafe_op_in_unsafe_fn)]\n"
+        ));
+        assert!(!has_forbid_unsafe_code(
+            "#![cfg_attr(not(test), forbid(unsafe_code))]\n"
+        ));
+        assert!(!has_forbid_unsafe_code(
+            "pub fn f() {}\n#![forbid(unsafe_code)]\n"
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+//! The cli entrypoint for the `sbom` subcommand
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, ValueEnum};
+use sha2::{Digest as Sha2Digest, Sha256};
+
+use crate::api::lockfile::{parse, CargoBazelLockfile};
+use crate::api::sbom::Sbom;
+
+/// The format of the software bill of materials.
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SbomFormat {
+    /// A [CycloneDX](https://cyclonedx.org/) json document.
+    #[default]
+    Cyclonedx,
+
+    /// An [SPDX](https://spdx.dev/) json document.
+    Spdx,
+}
+
+/// Command line options for the `sbom` subcommand
+#[derive(Parser, Debug)]
+#[clap(about = "Command line options for the `sbom` subcommand", version)]
+pub struct SbomOptions {
+    /// The path to a Cargo Bazel lockfile.
+    #[clap(long)]
+    pub lockfile: PathBuf,
+
+    /// The format of the bill of materials.
+    #[clap(long, value_enum, default_value_t)]
+    pub format: SbomFormat,
+
+    /// The name of a workspace member. If set, only it and its transitive dependencies are included.
+    #[clap(long)]
+    pub member: Option<String>,
+
+    /// The SPDX document namespace. Defaults to a uri derived from the contents of the lockfile.
+    #[clap(long)]
+    pub namespace: Option<String>,
+
+    /// The path to write the bill of materials to. If unset, it is printed to stdout.
+    #[clap(long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Generate a software bill of materials for the crates in a lockfile
+pub fn sbom(opt: SbomOptions) -> Result<()> {
+    let content = fs::read(&opt.lockfile)
+        .with_context(|| format!("Failed to read lockfile `{}`", opt.lockfile.display()))?;
+    let lockfile = parse(&opt.lockfile)
+        .with_context(|| format!("Failed to parse lockfile `{}`", opt.lockfile.display()))?;
+
+    let member = match &opt.member {
+        Some(name) => {
+            let mut members = lockfile
+                .workspace_members()
+                .into_iter()
+                .filter(|member| &member.name == name);
+            match (members.next(), members.next()) {
+                (Some(member), None) => Some(member),
+                (Some(_), Some(_)) => bail!("Multiple workspace members are named `{name}`"),
+                (None, _) => bail!("No workspace member is named `{name}`"),
+            }
+        }
+        None => None,
+    };
+
+    let sbom = Sbom::new(&lockfile, member.as_ref())?;
+
+    // Honor `SOURCE_DATE_EPOCH` so that bills of materials can be reproducible.
+    let timestamp = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => {
+            let secs = epoch
+                .parse()
+                .with_context(|| format!("Invalid SOURCE_DATE_EPOCH `{epoch}`"))?;
+            UNIX_EPOCH + Duration::from_secs(secs)
+        }
+        Err(_) => SystemTime::now(),
+    };
+
+    let document = match opt.format {
+        SbomFormat::Cyclonedx => sbom.to_cyclonedx(timestamp),
+        SbomFormat::Spdx => {
+            let namespace = opt.namespace.unwrap_or_else(|| {
+                format!(
+                    "https://github.com/bazelbuild/rules_rust/crate_universe/sbom/{}",
+                    hex::encode(Sha256::digest(&content))
+                )
+            });
+            sbom.to_spdx(&namespace, timestamp)
+        }
+    };
+    let document = serde_json::to_string_pretty(&document)? + "\n";
+
+    match &opt.output {
+        Some(path) => fs::write(path, document)
+            .with_context(|| format!("Failed to write bill of materials to `{}`", path.display())),
+        None => {
+            print!("{document}");
+            Ok(())
+        }
+    }
+}
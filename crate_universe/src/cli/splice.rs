@@ -77,6 +77,12 @@ pub struct SpliceOptions {
     /// You basically never want to use this value.
     #[clap(long)]
     pub nonhermetic_root_bazel_workspace_dir: Utf8PathBuf,
+
+    /// A directory in which to cache the output of `cargo tree` for each platform. Cached
+    /// outputs are reused as long as the Cargo.lock file, manifests, toolchain, Cargo config
+    /// and `CARGO_*` environment variables are unchanged. Outputs unused for 30 days are removed.
+    #[clap(long, env = "CARGO_BAZEL_TREE_RESOLVER_CACHE_DIR")]
+    pub tree_resolver_cache_dir: Option<PathBuf>,
 }
 
 /// Combine a set of disjoint manifests into a single workspace.
@@ -130,9 +136,12 @@ pub fn splice(opt: SpliceOptions) -> Result<()> {
     let config = Config::try_from_path(&opt.config).context("Failed to parse config")?;
 
     let resolver_data = TreeResolver::new(cargo.clone())
+        .with_cache_dir(opt.tree_resolver_cache_dir.clone())
         .generate(
             manifest_path.as_path_buf(),
             &config.supported_platform_triples,
+            &config.target_specs,
+            &config.platform_cfgs,
         )
         .context("Failed to generate features")?;
 
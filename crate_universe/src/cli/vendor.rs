@@ -21,6 +21,8 @@ use crate::metadata::{Annotations, Cargo, VendorGenerator};
 use crate::rendering::{render_module_label, write_outputs, Renderer};
 use crate::splicing::{generate_lockfile, Splicer, SplicingManifest, WorkspaceMetadata};
 use crate::utils::normalize_cargo_file_paths;
+use crate::utils::target_spec::TargetSpec;
+use crate::utils::target_triple::TargetTriple;
 
 /// Command line options for the `vendor` subcommand
 #[derive(Parser, Debug)]
@@ -88,6 +90,41 @@ pub struct VendorOptions {
     /// You basically never want to use this value.
     #[clap(long)]
     pub nonhermetic_root_bazel_workspace_dir: Utf8PathBuf,
+
+    /// A directory in which to cache the output of `cargo tree` for each platform. Cached
+    /// outputs are reused as long as the Cargo.lock file, manifests, toolchain, Cargo config
+    /// and `CARGO_*` environment variables are unchanged. Outputs unused for 30 days are removed.
+    #[clap(long, env = "CARGO_BAZEL_TREE_RESOLVER_CACHE_DIR")]
+    pub tree_resolver_cache_dir: Option<PathBuf>,
+
+    /// A [custom target specification](https://doc.rust-lang.org/rustc/targets/custom.html)
+    /// to support in addition to those in the config, in the form `<triple>=<path>`.
+    #[clap(long = "target-spec", value_parser = parse_target_spec)]
+    pub target_specs: Vec<(String, PathBuf)>,
+}
+
+pub(crate) fn parse_target_spec(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        Some((triple, path)) if !triple.is_empty() && !path.is_empty() => {
+            Ok((triple.to_owned(), PathBuf::from(path)))
+        }
+        _ => Err(format!("expected `<triple>=<path>`, got `{value}`")),
+    }
+}
+
+/// Add the custom target specifications passed on the command line to `config`.
+pub(crate) fn add_target_specs(
+    config: &mut Config,
+    target_specs: &[(String, PathBuf)],
+) -> anyhow::Result<()> {
+    for (triple, path) in target_specs {
+        let triple = TargetTriple::from_bazel(triple.clone());
+        config.supported_platform_triples.insert(triple.clone());
+        config
+            .target_specs
+            .insert(triple, TargetSpec::try_from_path(path)?);
+    }
+    Ok(())
 }
 
 /// Format content via buildifier's stdin/stdout, avoiding the need to write
@@ -252,12 +289,17 @@ pub fn vendor(opt: VendorOptions) -> anyhow::Result<()> {
     )?;
 
     // Load the config from disk
-    let config = Config::try_from_path(&opt.config)?;
-
-    let resolver_data = TreeResolver::new(cargo.clone()).generate(
-        manifest_path.as_path_buf(),
-        &config.supported_platform_triples,
-    )?;
+    let mut config = Config::try_from_path(&opt.config)?;
+    add_target_specs(&mut config, &opt.target_specs)?;
+
+    let resolver_data = TreeResolver::new(cargo.clone())
+        .with_cache_dir(opt.tree_resolver_cache_dir.clone())
+        .generate(
+            manifest_path.as_path_buf(),
+            &config.supported_platform_triples,
+            &config.target_specs,
+            &config.platform_cfgs,
+        )?;
 
     // Write the registry url info to the manifest now that a lockfile has been generated
     WorkspaceMetadata::write_registry_urls_and_feature_map(
@@ -400,4 +442,35 @@ mod tests {
         );
         assert_eq!(PathBuf::from("/tmp/output_base"), info.output_base);
     }
+
+    #[test]
+    fn test_add_target_specs() {
+        let (_tempdir, dir) = crate::test::test_tempdir("test_add_target_specs");
+        let spec = dir.join("thumbv7em-acme-none.json");
+        fs::write(
+            &spec,
+            r#"{"arch": "arm", "llvm-target": "thumbv7em-none-eabihf", "target-pointer-width": "32"}"#,
+        )
+        .unwrap();
+
+        let (triple, path) =
+            parse_target_spec(&format!("thumbv7em-acme-none={}", spec.display())).unwrap();
+        assert_eq!(triple, "thumbv7em-acme-none");
+        assert!(parse_target_spec("thumbv7em-acme-none").is_err());
+
+        let mut config = Config::default();
+        add_target_specs(&mut config, &[(triple.clone(), path)]).unwrap();
+        let triple = TargetTriple::from_bazel(triple);
+        assert!(config.supported_platform_triples.contains(&triple));
+        assert_eq!(
+            config.target_specs[&triple],
+            TargetSpec::try_from_path(&spec).unwrap()
+        );
+
+        assert!(add_target_specs(
+            &mut config,
+            &[(triple.to_bazel(), dir.join("missing.json"))]
+        )
+        .is_err());
+    }
 }
@@ -9,7 +9,7 @@ use std::path::Path;
 use std::str::FromStr;
 use std::{fmt, fs};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use cargo_lock::package::GitReference;
 use cargo_metadata::Package;
 use semver::VersionReq;
@@ -19,6 +19,7 @@ use serde::{Deserialize, Serialize, Serializer};
 
 use crate::select::{Select, Selectable};
 use crate::utils::starlark::Label;
+use crate::utils::target_spec::TargetSpec;
 use crate::utils::target_triple::TargetTriple;
 
 /// Representations of different kinds of crate vendoring into workspaces.
@@ -76,6 +77,13 @@ pub(crate) struct RenderConfig {
     #[serde(default = "default_crate_repository_template")]
     pub(crate) crate_repository_template: String,
 
+    /// The patterns of mirror URLs to download crates from before falling back to the registry,
+    /// such as a directory populated by `cargo-bazel mirror`. Patterns using `{sha256}` are skipped
+    /// for crates without a checksum.
+    /// Eg. `https://mirror.example.com/crates/sha256/{sha256}`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) crate_mirror_url_templates: Vec<String>,
+
     /// Default alias rule to use for packages.  Can be overridden by annotations.
     #[serde(default)]
     pub(crate) default_alias_rule: AliasRule,
@@ -121,6 +129,7 @@ impl Default for RenderConfig {
             crate_alias_template: default_crate_alias_template(),
             crates_module_template: default_crates_module_template(),
             crate_repository_template: default_crate_repository_template(),
+            crate_mirror_url_templates: Vec::default(),
             default_alias_rule: AliasRule::default(),
             default_package_name: Option::default(),
             generate_cargo_toml_env_vars: default_generate_cargo_toml_env_vars(),
@@ -278,6 +287,19 @@ pub(crate) struct CrateAnnotations {
     /// If true, disables pipelining for library targets generated for this crate.
     pub(crate) disable_pipelining: bool,
 
+    /// If true, `cdylib` targets of the crate are produced as `rust_shared_library` targets.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) gen_cdylib: bool,
+
+    /// If true, `staticlib` targets of the crate are produced as `rust_static_library` targets.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) gen_staticlib: bool,
+
+    /// If true, `rust_test` targets are produced for the library's unit tests and for each
+    /// integration test of the crate.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) gen_tests: bool,
+
     /// Additional data to pass to  the target's
     /// [rustc_env](https://bazelbuild.github.io/rules_rust/defs.html#rust_library-rustc_env) attribute.
     pub(crate) rustc_env: Option<Select<BTreeMap<String, String>>>,
@@ -419,6 +441,9 @@ impl Add for CrateAnnotations {
             data: select_merge(self.data, rhs.data),
             data_glob: joined_extra_member!(self.data_glob, rhs.data_glob, BTreeSet::new, BTreeSet::extend),
             disable_pipelining: self.disable_pipelining || rhs.disable_pipelining,
+            gen_cdylib: self.gen_cdylib || rhs.gen_cdylib,
+            gen_staticlib: self.gen_staticlib || rhs.gen_staticlib,
+            gen_tests: self.gen_tests || rhs.gen_tests,
             compile_data: select_merge(self.compile_data, rhs.compile_data),
             compile_data_glob: joined_extra_member!(self.compile_data_glob, rhs.compile_data_glob, BTreeSet::new, BTreeSet::extend),
             compile_data_glob_excludes: joined_extra_member!(self.compile_data_glob_excludes, rhs.compile_data_glob_excludes, BTreeSet::new, BTreeSet::extend),
@@ -693,6 +718,100 @@ impl<'de> Visitor<'de> for GenBinariesVisitor {
     }
 }
 
+/// A single `cfg` option, as would be passed to `rustc --cfg`. Either a name
+/// (`tokio_unstable`) or a key-value pair (`foo="bar"`).
+///
+/// `target_feature="..."` atoms enable a target feature rather than setting a
+/// `cfg`, as `rustc` does not allow built-in `cfg` names to be set directly.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum CfgAtom {
+    Name(String),
+    KeyValue { key: String, value: String },
+}
+
+impl CfgAtom {
+    /// The target feature enabled by this atom, if any.
+    pub(crate) fn target_feature(&self) -> Option<&str> {
+        match self {
+            CfgAtom::KeyValue { key, value } if key == "target_feature" => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The `rustc` flags which enable this atom.
+    pub(crate) fn rustc_flags(&self) -> [String; 2] {
+        match self.target_feature() {
+            Some(feature) => ["-C".to_owned(), format!("target-feature=+{feature}")],
+            None => ["--cfg".to_owned(), self.to_string()],
+        }
+    }
+}
+
+impl FromStr for CfgAtom {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let is_ident = |name: &str| {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+
+        let atom = match s.split_once('=') {
+            None => CfgAtom::Name(s.trim().to_owned()),
+            Some((key, value)) => {
+                let value = value
+                    .trim()
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .with_context(|| format!("The value of cfg `{s}` must be quoted"))?;
+                CfgAtom::KeyValue {
+                    key: key.trim().to_owned(),
+                    value: value.to_owned(),
+                }
+            }
+        };
+
+        let name = match &atom {
+            CfgAtom::Name(name) => name,
+            CfgAtom::KeyValue { key, .. } => key,
+        };
+        if !is_ident(name) {
+            bail!("Invalid cfg name `{name}` in `{s}`");
+        }
+
+        Ok(atom)
+    }
+}
+
+impl fmt::Display for CfgAtom {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CfgAtom::Name(name) => f.write_str(name),
+            CfgAtom::KeyValue { key, value } => write!(f, "{key}=\"{value}\""),
+        }
+    }
+}
+
+impl Serialize for CfgAtom {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for CfgAtom {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 /// Workspace specific settings to control how targets are generated
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
@@ -717,6 +836,17 @@ pub(crate) struct Config {
     /// A set of platform triples to use in generated select statements
     #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
     pub(crate) supported_platform_triples: BTreeSet<TargetTriple>,
+
+    /// [Custom target specifications](https://doc.rust-lang.org/rustc/targets/custom.html)
+    /// for any `supported_platform_triples` which are not built into `rustc`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) target_specs: BTreeMap<TargetTriple, TargetSpec>,
+
+    /// Extra `cfg` options enabled for each of the `supported_platform_triples`,
+    /// e.g. from `--cfg` flags in `rustflags`. These are considered both when
+    /// resolving features with Cargo and when evaluating `cfg(...)` dependencies.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) platform_cfgs: BTreeMap<TargetTriple, BTreeSet<CfgAtom>>,
 }
 
 // rules_rust/crate_universe/private/generate_utils.bzl:generate_config
@@ -993,6 +1123,40 @@ mod test {
         assert!(!id.matches(&package));
     }
 
+    #[test]
+    fn test_cfg_atom_serde() {
+        let atoms: Vec<CfgAtom> = serde_json::from_str(
+            r#"["tokio_unstable", "foo = \"bar\"", "target_feature=\"avx2\""]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            atoms,
+            vec![
+                CfgAtom::Name("tokio_unstable".to_owned()),
+                CfgAtom::KeyValue {
+                    key: "foo".to_owned(),
+                    value: "bar".to_owned(),
+                },
+                CfgAtom::KeyValue {
+                    key: "target_feature".to_owned(),
+                    value: "avx2".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&atoms).unwrap(),
+            r#"["tokio_unstable","foo=\"bar\"","target_feature=\"avx2\""]"#
+        );
+
+        assert_eq!(atoms[0].rustc_flags(), ["--cfg", "tokio_unstable"]);
+        assert_eq!(atoms[1].rustc_flags(), ["--cfg", "foo=\"bar\""]);
+        assert_eq!(atoms[2].rustc_flags(), ["-C", "target-feature=+avx2"]);
+
+        for invalid in ["foo=bar", "", "1foo", "foo-bar=\"baz\""] {
+            assert!(invalid.parse::<CfgAtom>().is_err(), "{invalid}");
+        }
+    }
+
     #[test]
     fn deserialize_config() {
         let runfiles = runfiles::Runfiles::create().unwrap();
@@ -9,12 +9,13 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use anyhow::Result;
+use cargo_metadata::PackageId;
 use serde::{Deserialize, Serialize};
 
 use crate::config::{CrateId, RenderConfig};
 use crate::context::platforms::resolve_cfg_platforms;
-use crate::lockfile::Digest;
-use crate::metadata::{Annotations, Dependency};
+use crate::lockfile::{Digest, DigestInputs};
+use crate::metadata::{Annotations, ArtifactDependency, Dependency};
 use crate::select::Select;
 use crate::utils::target_triple::TargetTriple;
 
@@ -27,6 +28,10 @@ pub(crate) struct Context {
     /// The collective checksum of all inputs to the context
     pub(crate) checksum: Option<Digest>,
 
+    /// The individual inputs of `checksum`, used to explain why a repin is required
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) digest_inputs: Option<DigestInputs>,
+
     /// The collection of all crates that make up the dependency graph
     pub(crate) crates: BTreeMap<CrateId, CrateContext>,
 
@@ -62,6 +67,24 @@ impl Context {
     }
 
     pub(crate) fn new(annotations: Annotations, sources_are_present: bool) -> anyhow::Result<Self> {
+        // Collect the artifacts each package must provide to its dependents
+        let mut required_artifacts: BTreeMap<PackageId, BTreeSet<ArtifactDependency>> =
+            BTreeMap::new();
+        for annotation in annotations.metadata.crates.values() {
+            for dep in annotation
+                .deps
+                .artifact_deps
+                .values()
+                .into_iter()
+                .chain(annotation.deps.build_artifact_deps.values())
+            {
+                required_artifacts
+                    .entry(dep.package_id.clone())
+                    .or_default()
+                    .insert(dep);
+            }
+        }
+
         // Build a map of crate contexts
         let crates: BTreeMap<CrateId, CrateContext> = annotations
             .metadata
@@ -74,6 +97,9 @@ impl Context {
                     &annotations.lockfile.crates,
                     &annotations.pairred_extras,
                     &annotations.metadata.workspace_metadata.tree_metadata,
+                    required_artifacts
+                        .get(&annotation.node.id)
+                        .unwrap_or(&BTreeSet::new()),
                     annotations.config.generate_binaries,
                     annotations.config.generate_build_scripts,
                     sources_are_present,
@@ -97,6 +123,8 @@ impl Context {
         let conditions = resolve_cfg_platforms(
             crates.values().collect(),
             &annotations.config.supported_platform_triples,
+            &annotations.config.target_specs,
+            &annotations.config.platform_cfgs,
         )?;
 
         // Generate a list of all workspace members
@@ -152,6 +180,7 @@ impl Context {
 
         Ok(Self {
             checksum: None,
+            digest_inputs: None,
             crates,
             binary_crates,
             workspace_members,
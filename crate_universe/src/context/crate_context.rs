@@ -8,7 +8,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::config::{AliasRule, CrateId, GenBinaries};
 use crate::metadata::{
-    CrateAnnotation, Dependency, PairedExtras, SourceAnnotation, TreeResolverMetadata,
+    ArtifactDependency, ArtifactKind, CrateAnnotation, Dependency, PairedExtras, SourceAnnotation,
+    TreeResolverMetadata,
 };
 use crate::select::Select;
 use crate::utils::sanitize_module_name;
@@ -33,6 +34,89 @@ pub struct CrateDependency {
     pub(crate) local_path: Option<Utf8PathBuf>,
 }
 
+/// A binary or C-ABI library of another crate consumed through an
+/// [artifact dependency](https://doc.rust-lang.org/nightly/cargo/reference/unstable.html#artifact-dependencies).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub(crate) struct CrateArtifactDependency {
+    /// The [CrateId] of the dependency
+    pub(crate) id: CrateId,
+
+    /// The name of the dependency from the perspective of the current crate.
+    /// This is the `<DEP>` in `CARGO_<ARTIFACT-TYPE>_FILE_<DEP>_<NAME>`.
+    pub(crate) name: String,
+
+    /// The kind of artifact.
+    pub(crate) kind: ArtifactKind,
+
+    /// The Cargo name of the binary or library target producing the artifact.
+    /// This is the `<NAME>` in `CARGO_<ARTIFACT-TYPE>_FILE_<DEP>_<NAME>`.
+    pub(crate) artifact_name: String,
+
+    /// The Bazel target name of the artifact within the dependency's package.
+    pub(crate) target: String,
+
+    /// An explicit `target` the artifact is built for. `<target>` refers to the target of
+    /// the current crate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) compile_target: Option<String>,
+
+    /// Local path of this dependency if provided.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) local_path: Option<Utf8PathBuf>,
+}
+
+impl CrateArtifactDependency {
+    /// Resolve an [ArtifactDependency] into the targets of the package producing it.
+    fn new(
+        dep: &ArtifactDependency,
+        packages: &BTreeMap<PackageId, Package>,
+        source_annotations: &BTreeMap<PackageId, SourceAnnotation>,
+    ) -> Vec<Self> {
+        let pkg = &packages[&dep.package_id];
+        pkg.targets
+            .iter()
+            .filter_map(|target| {
+                let (artifact_name, target_name) = match dep.kind {
+                    ArtifactKind::Bin => {
+                        if !target.is_bin()
+                            || dep.bin_name.as_ref().is_some_and(|bin| bin != &target.name)
+                        {
+                            return None;
+                        }
+                        (target.name.clone(), format!("{}__bin", target.name))
+                    }
+                    ArtifactKind::CDyLib => {
+                        if !target.is_cdylib() {
+                            return None;
+                        }
+                        let crate_name = sanitize_module_name(&target.name);
+                        (target.name.clone(), format!("{crate_name}__cdylib"))
+                    }
+                    ArtifactKind::StaticLib => {
+                        if !target.is_staticlib() {
+                            return None;
+                        }
+                        let crate_name = sanitize_module_name(&target.name);
+                        (target.name.clone(), format!("{crate_name}__staticlib"))
+                    }
+                };
+                Some(CrateArtifactDependency {
+                    id: CrateId::new(pkg.name.clone(), pkg.version.clone()),
+                    name: dep.name.clone(),
+                    kind: dep.kind,
+                    artifact_name,
+                    target: target_name,
+                    compile_target: dep.compile_target.clone(),
+                    local_path: match source_annotations.get(&dep.package_id) {
+                        Some(SourceAnnotation::Path { path }) => Some(path.clone()),
+                        _ => None,
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub(crate) struct TargetAttributes {
@@ -63,6 +147,18 @@ pub(crate) enum Rule {
     /// `rust_binary`
     Binary(TargetAttributes),
 
+    /// `rust_shared_library`
+    SharedLibrary(TargetAttributes),
+
+    /// `rust_static_library`
+    StaticLibrary(TargetAttributes),
+
+    /// `rust_test` for the unit tests of a library
+    LibraryTest(TargetAttributes),
+
+    /// `rust_test` for an integration test
+    Test(TargetAttributes),
+
     /// `cargo_build_script`
     BuildScript(TargetAttributes),
 }
@@ -75,6 +171,9 @@ impl Rule {
             Self::Library(..) => "lib",
             Self::ProcMacro(..) => "proc-macro",
             Self::Binary(..) => "bin",
+            Self::SharedLibrary(..) => "cdylib",
+            Self::StaticLibrary(..) => "staticlib",
+            Self::LibraryTest(..) | Self::Test(..) => "test",
             Self::BuildScript(..) => "custom-build",
         }
     }
@@ -84,6 +183,10 @@ impl Rule {
             Self::Library(attrs)
             | Self::ProcMacro(attrs)
             | Self::Binary(attrs)
+            | Self::SharedLibrary(attrs)
+            | Self::StaticLibrary(attrs)
+            | Self::LibraryTest(attrs)
+            | Self::Test(attrs)
             | Self::BuildScript(attrs) => &attrs.crate_name,
         }
     }
@@ -121,6 +224,9 @@ pub(crate) struct CommonAttributes {
     #[serde(skip_serializing_if = "Select::is_empty")]
     pub(crate) deps_dev: Select<BTreeSet<CrateDependency>>,
 
+    #[serde(skip_serializing_if = "Select::is_empty")]
+    pub(crate) artifact_deps: Select<BTreeSet<CrateArtifactDependency>>,
+
     pub(crate) edition: String,
 
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -163,6 +269,7 @@ impl Default for CommonAttributes {
             deps: Default::default(),
             extra_deps: Default::default(),
             deps_dev: Default::default(),
+            artifact_deps: Default::default(),
             edition: Default::default(),
             linker_script: Default::default(),
             proc_macro_deps: Default::default(),
@@ -226,6 +333,9 @@ pub(crate) struct BuildScriptAttributes {
     #[serde(skip_serializing_if = "Select::is_empty")]
     pub(crate) extra_link_deps: Select<BTreeSet<Label>>,
 
+    #[serde(skip_serializing_if = "Select::is_empty")]
+    pub(crate) artifact_deps: Select<BTreeSet<CrateArtifactDependency>>,
+
     #[serde(skip_serializing_if = "Select::is_empty")]
     pub(crate) build_script_env: Select<BTreeMap<String, String>>,
 
@@ -281,6 +391,7 @@ impl Default for BuildScriptAttributes {
             extra_deps: Default::default(),
             link_deps: Default::default(),
             extra_link_deps: Default::default(),
+            artifact_deps: Default::default(),
             build_script_env: Default::default(),
             build_script_env_files: Default::default(),
             exec_properties: Default::default(),
@@ -381,6 +492,7 @@ impl CrateContext {
         source_annotations: &BTreeMap<PackageId, SourceAnnotation>,
         extras: &BTreeMap<CrateId, PairedExtras>,
         resolver_data: &TreeResolverMetadata,
+        required_artifacts: &BTreeSet<ArtifactDependency>,
         include_binaries: bool,
         include_build_scripts: bool,
         sources_are_present: bool,
@@ -417,6 +529,17 @@ impl CrateContext {
             .clone()
             .map(new_crate_dep);
 
+        let new_artifact_deps = |deps: &Select<BTreeSet<ArtifactDependency>>| {
+            let mut artifact_deps: Select<BTreeSet<CrateArtifactDependency>> = Select::default();
+            for (configuration, dep) in deps.items() {
+                for artifact in CrateArtifactDependency::new(&dep, packages, source_annotations) {
+                    artifact_deps.insert(artifact, configuration.clone());
+                }
+            }
+            artifact_deps
+        };
+        let artifact_deps = new_artifact_deps(&annotation.deps.artifact_deps);
+
         let crate_features = resolver_data
             .get(&current_crate_id)
             .map(|tree_data| {
@@ -435,6 +558,7 @@ impl CrateContext {
             crate_features,
             deps,
             deps_dev,
+            artifact_deps,
             edition: package.edition.as_str().to_string(),
             proc_macro_deps,
             proc_macro_deps_dev,
@@ -451,20 +575,69 @@ impl CrateContext {
             Self::crate_includes_build_script(package_extra, include_build_scripts);
 
         let gen_none = GenBinaries::Some(BTreeSet::new());
-        let gen_binaries = package_extra
+        let mut gen_binaries = package_extra
             .and_then(|(_, settings)| settings.crate_extra.gen_binaries.as_ref())
             .unwrap_or(if include_binaries {
                 &GenBinaries::All
             } else {
                 &gen_none
-            });
+            })
+            .clone();
+
+        let (mut gen_cdylib, mut gen_staticlib, mut gen_tests) = package_extra
+            .map(|(_, settings)| {
+                (
+                    settings.crate_extra.gen_cdylib,
+                    settings.crate_extra.gen_staticlib,
+                    settings.crate_extra.gen_tests,
+                )
+            })
+            .unwrap_or_default();
+
+        // Cargo only resolves the dev-dependencies of workspace members, so the tests of
+        // other crates which declare any can't be built and are skipped.
+        if gen_tests
+            && common_attrs.deps_dev.is_empty()
+            && common_attrs.proc_macro_deps_dev.is_empty()
+        {
+            let dev_dependencies: BTreeSet<&str> = package
+                .dependencies
+                .iter()
+                .filter(|dep| matches!(dep.kind, cargo_metadata::DependencyKind::Development))
+                .map(|dep| dep.name.as_str())
+                .collect();
+            if !dev_dependencies.is_empty() {
+                tracing::warn!(
+                    "`gen_tests` is set for {}, which declares dev-dependencies ({}). Cargo doesn't resolve the dev-dependencies of crates outside the workspace, so its tests are not generated.",
+                    current_crate_id,
+                    dev_dependencies.into_iter().collect::<Vec<_>>().join(", ")
+                );
+                gen_tests = false;
+            }
+        }
+
+        // Artifacts other crates depend on must always be generated.
+        for artifact in required_artifacts {
+            match (artifact.kind, &artifact.bin_name, &mut gen_binaries) {
+                (ArtifactKind::Bin, _, GenBinaries::All) => {}
+                (ArtifactKind::Bin, Some(bin_name), GenBinaries::Some(set)) => {
+                    set.insert(bin_name.clone());
+                }
+                (ArtifactKind::Bin, None, _) => gen_binaries = GenBinaries::All,
+                (ArtifactKind::CDyLib, ..) => gen_cdylib = true,
+                (ArtifactKind::StaticLib, ..) => gen_staticlib = true,
+            }
+        }
 
         // Iterate over each target and produce a Bazel target for all supported "kinds"
         let targets = Self::collect_targets(
             &annotation.node,
             packages,
-            gen_binaries,
+            &gen_binaries,
             include_build_scripts,
+            gen_cdylib,
+            gen_staticlib,
+            gen_tests,
             sources_are_present,
         )?;
 
@@ -516,10 +689,12 @@ impl CrateContext {
                 .build_proc_macro_deps
                 .clone()
                 .map(new_crate_dep);
+            let build_artifact_deps = new_artifact_deps(&annotation.deps.build_artifact_deps);
 
             Some(BuildScriptAttributes {
                 deps: build_deps,
                 link_deps: build_link_deps,
+                artifact_deps: build_artifact_deps,
                 proc_macro_deps: build_proc_macro_deps,
                 links: package.links.clone(),
                 ..Default::default()
@@ -821,11 +996,15 @@ impl CrateContext {
     }
 
     /// Collect all Bazel targets that should be generated for a particular Package
+    #[allow(clippy::too_many_arguments)]
     fn collect_targets(
         node: &Node,
         packages: &BTreeMap<PackageId, Package>,
         gen_binaries: &GenBinaries,
         include_build_scripts: bool,
+        gen_cdylib: bool,
+        gen_staticlib: bool,
+        gen_tests: bool,
         sources_are_present: bool,
     ) -> anyhow::Result<BTreeSet<Rule>> {
         let package = &packages[&node.id];
@@ -836,7 +1015,7 @@ impl CrateContext {
             .parent()
             .expect("Every manifest should have a parent directory");
 
-        package
+        let mut targets = package
             .targets
             .iter()
             .flat_map(|target| {
@@ -883,6 +1062,32 @@ impl CrateContext {
                         })));
                     }
 
+                    // C-ABI libraries are only generated when requested as they're rarely consumed
+                    // from Bazel and compile the crate a second time.
+                    if gen_cdylib && matches!(kind, cargo_metadata::TargetKind::CDyLib) {
+                        return Some(Ok(Rule::SharedLibrary(TargetAttributes {
+                            crate_name,
+                            crate_root,
+                            srcs: Glob::new_rust_srcs(!sources_are_present),
+                        })));
+                    }
+
+                    if gen_staticlib && matches!(kind, cargo_metadata::TargetKind::StaticLib) {
+                        return Some(Ok(Rule::StaticLibrary(TargetAttributes {
+                            crate_name,
+                            crate_root,
+                            srcs: Glob::new_rust_srcs(!sources_are_present),
+                        })));
+                    }
+
+                    if gen_tests && target.test && matches!(kind, cargo_metadata::TargetKind::Test) {
+                        return Some(Ok(Rule::Test(TargetAttributes {
+                            crate_name,
+                            crate_root,
+                            srcs: Glob::new_rust_srcs(!sources_are_present),
+                        })));
+                    }
+
                     // Check if the target kind is binary and is one of the ones included in gen_binaries
                     if matches!(kind, cargo_metadata::TargetKind::Bin)
                         && match gen_binaries {
@@ -900,7 +1105,30 @@ impl CrateContext {
                     None
                 })
             })
-            .collect()
+            .collect::<anyhow::Result<BTreeSet<Rule>>>()?;
+
+        // Unit tests are compiled from the same crate root as the library itself.
+        let library_has_tests = package.targets.iter().any(|target| {
+            target.test
+                && target.kind.iter().any(|kind| {
+                    matches!(
+                        kind,
+                        cargo_metadata::TargetKind::Lib | cargo_metadata::TargetKind::RLib
+                    )
+                })
+        });
+        if gen_tests && library_has_tests {
+            let library_tests: Vec<Rule> = targets
+                .iter()
+                .filter_map(|rule| match rule {
+                    Rule::Library(attrs) => Some(Rule::LibraryTest(attrs.clone())),
+                    _ => None,
+                })
+                .collect();
+            targets.extend(library_tests);
+        }
+
+        Ok(targets)
     }
 }
 
@@ -942,6 +1170,7 @@ mod test {
             &annotations.lockfile.crates,
             &annotations.pairred_extras,
             &annotations.metadata.workspace_metadata.tree_metadata,
+            &BTreeSet::new(),
             include_binaries,
             include_build_scripts,
             are_sources_present,
@@ -991,6 +1220,7 @@ mod test {
             &annotations.lockfile.crates,
             &pairred_extras,
             &annotations.metadata.workspace_metadata.tree_metadata,
+            &BTreeSet::new(),
             include_binaries,
             include_build_scripts,
             are_sources_present,
@@ -1061,6 +1291,7 @@ mod test {
             &annotations.lockfile.crates,
             &annotations.pairred_extras,
             &annotations.metadata.workspace_metadata.tree_metadata,
+            &BTreeSet::new(),
             include_binaries,
             include_build_scripts,
             are_sources_present,
@@ -1109,6 +1340,7 @@ mod test {
             &annotations.lockfile.crates,
             &annotations.pairred_extras,
             &annotations.metadata.workspace_metadata.tree_metadata,
+            &BTreeSet::new(),
             include_binaries,
             include_build_scripts,
             are_sources_present,
@@ -1146,6 +1378,7 @@ mod test {
             &annotations.lockfile.crates,
             &annotations.pairred_extras,
             &annotations.metadata.workspace_metadata.tree_metadata,
+            &BTreeSet::new(),
             include_binaries,
             include_build_scripts,
             are_sources_present,
@@ -1164,6 +1397,166 @@ mod test {
         );
     }
 
+    #[test]
+    fn context_cdylib_crate_type() {
+        let annotations = crate_type_annotations();
+
+        let package_id = PackageId {
+            repr: "registry+https://github.com/rust-lang/crates.io-index#sysinfo@0.22.5".to_owned(),
+        };
+
+        let crate_annotation = &annotations.metadata.crates[&package_id];
+
+        let mut pairred_extras = BTreeMap::new();
+        pairred_extras.insert(
+            CrateId::new("sysinfo".to_owned(), semver::Version::new(0, 22, 5)),
+            PairedExtras {
+                package_id,
+                crate_extra: CrateAnnotations {
+                    gen_cdylib: true,
+                    ..CrateAnnotations::default()
+                },
+            },
+        );
+
+        let include_binaries = false;
+        let include_build_scripts = false;
+        let are_sources_present = false;
+        let context = CrateContext::new(
+            crate_annotation,
+            &annotations.metadata.packages,
+            &annotations.lockfile.crates,
+            &pairred_extras,
+            &annotations.metadata.workspace_metadata.tree_metadata,
+            &BTreeSet::new(),
+            include_binaries,
+            include_build_scripts,
+            are_sources_present,
+        )
+        .unwrap();
+
+        assert_eq!(context.library_target_name, Some("sysinfo".to_owned()));
+        assert_eq!(
+            context.targets,
+            BTreeSet::from([
+                Rule::Library(TargetAttributes {
+                    crate_name: "sysinfo".to_owned(),
+                    crate_root: Some("src/lib.rs".to_owned()),
+                    srcs: Glob::new_rust_srcs(!are_sources_present),
+                }),
+                Rule::SharedLibrary(TargetAttributes {
+                    crate_name: "sysinfo".to_owned(),
+                    crate_root: Some("src/lib.rs".to_owned()),
+                    srcs: Glob::new_rust_srcs(!are_sources_present),
+                }),
+            ]),
+        );
+    }
+
+    #[test]
+    fn context_with_tests() {
+        let annotations = crate_type_annotations();
+
+        let package_id = PackageId {
+            repr: "registry+https://github.com/rust-lang/crates.io-index#autocfg@1.0.1".to_owned(),
+        };
+
+        let crate_annotation = &annotations.metadata.crates[&package_id];
+
+        let mut pairred_extras = BTreeMap::new();
+        pairred_extras.insert(
+            CrateId::new("autocfg".to_owned(), semver::Version::new(1, 0, 1)),
+            PairedExtras {
+                package_id,
+                crate_extra: CrateAnnotations {
+                    gen_tests: true,
+                    ..CrateAnnotations::default()
+                },
+            },
+        );
+
+        let include_binaries = false;
+        let include_build_scripts = false;
+        let are_sources_present = false;
+        let context = CrateContext::new(
+            crate_annotation,
+            &annotations.metadata.packages,
+            &annotations.lockfile.crates,
+            &pairred_extras,
+            &annotations.metadata.workspace_metadata.tree_metadata,
+            &BTreeSet::new(),
+            include_binaries,
+            include_build_scripts,
+            are_sources_present,
+        )
+        .unwrap();
+
+        let library = TargetAttributes {
+            crate_name: "autocfg".to_owned(),
+            crate_root: Some("src/lib.rs".to_owned()),
+            srcs: Glob::new_rust_srcs(!are_sources_present),
+        };
+        assert_eq!(
+            context.targets,
+            BTreeSet::from([
+                Rule::Library(library.clone()),
+                Rule::LibraryTest(library),
+                Rule::Test(TargetAttributes {
+                    crate_name: "rustflags".to_owned(),
+                    crate_root: Some("tests/rustflags.rs".to_owned()),
+                    srcs: Glob::new_rust_srcs(!are_sources_present),
+                }),
+            ]),
+        );
+    }
+
+    #[test]
+    fn context_with_tests_and_unresolved_dev_dependencies() {
+        let annotations = crate_type_annotations();
+
+        let package_id = PackageId {
+            repr: "registry+https://github.com/rust-lang/crates.io-index#either@1.6.1".to_owned(),
+        };
+
+        let crate_annotation = &annotations.metadata.crates[&package_id];
+
+        let mut pairred_extras = BTreeMap::new();
+        pairred_extras.insert(
+            CrateId::new("either".to_owned(), semver::Version::new(1, 6, 1)),
+            PairedExtras {
+                package_id,
+                crate_extra: CrateAnnotations {
+                    gen_tests: true,
+                    ..CrateAnnotations::default()
+                },
+            },
+        );
+
+        let context = CrateContext::new(
+            crate_annotation,
+            &annotations.metadata.packages,
+            &annotations.lockfile.crates,
+            &pairred_extras,
+            &annotations.metadata.workspace_metadata.tree_metadata,
+            &BTreeSet::new(),
+            false,
+            false,
+            false,
+        )
+        .unwrap();
+
+        // The tests can't be built without their dev-dependencies, so only the library is
+        // generated.
+        assert_eq!(
+            context.targets,
+            BTreeSet::from([Rule::Library(TargetAttributes {
+                crate_name: "either".to_owned(),
+                crate_root: Some("src/lib.rs".to_owned()),
+                srcs: Glob::new_rust_srcs(true),
+            })]),
+        );
+    }
+
     fn package_context_test(
         set_package: fn(package: &mut Package),
         check_context: fn(context: CrateContext),
@@ -1189,6 +1582,7 @@ mod test {
             &annotations.lockfile.crates,
             &annotations.pairred_extras,
             &annotations.metadata.workspace_metadata.tree_metadata,
+            &BTreeSet::new(),
             include_binaries,
             include_build_scripts,
             are_sources_present,
@@ -1322,6 +1716,7 @@ mod test {
             &annotations.lockfile.crates,
             &annotations.pairred_extras,
             &annotations.metadata.workspace_metadata.tree_metadata,
+            &BTreeSet::new(),
             include_binaries,
             include_build_scripts,
             are_sources_present,
@@ -1358,6 +1753,7 @@ mod test {
             &annotations.lockfile.crates,
             &annotations.pairred_extras,
             &annotations.metadata.workspace_metadata.tree_metadata,
+            &BTreeSet::new(),
             include_binaries,
             include_build_scripts,
             are_sources_present,
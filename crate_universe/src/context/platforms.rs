@@ -1,18 +1,26 @@
+use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet};
 
 use anyhow::{anyhow, Context, Result};
 use cfg_expr::targets::{get_builtin_target_by_triple, TargetInfo};
 use cfg_expr::{Expression, Predicate};
 
+use crate::config::CfgAtom;
 use crate::context::CrateContext;
+use crate::utils::target_spec::TargetSpec;
 use crate::utils::target_triple::TargetTriple;
 
 /// Walk through all dependencies in a [CrateContext] list for all configuration specific
 /// dependencies to produce a mapping of configurations/Cargo target_triples to compatible
 /// Bazel target_triples.  Also adds mappings for all known target_triples.
+///
+/// Triples which are not built into `rustc` are resolved using their entry in `target_specs`,
+/// and any `platform_cfgs` of a triple are enabled in addition to its built-in configuration.
 pub(crate) fn resolve_cfg_platforms(
     crates: Vec<&CrateContext>,
     supported_platform_triples: &BTreeSet<TargetTriple>,
+    target_specs: &BTreeMap<TargetTriple, TargetSpec>,
+    platform_cfgs: &BTreeMap<TargetTriple, BTreeSet<CfgAtom>>,
 ) -> Result<BTreeMap<String, BTreeSet<TargetTriple>>> {
     // Collect all unique configurations from all dependencies into a single set
     let configurations: BTreeSet<String> = crates
@@ -36,19 +44,40 @@ pub(crate) fn resolve_cfg_platforms(
         })
         .collect();
 
-    // Generate target information for each triple string
+    // Generate target information and the enabled target features for each triple string.
+    // Target features are only known for custom targets, or when given in `platform_cfgs`.
     let target_infos = supported_platform_triples
         .iter()
-        .map(
-            |target_triple| match get_builtin_target_by_triple(&target_triple.to_cargo()) {
-                Some(info) => Ok((target_triple, info)),
-                None => Err(anyhow!(
-                    "Invalid platform triple in supported platforms: {}",
-                    target_triple
-                )),
-            },
-        )
-        .collect::<Result<BTreeMap<&TargetTriple, &'static TargetInfo>>>()?;
+        .map(|target_triple| {
+            let (info, mut features) = match target_specs.get(target_triple) {
+                Some(spec) => {
+                    let (info, features) = spec.target_info(target_triple)?;
+                    (Cow::Owned(info), features)
+                }
+                None => match get_builtin_target_by_triple(&target_triple.to_cargo()) {
+                    Some(info) => (Cow::Borrowed(info), BTreeSet::new()),
+                    None => return Err(anyhow!(
+                        "Invalid platform triple in supported platforms: {}. Triples which are not built into rustc require a custom target specification.",
+                        target_triple
+                    )),
+                },
+            };
+
+            let mut cfgs = BTreeSet::new();
+            for atom in platform_cfgs.get(target_triple).into_iter().flatten() {
+                match atom.target_feature() {
+                    Some(feature) => {
+                        features.insert(feature.to_owned());
+                    }
+                    None => {
+                        cfgs.insert(atom);
+                    }
+                }
+            }
+
+            Ok((target_triple, (info, features, cfgs)))
+        })
+        .collect::<Result<BTreeMap<&TargetTriple, (Cow<'static, TargetInfo>, BTreeSet<String>, BTreeSet<&CfgAtom>)>>>()?;
 
     // `cfg-expr` does not understand configurations that are simply platform triples
     // (`x86_64-unknown-linux-gnu` vs `cfg(target = "x86_64-unknown-linux-gnu")`). So
@@ -76,11 +105,17 @@ pub(crate) fn resolve_cfg_platforms(
 
             let triples = target_infos
                 .iter()
-                .filter(|(_, target_info)| {
+                .filter(|(_, (target_info, target_features, cfgs))| {
                     expression.eval(|p| match p {
-                        Predicate::Target(tp) => tp.matches(**target_info),
+                        Predicate::Target(tp) => tp.matches(target_info.as_ref()),
+                        Predicate::TargetFeature(feature) => target_features.contains(*feature),
+                        Predicate::Flag(flag) => cfgs.contains(&CfgAtom::Name(flag.to_string())),
                         Predicate::KeyValue { key, val } => {
-                            *key == "target" && val == &target_info.triple.as_str()
+                            (*key == "target" && val == &target_info.triple.as_str())
+                                || cfgs.contains(&CfgAtom::KeyValue {
+                                    key: key.to_string(),
+                                    value: val.to_string(),
+                                })
                         }
                         // For now there is no other kind of matching
                         _ => false,
@@ -162,8 +197,13 @@ mod test {
             override_targets: BTreeMap::default(),
         };
 
-        let configurations =
-            resolve_cfg_platforms(vec![&context], &supported_platform_triples()).unwrap();
+        let configurations = resolve_cfg_platforms(
+            vec![&context],
+            &supported_platform_triples(),
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+        )
+        .unwrap();
 
         assert_eq!(
             configurations,
@@ -243,8 +283,13 @@ mod test {
         data.into_iter().for_each(|(configuration, expectation)| {
             let context = mock_resolve_context(configuration.clone());
 
-            let configurations =
-                resolve_cfg_platforms(vec![&context], &supported_platform_triples()).unwrap();
+            let configurations = resolve_cfg_platforms(
+                vec![&context],
+                &supported_platform_triples(),
+                &BTreeMap::new(),
+                &BTreeMap::new(),
+            )
+            .unwrap();
 
             assert_eq!(
                 configurations,
@@ -308,8 +353,13 @@ mod test {
             override_targets: BTreeMap::default(),
         };
 
-        let configurations =
-            resolve_cfg_platforms(vec![&context], &supported_platform_triples()).unwrap();
+        let configurations = resolve_cfg_platforms(
+            vec![&context],
+            &supported_platform_triples(),
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+        )
+        .unwrap();
 
         assert_eq!(
             configurations,
@@ -375,8 +425,13 @@ mod test {
             override_targets: BTreeMap::default(),
         };
 
-        let configurations =
-            resolve_cfg_platforms(vec![&context], &supported_platform_triples()).unwrap();
+        let configurations = resolve_cfg_platforms(
+            vec![&context],
+            &supported_platform_triples(),
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+        )
+        .unwrap();
 
         assert_eq!(
             configurations,
@@ -400,4 +455,121 @@ mod test {
             ])
         );
     }
+
+    #[test]
+    fn resolve_custom_target() {
+        let custom = TargetTriple::from_bazel("thumbv7em-acme-none".to_owned());
+        let linux = TargetTriple::from_bazel("x86_64-unknown-linux-gnu".to_owned());
+        let target_specs = BTreeMap::from([(
+            custom.clone(),
+            serde_json::from_str(
+                r#"{
+                    "arch": "arm",
+                    "features": "+vfp4d16sp",
+                    "llvm-target": "thumbv7em-none-eabihf",
+                    "target-pointer-width": "32"
+                }"#,
+            )
+            .unwrap(),
+        )]);
+
+        let data = BTreeMap::from([
+            (
+                r#"cfg(target_arch = "arm")"#.to_owned(),
+                BTreeSet::from([custom.clone()]),
+            ),
+            (
+                r#"cfg(target_feature = "vfp4d16sp")"#.to_owned(),
+                BTreeSet::from([custom.clone()]),
+            ),
+            (
+                r#"cfg(target_os = "none")"#.to_owned(),
+                BTreeSet::from([custom.clone()]),
+            ),
+            (
+                r#"cfg(target_pointer_width = "64")"#.to_owned(),
+                BTreeSet::from([linux.clone()]),
+            ),
+            (
+                "thumbv7em-acme-none".to_owned(),
+                BTreeSet::from([custom.clone()]),
+            ),
+        ]);
+
+        for (configuration, expectation) in data.into_iter() {
+            let context = mock_resolve_context(configuration.clone());
+
+            let configurations = resolve_cfg_platforms(
+                vec![&context],
+                &BTreeSet::from([custom.clone(), linux.clone()]),
+                &target_specs,
+                &BTreeMap::new(),
+            )
+            .unwrap();
+
+            assert_eq!(
+                configurations[&configuration], expectation,
+                "{configuration}"
+            );
+            assert_eq!(
+                configurations["thumbv7em-acme-none"],
+                BTreeSet::from([custom.clone()])
+            );
+        }
+
+        // Without a specification, the triple is rejected.
+        assert!(resolve_cfg_platforms(
+            Vec::new(),
+            &BTreeSet::from([custom.clone()]),
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn resolve_platform_cfgs() {
+        let linux = TargetTriple::from_bazel("x86_64-unknown-linux-gnu".to_owned());
+        let darwin = TargetTriple::from_bazel("aarch64-apple-darwin".to_owned());
+        let platform_cfgs = BTreeMap::from([(
+            linux.clone(),
+            BTreeSet::from([
+                "tokio_unstable".parse().unwrap(),
+                r#"foo="bar""#.parse().unwrap(),
+                r#"target_feature="avx2""#.parse().unwrap(),
+            ]),
+        )]);
+
+        let data = BTreeMap::from([
+            ("cfg(tokio_unstable)", BTreeSet::from([linux.clone()])),
+            (r#"cfg(foo = "bar")"#, BTreeSet::from([linux.clone()])),
+            (r#"cfg(foo = "baz")"#, BTreeSet::new()),
+            (
+                r#"cfg(target_feature = "avx2")"#,
+                BTreeSet::from([linux.clone()]),
+            ),
+            ("cfg(not(tokio_unstable))", BTreeSet::from([darwin.clone()])),
+            (
+                r#"cfg(any(tokio_unstable, target_os = "macos"))"#,
+                BTreeSet::from([darwin.clone(), linux.clone()]),
+            ),
+        ]);
+
+        for (configuration, expectation) in data.into_iter() {
+            let context = mock_resolve_context(configuration.to_owned());
+
+            let configurations = resolve_cfg_platforms(
+                vec![&context],
+                &BTreeSet::from([darwin.clone(), linux.clone()]),
+                &BTreeMap::new(),
+                &platform_cfgs,
+            )
+            .unwrap();
+
+            assert_eq!(
+                configurations[configuration], expectation,
+                "{configuration}"
+            );
+        }
+    }
 }
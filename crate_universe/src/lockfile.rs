@@ -1,6 +1,7 @@
 //! Utility module for interacting with the cargo-bazel lockfile.
 
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ffi::OsStr;
 use std::fs;
 use std::path::Path;
@@ -25,12 +26,15 @@ pub(crate) fn lock_context(
 ) -> Result<Context> {
     // Ensure there is no existing checksum which could impact the lockfile results
     context.checksum = None;
+    context.digest_inputs = None;
 
-    let checksum = Digest::new(&context, config, splicing_manifest, cargo_bin, rustc_bin)
-        .context("Failed to generate context digest")?;
+    let (checksum, digest_inputs) =
+        Digest::new(&context, config, splicing_manifest, cargo_bin, rustc_bin)
+            .context("Failed to generate context digest")?;
 
     Ok(Context {
         checksum: Some(checksum),
+        digest_inputs: Some(digest_inputs),
         ..context
     })
 }
@@ -57,40 +61,47 @@ pub(crate) fn write_lockfile(lockfile: Context, path: &Path, dry_run: bool) -> R
 pub(crate) struct Digest(String);
 
 impl Digest {
+    /// Computes a digest along with the individual inputs it was computed from.
     pub(crate) fn new(
         context: &Context,
         config: &Config,
         splicing_manifest: &SplicingManifest,
         cargo_bin: &Cargo,
         rustc_bin: &Path,
-    ) -> Result<Self> {
+    ) -> Result<(Self, DigestInputs)> {
         let splicing_metadata = SplicingMetadata::try_from((*splicing_manifest).clone())?;
         let cargo_version = cargo_bin.full_version()?;
         let rustc_version = Self::bin_version(rustc_bin)?;
         let cargo_bazel_version = env!("CARGO_PKG_VERSION");
 
-        // Ensure the checksum of a digest is not present before computing one
-        Ok(match context.checksum {
-            Some(_) => Self::compute(
-                &Context {
-                    checksum: None,
-                    ..context.clone()
-                },
+        // Ensure the checksum of a digest and its inputs are not present before computing one
+        let context = match (&context.checksum, &context.digest_inputs) {
+            (None, None) => Cow::Borrowed(context),
+            _ => Cow::Owned(Context {
+                checksum: None,
+                digest_inputs: None,
+                ..context.clone()
+            }),
+        };
+
+        Ok((
+            Self::compute(
+                &context,
                 config,
                 &splicing_metadata,
                 cargo_bazel_version,
                 &cargo_version,
                 &rustc_version,
             ),
-            None => Self::compute(
-                context,
+            DigestInputs::new(
+                &context,
                 config,
                 &splicing_metadata,
                 cargo_bazel_version,
                 &cargo_version,
                 &rustc_version,
             ),
-        })
+        ))
     }
 
     /// A helper for generating a hash and logging it's contents.
@@ -115,6 +126,7 @@ impl Digest {
         // always None. This then allows us to have this method not return a
         // Result.
         debug_assert!(context.checksum.is_none());
+        debug_assert!(context.digest_inputs.is_none());
 
         let mut hasher = Sha256::new();
 
@@ -221,6 +233,127 @@ impl PartialEq<String> for Digest {
     }
 }
 
+/// The individual inputs of a [Digest]. These are recorded in the lockfile so
+/// that when the digest no longer matches, the inputs which changed can be
+/// reported rather than only the fact that a repin is required.
+///
+/// Inputs are only compared for equality, so to keep lockfiles small they are
+/// recorded as short hashes rather than the full digests of [Digest::compute].
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub(crate) struct DigestInputs {
+    pub(crate) cargo_bazel_version: String,
+    pub(crate) cargo_version: String,
+    pub(crate) rustc_version: String,
+
+    /// A hash of the lockfile context itself.
+    pub(crate) context: String,
+
+    /// Hashes of each field of the workspace config, with `annotations` split up per crate.
+    pub(crate) config: BTreeMap<String, String>,
+
+    /// Hashes of each field of the splicing manifest, with `direct_packages` and
+    /// `manifests` split up per entry.
+    pub(crate) splicing_manifest: BTreeMap<String, String>,
+}
+
+impl DigestInputs {
+    fn new(
+        context: &Context,
+        config: &Config,
+        splicing_metadata: &SplicingMetadata,
+        cargo_bazel_version: &str,
+        cargo_version: &str,
+        rustc_version: &str,
+    ) -> Self {
+        Self {
+            cargo_bazel_version: cargo_bazel_version.to_owned(),
+            cargo_version: cargo_version.to_owned(),
+            rustc_version: rustc_version.to_owned(),
+            context: Self::short_hash(&serde_json::to_value(context).unwrap()),
+            config: Self::component_hashes(serde_json::to_value(config).unwrap(), &["annotations"]),
+            splicing_manifest: Self::component_hashes(
+                serde_json::to_value(splicing_metadata).unwrap(),
+                &["direct_packages", "manifests"],
+            ),
+        }
+    }
+
+    /// Hashes a json value, keeping the first 64 bits of its sha256.
+    fn short_hash(value: &serde_json::Value) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_string(value).unwrap().as_bytes());
+        (&hasher.finalize()[..8]).encode_hex::<String>()
+    }
+
+    /// Hashes each field of a json object, hashing the entries of the fields in
+    /// `split` individually.
+    fn component_hashes(value: serde_json::Value, split: &[&str]) -> BTreeMap<String, String> {
+        let hash = Self::short_hash;
+
+        let mut hashes = BTreeMap::new();
+        if let serde_json::Value::Object(fields) = value {
+            for (field, value) in fields {
+                match value {
+                    serde_json::Value::Object(entries) if split.contains(&field.as_str()) => {
+                        for (key, value) in entries {
+                            hashes.insert(format!("{field}[{key}]"), hash(&value));
+                        }
+                    }
+                    value => {
+                        hashes.insert(field, hash(&value));
+                    }
+                }
+            }
+        }
+        hashes
+    }
+
+    /// Describes the inputs which differ between `self`, as recorded in a
+    /// lockfile, and `current`.
+    pub(crate) fn changes(&self, current: &DigestInputs) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        for (name, recorded, current) in [
+            (
+                "cargo-bazel version",
+                &self.cargo_bazel_version,
+                &current.cargo_bazel_version,
+            ),
+            ("Cargo version", &self.cargo_version, &current.cargo_version),
+            ("Rustc version", &self.rustc_version, &current.rustc_version),
+        ] {
+            if recorded != current {
+                changes.push(format!("{name} changed from `{recorded}` to `{current}`"));
+            }
+        }
+
+        if self.context != current.context {
+            changes.push("The lockfile was modified after it was generated".to_owned());
+        }
+
+        for (name, recorded, current) in [
+            ("workspace config", &self.config, &current.config),
+            (
+                "splicing manifest",
+                &self.splicing_manifest,
+                &current.splicing_manifest,
+            ),
+        ] {
+            let keys: BTreeSet<&String> = recorded.keys().chain(current.keys()).collect();
+            for key in keys {
+                match (recorded.get(key), current.get(key)) {
+                    (Some(_), None) => changes.push(format!("{name} `{key}` was removed")),
+                    (None, Some(_)) => changes.push(format!("{name} `{key}` was added")),
+                    (Some(a), Some(b)) if a != b => changes.push(format!("{name} `{key}` changed")),
+                    _ => {}
+                }
+            }
+        }
+
+        changes
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::config::{CrateAnnotations, CrateNameAndVersionReq};
@@ -229,8 +362,6 @@ mod test {
 
     use super::*;
 
-    use std::collections::BTreeSet;
-
     #[test]
     fn simple_digest() {
         let context = Context::default();
@@ -429,4 +560,68 @@ mod test {
             "Digests should be identical regardless of CRLF vs LF line endings in cargo_config"
         );
     }
+
+    #[test]
+    fn digest_inputs_changes() {
+        let context = Context::default();
+        let splicing_metadata = SplicingMetadata::default();
+        let inputs = |config: &Config, splicing_metadata: &SplicingMetadata, rustc: &str| {
+            DigestInputs::new(
+                &context,
+                config,
+                splicing_metadata,
+                "0.1.0",
+                "cargo 1.57.0 (b2e52d7ca 2021-10-21)",
+                rustc,
+            )
+        };
+
+        let recorded = inputs(
+            &Config {
+                annotations: BTreeMap::from([(
+                    CrateNameAndVersionReq::new(
+                        "rustonomicon".to_owned(),
+                        "1.0.0".parse().unwrap(),
+                    ),
+                    CrateAnnotations::default(),
+                )]),
+                ..Config::default()
+            },
+            &splicing_metadata,
+            "rustc 1.57.0 (f1edd0429 2021-11-29)",
+        );
+        assert!(recorded
+            .config
+            .contains_key("annotations[rustonomicon 1.0.0]"));
+        assert!(recorded.config.values().all(|hash| hash.len() == 16));
+        assert_eq!(recorded.context.len(), 16);
+        assert!(recorded.changes(&recorded).is_empty());
+
+        let current = inputs(
+            &Config {
+                generate_binaries: true,
+                ..Config::default()
+            },
+            &SplicingMetadata {
+                direct_packages: BTreeMap::from([(
+                    "rustonomicon".to_owned(),
+                    cargo_toml::DependencyDetail {
+                        version: Some("1.0.0".to_owned()),
+                        ..cargo_toml::DependencyDetail::default()
+                    },
+                )]),
+                ..SplicingMetadata::default()
+            },
+            "rustc 1.58.0 (02072b482 2022-01-11)",
+        );
+        assert_eq!(
+            recorded.changes(&current),
+            vec![
+                "Rustc version changed from `rustc 1.57.0 (f1edd0429 2021-11-29)` to `rustc 1.58.0 (02072b482 2022-01-11)`",
+                "workspace config `annotations[rustonomicon 1.0.0]` was removed",
+                "workspace config `generate_binaries` changed",
+                "splicing manifest `direct_packages[rustonomicon]` was added",
+            ]
+        );
+    }
 }
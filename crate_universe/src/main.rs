@@ -38,5 +38,33 @@ fn main() -> cli::Result<()> {
             cli::init_logging("Render", level);
             cli::render(opt)
         }
+        cli::Options::Diff(opt) => {
+            cli::init_logging("Diff", level);
+            cli::diff(opt)
+        }
+        cli::Options::Mirror(opt) => {
+            cli::init_logging("Mirror", level);
+            cli::mirror(opt)
+        }
+        cli::Options::Report(opt) => {
+            cli::init_logging("Report", level);
+            cli::report(opt)
+        }
+        cli::Options::Sbom(opt) => {
+            cli::init_logging("Sbom", level);
+            cli::sbom(opt)
+        }
+        cli::Options::Duplicates(opt) => {
+            cli::init_logging("Duplicates", level);
+            cli::duplicates(opt)
+        }
+        cli::Options::Add(opt) => {
+            cli::init_logging("Add", level);
+            cli::add(opt)
+        }
+        cli::Options::Remove(opt) => {
+            cli::init_logging("Remove", level);
+            cli::remove(opt)
+        }
     }
 }
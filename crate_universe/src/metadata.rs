@@ -284,9 +284,28 @@ pub(crate) fn load_metadata(
     let content = fs::read_to_string(metadata_path)
         .with_context(|| format!("Failed to load Cargo Metadata: {}", metadata_path.display()))?;
 
-    let metadata =
+    let mut metadata: serde_json::Value =
         serde_json::from_str(&content).context("Unable to deserialize Cargo metadata")?;
 
+    // Artifact dependencies (`-Zbindeps`) are not supported by `cargo_metadata` so they're
+    // stored with the rest of the cargo-bazel workspace metadata.
+    let artifact_deps =
+        split_artifact_deps(&mut metadata).context("Unable to parse artifact dependencies")?;
+    if !artifact_deps.is_empty() {
+        let workspace_metadata = metadata
+            .pointer_mut("/metadata/cargo-bazel")
+            .filter(|workspace_metadata| workspace_metadata.is_object())
+            .with_context(|| {
+                format!(
+                    "Cargo metadata has artifact dependencies but no cargo-bazel workspace metadata to record them in: {}",
+                    metadata_path.display()
+                )
+            })?;
+        workspace_metadata["artifact_deps"] = serde_json::to_value(artifact_deps)?;
+    }
+    let metadata: cargo_metadata::Metadata =
+        serde_json::from_value(metadata).context("Unable to deserialize Cargo metadata")?;
+
     let lockfile = cargo_lock::Lockfile::load(lockfile_path)
         .with_context(|| format!("Failed to load lockfile: {}", lockfile_path.display()))?;
 
@@ -297,6 +316,46 @@ pub(crate) fn load_metadata(
 mod test {
     use super::*;
 
+    #[test]
+    fn load_metadata_with_artifact_deps_requires_workspace_metadata() {
+        let (_temp_dir, temp_path) = crate::test::test_tempdir("load_metadata");
+        let metadata_path = temp_path.join("metadata.json");
+        fs::write(
+            &metadata_path,
+            serde_json::json!({
+                "resolve": {
+                    "nodes": [{
+                        "id": "path+file:///tmp/app#0.1.0",
+                        "deps": [{
+                            "name": "",
+                            "pkg": "registry+https://github.com/rust-lang/crates.io-index#tool@1.0.0",
+                            "dep_kinds": [{
+                                "kind": "build",
+                                "target": null,
+                                "extern_name": "tool",
+                                "artifact": "bin",
+                                "compile_target": null,
+                                "bin_name": "tool",
+                            }],
+                        }],
+                    }],
+                },
+                "metadata": null,
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let err = load_metadata(&metadata_path, &temp_path.join("Cargo.lock")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Cargo metadata has artifact dependencies but no cargo-bazel workspace metadata to record them in: {}",
+                metadata_path.display()
+            )
+        );
+    }
+
     #[test]
     fn deserialize_cargo_update_request_for_eager() {
         for value in ["all", "full", "eager"] {
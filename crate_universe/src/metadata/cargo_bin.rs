@@ -84,6 +84,27 @@ impl Cargo {
         Ok(full_version.clone().unwrap())
     }
 
+    /// Returns the output of running `rustc -vV` with the `rustc` used by this cargo.
+    pub(crate) fn rustc_verbose_version(&self) -> Result<String> {
+        let output = Command::new(&self.rustc_path)
+            .arg("-vV")
+            .output()
+            .with_context(|| {
+                format!(
+                    "Failed to run {} to get its version",
+                    self.rustc_path.display()
+                )
+            })?;
+
+        if !output.status.success() {
+            eprintln!("{}", String::from_utf8_lossy(&output.stdout));
+            eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+            bail!("Failed to query rustc version")
+        }
+
+        Ok(String::from_utf8(output.stdout)?.trim().to_owned())
+    }
+
     pub(crate) fn is_nightly(&self) -> Result<bool> {
         let full_version = self.full_version()?;
         let version_str = full_version.split(' ').nth(1);
@@ -1,22 +1,26 @@
 //! Tools for producing Crate metadata using `cargo tree`.
 
 use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::OsString;
 use std::io::BufRead;
 use std::path::{Path, PathBuf};
 use std::process::Output;
 use std::thread;
+use std::time::{Duration, SystemTime};
 
 use anyhow::{anyhow, bail, Context, Result};
 use camino::Utf8Path;
 use semver::Version;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, trace};
+use sha2::{Digest as Sha2Digest, Sha256};
+use tracing::{debug, trace, warn};
 use url::Url;
 
-use crate::config::CrateId;
+use crate::config::{CfgAtom, CrateId};
 use crate::metadata::cargo_bin::Cargo;
 use crate::select::{Select, SelectableScalar};
 use crate::utils::symlink::symlink;
+use crate::utils::target_spec::TargetSpec;
 use crate::utils::target_triple::TargetTriple;
 
 /// A list platform triples that support host tools
@@ -54,6 +58,9 @@ const RUSTC_TRIPLES_WITH_HOST_TOOLS: [&str; 26] = [
     "x86_64-unknown-netbsd",
 ];
 
+/// Cached `cargo tree` outputs which haven't been used for this long are removed.
+const CACHE_ENTRY_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
 /// Feature resolver info about a given crate.
 #[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub(crate) struct CargoTreeEntry {
@@ -97,20 +104,172 @@ pub(crate) type TreeResolverMetadata = BTreeMap<CrateId, Select<CargoTreeEntry>>
 pub(crate) struct TreeResolver {
     /// The path to a `cargo` binary
     cargo_bin: Cargo,
+
+    /// A directory in which the output of `cargo tree` is cached between runs.
+    cache_dir: Option<PathBuf>,
 }
 
 impl TreeResolver {
     pub(crate) fn new(cargo_bin: Cargo) -> Self {
-        Self { cargo_bin }
+        Self {
+            cargo_bin,
+            cache_dir: None,
+        }
+    }
+
+    /// Cache the output of each `cargo tree` invocation in `cache_dir`. Entries are keyed by
+    /// the resolved workspace, the toolchain, the Cargo configuration and the platform they
+    /// were generated for, so they're reused across repins which don't change any of them.
+    /// The key covers the whole workspace: any change to `Cargo.lock` or a manifest
+    /// re-resolves every platform rather than only the affected workspace members.
+    /// Entries which haven't been used for [CACHE_ENTRY_MAX_AGE] are removed.
+    pub(crate) fn with_cache_dir(mut self, cache_dir: Option<PathBuf>) -> Self {
+        self.cache_dir = cache_dir;
+        self
+    }
+
+    /// Compute a digest of all inputs to `cargo tree` which are shared between platforms.
+    ///
+    /// The metadata of spliced workspaces references the (often temporary) splicing directory
+    /// so the workspace root is stripped to allow cache hits across runs.
+    fn workspace_digest(
+        &self,
+        cargo_metadata: &cargo_metadata::Metadata,
+        lockfile_path: &Path,
+    ) -> Result<String> {
+        Self::workspace_digest_impl(
+            &[
+                self.cargo_bin.full_version()?,
+                self.cargo_bin.rustc_verbose_version()?,
+            ],
+            cargo_metadata,
+            lockfile_path,
+            std::env::vars_os(),
+        )
+    }
+
+    fn workspace_digest_impl(
+        toolchain_versions: &[String],
+        cargo_metadata: &cargo_metadata::Metadata,
+        lockfile_path: &Path,
+        env: impl IntoIterator<Item = (OsString, OsString)>,
+    ) -> Result<String> {
+        let lockfile = std::fs::read_to_string(lockfile_path)
+            .with_context(|| format!("Failed to read {}", lockfile_path.display()))?;
+        let metadata = serde_json::to_string(cargo_metadata)?
+            .replace(cargo_metadata.workspace_root.as_str(), "${workspace_root}");
+        let env: BTreeMap<String, OsString> = env
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value)))
+            .collect();
+
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION"));
+        hasher.update(b"\0");
+        for version in toolchain_versions {
+            hasher.update(version);
+            hasher.update(b"\0");
+        }
+        hasher.update(lockfile);
+        hasher.update(b"\0");
+        hasher.update(metadata);
+        hasher.update(b"\0");
+        for config in cargo_config_files(cargo_metadata.workspace_root.as_std_path(), &env) {
+            hasher
+                .update(std::fs::read(&config).with_context(|| {
+                    format!("Failed to read Cargo config {}", config.display())
+                })?);
+            hasher.update(b"\0");
+        }
+        for (name, value) in env.iter().filter(|(name, _)| affects_resolution(name)) {
+            hasher.update(name);
+            hasher.update(b"=");
+            hasher.update(value.as_encoded_bytes());
+            hasher.update(b"\0");
+        }
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    /// The path of the cached `cargo tree` output for a single host and target platform.
+    fn cache_path(
+        cache_dir: &Path,
+        workspace_digest: &str,
+        cargo_host: &str,
+        (cargo_target, rustflags): &(String, Option<String>),
+    ) -> Result<PathBuf> {
+        let mut hasher = Sha256::new();
+        hasher.update(workspace_digest);
+        hasher.update(b"\0");
+        hasher.update(cargo_host);
+        hasher.update(b"\0");
+        // Custom target specifications are written to temporary directories so their
+        // content is used instead of their path.
+        if cargo_target.ends_with(".json") {
+            hasher.update(
+                std::fs::read(cargo_target).with_context(|| {
+                    format!("Failed to read target specification {cargo_target}")
+                })?,
+            );
+        } else {
+            hasher.update(cargo_target);
+        }
+        hasher.update(b"\0");
+        hasher.update(rustflags.as_deref().unwrap_or_default());
+        Ok(cache_dir.join(format!("{}.tree", hex::encode(hasher.finalize()))))
+    }
+
+    /// Write `stdout` to `path` such that concurrent readers never observe a partial file.
+    fn write_cache_entry(path: &Path, stdout: &[u8]) -> Result<()> {
+        let cache_dir = path
+            .parent()
+            .expect("Cache entries are always in a directory");
+        std::fs::create_dir_all(cache_dir)
+            .with_context(|| format!("Failed to create cache directory {}", cache_dir.display()))?;
+        let mut file = tempfile::NamedTempFile::new_in(cache_dir)?;
+        std::io::Write::write_all(&mut file, stdout)?;
+        file.persist(path)
+            .with_context(|| format!("Failed to write cache entry {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Remove cache entries (and any files left behind by interrupted writes) which haven't
+    /// been used since `now - CACHE_ENTRY_MAX_AGE`. Failures are logged rather than returned
+    /// as other processes may be using the same cache.
+    fn prune_cache(cache_dir: &Path, now: SystemTime) {
+        let Ok(entries) = std::fs::read_dir(cache_dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_cache_file = path.extension().is_some_and(|ext| ext == "tree")
+                || entry.file_name().to_string_lossy().starts_with(".tmp");
+            if !is_cache_file {
+                continue;
+            }
+            let last_used = entry.metadata().and_then(|metadata| metadata.modified());
+            let is_stale = last_used
+                .map(|last_used| last_used + CACHE_ENTRY_MAX_AGE < now)
+                .unwrap_or(false);
+            if is_stale {
+                debug!("Removing stale `cargo tree` cache entry {}", path.display());
+                if let Err(err) = std::fs::remove_file(&path) {
+                    debug!("Failed to remove {}: {}", path.display(), err);
+                }
+            }
+        }
     }
 
     /// Execute `cargo tree` for each target triple and return the stdout
     /// streams containing structured output.
+    #[allow(clippy::too_many_arguments)]
     fn execute_cargo_tree(
         &self,
         manifest_path: &Path,
+        workspace_digest: &str,
         host_triples: &BTreeSet<TargetTriple>,
         target_triples: &BTreeSet<TargetTriple>,
+        target_spec_paths: &BTreeMap<TargetTriple, PathBuf>,
+        platform_cfgs: &BTreeMap<TargetTriple, BTreeSet<CfgAtom>>,
         rustc_wrapper: &Path,
     ) -> Result<BTreeMap<TargetTriple, BTreeMap<TargetTriple, Vec<u8>>>> {
         // A collection of all stdout logs from each process
@@ -124,10 +283,21 @@ impl TreeResolver {
                 .or_default()
                 .insert(triple);
         }
-        let mut cargo_target_triples = BTreeMap::<String, BTreeSet<&TargetTriple>>::new();
+        // Triples with extra cfgs are only de-duplicated with triples sharing the same cfgs.
+        let mut cargo_target_triples =
+            BTreeMap::<(String, Option<String>), BTreeSet<&TargetTriple>>::new();
         for triple in target_triples {
+            // Custom targets are passed to cargo as the path to their specification.
+            let cargo_target = match target_spec_paths.get(triple) {
+                Some(path) => path.display().to_string(),
+                None => triple.to_cargo(),
+            };
+            let rustflags = platform_cfgs
+                .get(triple)
+                .filter(|cfgs| !cfgs.is_empty())
+                .map(|cfgs| target_rustflags_config(triple, cfgs));
             cargo_target_triples
-                .entry(triple.to_cargo())
+                .entry((cargo_target, rustflags))
                 .or_default()
                 .insert(triple);
         }
@@ -138,8 +308,8 @@ impl TreeResolver {
             .map(|n| n.get())
             .unwrap_or(4);
 
-        // Prepare all unique jobs: (cargo_host, cargo_target)
-        let mut jobs = Vec::<(String, String)>::new();
+        // Prepare all unique jobs: (cargo_host, (cargo_target, rustflags))
+        let mut jobs = Vec::<(String, (String, Option<String>))>::new();
         for cargo_host in cargo_host_triples.keys() {
             for cargo_target in cargo_target_triples.keys() {
                 jobs.push((cargo_host.clone(), cargo_target.clone()));
@@ -147,20 +317,64 @@ impl TreeResolver {
         }
 
         // Spawn workers up to the cap; join one whenever the cap is reached.
-        let mut in_flight =
-            Vec::<thread::JoinHandle<anyhow::Result<(String, String, Output)>>>::new();
-        let mut results = Vec::<(String, String, Output)>::new();
+        type JobOutput = (String, (String, Option<String>), Output);
+        let mut in_flight = Vec::<thread::JoinHandle<anyhow::Result<JobOutput>>>::new();
+        let mut results = Vec::<(String, (String, Option<String>), Vec<u8>)>::new();
+
+        // Check the exit status of a finished worker and cache its output.
+        let finish = |handle: thread::JoinHandle<anyhow::Result<JobOutput>>| {
+            let (cargo_host, target_key, output) =
+                handle.join().expect("worker thread panicked")?;
+            if !output.status.success() {
+                tracing::error!("{}", String::from_utf8_lossy(&output.stdout));
+                tracing::error!("{}", String::from_utf8_lossy(&output.stderr));
+                bail!(format!("Failed to run cargo tree: {}", output.status));
+            }
+
+            tracing::trace!(
+                "`cargo tree --target={}` (host `{}`) completed.",
+                target_key.0,
+                cargo_host
+            );
+
+            if let Some(cache_dir) = &self.cache_dir {
+                let cache_path =
+                    Self::cache_path(cache_dir, workspace_digest, &cargo_host, &target_key)?;
+                // Caching is best-effort as other processes may be using the same cache.
+                if let Err(err) = Self::write_cache_entry(&cache_path, &output.stdout) {
+                    warn!("Failed to cache `cargo tree` output: {:?}", err);
+                }
+            }
+
+            Ok((cargo_host, target_key, output.stdout))
+        };
+
+        for (cargo_host, target_key) in jobs {
+            if let Some(cache_dir) = &self.cache_dir {
+                let cache_path =
+                    Self::cache_path(cache_dir, workspace_digest, &cargo_host, &target_key)?;
+                if let Ok(stdout) = std::fs::read(&cache_path) {
+                    // Mark the entry as used so it isn't pruned.
+                    let _ = std::fs::File::options()
+                        .write(true)
+                        .open(&cache_path)
+                        .and_then(|file| file.set_modified(SystemTime::now()));
+                    debug!(
+                        "Using cached `cargo tree` output for host `{}`: {}",
+                        cargo_host, target_key.0,
+                    );
+                    results.push((cargo_host, target_key, stdout));
+                    continue;
+                }
+            }
 
-        for (cargo_host, cargo_target) in jobs {
             // If we've hit the limit, free a slot by joining one worker.
             if in_flight.len() >= max_parallel {
-                let res = in_flight
-                    .remove(0)
-                    .join()
-                    .expect("worker thread panicked")?;
-                results.push(res);
+                results.push(finish(in_flight.remove(0))?);
             }
 
+            let (cargo_target, rustflags) = target_key;
+
             debug!(
                 "Spawning `cargo tree` process for host `{}`: {}",
                 cargo_host, cargo_target,
@@ -169,16 +383,30 @@ impl TreeResolver {
             let manifest_path = manifest_path.to_owned();
             let rustc_wrapper = rustc_wrapper.to_owned();
             let cargo_bin = self.cargo_bin.clone();
+            let is_custom_target = cargo_target.ends_with(".json");
 
             in_flight.push(thread::spawn(
-                move || -> anyhow::Result<(String, String, Output)> {
+                move || -> anyhow::Result<JobOutput> {
                     // We use `cargo tree` here because `cargo metadata` doesn't report
                     // back target-specific features (enabled with `resolver = "2"`).
                     // This is unfortunately a bit of a hack. See:
                     // - https://github.com/rust-lang/cargo/issues/9863
                     // - https://github.com/bazelbuild/rules_rust/issues/1662
-                    let child = cargo_bin
-                        .command()?
+                    let mut command = cargo_bin.command()?;
+                    if is_custom_target {
+                        // Custom target specifications are unstable in both Cargo and `rustc`.
+                        // The rustc wrapper is told to enable unstable options when querying
+                        // target info.
+                        command
+                            .env("RUSTC_BOOTSTRAP", "1")
+                            .env("CUSTOM_TARGET_SPEC", "1")
+                            .arg("--config")
+                            .arg("unstable.json-target-spec=true");
+                    }
+                    if let Some(rustflags) = &rustflags {
+                        command.arg("--config").arg(rustflags);
+                    }
+                    let child = command
                         // These next two environment variables are used to hack cargo into using a custom
                         // host triple instead of the host triple detected by rustc.
                         .env("RUSTC_WRAPPER", &rustc_wrapper)
@@ -222,37 +450,23 @@ impl TreeResolver {
                         )
                     })?;
 
-                    Ok((cargo_host, cargo_target, output))
+                    Ok((cargo_host, (cargo_target, rustflags), output))
                 },
             ));
         }
 
         for handle in in_flight {
-            let res = handle.join().expect("worker thread panicked")?;
-            results.push(res);
+            results.push(finish(handle)?);
         }
 
-        // Process results and replicate outputs for de-duplicated platforms.
-        for (cargo_host, cargo_target, output) in results {
-            if !output.status.success() {
-                tracing::error!("{}", String::from_utf8_lossy(&output.stdout));
-                tracing::error!("{}", String::from_utf8_lossy(&output.stderr));
-                bail!(format!("Failed to run cargo tree: {}", output.status));
-            }
-
-            tracing::trace!(
-                "`cargo tree --target={}` (host `{}`) completed.",
-                cargo_target,
-                cargo_host
-            );
-
-            // Replicate outputs for any de-duplicated platforms
+        // Replicate outputs for any de-duplicated platforms.
+        for (cargo_host, target_key, stdout) in results {
             for host_plat in cargo_host_triples[&cargo_host].iter() {
-                for target_plat in cargo_target_triples[&cargo_target].iter() {
+                for target_plat in cargo_target_triples[&target_key].iter() {
                     stdouts
                         .entry((*host_plat).clone())
                         .or_default()
-                        .insert((*target_plat).clone(), output.stdout.clone());
+                        .insert((*target_plat).clone(), stdout.clone());
                 }
             }
         }
@@ -260,6 +474,29 @@ impl TreeResolver {
         Ok(stdouts)
     }
 
+    /// Write the specifications of any custom targets in `target_triples` to `output_dir`.
+    /// `rustc` names custom targets after the specification's file stem, so each is written
+    /// as `{triple}.json`.
+    fn write_target_specs(
+        output_dir: &Path,
+        target_triples: &BTreeSet<TargetTriple>,
+        target_specs: &BTreeMap<TargetTriple, TargetSpec>,
+    ) -> Result<BTreeMap<TargetTriple, PathBuf>> {
+        let mut paths = BTreeMap::new();
+        for (triple, spec) in target_specs {
+            if !target_triples.contains(triple) {
+                continue;
+            }
+            std::fs::create_dir_all(output_dir)
+                .context("Failed to create target specification directory")?;
+            let path = output_dir.join(format!("{}.json", triple.to_cargo()));
+            std::fs::write(&path, serde_json::to_string_pretty(spec)?)
+                .with_context(|| format!("Failed to write target specification for {triple}"))?;
+            paths.insert(triple.clone(), path);
+        }
+        Ok(paths)
+    }
+
     // The use of this wrapper should __never__ escape this class.
     #[cfg(target_family = "windows")]
     fn create_rustc_wrapper_impl(output_dir: &Path) -> Result<PathBuf> {
@@ -353,6 +590,8 @@ impl TreeResolver {
         &self,
         pristine_manifest_path: &Utf8Path,
         target_triples: &BTreeSet<TargetTriple>,
+        target_specs: &BTreeMap<TargetTriple, TargetSpec>,
+        platform_cfgs: &BTreeMap<TargetTriple, BTreeSet<CfgAtom>>,
     ) -> Result<TreeResolverMetadata> {
         debug!(
             "Generating features for manifest {}",
@@ -361,15 +600,44 @@ impl TreeResolver {
 
         let tempdir = tempfile::tempdir().context("Failed to make tempdir")?;
 
+        let cargo_metadata = self
+            .cargo_bin
+            .metadata_command_with_options(
+                pristine_manifest_path.as_std_path(),
+                vec!["--locked".to_owned()],
+            )?
+            .manifest_path(pristine_manifest_path.as_std_path())
+            .exec()
+            .context("Failed to run cargo metadata to list transitive proc macros")?;
+
+        let workspace_digest = match &self.cache_dir {
+            Some(_) => self.workspace_digest(
+                &cargo_metadata,
+                pristine_manifest_path
+                    .parent()
+                    .unwrap()
+                    .join("Cargo.lock")
+                    .as_std_path(),
+            )?,
+            None => String::new(),
+        };
+
         let manifest_path_with_transitive_proc_macros = self
             .copy_project_with_explicit_deps_on_all_transitive_proc_macros(
                 pristine_manifest_path,
+                &cargo_metadata,
                 &tempdir.path().join("explicit_proc_macro_deps"),
             )
             .context("Failed to copy project with proc macro deps made direct")?;
 
         let rustc_wrapper = Self::create_rustc_wrapper(tempdir.path())?;
 
+        let target_spec_paths = Self::write_target_specs(
+            &tempdir.path().join("target_specs"),
+            target_triples,
+            target_specs,
+        )?;
+
         let host_triples: BTreeSet<TargetTriple> = target_triples
             .iter()
             // Only query triples for platforms that have host tools.
@@ -384,11 +652,18 @@ impl TreeResolver {
         let deps_tree_streams: BTreeMap<TargetTriple, BTreeMap<TargetTriple, Vec<u8>>> = self
             .execute_cargo_tree(
                 &manifest_path_with_transitive_proc_macros,
+                &workspace_digest,
                 &host_triples,
                 target_triples,
+                &target_spec_paths,
+                platform_cfgs,
                 &rustc_wrapper,
             )?;
 
+        if let Some(cache_dir) = &self.cache_dir {
+            Self::prune_cache(cache_dir, SystemTime::now());
+        }
+
         let mut metadata: BTreeMap<CrateId, BTreeMap<TargetTriple, CargoTreeEntry>> =
             BTreeMap::new();
 
@@ -495,6 +770,7 @@ impl TreeResolver {
     fn copy_project_with_explicit_deps_on_all_transitive_proc_macros(
         &self,
         pristine_manifest_path: &Utf8Path,
+        cargo_metadata: &cargo_metadata::Metadata,
         output_dir: &Path,
     ) -> Result<PathBuf> {
         if !output_dir.exists() {
@@ -526,15 +802,6 @@ impl TreeResolver {
             )
         })?;
 
-        let cargo_metadata = self
-            .cargo_bin
-            .metadata_command_with_options(
-                pristine_manifest_path.as_std_path(),
-                vec!["--locked".to_owned()],
-            )?
-            .manifest_path(pristine_manifest_path.as_std_path())
-            .exec()
-            .context("Failed to run cargo metadata to list transitive proc macros")?;
         let proc_macros = cargo_metadata
             .packages
             .iter()
@@ -805,6 +1072,58 @@ impl From<TreeDepCompileKind> for CrateId {
     }
 }
 
+/// Whether an environment variable can change how Cargo resolves a workspace. Most Cargo
+/// configuration can be set through `CARGO_*` variables. Credentials and cargo-bazel's own
+/// settings are excluded.
+fn affects_resolution(name: &str) -> bool {
+    if name.starts_with("CARGO_BAZEL_") || name.ends_with("_TOKEN") {
+        return false;
+    }
+    name.starts_with("CARGO_") || matches!(name, "RUSTFLAGS" | "RUSTC_BOOTSTRAP")
+}
+
+/// The Cargo config files that apply to a workspace, in the order Cargo reads them.
+/// See <https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure>
+fn cargo_config_files(workspace_root: &Path, env: &BTreeMap<String, OsString>) -> Vec<PathBuf> {
+    let cargo_home = env.get("CARGO_HOME").map(PathBuf::from).or_else(|| {
+        env.get("HOME")
+            .map(|home| PathBuf::from(home).join(".cargo"))
+    });
+    let mut config_dirs: Vec<PathBuf> = workspace_root
+        .ancestors()
+        .map(|dir| dir.join(".cargo"))
+        .collect();
+    if let Some(cargo_home) = cargo_home {
+        if !config_dirs.contains(&cargo_home) {
+            config_dirs.push(cargo_home);
+        }
+    }
+    config_dirs
+        .iter()
+        .flat_map(|dir| [dir.join("config"), dir.join("config.toml")])
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Generates a `--config` argument which enables `cfgs` in the rustflags of `triple`, so
+/// that `cfg(...)` dependencies in the `cargo tree` output are resolved with them.
+///
+/// Note that these rustflags take precedence over any `build.rustflags` in the Cargo config.
+/// Cargo looks up config for custom targets by the file stem of their specification, which
+/// matches the name of the triple.
+fn target_rustflags_config(triple: &TargetTriple, cfgs: &BTreeSet<CfgAtom>) -> String {
+    let rustflags: toml::value::Array = cfgs
+        .iter()
+        .flat_map(CfgAtom::rustc_flags)
+        .map(toml::Value::String)
+        .collect();
+    format!(
+        "target.{}.rustflags={}",
+        toml::Value::String(triple.to_cargo()),
+        toml::Value::Array(rustflags)
+    )
+}
+
 /// Parses the output of `cargo tree --format=|{p}|{f}|`. Other flags may be
 /// passed to `cargo tree` as well, but this format is critical.
 fn parse_cargo_tree_output<I, S, E>(
@@ -1265,6 +1584,71 @@ mod test {
         }
     }
 
+    #[test]
+    fn cargo_tree_rustc_wrapper_custom_target() {
+        let (_, tempdir) = crate::test::test_tempdir("cargo_tree_rustc_wrapper_custom_target");
+
+        let script_text = if cfg!(windows) {
+            dedent(
+                r#"
+                @ECHO OFF
+                echo %*
+                "#,
+            )
+        } else {
+            dedent(
+                r#"
+                #!/bin/sh
+                set -eu
+                echo "$@"
+                "#,
+            )
+        };
+
+        let (rustc, rustc_wrapper) = create_mock_rustc(&tempdir, &script_text);
+
+        let output = new_mock_rustc_command(&rustc_wrapper, &rustc, &tempdir)
+            .env("CUSTOM_TARGET_SPEC", "1")
+            .args([
+                "-",
+                "--crate-name",
+                "___",
+                "--print=cfg",
+                "--target",
+                "thumbv7em-acme-none.json",
+            ])
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8(output.stdout).unwrap().replace('"', "");
+        assert!(
+            stdout
+                .trim()
+                .ends_with("--target thumbv7em-acme-none.json -Zunstable-options"),
+            "The rustc wrapper did not enable unstable options\ngot: `{}`",
+            stdout
+        )
+    }
+
+    #[test]
+    fn target_rustflags_config_from_cfgs() {
+        let cfgs = BTreeSet::from([
+            "tokio_unstable".parse().unwrap(),
+            r#"foo="bar""#.parse().unwrap(),
+            r#"target_feature="avx2""#.parse().unwrap(),
+        ]);
+
+        assert_eq!(
+            target_rustflags_config(
+                &TargetTriple::from_bazel("thumbv8m.main-none-eabi".to_owned()),
+                &cfgs
+            ),
+            r#"target."thumbv8m.main-none-eabi".rustflags=["--cfg", "tokio_unstable", "--cfg", 'foo="bar"', "-C", "target-feature=+avx2"]"#
+        );
+    }
+
     #[test]
     fn serde_cargo_tree_entry() {
         {
@@ -1764,4 +2148,188 @@ mod test {
             "Failed checking host dependencies."
         );
     }
+
+    #[test]
+    fn execute_cargo_tree_uses_cache() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let triple = TargetTriple::from_bazel("x86_64-unknown-linux-gnu".to_owned());
+        let target_key = (triple.to_cargo(), None);
+
+        let cache_path =
+            TreeResolver::cache_path(cache_dir.path(), "digest", &triple.to_cargo(), &target_key)
+                .unwrap();
+        TreeResolver::write_cache_entry(&cache_path, b";cached v1.0.0;;").unwrap();
+
+        // A cache hit never invokes `cargo`.
+        let resolver = TreeResolver::new(Cargo::new(
+            PathBuf::from("/doesnotexist/cargo"),
+            PathBuf::from("/doesnotexist/rustc"),
+        ))
+        .with_cache_dir(Some(cache_dir.path().to_owned()));
+
+        let stdouts = resolver
+            .execute_cargo_tree(
+                Path::new("/doesnotexist/Cargo.toml"),
+                "digest",
+                &BTreeSet::from([triple.clone()]),
+                &BTreeSet::from([triple.clone()]),
+                &BTreeMap::new(),
+                &BTreeMap::new(),
+                Path::new("/doesnotexist/rustc_wrapper"),
+            )
+            .unwrap();
+
+        assert_eq!(
+            stdouts,
+            BTreeMap::from([(
+                triple.clone(),
+                BTreeMap::from([(triple, b";cached v1.0.0;;".to_vec())])
+            )])
+        );
+
+        // Entries for other workspaces are distinct.
+        assert_ne!(
+            cache_path,
+            TreeResolver::cache_path(
+                cache_dir.path(),
+                "other",
+                "x86_64-unknown-linux-gnu",
+                &target_key
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn workspace_digest_inputs() {
+        let (_tempdir, root) = crate::test::test_tempdir("workspace_digest_inputs");
+        let mut metadata = crate::test::metadata::no_deps();
+        metadata.workspace_root = camino::Utf8PathBuf::from_path_buf(root.clone()).unwrap();
+        let lockfile_path = root.join("Cargo.lock");
+        std::fs::write(&lockfile_path, "version = 4\n").unwrap();
+
+        let cargo_home = root.join("cargo_home").display().to_string();
+        let digest = |rustc_version: &str, env: &[(&str, &str)]| {
+            TreeResolver::workspace_digest_impl(
+                &["cargo 1.0.0".to_owned(), rustc_version.to_owned()],
+                &metadata,
+                &lockfile_path,
+                [("CARGO_HOME", cargo_home.as_str())]
+                    .iter()
+                    .chain(env)
+                    .map(|(name, value)| (OsString::from(name), OsString::from(value))),
+            )
+            .unwrap()
+        };
+
+        let original = digest("rustc 1.0.0", &[]);
+
+        // Environment variables which don't affect resolution are ignored.
+        assert_eq!(
+            original,
+            digest(
+                "rustc 1.0.0",
+                &[
+                    ("PATH", "/bin"),
+                    ("CARGO_BAZEL_REPIN", "true"),
+                    ("CARGO_REGISTRY_TOKEN", "secret"),
+                ]
+            )
+        );
+        assert_ne!(
+            original,
+            digest("rustc 1.0.0", &[("CARGO_NET_GIT_FETCH_WITH_CLI", "true")])
+        );
+        assert_ne!(original, digest("rustc 1.0.1", &[]));
+
+        std::fs::create_dir_all(root.join(".cargo")).unwrap();
+        std::fs::write(
+            root.join(".cargo/config.toml"),
+            "[build]\nrustflags = [\"--cfg=foo\"]\n",
+        )
+        .unwrap();
+        let with_config = digest("rustc 1.0.0", &[]);
+        assert_ne!(original, with_config);
+
+        std::fs::write(
+            &lockfile_path,
+            "version = 4\n\n[[package]]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        assert_ne!(with_config, digest("rustc 1.0.0", &[]));
+    }
+
+    #[test]
+    fn cargo_config_files_from_env() {
+        let (_tempdir, root) = crate::test::test_tempdir("cargo_config_files_from_env");
+        let workspace_root = root.join("workspace");
+        for dir in ["workspace/.cargo", "home/.cargo", "cargo_home"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(workspace_root.join(".cargo/config.toml"), "").unwrap();
+        std::fs::write(root.join("home/.cargo/config.toml"), "").unwrap();
+        std::fs::write(root.join("cargo_home/config"), "").unwrap();
+
+        let env = |vars: &[(&str, PathBuf)]| -> BTreeMap<String, OsString> {
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.clone().into_os_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            cargo_config_files(&workspace_root, &env(&[])),
+            vec![workspace_root.join(".cargo/config.toml")]
+        );
+        assert_eq!(
+            cargo_config_files(&workspace_root, &env(&[("HOME", root.join("home"))])),
+            vec![
+                workspace_root.join(".cargo/config.toml"),
+                root.join("home/.cargo/config.toml"),
+            ]
+        );
+        assert_eq!(
+            cargo_config_files(
+                &workspace_root,
+                &env(&[
+                    ("HOME", root.join("home")),
+                    ("CARGO_HOME", root.join("cargo_home")),
+                ])
+            ),
+            vec![
+                workspace_root.join(".cargo/config.toml"),
+                root.join("cargo_home/config"),
+            ]
+        );
+    }
+
+    #[test]
+    fn prune_cache_removes_stale_entries() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let now = SystemTime::now();
+        let stale = now - CACHE_ENTRY_MAX_AGE - Duration::from_secs(60);
+
+        let write = |name: &str, modified: SystemTime| {
+            let path = cache_dir.path().join(name);
+            std::fs::write(&path, "").unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        write("fresh.tree", now);
+        write("stale.tree", stale);
+        write(".tmpAbC123", stale);
+        write("unrelated.txt", stale);
+
+        TreeResolver::prune_cache(cache_dir.path(), now);
+
+        let mut remaining: Vec<String> = std::fs::read_dir(cache_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["fresh.tree", "unrelated.txt"]);
+    }
 }
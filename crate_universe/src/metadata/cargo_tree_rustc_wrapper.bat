@@ -5,11 +5,15 @@
 
 SETLOCAL ENABLEDELAYEDEXPANSION
 
+@REM Custom target specifications require unstable options to be enabled.
+set _EXTRA_ARGS=
+if defined CUSTOM_TARGET_SPEC set _EXTRA_ARGS=-Zunstable-options
+
 @REM When cargo is detecting the host configuration, the host target needs to be
 @REM injected into the command.
 echo %*| FINDSTR /R /C:".*rustc[\.exe\"\"]* - --crate-name ___ " | FINDSTR /V /C:"--target" >NUL
 if %errorlevel%==0 (
-    %* --target %HOST_TRIPLE%
+    %* --target %HOST_TRIPLE% %_EXTRA_ARGS%
     exit /b
 )
 
@@ -20,7 +24,7 @@ if %errorlevel%==0 (
 
     @REM TODO: The exit code is lost here. It should be captured and explicitly
     @REM returned.
-    for /F "delims=" %%i in ('%*') do (
+    for /F "delims=" %%i in ('%* %_EXTRA_ARGS%') do (
         echo %%i| FINDSTR /R /C:"^host:" >NUL
         if errorlevel 1 (
             echo %%i
@@ -33,5 +37,5 @@ if %errorlevel%==0 (
 )
 
 @REM No unique calls intercepted. Simply call rustc.exe as normal.
-%*
+%* %_EXTRA_ARGS%
 exit /b
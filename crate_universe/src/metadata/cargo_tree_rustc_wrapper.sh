@@ -5,6 +5,11 @@
 
 set -eu
 
+# Custom target specifications require unstable options to be enabled.
+if [ -n "${CUSTOM_TARGET_SPEC:-}" ]; then
+    set -- "$@" -Zunstable-options
+fi
+
 # When cargo is detecting the host configuration, the host target needs to be
 # injected into the command.
 case "$*" in
@@ -27,6 +27,78 @@ pub(crate) struct Dependency {
     pub(crate) alias: Option<String>,
 }
 
+/// The kind of artifact consumed through an
+/// [artifact dependency](https://doc.rust-lang.org/nightly/cargo/reference/unstable.html#artifact-dependencies).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ArtifactKind {
+    Bin,
+    CDyLib,
+    StaticLib,
+}
+
+impl ArtifactKind {
+    /// The artifact type as it appears in `CARGO_<TYPE>_FILE_<DEP>` environment variables.
+    pub(crate) fn env_var_type(&self) -> &'static str {
+        match self {
+            ArtifactKind::Bin => "BIN",
+            ArtifactKind::CDyLib => "CDYLIB",
+            ArtifactKind::StaticLib => "STATICLIB",
+        }
+    }
+}
+
+/// An entry of `resolve.nodes[#].deps[#].dep_kinds` in Cargo metadata which describes an
+/// artifact dependency. These are only produced with `-Zbindeps` and are not represented
+/// by `cargo_metadata`, so they're split out of the raw metadata by [split_artifact_deps].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct NodeArtifactDep {
+    /// The PackageId of the dependency
+    pub(crate) pkg: PackageId,
+
+    /// `"build"` or `"dev"` for non-normal dependencies.
+    #[serde(default)]
+    pub(crate) kind: Option<String>,
+
+    /// The platform `cfg` the dependency is conditional on.
+    #[serde(default)]
+    pub(crate) target: Option<String>,
+
+    /// The name of the dependency from the perspective of the dependent.
+    pub(crate) extern_name: String,
+
+    /// The kind of artifact.
+    pub(crate) artifact: ArtifactKind,
+
+    /// The `target` the artifact is built for, `<target>` being the target of the dependent.
+    #[serde(default)]
+    pub(crate) compile_target: Option<String>,
+
+    /// The name of the binary for `bin` artifacts.
+    #[serde(default)]
+    pub(crate) bin_name: Option<String>,
+}
+
+/// A binary or C-ABI library of another package required by a crate.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub(crate) struct ArtifactDependency {
+    /// The PackageId of the target
+    pub(crate) package_id: PackageId,
+
+    /// The name of the dependency from the perspective of the current package.
+    pub(crate) name: String,
+
+    /// The kind of artifact.
+    pub(crate) kind: ArtifactKind,
+
+    /// The name of the binary for `bin` artifacts.
+    pub(crate) bin_name: Option<String>,
+
+    /// An explicit `target` the artifact is built for, `<target>` being the target of the
+    /// current package. If unset, build dependencies are built for the host.
+    pub(crate) compile_target: Option<String>,
+}
+
 /// A collection of [Dependency]s sorted by dependency kind.
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub(crate) struct DependencySet {
@@ -37,6 +109,8 @@ pub(crate) struct DependencySet {
     pub(crate) build_deps: Select<BTreeSet<Dependency>>,
     pub(crate) build_link_deps: Select<BTreeSet<Dependency>>,
     pub(crate) build_proc_macro_deps: Select<BTreeSet<Dependency>>,
+    pub(crate) artifact_deps: Select<BTreeSet<ArtifactDependency>>,
+    pub(crate) build_artifact_deps: Select<BTreeSet<ArtifactDependency>>,
 }
 
 impl DependencySet {
@@ -45,6 +119,7 @@ impl DependencySet {
         node: &Node,
         metadata: &CargoMetadata,
         tree_data: Option<&Select<BTreeSet<CrateId>>>,
+        artifact_data: Option<&Vec<NodeArtifactDep>>,
     ) -> Self {
         let (normal_dev_deps, normal_deps) = {
             let (dev, normal) = node
@@ -132,6 +207,26 @@ impl DependencySet {
             build_link_deps.insert(dependency, configuration);
         }
 
+        // Artifacts of dev dependencies are not supported as tests of dependencies are rarely built.
+        let mut artifact_deps: Select<BTreeSet<ArtifactDependency>> = Select::default();
+        let mut build_artifact_deps: Select<BTreeSet<ArtifactDependency>> = Select::default();
+        for dep in artifact_data.into_iter().flatten() {
+            let artifact_dependency = ArtifactDependency {
+                package_id: dep.pkg.clone(),
+                name: dep.extern_name.clone(),
+                kind: dep.artifact,
+                bin_name: dep.bin_name.clone(),
+                compile_target: dep.compile_target.clone(),
+            };
+            match dep.kind.as_deref() {
+                None => artifact_deps.insert(artifact_dependency, dep.target.clone()),
+                Some("build") => {
+                    build_artifact_deps.insert(artifact_dependency, dep.target.clone())
+                }
+                _ => {}
+            }
+        }
+
         Self {
             normal_deps,
             normal_dev_deps,
@@ -140,10 +235,71 @@ impl DependencySet {
             build_deps,
             build_link_deps,
             build_proc_macro_deps,
+            artifact_deps,
+            build_artifact_deps,
         }
     }
 }
 
+/// Remove artifact dependencies from raw Cargo metadata, returning them keyed by the
+/// dependent package.
+///
+/// Cargo reports each artifact of a dependency as an additional `dep_kinds` entry. Library
+/// dependencies are reported separately, so once the artifact entries are removed, the
+/// metadata only describes library dependencies as the rest of crate_universe expects.
+pub(crate) fn split_artifact_deps(
+    metadata: &mut serde_json::Value,
+) -> Result<BTreeMap<PackageId, Vec<NodeArtifactDep>>> {
+    let mut artifact_deps: BTreeMap<PackageId, Vec<NodeArtifactDep>> = BTreeMap::new();
+
+    let nodes = match metadata
+        .pointer_mut("/resolve/nodes")
+        .and_then(|nodes| nodes.as_array_mut())
+    {
+        Some(nodes) => nodes,
+        None => return Ok(artifact_deps),
+    };
+
+    for node in nodes.iter_mut() {
+        let node_id: PackageId = serde_json::from_value(node["id"].clone())?;
+        let deps = match node.get_mut("deps").and_then(|deps| deps.as_array_mut()) {
+            Some(deps) => deps,
+            None => continue,
+        };
+
+        for dep in deps.iter_mut() {
+            let pkg = dep["pkg"].clone();
+            if let Some(dep_kinds) = dep.get_mut("dep_kinds").and_then(|k| k.as_array_mut()) {
+                let mut artifacts = Vec::new();
+                dep_kinds.retain(|dep_kind| {
+                    if dep_kind.get("artifact").is_none() {
+                        return true;
+                    }
+                    let mut dep_kind = dep_kind.clone();
+                    dep_kind["pkg"] = pkg.clone();
+                    artifacts.push(dep_kind);
+                    false
+                });
+                for artifact in artifacts {
+                    artifact_deps
+                        .entry(node_id.clone())
+                        .or_default()
+                        .push(serde_json::from_value(artifact)?);
+                }
+            }
+        }
+
+        // Dependencies which are only consumed as artifacts are no longer dependencies.
+        deps.retain(|dep| {
+            dep["dep_kinds"]
+                .as_array()
+                .is_none_or(|dep_kinds| !dep_kinds.is_empty())
+        });
+    }
+
+    Ok(artifact_deps)
+}
+
 /// Build a dep tree mapping that's easily indexable via `cargo_metadata::PackageId`
 pub(crate) fn build_dep_tree(
     resolver_data: &TreeResolverMetadata,
@@ -520,7 +676,7 @@ mod test {
         let metadata = metadata::example_proc_macro_dep();
 
         let node = find_metadata_node("example-proc-macro-dep", &metadata);
-        let dependencies = DependencySet::new_for_node(node, &metadata, None);
+        let dependencies = DependencySet::new_for_node(node, &metadata, None, None);
 
         let normal_deps: Vec<_> = dependencies
             .normal_deps
@@ -544,7 +700,7 @@ mod test {
         let metadata = metadata::alias();
 
         let node = find_metadata_node("surrealdb-core", &metadata);
-        let dependencies = DependencySet::new_for_node(node, &metadata, None);
+        let dependencies = DependencySet::new_for_node(node, &metadata, None, None);
 
         let bindings = dependencies.normal_deps.items();
 
@@ -567,7 +723,7 @@ mod test {
 
         let openssl_node = find_metadata_node("openssl", &metadata);
 
-        let dependencies = DependencySet::new_for_node(openssl_node, &metadata, None);
+        let dependencies = DependencySet::new_for_node(openssl_node, &metadata, None, None);
 
         let normal_sys_crate =
             dependencies
@@ -600,7 +756,7 @@ mod test {
         let metadata = metadata::build_scripts();
 
         let libssh2 = find_metadata_node("libssh2-sys", &metadata);
-        let libssh2_depset = DependencySet::new_for_node(libssh2, &metadata, None);
+        let libssh2_depset = DependencySet::new_for_node(libssh2, &metadata, None, None);
 
         // Collect build dependencies into a set
         let build_deps: BTreeSet<String> = libssh2_depset
@@ -649,7 +805,7 @@ mod test {
         let metadata = metadata::alias();
 
         let aliases_node = find_metadata_node("aliases", &metadata);
-        let dependencies = DependencySet::new_for_node(aliases_node, &metadata, None);
+        let dependencies = DependencySet::new_for_node(aliases_node, &metadata, None, None);
 
         let aliases: Vec<Dependency> = dependencies
             .normal_deps
@@ -675,7 +831,7 @@ mod test {
         let metadata = metadata::crate_types();
 
         let node = find_metadata_node("crate-types", &metadata);
-        let dependencies = DependencySet::new_for_node(node, &metadata, None);
+        let dependencies = DependencySet::new_for_node(node, &metadata, None, None);
 
         let rlib_deps: Vec<Dependency> = dependencies
             .normal_deps
@@ -705,7 +861,7 @@ mod test {
         let metadata = metadata::multi_cfg_dep();
 
         let node = find_metadata_node("cpufeatures", &metadata);
-        let dependencies = DependencySet::new_for_node(node, &metadata, None);
+        let dependencies = DependencySet::new_for_node(node, &metadata, None, None);
 
         let libc_cfgs: BTreeSet<Option<String>> = dependencies
             .normal_deps
@@ -730,7 +886,7 @@ mod test {
         let metadata = metadata::multi_kind_proc_macro_dep();
 
         let node = find_metadata_node("multi-kind-proc-macro-dep", &metadata);
-        let dependencies = DependencySet::new_for_node(node, &metadata, None);
+        let dependencies = DependencySet::new_for_node(node, &metadata, None, None);
 
         let lib_deps: Vec<_> = dependencies
             .proc_macro_deps
@@ -754,7 +910,7 @@ mod test {
         let metadata = metadata::optional_deps_disabled();
 
         let node = find_metadata_node("clap", &metadata);
-        let dependencies = DependencySet::new_for_node(node, &metadata, None);
+        let dependencies = DependencySet::new_for_node(node, &metadata, None, None);
 
         assert!(!dependencies
             .normal_deps
@@ -769,7 +925,7 @@ mod test {
         let metadata = metadata::renamed_optional_deps_disabled();
 
         let serde_with = find_metadata_node("serde_with", &metadata);
-        let serde_with_depset = DependencySet::new_for_node(serde_with, &metadata, None);
+        let serde_with_depset = DependencySet::new_for_node(serde_with, &metadata, None, None);
         assert!(!serde_with_depset
             .normal_deps
             .items()
@@ -797,7 +953,8 @@ mod test {
 
         let clap = find_metadata_node("clap", &metadata);
         let dep_tree = build_dep_tree(&resolver_data);
-        let clap_depset = DependencySet::new_for_node(clap, &metadata, dep_tree.get(&crate_id));
+        let clap_depset =
+            DependencySet::new_for_node(clap, &metadata, dep_tree.get(&crate_id), None);
         assert_eq!(
             clap_depset
                 .normal_deps
@@ -810,7 +967,7 @@ mod test {
         );
 
         let notify = find_metadata_node("notify", &metadata);
-        let notify_depset = DependencySet::new_for_node(notify, &metadata, None);
+        let notify_depset = DependencySet::new_for_node(notify, &metadata, None, None);
 
         // mio is not present in the common list of dependencies
         assert!(!notify_depset
@@ -843,7 +1000,7 @@ mod test {
         let metadata = metadata::optional_deps_disabled_build_dep_enabled();
 
         let node = find_metadata_node("gherkin", &metadata);
-        let dependencies = DependencySet::new_for_node(node, &metadata, None);
+        let dependencies = DependencySet::new_for_node(node, &metadata, None, None);
 
         assert!(!dependencies
             .normal_deps
@@ -876,7 +1033,8 @@ mod test {
 
         let p256 = find_metadata_node("p256", &metadata);
         let dep_tree = build_dep_tree(&resolver_data);
-        let p256_depset = DependencySet::new_for_node(p256, &metadata, dep_tree.get(&crate_id));
+        let p256_depset =
+            DependencySet::new_for_node(p256, &metadata, dep_tree.get(&crate_id), None);
         assert_eq!(
             p256_depset
                 .normal_deps
@@ -934,7 +1092,7 @@ mod test {
         let tokio_node = find_metadata_node("tokio", &metadata);
         let dep_tree = build_dep_tree(&resolver_data);
         let tokio_depset =
-            DependencySet::new_for_node(tokio_node, &metadata, dep_tree.get(&crate_id));
+            DependencySet::new_for_node(tokio_node, &metadata, dep_tree.get(&crate_id), None);
         assert_eq!(
             tokio_depset
                 .normal_deps
@@ -952,4 +1110,94 @@ mod test {
             "`mio` is a platform specific dependency and therefore should not be identified under the common configuration."
         );
     }
+
+    #[test]
+    fn split_artifact_deps_from_metadata() {
+        let mut metadata = serde_json::json!({
+            "resolve": {
+                "nodes": [{
+                    "id": "path+file:///tmp/app#0.1.0",
+                    "deps": [
+                        {
+                            "name": "tool",
+                            "pkg": "registry+https://github.com/rust-lang/crates.io-index#tool@1.0.0",
+                            "dep_kinds": [
+                                {"kind": null, "target": null},
+                                {
+                                    "kind": "build",
+                                    "target": null,
+                                    "extern_name": "tool",
+                                    "artifact": "bin",
+                                    "compile_target": null,
+                                    "bin_name": "tool",
+                                },
+                            ],
+                        },
+                        {
+                            "name": "",
+                            "pkg": "registry+https://github.com/rust-lang/crates.io-index#plugin@2.0.0",
+                            "dep_kinds": [
+                                {
+                                    "kind": null,
+                                    "target": "cfg(unix)",
+                                    "extern_name": "plugin",
+                                    "artifact": "cdylib",
+                                    "compile_target": "<target>",
+                                },
+                            ],
+                        },
+                    ],
+                }],
+            },
+        });
+
+        let artifact_deps = split_artifact_deps(&mut metadata).unwrap();
+
+        assert_eq!(
+            artifact_deps,
+            BTreeMap::from([(
+                PackageId {
+                    repr: "path+file:///tmp/app#0.1.0".to_owned()
+                },
+                vec![
+                    NodeArtifactDep {
+                        pkg: PackageId {
+                            repr:
+                                "registry+https://github.com/rust-lang/crates.io-index#tool@1.0.0"
+                                    .to_owned()
+                        },
+                        kind: Some("build".to_owned()),
+                        target: None,
+                        extern_name: "tool".to_owned(),
+                        artifact: ArtifactKind::Bin,
+                        compile_target: None,
+                        bin_name: Some("tool".to_owned()),
+                    },
+                    NodeArtifactDep {
+                        pkg: PackageId {
+                            repr:
+                                "registry+https://github.com/rust-lang/crates.io-index#plugin@2.0.0"
+                                    .to_owned()
+                        },
+                        kind: None,
+                        target: Some("cfg(unix)".to_owned()),
+                        extern_name: "plugin".to_owned(),
+                        artifact: ArtifactKind::CDyLib,
+                        compile_target: Some("<target>".to_owned()),
+                        bin_name: None,
+                    },
+                ],
+            )])
+        );
+
+        // Only the library dependency on `tool` remains.
+        assert_eq!(
+            metadata["resolve"]["nodes"][0]["deps"],
+            serde_json::json!([{
+                "name": "tool",
+                "pkg": "registry+https://github.com/rust-lang/crates.io-index#tool@1.0.0",
+                "dep_kinds": [{"kind": null, "target": null}],
+            }])
+        );
+    }
 }
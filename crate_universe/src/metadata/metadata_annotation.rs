@@ -76,7 +76,7 @@ impl MetadataAnnotation {
             .map(|node| {
                 (
                     node.id.clone(),
-                    Self::annotate_crate(node, &metadata, &dep_tree),
+                    Self::annotate_crate(node, &metadata, &dep_tree, &workspace_metadata),
                 )
             })
             .collect();
@@ -100,11 +100,13 @@ impl MetadataAnnotation {
         node: Node,
         metadata: &CargoMetadata,
         dep_tree: &BTreeMap<CrateId, Select<BTreeSet<CrateId>>>,
+        workspace_metadata: &WorkspaceMetadata,
     ) -> CrateAnnotation {
         // Gather all dependencies
         let tree_data = dep_tree.get(&CrateId::from(&metadata[&node.id]));
+        let artifact_data = workspace_metadata.artifact_deps.get(&node.id);
 
-        let deps = DependencySet::new_for_node(&node, metadata, tree_data);
+        let deps = DependencySet::new_for_node(&node, metadata, tree_data, artifact_data);
 
         CrateAnnotation { node, deps }
     }
@@ -12,16 +12,17 @@ use anyhow::{bail, Context as AnyhowContext, Result};
 use itertools::Itertools;
 
 use crate::config::{AliasRule, RenderConfig, VendorMode};
-use crate::context::crate_context::{CrateContext, CrateDependency, Rule};
+use crate::context::crate_context::{CrateArtifactDependency, CrateContext, CrateDependency, Rule};
 use crate::context::{Context, TargetAttributes};
-use crate::metadata::SourceAnnotation;
+use crate::metadata::{ArtifactKind, SourceAnnotation};
 use crate::rendering::template_engine::TemplateEngine;
 use crate::select::Select;
 use crate::splicing::default_splicing_package_crate_id;
 use crate::utils::starlark::{
     self, Alias, CargoBuildScript, CargoTomlEnvVars, CommonAttrs, Data, ExportsFiles, Filegroup,
-    Glob, Label, Load, Package, RustBinary, RustLibrary, RustProcMacro, SelectDict, SelectList,
-    SelectScalar, SelectSet, Starlark, TargetCompatibleWith,
+    Glob, Label, Load, Package, RustBinary, RustLibrary, RustProcMacro, RustSharedLibrary,
+    RustStaticLibrary, RustTest, SelectDict, SelectList, SelectScalar, SelectSet, Starlark,
+    TargetCompatibleWith,
 };
 use crate::utils::target_triple::TargetTriple;
 use crate::utils::{self, sanitize_repository_name};
@@ -331,6 +332,50 @@ impl Renderer {
             starlark.extend(binaries.into_iter().map(Starlark::Alias));
         }
 
+        // An `alias` for each shared and static library, named like those of binaries.
+        let library_targets: Vec<(&CrateContext, &TargetAttributes, &str)> = context
+            .crates
+            .values()
+            // Only consider remote repositories (so non-workspace members).
+            .filter(|krate| krate.repository.is_some())
+            .flat_map(|krate| {
+                krate.targets.iter().filter_map(move |rule| match rule {
+                    Rule::SharedLibrary(lib) => Some((krate, lib, "cdylib")),
+                    Rule::StaticLibrary(lib) => Some((krate, lib, "staticlib")),
+                    _ => None,
+                })
+            })
+            .collect();
+        let libraries: Vec<Alias> = library_targets
+            .iter()
+            .map(|(krate, lib, kind)| {
+                let has_duplicate = 1 < library_targets
+                    .iter()
+                    .filter(|(other, _, other_kind)| other.name == krate.name && other_kind == kind)
+                    .count();
+                Alias {
+                    rule: AliasRule::default().rule(),
+                    // If duplicates exist, include version to disambiguate them.
+                    name: if has_duplicate {
+                        format!("{}-{}__{}", krate.name, krate.version, kind)
+                    } else {
+                        format!("{}__{}", krate.name, kind)
+                    },
+                    actual: self.crate_label(
+                        &krate.name,
+                        &krate.version.to_string(),
+                        &format!("{}__{}", lib.crate_name, kind),
+                    ),
+                    tags: BTreeSet::from(["manual".to_owned()]),
+                }
+            })
+            .collect();
+        if !libraries.is_empty() {
+            let comment = "# Shared and static libraries".to_owned();
+            starlark.push(Starlark::Verbatim(comment));
+            starlark.extend(libraries.into_iter().map(Starlark::Alias));
+        }
+
         let starlark = starlark::serialize(&starlark)?;
         Ok(starlark)
     }
@@ -375,6 +420,7 @@ impl Renderer {
         platforms: &Platforms,
         krate: &CrateContext,
     ) -> Result<String> {
+        check_artifact_targets(krate)?;
         let mut krate = krate.clone();
 
         if self.config.generate_cargo_toml_env_vars {
@@ -511,6 +557,28 @@ impl Renderer {
                         let rust_binary = self.make_rust_binary(platforms, &krate, target)?;
                         starlark.push(Starlark::RustBinary(rust_binary));
                     }
+                    Rule::SharedLibrary(target) => {
+                        load("@rules_rust//rust:defs.bzl", "rust_shared_library");
+                        let rust_shared_library =
+                            self.make_rust_shared_library(platforms, &krate, target)?;
+                        starlark.push(Starlark::RustSharedLibrary(rust_shared_library));
+                    }
+                    Rule::StaticLibrary(target) => {
+                        load("@rules_rust//rust:defs.bzl", "rust_static_library");
+                        let rust_static_library =
+                            self.make_rust_static_library(platforms, &krate, target)?;
+                        starlark.push(Starlark::RustStaticLibrary(rust_static_library));
+                    }
+                    Rule::LibraryTest(target) | Rule::Test(target) => {
+                        load("@rules_rust//rust:defs.bzl", "rust_test");
+                        let rust_test = self.make_rust_test(
+                            platforms,
+                            &krate,
+                            target,
+                            matches!(rule, Rule::LibraryTest(..)),
+                        )?;
+                        starlark.push(Starlark::RustTest(rust_test));
+                    }
                 }
             }
         }
@@ -538,6 +606,7 @@ impl Renderer {
         target: &TargetAttributes,
     ) -> Result<CargoBuildScript> {
         let attrs = krate.build_script_attrs.as_ref();
+        let artifact_deps = attrs.map(|attrs| &attrs.artifact_deps);
 
         Ok(CargoBuildScript {
             // Because `cargo_build_script` does some invisible target name
@@ -552,9 +621,12 @@ impl Renderer {
             name: "_bs".to_string(),
             aliases: SelectDict::new(self.make_aliases(krate, true, false), platforms),
             build_script_env: SelectDict::new(
-                attrs
-                    .map(|attrs| attrs.build_script_env.clone())
-                    .unwrap_or_default(),
+                Select::merge(
+                    attrs
+                        .map(|attrs| attrs.build_script_env.clone())
+                        .unwrap_or_default(),
+                    self.make_artifact_env(artifact_deps),
+                ),
                 platforms,
             ),
             use_default_shell_env: krate
@@ -588,7 +660,11 @@ impl Renderer {
                     .map(|attrs| attrs.data_glob.clone())
                     .unwrap_or_default(),
                 Default::default(),
-                attrs.map(|attrs| attrs.data.clone()).unwrap_or_default(),
+                Select::merge(
+                    attrs.map(|attrs| attrs.data.clone()).unwrap_or_default(),
+                    // Artifacts explicitly built for a target are built in the target configuration.
+                    self.make_artifact_deps(artifact_deps, |dep| dep.compile_target.is_some()),
+                ),
             ),
             deps: SelectSet::new(
                 self.make_deps(
@@ -665,7 +741,12 @@ impl Renderer {
                 tags
             },
             tools: SelectSet::new(
-                attrs.map(|attrs| attrs.tools.clone()).unwrap_or_default(),
+                Select::merge(
+                    attrs.map(|attrs| attrs.tools.clone()).unwrap_or_default(),
+                    // Like build scripts themselves, artifacts of build dependencies are built
+                    // for the host unless a target is requested.
+                    self.make_artifact_deps(artifact_deps, |dep| dep.compile_target.is_none()),
+                ),
                 platforms,
             ),
             toolchains: attrs.map_or_else(BTreeSet::new, |attrs| attrs.toolchains.clone()),
@@ -762,6 +843,107 @@ impl Renderer {
         })
     }
 
+    fn make_rust_shared_library(
+        &self,
+        platforms: &Platforms,
+        krate: &CrateContext,
+        target: &TargetAttributes,
+    ) -> Result<RustSharedLibrary> {
+        Ok(RustSharedLibrary {
+            name: format!("{}__cdylib", target.crate_name),
+            deps: SelectSet::new(
+                self.make_deps(
+                    krate.common_attrs.deps.clone(),
+                    krate.common_attrs.extra_deps.clone(),
+                ),
+                platforms,
+            ),
+            proc_macro_deps: SelectSet::new(
+                self.make_deps(
+                    krate.common_attrs.proc_macro_deps.clone(),
+                    krate.common_attrs.extra_proc_macro_deps.clone(),
+                ),
+                platforms,
+            ),
+            aliases: SelectDict::new(self.make_aliases(krate, false, false), platforms),
+            common: self.make_common_attrs(platforms, krate, target)?,
+        })
+    }
+
+    fn make_rust_static_library(
+        &self,
+        platforms: &Platforms,
+        krate: &CrateContext,
+        target: &TargetAttributes,
+    ) -> Result<RustStaticLibrary> {
+        Ok(RustStaticLibrary {
+            name: format!("{}__staticlib", target.crate_name),
+            deps: SelectSet::new(
+                self.make_deps(
+                    krate.common_attrs.deps.clone(),
+                    krate.common_attrs.extra_deps.clone(),
+                ),
+                platforms,
+            ),
+            proc_macro_deps: SelectSet::new(
+                self.make_deps(
+                    krate.common_attrs.proc_macro_deps.clone(),
+                    krate.common_attrs.extra_proc_macro_deps.clone(),
+                ),
+                platforms,
+            ),
+            aliases: SelectDict::new(self.make_aliases(krate, false, false), platforms),
+            common: self.make_common_attrs(platforms, krate, target)?,
+        })
+    }
+
+    fn make_rust_test(
+        &self,
+        platforms: &Platforms,
+        krate: &CrateContext,
+        target: &TargetAttributes,
+        is_library_test: bool,
+    ) -> Result<RustTest> {
+        Ok(RustTest {
+            name: if is_library_test {
+                format!("{}__lib_test", target.crate_name)
+            } else {
+                format!("{}__test", target.crate_name)
+            },
+            deps: {
+                let mut deps = self.make_deps(
+                    Select::merge(
+                        krate.common_attrs.deps.clone(),
+                        krate.common_attrs.deps_dev.clone(),
+                    ),
+                    krate.common_attrs.extra_deps.clone(),
+                );
+                // Integration tests use the library like any other dependent would.
+                if !is_library_test {
+                    if let Some(library_target_name) = &krate.library_target_name {
+                        deps.insert(
+                            Label::from_str(&format!(":{library_target_name}")).unwrap(),
+                            None,
+                        );
+                    }
+                }
+                SelectSet::new(deps, platforms)
+            },
+            proc_macro_deps: SelectSet::new(
+                self.make_deps(
+                    Select::merge(
+                        krate.common_attrs.proc_macro_deps.clone(),
+                        krate.common_attrs.proc_macro_deps_dev.clone(),
+                    ),
+                    krate.common_attrs.extra_proc_macro_deps.clone(),
+                ),
+                platforms,
+            ),
+            aliases: SelectDict::new(self.make_aliases(krate, false, true), platforms),
+            common: self.make_common_attrs(platforms, krate, target)?,
+        })
+    }
+
     fn make_common_attrs(
         &self,
         platforms: &Platforms,
@@ -773,7 +955,10 @@ impl Renderer {
                 platforms,
                 krate.common_attrs.compile_data_glob.clone(),
                 krate.common_attrs.compile_data_glob_excludes.clone(),
-                krate.common_attrs.compile_data.clone(),
+                Select::merge(
+                    krate.common_attrs.compile_data.clone(),
+                    self.make_artifact_deps(Some(&krate.common_attrs.artifact_deps), |_| true),
+                ),
             ),
             crate_features: SelectSet::new(krate.common_attrs.crate_features.clone(), platforms),
             crate_root: target.crate_root.clone(),
@@ -785,7 +970,13 @@ impl Renderer {
             ),
             edition: krate.common_attrs.edition.clone(),
             linker_script: krate.common_attrs.linker_script.clone(),
-            rustc_env: SelectDict::new(krate.common_attrs.rustc_env.clone(), platforms),
+            rustc_env: SelectDict::new(
+                Select::merge(
+                    krate.common_attrs.rustc_env.clone(),
+                    self.make_artifact_env(Some(&krate.common_attrs.artifact_deps)),
+                ),
+                platforms,
+            ),
             rustc_env_files: SelectSet::new(krate.common_attrs.rustc_env_files.clone(), platforms),
             rustc_flags: SelectList::new(
                 // In most cases, warnings in 3rd party crates are not
@@ -883,6 +1074,58 @@ impl Renderer {
         )
     }
 
+    fn artifact_label(&self, dep: &CrateArtifactDependency) -> Label {
+        match (&dep.local_path, self.config.vendor_mode) {
+            (Some(path), Some(VendorMode::Local)) => {
+                Label::from_str(&format!("//{}:{}", path, &dep.target)).unwrap()
+            }
+            _ => self.crate_label(&dep.id.name, &dep.id.version.to_string(), &dep.target),
+        }
+    }
+
+    fn make_artifact_deps(
+        &self,
+        deps: Option<&Select<BTreeSet<CrateArtifactDependency>>>,
+        filter: impl Fn(&CrateArtifactDependency) -> bool,
+    ) -> Select<BTreeSet<Label>> {
+        let mut labels: Select<BTreeSet<Label>> = Select::default();
+        for (configuration, dep) in deps.map(Select::items).unwrap_or_default() {
+            if filter(&dep) {
+                labels.insert(self.artifact_label(&dep), configuration);
+            }
+        }
+        labels
+    }
+
+    /// The `CARGO_<ARTIFACT-TYPE>_FILE_<DEP>[_<NAME>]` environment variables Cargo would set
+    /// for artifact dependencies.
+    fn make_artifact_env(
+        &self,
+        deps: Option<&Select<BTreeSet<CrateArtifactDependency>>>,
+    ) -> Select<BTreeMap<String, String>> {
+        let mut env: Select<BTreeMap<String, String>> = Select::default();
+        for (configuration, dep) in deps.map(Select::items).unwrap_or_default() {
+            let path = format!("${{pwd}}/$(execpath {})", self.artifact_label(&dep));
+            let prefix = format!(
+                "CARGO_{}_FILE_{}",
+                dep.kind.env_var_type(),
+                dep.name.to_uppercase().replace('-', "_")
+            );
+
+            // The unsuffixed variable is only set for binaries named after the dependency.
+            if dep.kind != ArtifactKind::Bin
+                || utils::sanitize_module_name(&dep.artifact_name) == dep.name
+            {
+                env.insert((prefix.clone(), path.clone()), configuration.clone());
+            }
+            env.insert(
+                (format!("{}_{}", prefix, dep.artifact_name), path),
+                configuration,
+            );
+        }
+        env
+    }
+
     fn render_vendor_support_files(
         &self,
         engine: &TemplateEngine,
@@ -977,6 +1220,29 @@ pub(crate) fn render_crate_bazel_repository(
         .replace("{version}", version)
 }
 
+/// Render the mirror URLs of a crate. Templates which require a checksum are
+/// skipped if `sha256` is unknown.
+pub(crate) fn render_crate_mirror_urls(
+    templates: &[String],
+    name: &str,
+    version: &str,
+    sha256: Option<&str>,
+) -> Vec<String> {
+    templates
+        .iter()
+        .filter_map(|template| {
+            let url = template
+                .replace("{name}", name)
+                .replace("{version}", version);
+            match sha256 {
+                Some(sha256) => Some(url.replace("{sha256}", sha256)),
+                None if url.contains("{sha256}") => None,
+                None => Some(url),
+            }
+        })
+        .collect()
+}
+
 /// Render the Bazel label of a crate
 pub(crate) fn render_crate_build_file(template: &str, name: &str, version: &str) -> String {
     template
@@ -1031,6 +1297,39 @@ fn make_data_with_exclude(
     }
 }
 
+/// Artifacts are built either for the host, or for the target platform of the crate depending
+/// on them (`target = "target"`). Building them for a specific triple would need a transition
+/// to a platform for it, which isn't supported.
+fn check_artifact_targets(krate: &CrateContext) -> Result<()> {
+    let build_script_artifact_deps = krate
+        .build_script_attrs
+        .iter()
+        .flat_map(|attrs| attrs.artifact_deps.values());
+    for dep in krate
+        .common_attrs
+        .artifact_deps
+        .values()
+        .into_iter()
+        .chain(build_script_artifact_deps)
+    {
+        if let Some(triple) = dep
+            .compile_target
+            .as_deref()
+            .filter(|compile_target| *compile_target != "<target>")
+        {
+            bail!(
+                "{} {} depends on the `{}` artifact of {} built for `{}`, but artifact dependencies on a specific target triple are not supported. Use `target = \"target\"` or remove the `target` key.",
+                krate.name,
+                krate.version,
+                dep.artifact_name,
+                dep.id,
+                triple
+            );
+        }
+    }
+    Ok(())
+}
+
 fn make_data(
     platforms: &Platforms,
     glob: BTreeSet<String>,
@@ -1242,6 +1541,129 @@ mod test {
         assert!(build_file_content.contains("name = \"_bs\""));
     }
 
+    #[test]
+    fn render_artifact_deps() {
+        let mut context = Context::default();
+        let crate_id = CrateId::new("mock_crate".to_owned(), VERSION_ZERO_ONE_ZERO);
+        let tool = CrateArtifactDependency {
+            id: CrateId::new("tool".to_owned(), VERSION_ZERO_ONE_ZERO),
+            name: "tool".to_owned(),
+            kind: ArtifactKind::Bin,
+            artifact_name: "tool".to_owned(),
+            target: "tool__bin".to_owned(),
+            compile_target: None,
+            local_path: None,
+        };
+        let plugin = CrateArtifactDependency {
+            id: CrateId::new("plugin".to_owned(), VERSION_ZERO_ONE_ZERO),
+            name: "plugin".to_owned(),
+            kind: ArtifactKind::CDyLib,
+            artifact_name: "plugin".to_owned(),
+            target: "plugin__cdylib".to_owned(),
+            compile_target: Some("<target>".to_owned()),
+            local_path: None,
+        };
+
+        context.crates.insert(
+            crate_id.clone(),
+            CrateContext {
+                name: crate_id.name,
+                version: crate_id.version,
+                package_url: None,
+                repository: None,
+                targets: BTreeSet::from([
+                    Rule::Library(mock_target_attributes()),
+                    Rule::BuildScript(TargetAttributes {
+                        crate_name: "build_script_build".to_owned(),
+                        crate_root: Some("build.rs".to_owned()),
+                        ..TargetAttributes::default()
+                    }),
+                ]),
+                library_target_name: None,
+                common_attrs: CommonAttributes {
+                    artifact_deps: Select::from_value(BTreeSet::from([tool.clone()])),
+                    ..CommonAttributes::default()
+                },
+                build_script_attrs: Some(BuildScriptAttributes {
+                    artifact_deps: Select::from_value(BTreeSet::from([tool, plugin.clone()])),
+                    ..BuildScriptAttributes::default()
+                }),
+                license: None,
+                license_ids: BTreeSet::default(),
+                license_file: None,
+                additive_build_file_content: None,
+                disable_pipelining: false,
+                extra_aliased_targets: BTreeMap::default(),
+                alias_rule: None,
+                override_targets: BTreeMap::default(),
+            },
+        );
+
+        let renderer = Renderer::new(mock_render_config(None), mock_supported_platform_triples());
+        let output = renderer.render(&context, None).unwrap();
+
+        let build_file_content = output
+            .get(&PathBuf::from("BUILD.mock_crate-0.1.0.bazel"))
+            .unwrap();
+
+        // Host artifacts of build dependencies are tools of the build script.
+        assert!(
+            build_file_content.contains(
+                "    tools = [\n        \"@test_rendering__tool-0.1.0//:tool__bin\",\n    ],"
+            ),
+            "```\n{}```\n",
+            build_file_content
+        );
+        assert!(
+            build_file_content
+                .contains("\"@test_rendering__plugin-0.1.0//:plugin__cdylib\",\n    ],"),
+            "```\n{}```\n",
+            build_file_content
+        );
+        assert!(
+            build_file_content.contains(
+                "\"CARGO_CDYLIB_FILE_PLUGIN_plugin\": \"${pwd}/$(execpath @test_rendering__plugin-0.1.0//:plugin__cdylib)\","
+            ),
+            "```\n{}```\n",
+            build_file_content
+        );
+        assert_eq!(
+            build_file_content
+                .matches(
+                    "\"CARGO_BIN_FILE_TOOL\": \"${pwd}/$(execpath @test_rendering__tool-0.1.0//:tool__bin)\","
+                )
+                .count(),
+            2,
+            "```\n{}```\n",
+            build_file_content
+        );
+
+        // Artifacts can't be built for a specific target triple.
+        context
+            .crates
+            .get_mut(&CrateId::new(
+                "mock_crate".to_owned(),
+                VERSION_ZERO_ONE_ZERO,
+            ))
+            .unwrap()
+            .build_script_attrs
+            .as_mut()
+            .unwrap()
+            .artifact_deps
+            .insert(
+                CrateArtifactDependency {
+                    compile_target: Some("wasm32-unknown-unknown".to_owned()),
+                    ..plugin
+                },
+                None,
+            );
+        let err = renderer.render(&context, None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "mock_crate 0.1.0 depends on the `plugin` artifact of plugin 0.1.0 built for `wasm32-unknown-unknown`, but artifact dependencies on a specific target triple are not supported. Use `target = \"target\"` or remove the `target` key."
+        );
+    }
+
     #[test]
     fn render_cargo_build_script_complex() {
         let mut context = Context::default();
@@ -1411,6 +1833,192 @@ mod test {
         assert!(build_file_content.contains("\"crate-name=mock_crate\""));
     }
 
+    #[test]
+    fn render_shared_and_static_libraries() {
+        let mut context = Context::default();
+        let crate_id = CrateId::new("mock_crate".to_owned(), VERSION_ZERO_ONE_ZERO);
+        context.crates.insert(
+            crate_id.clone(),
+            CrateContext {
+                name: crate_id.name,
+                version: crate_id.version,
+                package_url: None,
+                repository: None,
+                targets: BTreeSet::from([
+                    Rule::SharedLibrary(mock_target_attributes()),
+                    Rule::StaticLibrary(mock_target_attributes()),
+                ]),
+                library_target_name: None,
+                common_attrs: CommonAttributes::default(),
+                build_script_attrs: None,
+                license: None,
+                license_ids: BTreeSet::default(),
+                license_file: None,
+                additive_build_file_content: None,
+                disable_pipelining: false,
+                extra_aliased_targets: BTreeMap::default(),
+                alias_rule: None,
+                override_targets: BTreeMap::default(),
+            },
+        );
+
+        let renderer = Renderer::new(mock_render_config(None), mock_supported_platform_triples());
+        let output = renderer.render(&context, None).unwrap();
+
+        let build_file_content = output
+            .get(&PathBuf::from("BUILD.mock_crate-0.1.0.bazel"))
+            .unwrap();
+
+        assert!(build_file_content.contains("\"rust_shared_library\""));
+        assert!(build_file_content.contains("\"rust_static_library\""));
+        assert!(
+            build_file_content.contains("rust_shared_library(\n    name = \"mock_crate__cdylib\"")
+        );
+        assert!(build_file_content
+            .contains("rust_static_library(\n    name = \"mock_crate__staticlib\""));
+    }
+
+    #[test]
+    fn render_shared_and_static_library_aliases() {
+        let mut context = Context::default();
+        for version in ["0.1.0", "0.2.0"] {
+            let crate_id = CrateId::new(
+                "mock_crate".to_owned(),
+                semver::Version::parse(version).unwrap(),
+            );
+            let mut targets = BTreeSet::from([Rule::SharedLibrary(mock_target_attributes())]);
+            if version == "0.1.0" {
+                targets.insert(Rule::StaticLibrary(mock_target_attributes()));
+            }
+            context.crates.insert(
+                crate_id.clone(),
+                CrateContext {
+                    name: crate_id.name,
+                    version: crate_id.version,
+                    package_url: None,
+                    repository: Some(SourceAnnotation::Http {
+                        url: format!(
+                            "https://crates.io/api/v1/crates/mock_crate/{version}/download"
+                        ),
+                        sha256: None,
+                        patch_args: None,
+                        patch_tool: None,
+                        patches: None,
+                    }),
+                    targets,
+                    library_target_name: None,
+                    common_attrs: CommonAttributes::default(),
+                    build_script_attrs: None,
+                    license: None,
+                    license_ids: BTreeSet::default(),
+                    license_file: None,
+                    additive_build_file_content: None,
+                    disable_pipelining: false,
+                    extra_aliased_targets: BTreeMap::default(),
+                    alias_rule: None,
+                    override_targets: BTreeMap::default(),
+                },
+            );
+        }
+
+        let renderer = Renderer::new(mock_render_config(None), mock_supported_platform_triples());
+        let output = renderer.render(&context, None).unwrap();
+
+        let build_file_content = output.get(&PathBuf::from("BUILD.bazel")).unwrap();
+
+        assert!(build_file_content.contains("# Shared and static libraries"));
+        assert!(build_file_content.contains(indoc! {r#"
+            alias(
+                name = "mock_crate-0.1.0__cdylib",
+                actual = "@test_rendering__mock_crate-0.1.0//:mock_crate__cdylib",
+                tags = ["manual"],
+            )
+        "#}));
+        assert!(build_file_content.contains(indoc! {r#"
+            alias(
+                name = "mock_crate-0.2.0__cdylib",
+                actual = "@test_rendering__mock_crate-0.2.0//:mock_crate__cdylib",
+                tags = ["manual"],
+            )
+        "#}));
+        // Only one version has a static library, so its alias doesn't need the version.
+        assert!(build_file_content.contains(indoc! {r#"
+            alias(
+                name = "mock_crate__staticlib",
+                actual = "@test_rendering__mock_crate-0.1.0//:mock_crate__staticlib",
+                tags = ["manual"],
+            )
+        "#}));
+    }
+
+    #[test]
+    fn render_tests() {
+        let mut context = Context::default();
+        let crate_id = CrateId::new("mock_crate".to_owned(), VERSION_ZERO_ONE_ZERO);
+        let dev_dep = CrateId::new("mock_dev_dep".to_owned(), VERSION_ZERO_ONE_ZERO);
+        context.crates.insert(
+            crate_id.clone(),
+            CrateContext {
+                name: crate_id.name.clone(),
+                version: crate_id.version.clone(),
+                package_url: None,
+                repository: None,
+                targets: BTreeSet::from([
+                    Rule::Library(mock_target_attributes()),
+                    Rule::LibraryTest(mock_target_attributes()),
+                    Rule::Test(TargetAttributes {
+                        crate_name: "integration".to_owned(),
+                        crate_root: Some("tests/integration.rs".to_owned()),
+                        srcs: Glob::new_rust_srcs(true),
+                    }),
+                ]),
+                library_target_name: Some("mock_crate".to_owned()),
+                common_attrs: CommonAttributes {
+                    deps_dev: Select::from_value(BTreeSet::from([CrateDependency {
+                        id: dev_dep.clone(),
+                        target: "mock_dev_dep".to_owned(),
+                        alias: None,
+                        local_path: None,
+                    }])),
+                    ..CommonAttributes::default()
+                },
+                build_script_attrs: None,
+                license: None,
+                license_ids: BTreeSet::default(),
+                license_file: None,
+                additive_build_file_content: None,
+                disable_pipelining: false,
+                extra_aliased_targets: BTreeMap::default(),
+                alias_rule: None,
+                override_targets: BTreeMap::default(),
+            },
+        );
+
+        let renderer = Renderer::new(mock_render_config(None), mock_supported_platform_triples());
+        let output = renderer.render(&context, None).unwrap();
+
+        let build_file_content = output
+            .get(&PathBuf::from("BUILD.mock_crate-0.1.0.bazel"))
+            .unwrap();
+
+        let rust_test = |name: &str| {
+            let start = build_file_content
+                .find(&format!("rust_test(\n    name = \"{name}\""))
+                .unwrap_or_else(|| panic!("No rust_test named {name}"));
+            let end = build_file_content[start..].find("\n)").unwrap();
+            &build_file_content[start..start + end]
+        };
+
+        let lib_test = rust_test("mock_crate__lib_test");
+        assert!(lib_test.contains("\"@test_rendering__mock_dev_dep-0.1.0//:mock_dev_dep\""));
+        assert!(!lib_test.contains("\":mock_crate\""));
+
+        let integration_test = rust_test("integration__test");
+        assert!(integration_test.contains("\"@test_rendering__mock_dev_dep-0.1.0//:mock_dev_dep\""));
+        assert!(integration_test.contains("\":mock_crate\""));
+        assert!(integration_test.contains("crate_root = \"tests/integration.rs\""));
+    }
+
     #[test]
     fn render_additive_build_contents() {
         let mut context = Context::default();
@@ -1508,6 +2116,67 @@ mod test {
         assert!(defs_module.contains("def crate_repositories():"));
     }
 
+    #[test]
+    fn render_crate_repositories_with_mirrors() {
+        let mut context = Context::default();
+        for (name, sha256) in [("with_sha", Some("abc123")), ("without_sha", None)] {
+            let crate_id = CrateId::new(name.to_owned(), VERSION_ZERO_ONE_ZERO);
+            context.crates.insert(
+                crate_id.clone(),
+                CrateContext {
+                    name: crate_id.name.clone(),
+                    version: crate_id.version,
+                    package_url: None,
+                    repository: Some(SourceAnnotation::Http {
+                        url: format!("https://static.crates.io/crates/{name}/{name}-0.1.0.crate"),
+                        sha256: sha256.map(str::to_owned),
+                        patch_args: None,
+                        patch_tool: None,
+                        patches: None,
+                    }),
+                    targets: BTreeSet::from([Rule::Library(mock_target_attributes())]),
+                    library_target_name: None,
+                    common_attrs: CommonAttributes::default(),
+                    build_script_attrs: None,
+                    license: None,
+                    license_ids: BTreeSet::default(),
+                    license_file: None,
+                    additive_build_file_content: None,
+                    disable_pipelining: false,
+                    extra_aliased_targets: BTreeMap::default(),
+                    alias_rule: None,
+                    override_targets: BTreeMap::default(),
+                },
+            );
+        }
+
+        let render_config = Arc::new(RenderConfig {
+            crate_mirror_url_templates: vec![
+                "https://mirror.example.com/sha256/{sha256}".to_owned(),
+                "https://other.example.com/{name}/{version}/download".to_owned(),
+            ],
+            ..(*mock_render_config(None)).clone()
+        });
+        let renderer = Renderer::new(render_config, mock_supported_platform_triples());
+        let output = renderer.render(&context, None).unwrap();
+
+        let defs_module = output.get(&PathBuf::from("defs.bzl")).unwrap();
+
+        assert!(defs_module.contains(concat!(
+            "        urls = [\n",
+            "            \"https://mirror.example.com/sha256/abc123\",\n",
+            "            \"https://other.example.com/with_sha/0.1.0/download\",\n",
+            "            \"https://static.crates.io/crates/with_sha/with_sha-0.1.0.crate\",\n",
+            "        ],\n",
+        )));
+        assert!(defs_module.contains(concat!(
+            "        urls = [\n",
+            "            \"https://other.example.com/without_sha/0.1.0/download\",\n",
+            "            \"https://static.crates.io/crates/without_sha/without_sha-0.1.0.crate\",\n",
+            "        ],\n",
+        )));
+    }
+
     #[test]
     fn remote_remote_vendor_mode() {
         let mut context = Context::default();
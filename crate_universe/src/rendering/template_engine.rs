@@ -10,7 +10,7 @@ use crate::config::RenderConfig;
 use crate::context::{Context, SingleBuildFileRenderContext};
 use crate::rendering::{
     render_crate_bazel_label, render_crate_bazel_repository, render_crate_build_file,
-    render_module_label, CrateContext, Platforms,
+    render_crate_mirror_urls, render_module_label, CrateContext, Platforms,
 };
 use crate::select::Select;
 use crate::utils::sanitize_repository_name;
@@ -107,6 +107,10 @@ impl TemplateEngine {
                 render_config.repository_name.clone(),
             ),
         );
+        tera.register_function(
+            "crate_mirror_urls",
+            crate_mirror_urls_fn_generator(render_config.crate_mirror_url_templates.clone()),
+        );
         tera.register_function(
             "crates_module_label",
             module_label_fn_generator(render_config.crates_module_template.clone()),
@@ -301,6 +305,28 @@ fn crate_repository_fn_generator(template: String, repository_name: String) -> i
     )
 }
 
+/// Render the URLs of the mirrors to download a crate from. An empty `sha256`
+/// indicates the checksum of the crate is unknown.
+fn crate_mirror_urls_fn_generator(templates: Vec<String>) -> impl tera::Function {
+    Box::new(
+        move |args: &HashMap<String, Value>| -> tera::Result<Value> {
+            let name = parse_tera_param!("name", String, args);
+            let version = parse_tera_param!("version", String, args);
+            let sha256 = parse_tera_param!("sha256", String, args);
+
+            match to_value(render_crate_mirror_urls(
+                &templates,
+                &name,
+                &version,
+                Some(sha256.as_str()).filter(|sha256| !sha256.is_empty()),
+            )) {
+                Ok(v) => Ok(v),
+                Err(_) => Err(tera::Error::msg("Failed to generate crate mirror urls")),
+            }
+        },
+    )
+}
+
 fn local_crate_mirror_options_json_fn_generator(
     config: Arc<RenderConfig>,
     supported_platform_triples: Arc<BTreeSet<TargetTriple>>,
@@ -2,6 +2,7 @@
 
 pub(crate) mod cargo_config;
 mod crate_index_lookup;
+pub(crate) mod manifest_editor;
 mod splicer;
 
 use std::collections::{BTreeMap, BTreeSet};
@@ -12,11 +13,14 @@ use std::str::FromStr;
 use anyhow::{anyhow, bail, Context, Result};
 use camino::{Utf8Path, Utf8PathBuf};
 use cargo_lock::package::SourceKind;
+use cargo_metadata::PackageId;
 use cargo_toml::Manifest;
 use serde::{Deserialize, Serialize};
 
 use crate::config::CrateId;
-use crate::metadata::{Cargo, CargoUpdateRequest, LockGenerator, TreeResolverMetadata};
+use crate::metadata::{
+    Cargo, CargoUpdateRequest, LockGenerator, NodeArtifactDep, TreeResolverMetadata,
+};
 use crate::utils;
 use crate::utils::starlark::Label;
 
@@ -175,6 +179,13 @@ pub(crate) struct WorkspaceMetadata {
     /// We store this here because it's computed during the splicing phase via
     /// calls to "cargo tree" which need the full spliced workspace.
     pub(crate) tree_metadata: TreeResolverMetadata,
+
+    /// Artifact dependencies of each package.
+    ///
+    /// These are split out of the Cargo metadata when it's loaded as `cargo_metadata`
+    /// has no representation of them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) artifact_deps: BTreeMap<PackageId, Vec<NodeArtifactDep>>,
 }
 
 impl TryFrom<toml::Value> for WorkspaceMetadata {
@@ -248,6 +259,7 @@ impl WorkspaceMetadata {
             workspace_prefix,
             package_prefixes,
             tree_metadata: TreeResolverMetadata::new(),
+            artifact_deps: BTreeMap::new(),
         })
     }
 
@@ -0,0 +1,642 @@
+//! Utilities for adding and removing dependencies from Cargo manifests while preserving
+//! their formatting and comments.
+//!
+//! Dependencies are matched by their key, so a crate renamed with `package = "..."` is
+//! edited through its new name.
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Table, TableLike, Value};
+
+/// The kinds of dependency tables in a manifest.
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DependencyKind {
+    /// `[dependencies]`
+    #[default]
+    Normal,
+
+    /// `[dev-dependencies]`
+    Dev,
+
+    /// `[build-dependencies]`
+    Build,
+}
+
+impl DependencyKind {
+    fn name(&self) -> &'static str {
+        match self {
+            DependencyKind::Normal => "dependencies",
+            DependencyKind::Dev => "dev-dependencies",
+            DependencyKind::Build => "build-dependencies",
+        }
+    }
+}
+
+/// A dependency table of a manifest which can be edited.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct DependencyTable {
+    /// The kind of dependencies in the table.
+    pub(crate) kind: DependencyKind,
+
+    /// The platform of a `[target.'<target>'.dependencies]` table, either a target triple or a
+    /// `cfg(...)` expression.
+    pub(crate) target: Option<String>,
+}
+
+impl DependencyTable {
+    /// The path of the table's keys, e.g. `["target", "cfg(unix)", "dependencies"]`.
+    fn path(&self) -> Vec<&str> {
+        match &self.target {
+            Some(target) => vec!["target", target, self.kind.name()],
+            None => vec![self.kind.name()],
+        }
+    }
+
+    /// Returns the table in `document`, creating it if `create` is set.
+    fn get<'a>(
+        &self,
+        document: &'a mut DocumentMut,
+        create: bool,
+    ) -> Option<&'a mut dyn TableLike> {
+        let path = self.path();
+        let mut table: &mut dyn TableLike = document.as_table_mut();
+        for (index, key) in path.iter().enumerate() {
+            if !table.contains_key(key) {
+                if !create {
+                    return None;
+                }
+                let mut new_table = Table::new();
+                // Parent tables like `[target]` are only written through their children.
+                new_table.set_implicit(index + 1 < path.len());
+                table.insert(key, Item::Table(new_table));
+            }
+            table = table.get_mut(key)?.as_table_like_mut()?;
+        }
+        Some(table)
+    }
+}
+
+impl std::fmt::Display for DependencyTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.target {
+            Some(target) => write!(
+                f,
+                "[target.{}.{}]",
+                Value::from(target.as_str()).to_string().trim(),
+                self.kind.name()
+            ),
+            None => write!(f, "[{}]", self.kind.name()),
+        }
+    }
+}
+
+/// A dependency to write to a manifest.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct DependencySpec {
+    /// The name of the package.
+    pub(crate) name: String,
+
+    /// The version requirement of the dependency. If unset, the version of an existing entry
+    /// is kept and new entries use `*`.
+    pub(crate) version: Option<String>,
+
+    /// Features to enable on the dependency, in addition to those of an existing entry.
+    pub(crate) features: Vec<String>,
+
+    /// Whether or not the default features of the dependency are enabled. Only `false` changes
+    /// an existing entry.
+    pub(crate) default_features: bool,
+}
+
+impl DependencySpec {
+    /// Render the dependency as the value of a new entry.
+    fn to_value(&self) -> Value {
+        let version = self.version.as_deref().unwrap_or("*");
+        if self.features.is_empty() && self.default_features {
+            return Value::from(version);
+        }
+
+        let mut fields = InlineTable::new();
+        fields.insert("version", Value::from(version));
+        if !self.features.is_empty() {
+            fields.insert("features", Value::Array(Array::from_iter(&self.features)));
+        }
+        if !self.default_features {
+            fields.insert("default-features", Value::from(false));
+        }
+        fields.fmt();
+        Value::InlineTable(fields)
+    }
+
+    /// Merge the dependency into the existing entry `item` the way `cargo add` does. The
+    /// version is replaced, features are added to the existing ones and all other keys
+    /// (e.g. `path`, `optional`, `package` or `workspace`) are kept.
+    fn merge_into(&self, item: &mut Item, table: &DependencyTable) -> Result<()> {
+        let cant_edit = |reason: &str| {
+            anyhow!(
+                "`{}` in `{}` {}. Please update it manually.",
+                self.name,
+                table,
+                reason
+            )
+        };
+
+        // A plain version requirement only needs to become a table if other keys are set.
+        if let Some(version) = item.as_value_mut().filter(|value| value.is_str()) {
+            if self.features.is_empty() && self.default_features {
+                if let Some(new_version) = &self.version {
+                    replace_value(version, Value::from(new_version.as_str()));
+                }
+                return Ok(());
+            }
+            let mut fields = InlineTable::new();
+            fields.insert("version", Value::from(version.as_str().unwrap_or_default()));
+            fields.fmt();
+            replace_value(version, Value::InlineTable(fields));
+        }
+
+        let fields = item
+            .as_table_like_mut()
+            .ok_or_else(|| cant_edit("isn't a version or a table"))?;
+
+        if let Some(version) = &self.version {
+            if fields
+                .get("workspace")
+                .and_then(Item::as_bool)
+                .unwrap_or(false)
+            {
+                return Err(cant_edit(
+                    "is inherited from the workspace so its version can only be changed in `[workspace.dependencies]`",
+                ));
+            }
+            set_field(fields, "version", Value::from(version.as_str()));
+        }
+
+        if !self.features.is_empty() {
+            if !fields.contains_key("features") {
+                set_field(fields, "features", Value::Array(Array::new()));
+            }
+            let features = fields
+                .get_mut("features")
+                .and_then(Item::as_array_mut)
+                .ok_or_else(|| cant_edit("has `features` which aren't an array"))?;
+            // Arrays with one feature per line stay that way.
+            let multi_line_decor = features
+                .iter()
+                .last()
+                .map(|value| value.decor().clone())
+                .filter(|decor| {
+                    decor
+                        .prefix()
+                        .and_then(|prefix| prefix.as_str())
+                        .is_some_and(|prefix| prefix.contains('\n'))
+                });
+            for feature in &self.features {
+                if !features.iter().any(|value| value.as_str() == Some(feature)) {
+                    features.push(feature.as_str());
+                    if let (Some(decor), Some(value)) =
+                        (&multi_line_decor, features.iter_mut().last())
+                    {
+                        *value.decor_mut() = decor.clone();
+                    }
+                }
+            }
+        }
+
+        if !self.default_features {
+            let name = if fields.contains_key("default_features") {
+                "default_features"
+            } else {
+                "default-features"
+            };
+            set_field(fields, name, Value::from(false));
+        }
+
+        // Keys appended to inline tables aren't separated from their neighbours.
+        if let Some(fields) = item.as_inline_table_mut() {
+            fields.fmt();
+        }
+
+        Ok(())
+    }
+}
+
+/// Replace `value` with `new_value`, keeping the whitespace and comments around it.
+fn replace_value(value: &mut Value, new_value: Value) {
+    let decor = value.decor().clone();
+    *value = new_value;
+    *value.decor_mut() = decor;
+}
+
+/// Set the field `name` of a dependency entry, keeping its position and formatting if it exists.
+fn set_field(fields: &mut dyn TableLike, name: &str, new_value: Value) {
+    match fields.get_mut(name).and_then(Item::as_value_mut) {
+        Some(value) => replace_value(value, new_value),
+        None => {
+            fields.insert(name, Item::Value(new_value));
+        }
+    }
+}
+
+/// Whether the entries of a table are in alphabetical order.
+fn is_sorted(table: &dyn TableLike) -> bool {
+    let keys: Vec<&str> = table.iter().map(|(key, _)| key).collect();
+    keys.windows(2).all(|pair| pair[0] <= pair[1])
+}
+
+fn parse(content: &str) -> Result<DocumentMut> {
+    content
+        .parse::<DocumentMut>()
+        .context("Failed to parse the manifest")
+}
+
+/// Add `dependency` to `table` in the manifest `content`. An existing entry is merged with the
+/// dependency like `cargo add` does (see [DependencySpec::merge_into]).
+///
+/// New entries are inserted in alphabetical order if the table is already sorted and
+/// appended otherwise.
+pub(crate) fn add_dependency(
+    content: &str,
+    table: &DependencyTable,
+    dependency: &DependencySpec,
+) -> Result<String> {
+    let mut document = parse(content)?;
+    let dependencies = table
+        .get(&mut document, true)
+        .ok_or_else(|| anyhow!("`{}` in the manifest isn't a table", table))?;
+
+    match dependencies.get_mut(&dependency.name) {
+        Some(item) => dependency.merge_into(item, table)?,
+        None => {
+            let was_sorted = is_sorted(dependencies);
+            dependencies.insert(&dependency.name, Item::Value(dependency.to_value()));
+            if was_sorted {
+                dependencies.sort_values();
+            }
+        }
+    }
+
+    Ok(document.to_string())
+}
+
+/// Whether `table` in the manifest `content` has an entry for `name`.
+pub(crate) fn has_dependency(content: &str, table: &DependencyTable, name: &str) -> bool {
+    parse(content).is_ok_and(|mut document| {
+        table
+            .get(&mut document, false)
+            .is_some_and(|dependencies| dependencies.contains_key(name))
+    })
+}
+
+/// Remove the dependency `name` from `table` in the manifest `content`.
+pub(crate) fn remove_dependency(
+    content: &str,
+    table: &DependencyTable,
+    name: &str,
+) -> Result<String> {
+    let mut document = parse(content)?;
+    table
+        .get(&mut document, false)
+        .and_then(|dependencies| dependencies.remove(name))
+        .ok_or_else(|| anyhow!("`{}` is not a dependency in `{}`", name, table))?;
+    Ok(document.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use indoc::indoc;
+
+    fn spec(name: &str, version: &str) -> DependencySpec {
+        DependencySpec {
+            name: name.to_owned(),
+            version: Some(version.to_owned()),
+            features: Vec::new(),
+            default_features: true,
+        }
+    }
+
+    fn table(kind: DependencyKind) -> DependencyTable {
+        DependencyTable { kind, target: None }
+    }
+
+    const MANIFEST: &str = indoc! {r#"
+        [package]
+        name = "pkg"
+        version = "0.1.0"
+
+        # Runtime dependencies
+        [dependencies]
+        anyhow = "1.0" # errors
+        serde = { version = "1.0", features = ["derive"] }
+
+        [dev-dependencies]
+        tempfile = "3"
+    "#};
+
+    #[test]
+    fn add_dependency_sorted() {
+        let content = add_dependency(
+            MANIFEST,
+            &table(DependencyKind::Normal),
+            &spec("log", "0.4"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            content,
+            indoc! {r#"
+                [package]
+                name = "pkg"
+                version = "0.1.0"
+
+                # Runtime dependencies
+                [dependencies]
+                anyhow = "1.0" # errors
+                log = "0.4"
+                serde = { version = "1.0", features = ["derive"] }
+
+                [dev-dependencies]
+                tempfile = "3"
+            "#}
+        );
+    }
+
+    #[test]
+    fn add_dependency_unsorted() {
+        let manifest = indoc! {r#"
+            [dependencies]
+            serde = "1.0"
+            anyhow = "1.0"
+        "#};
+
+        assert_eq!(
+            add_dependency(
+                manifest,
+                &table(DependencyKind::Normal),
+                &spec("log", "0.4")
+            )
+            .unwrap(),
+            indoc! {r#"
+                [dependencies]
+                serde = "1.0"
+                anyhow = "1.0"
+                log = "0.4"
+            "#}
+        );
+    }
+
+    #[test]
+    fn add_dependency_merges_existing() {
+        let dependency = DependencySpec {
+            features: vec!["std".to_owned(), "derive".to_owned()],
+            default_features: false,
+            ..spec("serde", "1.0.219")
+        };
+        let content =
+            add_dependency(MANIFEST, &table(DependencyKind::Normal), &dependency).unwrap();
+
+        assert!(content.contains(
+            "\nserde = { version = \"1.0.219\", features = [\"derive\", \"std\"], default-features = false }\n"
+        ));
+
+        // Without a version, the existing one is kept.
+        let dependency = DependencySpec {
+            version: None,
+            ..spec("anyhow", "")
+        };
+        assert_eq!(
+            add_dependency(MANIFEST, &table(DependencyKind::Normal), &dependency).unwrap(),
+            MANIFEST
+        );
+
+        let content = add_dependency(
+            MANIFEST,
+            &table(DependencyKind::Normal),
+            &spec("anyhow", "1.0.98"),
+        )
+        .unwrap();
+        assert!(content.contains("\nanyhow = \"1.0.98\" # errors\n"));
+
+        // Plain versions become tables when features are added.
+        let dependency = DependencySpec {
+            features: vec!["backtrace".to_owned()],
+            ..spec("anyhow", "1.0.98")
+        };
+        let content =
+            add_dependency(MANIFEST, &table(DependencyKind::Normal), &dependency).unwrap();
+        assert!(content.contains(
+            "\nanyhow = { version = \"1.0.98\", features = [\"backtrace\"] } # errors\n"
+        ));
+    }
+
+    #[test]
+    fn add_dependency_preserves_existing_keys() {
+        let manifest = indoc! {r#"
+            [dependencies]
+            local = { path = "../local", optional = true } # local
+            renamed = { package = "real", version = '0.1', features = ['a'] }
+            shared = { workspace = true }
+        "#};
+        let with_features = |name: &str, version: Option<&str>| DependencySpec {
+            version: version.map(str::to_owned),
+            features: vec!["b".to_owned()],
+            ..spec(name, "")
+        };
+        let normal = table(DependencyKind::Normal);
+
+        let content = add_dependency(manifest, &normal, &with_features("local", None)).unwrap();
+        assert!(content.contains(
+            "\nlocal = { path = \"../local\", optional = true, features = [\"b\"] } # local\n"
+        ));
+
+        let content =
+            add_dependency(manifest, &normal, &with_features("renamed", Some("0.2"))).unwrap();
+        assert!(content.contains(
+            "\nrenamed = { package = \"real\", version = \"0.2\", features = ['a', \"b\"] }\n"
+        ));
+
+        let content = add_dependency(manifest, &normal, &with_features("shared", None)).unwrap();
+        assert!(content.contains("\nshared = { workspace = true, features = [\"b\"] }\n"));
+
+        // The version of workspace dependencies can't be changed here.
+        assert!(add_dependency(manifest, &normal, &spec("shared", "1")).is_err());
+    }
+
+    #[test]
+    fn add_dependency_with_literal_strings_and_target_tables() {
+        let manifest = indoc! {r##"
+            [dependencies]
+            odd = { version = '1', features = ['a#b', "c\"#"] } # note
+
+            [target.'cfg(unix)'.dependencies]
+            libc = "0.2"
+        "##};
+        let normal = table(DependencyKind::Normal);
+        let unix = DependencyTable {
+            kind: DependencyKind::Normal,
+            target: Some("cfg(unix)".to_owned()),
+        };
+
+        let content = add_dependency(manifest, &normal, &spec("libc", "0.2.172")).unwrap();
+        assert_eq!(
+            content,
+            indoc! {r##"
+                [dependencies]
+                libc = "0.2.172"
+                odd = { version = '1', features = ['a#b', "c\"#"] } # note
+
+                [target.'cfg(unix)'.dependencies]
+                libc = "0.2"
+            "##}
+        );
+
+        let content = add_dependency(manifest, &normal, &spec("odd", "2")).unwrap();
+        assert!(content
+            .contains("\nodd = { version = \"2\", features = ['a#b', \"c\\\"#\"] } # note\n"));
+
+        assert!(has_dependency(manifest, &normal, "odd"));
+        assert!(!has_dependency(manifest, &normal, "libc"));
+        assert!(remove_dependency(manifest, &normal, "libc").is_err());
+
+        // Platform specific tables are edited when selected.
+        assert!(has_dependency(manifest, &unix, "libc"));
+        let content = add_dependency(manifest, &unix, &spec("libc", "0.2.172")).unwrap();
+        assert!(content.ends_with("[target.'cfg(unix)'.dependencies]\nlibc = \"0.2.172\"\n"));
+        assert_eq!(
+            remove_dependency(manifest, &unix, "libc").unwrap(),
+            indoc! {r##"
+                [dependencies]
+                odd = { version = '1', features = ['a#b', "c\"#"] } # note
+
+                [target.'cfg(unix)'.dependencies]
+            "##}
+        );
+
+        let windows = DependencyTable {
+            kind: DependencyKind::Dev,
+            target: Some("cfg(windows)".to_owned()),
+        };
+        let content = add_dependency(manifest, &windows, &spec("winapi", "0.3")).unwrap();
+        assert!(content.ends_with(
+            "libc = \"0.2\"\n\n[target.\"cfg(windows)\".dev-dependencies]\nwinapi = \"0.3\"\n"
+        ));
+    }
+
+    #[test]
+    fn add_dependency_to_new_table() {
+        let content =
+            add_dependency(MANIFEST, &table(DependencyKind::Build), &spec("cc", "1")).unwrap();
+
+        assert!(content.ends_with("tempfile = \"3\"\n\n[build-dependencies]\ncc = \"1\"\n"));
+    }
+
+    #[test]
+    fn add_dependency_declared_as_table() {
+        let manifest = indoc! {r#"
+            [dependencies]
+            anyhow = "1.0"
+
+            [dependencies.serde]
+            version = "1.0" # serialization
+            features = ["derive"]
+        "#};
+        let dependency = DependencySpec {
+            features: vec!["rc".to_owned()],
+            ..spec("serde", "1.0.219")
+        };
+
+        assert_eq!(
+            add_dependency(manifest, &table(DependencyKind::Normal), &dependency).unwrap(),
+            indoc! {r#"
+                [dependencies]
+                anyhow = "1.0"
+
+                [dependencies.serde]
+                version = "1.0.219" # serialization
+                features = ["derive", "rc"]
+            "#}
+        );
+    }
+
+    #[test]
+    fn add_dependency_spanning_multiple_lines() {
+        let manifest = indoc! {r#"
+            [dependencies]
+            serde = { version = "1.0", features = [
+                "derive",
+                "rc",
+            ] }
+            tokio = "1"
+        "#};
+        let dependency = DependencySpec {
+            features: vec!["std".to_owned()],
+            ..spec("serde", "1.0.219")
+        };
+
+        assert_eq!(
+            add_dependency(manifest, &table(DependencyKind::Normal), &dependency).unwrap(),
+            indoc! {r#"
+                [dependencies]
+                serde = { version = "1.0.219", features = [
+                    "derive",
+                    "rc",
+                    "std",
+                ] }
+                tokio = "1"
+            "#}
+        );
+
+        assert_eq!(
+            remove_dependency(manifest, &table(DependencyKind::Normal), "serde").unwrap(),
+            "[dependencies]\ntokio = \"1\"\n"
+        );
+    }
+
+    #[test]
+    fn remove_dependencies() {
+        let normal = table(DependencyKind::Normal);
+        let content = remove_dependency(MANIFEST, &normal, "anyhow").unwrap();
+        assert!(!content.contains("anyhow"));
+        assert!(content.contains("serde = "));
+
+        let content = remove_dependency(MANIFEST, &table(DependencyKind::Dev), "tempfile").unwrap();
+        assert!(content.contains("[dev-dependencies]\n"));
+        assert!(!content.contains("tempfile"));
+
+        assert!(remove_dependency(MANIFEST, &table(DependencyKind::Build), "anyhow").is_err());
+        assert_eq!(
+            remove_dependency(MANIFEST, &normal, "tempfile")
+                .unwrap_err()
+                .to_string(),
+            "`tempfile` is not a dependency in `[dependencies]`"
+        );
+    }
+
+    #[test]
+    fn remove_dependency_table() {
+        let manifest = indoc! {r#"
+            [dependencies]
+            anyhow = "1.0"
+
+            [dependencies.serde]
+            version = "1.0"
+            features = ["derive"]
+
+            [features]
+            default = []
+        "#};
+
+        assert_eq!(
+            remove_dependency(manifest, &table(DependencyKind::Normal), "serde").unwrap(),
+            indoc! {r#"
+                [dependencies]
+                anyhow = "1.0"
+
+                [features]
+                default = []
+            "#}
+        );
+    }
+}
@@ -305,3 +305,186 @@ pub(crate) mod lockfile {
         .unwrap()
     }
 }
+
+/// Builders of cargo-bazel lockfiles, for tests of the APIs reading them.
+pub(crate) mod cargo_bazel_lockfile {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use semver::Version;
+
+    use crate::api::lockfile::CargoBazelLockfileImpl;
+    use crate::config::CrateId;
+    use crate::context::crate_context::{CrateContext, CrateDependency, Rule, TargetAttributes};
+    use crate::context::{CommonAttributes, Context};
+    use crate::metadata::SourceAnnotation;
+    use crate::utils::starlark::Glob;
+    use crate::utils::target_triple::TargetTriple;
+
+    pub(crate) fn crate_id(name: &str, version: &str) -> CrateId {
+        CrateId::new(name.to_owned(), Version::parse(version).unwrap())
+    }
+
+    pub(crate) fn dependency(name: &str, version: &str) -> CrateDependency {
+        CrateDependency {
+            id: crate_id(name, version),
+            target: name.replace('-', "_"),
+            alias: None,
+            local_path: None,
+        }
+    }
+
+    pub(crate) fn target(crate_name: &str) -> TargetAttributes {
+        TargetAttributes {
+            crate_name: crate_name.to_owned(),
+            crate_root: None,
+            srcs: Glob::default(),
+        }
+    }
+
+    /// A builder of [CrateContext]s. Crates are libraries from crates.io unless
+    /// configured otherwise.
+    pub(crate) struct MockCrate(CrateContext);
+
+    impl MockCrate {
+        pub(crate) fn new(name: &str, version: &str) -> Self {
+            let crate_name = name.replace('-', "_");
+            Self(CrateContext {
+                name: name.to_owned(),
+                version: Version::parse(version).unwrap(),
+                package_url: None,
+                repository: Some(SourceAnnotation::Http {
+                    url: format!("https://static.crates.io/crates/{name}/{name}-{version}.crate"),
+                    sha256: None,
+                    patch_args: None,
+                    patch_tool: None,
+                    patches: None,
+                }),
+                targets: BTreeSet::from([Rule::Library(target(&crate_name))]),
+                library_target_name: Some(crate_name),
+                common_attrs: CommonAttributes::default(),
+                build_script_attrs: None,
+                license: None,
+                license_ids: BTreeSet::new(),
+                license_file: None,
+                additive_build_file_content: None,
+                disable_pipelining: false,
+                extra_aliased_targets: BTreeMap::new(),
+                alias_rule: None,
+                override_targets: BTreeMap::new(),
+            })
+        }
+
+        /// Makes the crate a workspace member, which has no repository.
+        pub(crate) fn workspace_member(mut self) -> Self {
+            self.0.repository = None;
+            self
+        }
+
+        pub(crate) fn repository(mut self, repository: SourceAnnotation) -> Self {
+            self.0.repository = Some(repository);
+            self
+        }
+
+        /// Sets the sha256 of the crates.io download.
+        pub(crate) fn sha256(mut self, checksum: &str) -> Self {
+            if let Some(SourceAnnotation::Http { sha256, .. }) = &mut self.0.repository {
+                *sha256 = Some(checksum.to_owned());
+            }
+            self
+        }
+
+        pub(crate) fn package_url(mut self, url: &str) -> Self {
+            self.0.package_url = Some(url.to_owned());
+            self
+        }
+
+        pub(crate) fn license(mut self, license: &str, license_ids: &[&str]) -> Self {
+            self.0.license = Some(license.to_owned());
+            self.0.license_ids = license_ids.iter().map(|id| id.to_string()).collect();
+            self
+        }
+
+        /// Replaces the default library target.
+        pub(crate) fn targets(mut self, targets: impl IntoIterator<Item = Rule>) -> Self {
+            self.0.targets = targets.into_iter().collect();
+            self.0.library_target_name = self.0.targets.iter().find_map(|rule| match rule {
+                Rule::Library(attrs) => Some(attrs.crate_name.clone()),
+                _ => None,
+            });
+            self
+        }
+
+        pub(crate) fn build_script(mut self) -> Self {
+            self.0
+                .targets
+                .insert(Rule::BuildScript(target("build_script_build")));
+            self
+        }
+
+        /// Adds a normal dependency, which only applies to `configuration` if set.
+        pub(crate) fn dep(
+            mut self,
+            configuration: Option<&str>,
+            name: &str,
+            version: &str,
+        ) -> Self {
+            self.0
+                .common_attrs
+                .deps
+                .insert(dependency(name, version), configuration.map(str::to_owned));
+            self
+        }
+
+        /// Enables a feature, which only applies to `configuration` if set.
+        pub(crate) fn feature(mut self, configuration: Option<&str>, feature: &str) -> Self {
+            self.0
+                .common_attrs
+                .crate_features
+                .insert(feature.to_owned(), configuration.map(str::to_owned));
+            self
+        }
+
+        pub(crate) fn build(self) -> CrateContext {
+            self.0
+        }
+    }
+
+    /// A builder of lockfiles, in which crates without a repository are workspace members.
+    pub(crate) struct MockLockfile(Context);
+
+    impl MockLockfile {
+        pub(crate) fn new(crates: impl IntoIterator<Item = CrateContext>) -> Self {
+            let mut context = Context::default();
+            for crate_context in crates {
+                let id = CrateId::new(crate_context.name.clone(), crate_context.version.clone());
+                if crate_context.repository.is_none() {
+                    context
+                        .workspace_members
+                        .insert(id.clone(), crate_context.name.clone());
+                }
+                context.crates.insert(id, crate_context);
+            }
+            Self(context)
+        }
+
+        /// Adds a configuration, e.g. `cfg(unix)`, which applies to `triples`.
+        pub(crate) fn condition(mut self, condition: &str, triples: &[&str]) -> Self {
+            self.0.conditions.insert(
+                condition.to_owned(),
+                triples
+                    .iter()
+                    .map(|triple| TargetTriple::from_bazel(triple.to_string()))
+                    .collect(),
+            );
+            self
+        }
+
+        pub(crate) fn context(self) -> Context {
+            self.0
+        }
+
+        pub(crate) fn build(self) -> CargoBazelLockfileImpl {
+            CargoBazelLockfileImpl(self.0)
+        }
+    }
+}
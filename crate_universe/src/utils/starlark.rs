@@ -42,6 +42,12 @@ pub(crate) enum Starlark {
     RustLibrary(RustLibrary),
     #[serde(serialize_with = "serialize::rust_binary")]
     RustBinary(RustBinary),
+    #[serde(serialize_with = "serialize::rust_shared_library")]
+    RustSharedLibrary(RustSharedLibrary),
+    #[serde(serialize_with = "serialize::rust_static_library")]
+    RustStaticLibrary(RustStaticLibrary),
+    #[serde(serialize_with = "serialize::rust_test")]
+    RustTest(RustTest),
 
     #[serde(skip_serializing)]
     Verbatim(String),
@@ -191,6 +197,45 @@ pub(crate) struct RustBinary {
     pub(crate) common: CommonAttrs,
 }
 
+#[derive(Serialize)]
+pub(crate) struct RustSharedLibrary {
+    pub(crate) name: String,
+    #[serde(skip_serializing_if = "SelectSet::is_empty")]
+    pub(crate) deps: SelectSet<Label>,
+    #[serde(skip_serializing_if = "SelectSet::is_empty")]
+    pub(crate) proc_macro_deps: SelectSet<Label>,
+    #[serde(skip_serializing_if = "SelectDict::is_empty")]
+    pub(crate) aliases: SelectDict<Label, String>,
+    #[serde(flatten)]
+    pub(crate) common: CommonAttrs,
+}
+
+#[derive(Serialize)]
+pub(crate) struct RustStaticLibrary {
+    pub(crate) name: String,
+    #[serde(skip_serializing_if = "SelectSet::is_empty")]
+    pub(crate) deps: SelectSet<Label>,
+    #[serde(skip_serializing_if = "SelectSet::is_empty")]
+    pub(crate) proc_macro_deps: SelectSet<Label>,
+    #[serde(skip_serializing_if = "SelectDict::is_empty")]
+    pub(crate) aliases: SelectDict<Label, String>,
+    #[serde(flatten)]
+    pub(crate) common: CommonAttrs,
+}
+
+#[derive(Serialize)]
+pub(crate) struct RustTest {
+    pub(crate) name: String,
+    #[serde(skip_serializing_if = "SelectSet::is_empty")]
+    pub(crate) deps: SelectSet<Label>,
+    #[serde(skip_serializing_if = "SelectSet::is_empty")]
+    pub(crate) proc_macro_deps: SelectSet<Label>,
+    #[serde(skip_serializing_if = "SelectDict::is_empty")]
+    pub(crate) aliases: SelectDict<Label, String>,
+    #[serde(flatten)]
+    pub(crate) common: CommonAttrs,
+}
+
 #[derive(Serialize)]
 pub(crate) struct CommonAttrs {
     #[serde(skip_serializing_if = "Data::is_empty")]
@@ -0,0 +1,252 @@
+//! Support for [custom target specifications](https://doc.rust-lang.org/rustc/targets/custom.html).
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use cfg_expr::targets::{
+    Abi, Arch, Endian, Env, Families, Family, HasAtomic, HasAtomics, Os, Panic, TargetInfo, Triple,
+    Vendor,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::target_triple::TargetTriple;
+
+/// The contents of a custom target specification file, as would be passed to
+/// `rustc --target <spec>.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct TargetSpec(serde_json::Value);
+
+/// The fields of a [TargetSpec] which determine the `cfg` values of a target.
+/// Defaults match those used by `rustc`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TargetSpecCfg {
+    arch: String,
+
+    #[serde(default = "TargetSpecCfg::default_os")]
+    os: String,
+
+    #[serde(default)]
+    env: String,
+
+    #[serde(default = "TargetSpecCfg::default_vendor")]
+    vendor: String,
+
+    #[serde(default)]
+    abi: String,
+
+    #[serde(default)]
+    target_family: OneOrMany,
+
+    #[serde(default = "TargetSpecCfg::default_endian")]
+    target_endian: String,
+
+    target_pointer_width: StringOrNumber,
+
+    #[serde(default)]
+    features: String,
+
+    #[serde(default)]
+    min_atomic_width: Option<u16>,
+
+    #[serde(default)]
+    max_atomic_width: Option<u16>,
+
+    #[serde(default = "TargetSpecCfg::default_panic_strategy")]
+    panic_strategy: String,
+}
+
+impl TargetSpecCfg {
+    fn default_os() -> String {
+        "none".to_owned()
+    }
+
+    fn default_vendor() -> String {
+        "unknown".to_owned()
+    }
+
+    fn default_endian() -> String {
+        "little".to_owned()
+    }
+
+    fn default_panic_strategy() -> String {
+        "unwind".to_owned()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    #[default]
+    None,
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Older specifications use strings for numeric values.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StringOrNumber {
+    String(String),
+    Number(u16),
+}
+
+impl TargetSpec {
+    pub(crate) fn try_from_path<T: AsRef<Path>>(path: T) -> Result<Self> {
+        let data = fs::read_to_string(path.as_ref()).with_context(|| {
+            format!(
+                "Failed to read target specification {}",
+                path.as_ref().display()
+            )
+        })?;
+        serde_json::from_str(&data).with_context(|| {
+            format!(
+                "Failed to parse target specification {}",
+                path.as_ref().display()
+            )
+        })
+    }
+
+    /// Compute the `cfg` values of the target named `triple`, along with the
+    /// names of the target features it enables.
+    pub(crate) fn target_info(
+        &self,
+        triple: &TargetTriple,
+    ) -> Result<(TargetInfo, BTreeSet<String>)> {
+        let cfg = TargetSpecCfg::deserialize(&self.0)
+            .with_context(|| format!("Failed to parse target specification for {triple}"))?;
+
+        let pointer_width = match &cfg.target_pointer_width {
+            StringOrNumber::String(width) => width.parse::<u8>().ok(),
+            StringOrNumber::Number(width) => u8::try_from(*width).ok(),
+        }
+        .ok_or_else(|| {
+            anyhow!("Invalid `target-pointer-width` in target specification for {triple}")
+        })?;
+
+        let endian = match cfg.target_endian.as_str() {
+            "big" => Endian::big,
+            "little" => Endian::little,
+            other => {
+                return Err(anyhow!(
+                    "Invalid `target-endian` in target specification for {triple}: {other}"
+                ))
+            }
+        };
+
+        let families = match cfg.target_family {
+            OneOrMany::None => Vec::new(),
+            OneOrMany::One(family) => vec![family],
+            OneOrMany::Many(families) => families,
+        };
+
+        // Like rustc, atomics are supported for every integer size between the
+        // minimum and maximum widths, which default to 8 and the pointer width.
+        let min_atomic_width = cfg.min_atomic_width.unwrap_or(8);
+        let max_atomic_width = cfg.max_atomic_width.unwrap_or(pointer_width.into());
+        let mut has_atomics: Vec<HasAtomic> = [8, 16, 32, 64, 128]
+            .into_iter()
+            .filter(|width| (min_atomic_width..=max_atomic_width).contains(width))
+            .map(HasAtomic::IntegerSize)
+            .collect();
+        if max_atomic_width >= u16::from(pointer_width) {
+            has_atomics.push(HasAtomic::Pointer);
+        }
+
+        let features = cfg
+            .features
+            .split(',')
+            .filter_map(|feature| feature.strip_prefix('+'))
+            .map(str::to_owned)
+            .collect();
+
+        let non_empty = |value: String| (!value.is_empty()).then_some(Cow::Owned(value));
+
+        Ok((
+            TargetInfo {
+                triple: Triple(Cow::Owned(triple.to_cargo())),
+                os: (cfg.os != "none").then_some(Os(Cow::Owned(cfg.os))),
+                abi: non_empty(cfg.abi).map(Abi),
+                arch: Arch(Cow::Owned(cfg.arch)),
+                env: non_empty(cfg.env).map(Env),
+                vendor: Some(Vendor(Cow::Owned(cfg.vendor))),
+                families: Families::new(families.into_iter().map(|f| Family(Cow::Owned(f)))),
+                pointer_width,
+                endian,
+                has_atomics: HasAtomics::new(has_atomics),
+                panic: Panic(Cow::Owned(cfg.panic_strategy)),
+            },
+            features,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use cfg_expr::{Expression, Predicate};
+
+    fn eval(cfg: &str, info: &TargetInfo, features: &BTreeSet<String>) -> bool {
+        Expression::parse(cfg).unwrap().eval(|p| match p {
+            Predicate::Target(tp) => tp.matches(info),
+            Predicate::TargetFeature(feature) => features.contains(*feature),
+            _ => false,
+        })
+    }
+
+    #[test]
+    fn target_info_from_spec() {
+        let spec: TargetSpec = serde_json::from_str(
+            r#"{
+                "arch": "arm",
+                "abi": "eabihf",
+                "data-layout": "e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64",
+                "features": "+vfp4d16sp,-fp64",
+                "llvm-target": "thumbv7em-none-eabihf",
+                "max-atomic-width": 32,
+                "panic-strategy": "abort",
+                "target-pointer-width": "32"
+            }"#,
+        )
+        .unwrap();
+
+        let (info, features) = spec
+            .target_info(&TargetTriple::from_bazel("thumbv7em-acme-none".to_owned()))
+            .unwrap();
+        assert_eq!(info.triple.as_str(), "thumbv7em-acme-none");
+        assert_eq!(features, BTreeSet::from(["vfp4d16sp".to_owned()]));
+
+        for cfg in [
+            r#"cfg(target_arch = "arm")"#,
+            r#"cfg(target_os = "none")"#,
+            r#"cfg(target_abi = "eabihf")"#,
+            r#"cfg(target_pointer_width = "32")"#,
+            r#"cfg(target_has_atomic = "ptr")"#,
+            r#"cfg(target_feature = "vfp4d16sp")"#,
+            r#"cfg(panic = "abort")"#,
+            r#"cfg(not(unix))"#,
+        ] {
+            assert!(eval(cfg, &info, &features), "{cfg}");
+        }
+        for cfg in [
+            r#"cfg(target_has_atomic = "64")"#,
+            r#"cfg(target_feature = "fp64")"#,
+            r#"cfg(target_os = "linux")"#,
+        ] {
+            assert!(!eval(cfg, &info, &features), "{cfg}");
+        }
+    }
+
+    #[test]
+    fn target_info_requires_pointer_width() {
+        let spec: TargetSpec = serde_json::from_str(r#"{"arch": "riscv32"}"#).unwrap();
+        assert!(spec
+            .target_info(&TargetTriple::from_bazel("riscv32-acme-none".to_owned()))
+            .is_err());
+    }
+}
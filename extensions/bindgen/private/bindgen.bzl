@@ -178,6 +178,8 @@ def _generate_cc_link_build_info(ctx, cc_lib):
     )
 
     return BuildInfo(
+        bin_linker_flags = None,
+        cdylib_linker_flags = None,
         compile_data = depset(compile_data),
         dep_env = None,
         flags = rustc_flags_file,
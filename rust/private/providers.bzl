@@ -79,6 +79,8 @@ CrateGroupInfo = provider(
 BuildInfo = provider(
     doc = "A provider containing `rustc` build settings for a given Crate.",
     fields = {
+        "bin_linker_flags": "Optional[File]: file containing `KEY=FLAG` lines of flags to pass to the linker when linking binaries, keyed by the binary's crate name or `*` for all binaries",
+        "cdylib_linker_flags": "Optional[File]: file containing flags to pass to the linker when linking a cdylib",
         "compile_data": "Depset[File]: Compile data provided by the build script that was not copied into `out_dir`.",
         "dep_env": "Optional[File]: extra build script environment variables to be set to direct dependencies.",
         "flags": "Optional[File]: file containing additional flags to pass to rustc",
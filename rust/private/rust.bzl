@@ -40,6 +40,7 @@ load(
     ":utils.bzl",
     "can_build_metadata",
     "can_use_metadata_for_pipelining",
+    "can_use_worker_pipelining",
     "compute_crate_name",
     "crate_root_src",
     "dedent",
@@ -172,13 +173,21 @@ def _rust_library_common(ctx, crate_type):
         crate_type,
         disable_pipelining = getattr(ctx.attr, "disable_pipelining", False),
     ):
-        # The hollow rlib uses .rlib extension (not .rmeta) so rustc reads it as an
-        # rlib archive containing lib.rmeta with optimized MIR. It is placed in a
-        # "_hollow/" subdirectory so the full rlib and hollow rlib never appear in the
-        # same -Ldependency= search directory (which would cause E0463).
-        rust_metadata = ctx.actions.declare_file(
-            "_hollow/" + rust_lib_name[:-len(".rlib")] + "-hollow.rlib",
-        )
+        if can_use_worker_pipelining(toolchain, ctx, crate_type) and not ctx.attr.disable_pipelining:
+            # The metadata emitted by the rustc invocation shared with the full action.
+            # It is kept out of the rlib's directory for the same reason as the hollow
+            # rlib below.
+            rust_metadata = ctx.actions.declare_file(
+                "_pipelined/" + rust_lib_name[:-len(".rlib")] + ".rmeta",
+            )
+        else:
+            # The hollow rlib uses .rlib extension (not .rmeta) so rustc reads it as an
+            # rlib archive containing lib.rmeta with optimized MIR. It is placed in a
+            # "_hollow/" subdirectory so the full rlib and hollow rlib never appear in the
+            # same -Ldependency= search directory (which would cause E0463).
+            rust_metadata = ctx.actions.declare_file(
+                "_hollow/" + rust_lib_name[:-len(".rlib")] + "-hollow.rlib",
+            )
         rustc_rmeta_output = generate_output_diagnostics(ctx, rust_metadata)
 
         metadata_supports_pipelining = (
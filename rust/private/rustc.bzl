@@ -40,6 +40,7 @@ load(":rustc_resource_set.bzl", "get_rustc_resource_set", "is_codegen_units_enab
 load(":stamp.bzl", "is_stamping_enabled")
 load(
     ":utils.bzl",
+    "can_use_worker_pipelining",
     "expand_dict_value_locations",
     "expand_list_element_locations",
     "find_cc_toolchain",
@@ -881,6 +882,7 @@ def collect_inputs(
     build_script_compile_inputs, out_dir, build_env_file, build_flags_files = _process_build_scripts(
         build_info = build_info,
         dep_info = dep_info,
+        crate_type = crate_info.type,
         include_link_flags = include_link_flags,
         include_transitive_data = not toolchain._incompatible_do_not_include_transitive_data_in_compile_inputs,
     )
@@ -904,6 +906,14 @@ def _will_emit_object_file(emit):
             return True
     return False
 
+def _keyed_arg_file(file):
+    """Selects the build flag files which contain `KEY=FLAG` lines keyed by crate name."""
+    return file.path if file.extension == "bin_linkflags" else None
+
+def _arg_file(file):
+    """Selects the build flag files which contain plain flags."""
+    return None if file.extension == "bin_linkflags" else file.path
+
 def _remove_codegen_units(flag):
     return None if flag.startswith("-Ccodegen-units") else flag
 
@@ -938,7 +948,13 @@ def construct_arguments(
         skip_expanding_rustc_env = False,
         require_explicit_unstable_features = False,
         always_use_param_file = False,
-        error_format = None):
+        error_format = None,
+        use_worker = False,
+        worker_env_file = None,
+        pipelining_mode = None,
+        diagnostics_report = None,
+        profile_output = None,
+        link_args_output = None):
     """Builds an Args object containing common rustc flags
 
     Args:
@@ -972,6 +988,15 @@ def construct_arguments(
         skip_expanding_rustc_env (bool): Whether to skip expanding CrateInfo.rustc_env_attr
         require_explicit_unstable_features (bool): Whether to require all unstable features to be explicitly opted in to using `-Zallow-features=...`.
         error_format (str, optional): Error format to pass to the `--error-format` command line argument. If set to None, uses the "_error_format" entry in `attr`.
+        use_worker (bool, optional): Whether the action may run in a persistent process_wrapper worker.
+        worker_env_file (File, optional): A file containing the environment of the action, for use with `use_worker`.
+        pipelining_mode (str, optional): Which half ("metadata" or "full") of a worker pipelined compilation the
+            action is. Both halves must be built with otherwise identical arguments, and emit `metadata` and `link`.
+        diagnostics_report (File, optional): Where to write a report of the rustc diagnostics. Its extension selects
+            the format. This requires use_json_output.
+        profile_output (File, optional): Where to write a resource and timing profile of the action.
+        link_args_output (File, optional): Where to write the linker arguments of `build_flags_files` as a
+            response file, for outputs which are linked by a separate action.
 
     Returns:
         tuple: A tuple of the following items
@@ -985,6 +1010,8 @@ def construct_arguments(
     """
     if build_metadata and not use_json_output:
         fail("build_metadata requires parse_json_output")
+    if diagnostics_report and not use_json_output:
+        fail("diagnostics_report requires use_json_output")
 
     output_dir = getattr(crate_info.output, "dirname", None)
     linker_script = getattr(file, "linker_script", None)
@@ -994,14 +1021,24 @@ def construct_arguments(
     # Wrapper args first
     process_wrapper_flags = ctx.actions.args()
 
+    # Added before the build script environment files so they keep taking precedence.
+    if worker_env_file:
+        process_wrapper_flags.add("--env-file", worker_env_file)
+
     for build_env_file in build_env_files:
         process_wrapper_flags.add("--env-file", build_env_file)
 
-    process_wrapper_flags.add_all(build_flags_files, before_each = "--arg-file")
+    process_wrapper_flags.add_all(build_flags_files, before_each = "--arg-file", map_each = _arg_file)
+    if crate_info.type == "bin":
+        process_wrapper_flags.add_all(build_flags_files, before_each = "--keyed-arg-file", map_each = _keyed_arg_file)
+        process_wrapper_flags.add("--arg-file-key", crate_info.name)
 
     if require_explicit_unstable_features:
         process_wrapper_flags.add("--require-explicit-unstable-features", "true")
 
+    if pipelining_mode:
+        process_wrapper_flags.add("--pipelining-mode", pipelining_mode)
+
     # Certain rust build processes expect to find files from the environment
     # variable `$CARGO_MANIFEST_DIR`. Examples of this include pest, tera,
     # asakuma.
@@ -1043,7 +1080,7 @@ def construct_arguments(
     # Rustc arguments
     rustc_flags = ctx.actions.args()
     rustc_flags.set_param_file_format("multiline")
-    rustc_flags.use_param_file("@%s", use_always = always_use_param_file)
+    rustc_flags.use_param_file("@%s", use_always = always_use_param_file or use_worker)
     rustc_flags.add(crate_info.root)
     rustc_flags.add(crate_info.name, format = "--crate-name=%s")
     rustc_flags.add(crate_info.type, format = "--crate-type=%s")
@@ -1055,6 +1092,8 @@ def construct_arguments(
         # If --error-format was set to json, we just pass the output through
         # Otherwise process_wrapper uses the "rendered" field.
         process_wrapper_flags.add("--rustc-output-format", "json" if error_format == "json" else "rendered")
+        for remap in toolchain._rustc_diagnostic_path_remap:
+            process_wrapper_flags.add("--diagnostic-path-remap", remap.replace("{bin_dir}", ctx.bin_dir.path))
 
         # Configure rustc json output by adding artifact notifications.
         # These are filtered out by process_wrapper.
@@ -1083,6 +1122,16 @@ def construct_arguments(
     elif crate_info.rustc_output:
         process_wrapper_flags.add("--output-file", crate_info.rustc_output.path)
 
+    if diagnostics_report:
+        process_wrapper_flags.add("--diagnostics-output", diagnostics_report)
+        process_wrapper_flags.add("--diagnostics-format", diagnostics_report.extension)
+
+    if profile_output:
+        process_wrapper_flags.add("--profile-output", profile_output)
+
+    if link_args_output:
+        process_wrapper_flags.add("--link-args-output", link_args_output)
+
     rustc_flags.add(error_format, format = "--error-format=%s")
 
     # Mangle symbols to disambiguate crates with the same name. Used for
@@ -1117,6 +1166,11 @@ def construct_arguments(
             # E0786 "found invalid metadata files" because rustc parses .rmeta files
             # as raw metadata blobs, not rlib archives.
             rustc_flags.add(crate_info.metadata, format = "--emit=link=%s")
+        elif kind == "metadata" and pipelining_mode:
+            # The process wrapper moves each output into place for the action declaring it.
+            rustc_flags.add(crate_info.metadata, format = "--emit=metadata=%s")
+        elif kind == "link" and pipelining_mode:
+            rustc_flags.add(crate_info.output, format = "--emit=link=%s")
         elif kind == "link" and crate_info.type == "bin" and crate_info.output != None:
             rustc_flags.add(crate_info.output, format = "--emit=link=%s")
         else:
@@ -1265,6 +1319,13 @@ def construct_arguments(
     # Needed for bzlmod-aware runfiles resolution.
     env["REPOSITORY_NAME"] = ctx.label.workspace_name
 
+    # Persistent workers receive all of their per-request arguments through flag files.
+    # The process wrapper expands `--flagfile=` itself when it isn't run as a worker.
+    if use_worker:
+        for flag_file_args in (process_wrapper_flags, rustc_path):
+            flag_file_args.set_param_file_format("multiline")
+            flag_file_args.use_param_file("--flagfile=%s", use_always = True)
+
     # Create a struct which keeps the arguments separate so each may be tuned or
     # replaced where necessary
     args = struct(
@@ -1276,6 +1337,33 @@ def construct_arguments(
 
     return args, env
 
+def _write_worker_env(ctx, output, env):
+    """Moves the variables of `env` which are specific to an action into an `--env-file`.
+
+    Persistent workers are keyed by their environment, so only the default shell
+    environment shared by all actions and values which can't be written to an env
+    file (multi-line values or values ending in a backslash) are left in the action's
+    environment.
+
+    Args:
+        ctx (ctx): The rule's context object
+        output (File): The env file to write
+        env (dict): The environment of the action
+
+    Returns:
+        dict: The environment to set on the action
+    """
+    shell_env = ctx.configuration.default_shell_env
+    action_env = {}
+    lines = []
+    for key, value in env.items():
+        if shell_env.get(key) == value or "\n" in value or value.endswith("\\"):
+            action_env[key] = value
+        else:
+            lines.append("{}={}".format(key, value))
+    ctx.actions.write(output = output, content = "\n".join(lines) + "\n")
+    return action_env
+
 def collect_extra_rustc_flags(ctx, toolchain, crate_root, crate_type):
     """Gather all 'extra' rustc flags from the target's attributes and toolchain.
 
@@ -1366,7 +1454,18 @@ def rustc_compile_action(
     # pipelining is globally enabled — the hollow rlib is simpler than killing rustc.
     # Non-rlib types (bin, proc-macro, etc.) use --emit=dep-info,metadata instead
     # (rustc exits naturally after writing .rmeta, no process-wrapper kill needed).
-    use_hollow_rlib = bool(build_metadata) and crate_info.type in ("rlib", "lib")
+    use_worker = toolchain._experimental_process_wrapper_worker and bool(ctx.executable._process_wrapper)
+
+    # With worker pipelining the metadata and full actions instead run an identical rustc
+    # command, which a process_wrapper worker shares between them. Rules which declare a
+    # hollow rlib as their metadata keep using it.
+    use_worker_pipelining = (
+        use_worker and
+        bool(build_metadata) and
+        build_metadata.extension == "rmeta" and
+        can_use_worker_pipelining(toolchain, ctx, crate_info.type)
+    )
+    use_hollow_rlib = bool(build_metadata) and crate_info.type in ("rlib", "lib") and not use_worker_pipelining
 
     # Determine whether to use cc_common.link:
     #  * either if experimental_use_cc_common_link is 1,
@@ -1450,11 +1549,38 @@ def rustc_compile_action(
             experimental_use_cc_common_link = experimental_use_cc_common_link,
         )
 
+    worker_env_file = None
+    if use_worker:
+        worker_env_file = ctx.actions.declare_file(crate_info.output.basename + ".worker_env", sibling = crate_info.output)
+        compile_inputs = depset([worker_env_file], transitive = [compile_inputs])
+        compile_inputs_for_metadata = depset([worker_env_file], transitive = [compile_inputs_for_metadata])
+
+    # See the `rustc_diagnostics_report` setting.
+    diagnostics_report = None
+    if toolchain._rustc_diagnostics_report != "none" and ctx.executable._process_wrapper:
+        diagnostics_report = ctx.actions.declare_file(
+            crate_info.output.basename + ".diagnostics." + toolchain._rustc_diagnostics_report,
+            sibling = crate_info.output,
+        )
+
+    # See the `rustc_profile` setting.
+    profile_output = None
+    if toolchain._rustc_profile and ctx.executable._process_wrapper:
+        profile_output = ctx.actions.declare_file(crate_info.output.basename + ".profile.json", sibling = crate_info.output)
+
+    # rustc doesn't link when cc_common.link is used, so the `cargo::rustc-link-arg*`
+    # flags of the crate's build script are passed to the link action in a response file.
+    link_args_output = None
+    if experimental_use_cc_common_link and crate_info.type in ("cdylib", "bin") and build_info and ctx.executable._process_wrapper:
+        link_args_output = ctx.actions.declare_file(crate_info.output.basename + ".link_args", sibling = crate_info.output)
+
     # The main Rustc action emits dep-info and link (the full rlib/binary/cdylib).
     # When cc_common linking is enabled, emit a `.o` file instead.
     emit = ["dep-info", "link"]
     if experimental_use_cc_common_link:
         emit = ["obj"]
+    elif use_worker_pipelining:
+        emit = ["dep-info", "metadata", "link"]
 
     # Determine whether to pass `--require-explicit-unstable-features true` to the process wrapper:
     require_explicit_unstable_features = False
@@ -1486,17 +1612,54 @@ def rustc_compile_action(
         build_flags_files = build_flags_files,
         force_all_deps_direct = force_all_deps_direct,
         stamp = stamp,
-        use_json_output = bool(build_metadata) or bool(rustc_output) or bool(rustc_rmeta_output),
+        use_json_output = bool(build_metadata) or bool(rustc_output) or bool(rustc_rmeta_output) or bool(diagnostics_report) or bool(toolchain._rustc_diagnostic_path_remap),
         # Force full rlib --extern deps so the full rlib records full-rlib SVHs.
         force_depend_on_objects = use_hollow_rlib,
         experimental_use_cc_common_link = experimental_use_cc_common_link,
         skip_expanding_rustc_env = skip_expanding_rustc_env,
         require_explicit_unstable_features = require_explicit_unstable_features,
         always_use_param_file = not ctx.executable._process_wrapper,
+        use_worker = use_worker,
+        worker_env_file = worker_env_file,
+        pipelining_mode = "full" if use_worker_pipelining else None,
+        diagnostics_report = diagnostics_report,
+        profile_output = profile_output,
+        link_args_output = link_args_output,
     )
 
     args_metadata = None
-    if build_metadata:
+    if use_worker_pipelining:
+        # Identical to `args` apart from the pipelining mode.
+        args_metadata, _ = construct_arguments(
+            ctx = ctx,
+            attr = attr,
+            file = ctx.file,
+            toolchain = toolchain,
+            tool_path = toolchain.rustc.path,
+            cc_toolchain = cc_toolchain,
+            emit = emit,
+            feature_configuration = feature_configuration,
+            crate_info = crate_info,
+            dep_info = dep_info,
+            linkstamp_outs = linkstamp_outs,
+            ambiguous_libs = ambiguous_libs,
+            output_hash = output_hash,
+            rust_flags = rust_flags,
+            out_dir = out_dir,
+            build_env_files = build_env_files,
+            build_flags_files = build_flags_files,
+            force_all_deps_direct = force_all_deps_direct,
+            stamp = stamp,
+            use_json_output = True,
+            experimental_use_cc_common_link = experimental_use_cc_common_link,
+            skip_expanding_rustc_env = skip_expanding_rustc_env,
+            require_explicit_unstable_features = require_explicit_unstable_features,
+            always_use_param_file = not ctx.executable._process_wrapper,
+            use_worker = use_worker,
+            worker_env_file = worker_env_file,
+            pipelining_mode = "metadata",
+        )
+    elif build_metadata:
         if use_hollow_rlib:
             # Hollow rlib: emit dep-info and link (directed to the -hollow.rlib path via
             # -Zno-codegen). dep-info must be included: it affects the SVH stored in the
@@ -1530,6 +1693,8 @@ def rustc_compile_action(
             build_metadata = True,
             experimental_use_cc_common_link = experimental_use_cc_common_link,
             require_explicit_unstable_features = require_explicit_unstable_features,
+            use_worker = use_worker,
+            worker_env_file = worker_env_file,
         )
 
     env = dict(ctx.configuration.default_shell_env)
@@ -1544,6 +1709,18 @@ def rustc_compile_action(
         # This enables -Zno-codegen on stable Rust compilers for the metadata action.
         env["RUSTC_BOOTSTRAP"] = "1"
 
+    execution_requirements = {}
+    if use_worker:
+        env = _write_worker_env(ctx, worker_env_file, env)
+        execution_requirements = {
+            "requires-worker-protocol": "proto",
+            "supports-multiplex-workers": "1",
+            "supports-workers": "1",
+            # Run `RustcMetadata` actions in the same workers, so that worker pipelining can
+            # share a rustc invocation between the two halves.
+            "worker-key-mnemonic": "Rustc",
+        }
+
     if hasattr(attr, "version") and attr.version != "0.0.0":
         formatted_version = " v{}".format(attr.version)
     else:
@@ -1577,6 +1754,12 @@ def rustc_compile_action(
     action_outputs = list(outputs)
     if rustc_output:
         action_outputs.append(rustc_output)
+    if diagnostics_report:
+        action_outputs.append(diagnostics_report)
+    if profile_output:
+        action_outputs.append(profile_output)
+    if link_args_output:
+        action_outputs.append(link_args_output)
 
     # Get the compilation mode for the current target.
     compilation_mode = get_compilation_mode_opts(ctx, toolchain)
@@ -1601,6 +1784,7 @@ def rustc_compile_action(
             outputs = action_outputs,
             env = env,
             arguments = args.all,
+            execution_requirements = execution_requirements,
             mnemonic = "Rustc",
             progress_message = "Compiling Rust {} {}{} ({} file{})".format(
                 crate_info.type,
@@ -1619,6 +1803,7 @@ def rustc_compile_action(
                 outputs = [build_metadata] + [x for x in [rustc_rmeta_output] if x],
                 env = env,
                 arguments = args_metadata.all,
+                execution_requirements = execution_requirements,
                 mnemonic = "RustcMetadata",
                 progress_message = "Compiling Rust metadata {} {}{} ({} file{})".format(
                     crate_info.type,
@@ -1732,6 +1917,8 @@ def rustc_compile_action(
             main_output = crate_info.output,
             output_type = "executable" if crate_info.type == "bin" else "dynamic_library",
             additional_outputs = additional_linker_outputs,
+            user_link_flags = ["@" + link_args_output.path] if link_args_output else [],
+            additional_inputs = [link_args_output] if link_args_output else [],
         )
 
         outputs = [crate_info.output]
@@ -1838,6 +2025,10 @@ def rustc_compile_action(
             output_group_info["rustc_rmeta_output"] = depset([rustc_rmeta_output])
     if rustc_output:
         output_group_info["rustc_output"] = depset([rustc_output])
+    if diagnostics_report:
+        output_group_info["rustc_diagnostics_report"] = depset([diagnostics_report])
+    if profile_output:
+        output_group_info["rustc_profile"] = depset([profile_output])
 
     if output_group_info:
         providers.append(OutputGroupInfo(**output_group_info))
@@ -2082,6 +2273,7 @@ def add_edition_flags(args, crate):
 def _process_build_scripts(
         build_info,
         dep_info,
+        crate_type = None,
         include_link_flags = True,
         include_transitive_data = False):
     """Gathers the outputs from a target's `cargo_build_script` action.
@@ -2089,6 +2281,7 @@ def _process_build_scripts(
     Args:
         build_info (BuildInfo): The target Build's dependency info.
         dep_info (DepInfo): The Depinfo provider form the target Crate's set of inputs.
+        crate_type (str, optional): The type of the target Crate, used to select the link flags specific to cdylibs and binaries.
         include_link_flags (bool, optional): Whether to include flags like `-l` that instruct the linker to search for a library.
         include_transitive_data (bool, optional): Whether to include transitive data dependencies in compile inputs.
 
@@ -2122,6 +2315,17 @@ def _process_build_scripts(
             build_flags_files.append(build_info.linker_flags)
             direct_inputs.append(build_info.linker_flags)
 
+        # Flags from `cargo::rustc-cdylib-link-arg` and `cargo::rustc-link-arg-bin(s)`.
+        # The binary flags file is keyed by crate name, see `_keyed_arg_file`.
+        target_linker_flags = None
+        if crate_type == "cdylib":
+            target_linker_flags = getattr(build_info, "cdylib_linker_flags", None)
+        elif crate_type == "bin":
+            target_linker_flags = getattr(build_info, "bin_linker_flags", None)
+        if target_linker_flags and include_link_flags:
+            build_flags_files.append(target_linker_flags)
+            direct_inputs.append(target_linker_flags)
+
         transitive_inputs.append(build_info.compile_data)
 
     # We include transitive dep build_infos because cargo build scripts may generate files which get linked into the final binary.
@@ -2297,7 +2501,7 @@ def _get_crate_dirname(crate):
     return crate.output.dirname
 
 def _get_crate_dirname_pipelined(crate):
-    """For pipelined compilation: returns the _hollow/ directory for pipelined crates
+    """For pipelined compilation: returns the _hollow/ (or _pipelined/) directory for pipelined crates
 
     When a crate supports pipelining and has a hollow rlib in its _hollow/ subdirectory,
     pointing -Ldependency= to that subdirectory lets rustc find the hollow rlib (which has
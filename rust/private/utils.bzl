@@ -788,6 +788,32 @@ def can_use_metadata_for_pipelining(toolchain, crate_type):
     return toolchain._pipelined_compilation and \
            crate_type in ("rlib", "lib")
 
+def can_use_worker_pipelining(toolchain, ctx, crate_type):
+    """Can the metadata and full actions of this crate share a single rustc invocation?
+
+    See the `experimental_worker_pipelining` setting. The metadata of such crates is a
+    `.rmeta` file rather than a hollow rlib.
+
+    Args:
+        toolchain (toolchain): The rust toolchain
+        ctx (ctx): The rule's context object
+        crate_type (String): one of lib|rlib|dylib|staticlib|cdylib|proc-macro
+
+    Returns:
+        bool: whether both actions should run the same rustc command.
+    """
+
+    # `rustc_output_diagnostics` writes a separate output for each action, and the
+    # diagnostics report and profile are only written by the full action, so the
+    # commands would differ.
+    rustc_output_diagnostics = getattr(ctx.attr, "_rustc_output_diagnostics", None)
+    return toolchain._experimental_process_wrapper_worker and \
+           toolchain._experimental_worker_pipelining and \
+           toolchain._rustc_diagnostics_report == "none" and \
+           not toolchain._rustc_profile and \
+           can_use_metadata_for_pipelining(toolchain, crate_type) and \
+           not (rustc_output_diagnostics and rustc_output_diagnostics[RustcOutputDiagnosticsInfo].rustc_output_diagnostics)
+
 def crate_root_src(name, crate_name, srcs, crate_type):
     """Determines the source file for the crate root, should it not be specified in `attr.crate_root`.
 
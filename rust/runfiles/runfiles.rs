@@ -29,6 +29,38 @@
 //!
 //! // ...
 //! ```
+//!
+//! 4. Use `rlocation_label!` to look up a runfile by its Bazel label, which is parsed at compile time
+//!    (only the repository mapping is applied at runtime):
+//! ```ignore
+//!
+//! use runfiles::{Runfiles, rlocation_label};
+//!
+//! let r = Runfiles::create().unwrap();
+//! let path = rlocation_label!(r, "@my_repo//path/to/my:data.txt").expect("Failed to locate runfile");
+//! ```
+//!
+//! 5. Use `walk!` to list the files under a runfiles directory:
+//! ```ignore
+//!
+//! use runfiles::{Runfiles, walk};
+//!
+//! let r = Runfiles::create().unwrap();
+//! for entry in walk!(r, "my_workspace/path/to/my/data").expect("Failed to locate runfiles") {
+//!     println!("{} -> {}", entry.relative_path(), entry.path().display());
+//! }
+//! ```
+//!
+//! 6. Use `configure_command` to let a child process find the same runfiles:
+//! ```ignore
+//!
+//! let r = Runfiles::create().unwrap();
+//! let tool = rlocation!(r, "my_workspace/path/to/my/tool").expect("Failed to locate runfile");
+//!
+//! let mut command = std::process::Command::new(tool);
+//! r.configure_command(&mut command);
+//! let status = command.status().unwrap();
+//! ```
 
 use std::collections::{BTreeMap, HashMap};
 use std::env;
@@ -36,10 +68,14 @@ use std::fs;
 use std::io;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::Command;
+
+use label::{Label, LabelSpans, Repository};
 
 const RUNFILES_DIR_ENV_VAR: &str = "RUNFILES_DIR";
 const MANIFEST_FILE_ENV_VAR: &str = "RUNFILES_MANIFEST_FILE";
 const TEST_SRCDIR_ENV_VAR: &str = "TEST_SRCDIR";
+const JAVA_RUNFILES_ENV_VAR: &str = "JAVA_RUNFILES";
 
 #[macro_export]
 macro_rules! rlocation {
@@ -48,6 +84,21 @@ macro_rules! rlocation {
     };
 }
 
+#[macro_export]
+macro_rules! rlocation_label {
+    ($r:expr, $label:expr) => {{
+        const LABEL: $crate::__LabelSpans = $crate::__parse_label($label);
+        $r.__rlocation_from_label_spans($label, LABEL, env!("REPOSITORY_NAME"))
+    }};
+}
+
+#[macro_export]
+macro_rules! walk {
+    ($r:expr, $path:expr) => {
+        $r.walk_from($path, env!("REPOSITORY_NAME"))
+    };
+}
+
 /// The error type for [Runfiles] construction.
 #[derive(Debug)]
 pub enum RunfilesError {
@@ -81,6 +132,12 @@ pub enum RunfilesError {
     /// An [I/O Error](https://doc.rust-lang.org/std/io/struct.Error.html)
     /// which occurred when operating with a particular runfile.
     RunfileIoError(io::Error),
+
+    /// A label could not be parsed or does not refer to a runfile.
+    InvalidLabel(String),
+
+    /// A path is not valid UTF-8, so it can't be a runfiles path.
+    InvalidPath(PathBuf),
 }
 
 impl std::fmt::Display for RunfilesError {
@@ -99,6 +156,8 @@ impl std::fmt::Display for RunfilesError {
                 write!(f, "RunfileNotFound: {}", path.display())
             }
             RunfilesError::RunfileIoError(err) => write!(f, "RunfileIoError: {:?}", err),
+            RunfilesError::InvalidLabel(msg) => write!(f, "InvalidLabel: {}", msg),
+            RunfilesError::InvalidPath(path) => write!(f, "InvalidPath: {}", path.display()),
         }
     }
 }
@@ -121,6 +180,8 @@ impl PartialEq for RunfilesError {
             (Self::RunfileIoError(l0), Self::RunfileIoError(r0)) => {
                 l0.to_string() == r0.to_string()
             }
+            (Self::InvalidLabel(l0), Self::InvalidLabel(r0)) => l0 == r0,
+            (Self::InvalidPath(l0), Self::InvalidPath(r0)) => l0 == r0,
             _ => core::mem::discriminant(self) == core::mem::discriminant(other),
         }
     }
@@ -137,7 +198,10 @@ enum Mode {
 
     /// Runfiles represented as a mapping of `rlocationpath` to real paths indicated
     /// by the `RUNFILES_MANIFEST_FILE` environment variable.
-    ManifestBased(HashMap<PathBuf, PathBuf>),
+    ManifestBased {
+        manifest_file: PathBuf,
+        path_mapping: HashMap<PathBuf, PathBuf>,
+    },
 }
 
 /// A pair of "source" (the workspace the mapping affects) and "target apparent name" (the
@@ -255,7 +319,10 @@ impl Runfiles {
                 Ok::<(PathBuf, PathBuf), RunfilesError>(pair)
             })
             .collect::<HashMap<_, _>>();
-        Ok(Mode::ManifestBased(path_mapping))
+        Ok(Mode::ManifestBased {
+            manifest_file: manifest_path.to_path_buf(),
+            path_mapping,
+        })
     }
 
     /// Returns the runtime path of a runfile.
@@ -286,29 +353,365 @@ impl Runfiles {
         }
 
         let path_str = path.to_str().expect("Should be valid UTF8");
-        let (repo_alias, repo_path): (&str, Option<&str>) = match path_str.split_once('/') {
+        raw_rlocation(&self.mode, self.map_path(path_str, source_repo))
+    }
+
+    /// Returns the runtime path of the runfile built or provided by `label`.
+    ///
+    /// The repository of the label is translated with the repo mapping of `source_repo`,
+    /// so `@my_repo//pkg:data.txt` resolves correctly regardless of the canonical name
+    /// bzlmod assigned to `my_repo`. Labels without a repository refer to `source_repo`.
+    /// Relative labels (`:data.txt`) are rejected as the package of the caller is unknown.
+    ///
+    /// As with [Runfiles::rlocation_from], the returned path may not exist.
+    ///
+    /// Typically this should be used via the `rlocation_label!` macro to properly set source_repo.
+    pub fn rlocation_from_label(&self, label: &str, source_repo: &str) -> Result<PathBuf> {
+        let spans = parse_label(label)
+            .map_err(|msg| RunfilesError::InvalidLabel(format!("{}: {}", label, msg)))?;
+        self.__rlocation_from_label_spans(label, spans, source_repo)
+    }
+
+    /// [Runfiles::rlocation_from_label] for a label which has already been parsed, at
+    /// compile time by `rlocation_label!`.
+    #[doc(hidden)]
+    pub fn __rlocation_from_label_spans(
+        &self,
+        label: &str,
+        spans: LabelSpans,
+        source_repo: &str,
+    ) -> Result<PathBuf> {
+        let rlocation_path = self.label_to_rlocation_path(&spans.label(label), source_repo)?;
+        raw_rlocation(&self.mode, &rlocation_path)
+            .ok_or_else(|| RunfilesError::RunfileNotFound(rlocation_path.into()))
+    }
+
+    /// Returns the files under a runfiles directory, recursively, in order of their
+    /// rlocation paths. If `path` is a file, only that file is returned.
+    ///
+    /// Directory and manifest based runfiles produce the same entries, except that a manifest
+    /// entry without a target, which Bazel writes for empty files such as generated
+    /// `__init__.py` files, is always returned as a file with an empty
+    /// [RunfileEntry::path], even if it stands for an empty directory. A directory without
+    /// any files or entries is reported as [RunfilesError::RunfileNotFound]. Files whose
+    /// names aren't valid UTF-8 are skipped, as they have no rlocation path, and symlinks
+    /// back to a directory being walked aren't followed.
+    ///
+    /// Typically this should be used via the `walk!` macro to properly set source_repo.
+    pub fn walk_from(&self, path: impl AsRef<Path>, source_repo: &str) -> Result<Walk> {
+        let path = path.as_ref();
+        let mut entries = BTreeMap::new();
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| RunfilesError::InvalidPath(path.to_path_buf()))?;
+
+        if path.is_absolute() {
+            walk_dir(path, path_str, "", &mut entries)?;
+        } else {
+            let mapped = self.map_path(path_str, source_repo);
+            let root = mapped.trim_end_matches('/');
+
+            match &self.mode {
+                Mode::DirectoryBased(runfiles_dir) => {
+                    let dir = runfiles_dir.join(root);
+                    if dir.exists() {
+                        walk_dir(&dir, root, "", &mut entries)?;
+                    }
+                }
+                Mode::ManifestBased { path_mapping, .. } => {
+                    for (key, target) in path_mapping {
+                        let Some(key) = key.to_str() else {
+                            continue;
+                        };
+                        let relative = if key == root {
+                            ""
+                        } else if root.is_empty() {
+                            key
+                        } else {
+                            match key.strip_prefix(root).and_then(|k| k.strip_prefix('/')) {
+                                Some(relative) => relative,
+                                None => continue,
+                            }
+                        };
+                        // Entries without a target have no file on disk to walk.
+                        if target.as_os_str().is_empty() {
+                            entries.insert(
+                                key.to_owned(),
+                                RunfileEntry {
+                                    rlocation: key.to_owned(),
+                                    relative_path: relative.to_owned(),
+                                    path: PathBuf::new(),
+                                },
+                            );
+                            continue;
+                        }
+                        // Manifests may map a path to a directory rather than listing its files.
+                        walk_dir(target, key, relative, &mut entries)?;
+                    }
+                }
+            }
+        }
+
+        if entries.is_empty() {
+            return Err(RunfilesError::RunfileNotFound(path.to_path_buf()));
+        }
+
+        Ok(Walk {
+            entries: entries.into_values().collect::<Vec<_>>().into_iter(),
+        })
+    }
+
+    /// Returns the environment variables a child process needs to find these runfiles,
+    /// matching those provided by the C++, Java and Python runfiles libraries.
+    ///
+    /// `RUNFILES_DIR` and `JAVA_RUNFILES` are set to the runfiles directory. For manifest
+    /// based runfiles, `RUNFILES_MANIFEST_FILE` is set too, and the runfiles directory is
+    /// only set if it can be derived from the location of the manifest.
+    pub fn env_vars(&self) -> Vec<(&'static str, PathBuf)> {
+        let (manifest_file, runfiles_dir) = match &self.mode {
+            Mode::DirectoryBased(runfiles_dir) => (None, Some(runfiles_dir.clone())),
+            Mode::ManifestBased { manifest_file, .. } => (
+                Some(manifest_file.clone()),
+                runfiles_dir_from_manifest(manifest_file),
+            ),
+        };
+
+        let mut env_vars = Vec::new();
+        if let Some(manifest_file) = manifest_file {
+            env_vars.push((MANIFEST_FILE_ENV_VAR, manifest_file));
+        }
+        if let Some(runfiles_dir) = runfiles_dir {
+            env_vars.push((RUNFILES_DIR_ENV_VAR, runfiles_dir.clone()));
+            env_vars.push((JAVA_RUNFILES_ENV_VAR, runfiles_dir));
+        }
+        env_vars
+    }
+
+    /// Configures `command` to find these runfiles by setting [Runfiles::env_vars]. Any of
+    /// those variables which don't apply are removed, so that values inherited from this
+    /// process can't take precedence in the child.
+    pub fn configure_command<'a>(&self, command: &'a mut Command) -> &'a mut Command {
+        let env_vars = self.env_vars();
+        for name in [
+            MANIFEST_FILE_ENV_VAR,
+            RUNFILES_DIR_ENV_VAR,
+            JAVA_RUNFILES_ENV_VAR,
+        ] {
+            if !env_vars.iter().any(|(var, _)| *var == name) {
+                command.env_remove(name);
+            }
+        }
+        command.envs(env_vars)
+    }
+
+    /// Converts `label` to a runfiles path rooted at the runfiles directory of its repository.
+    fn label_to_rlocation_path(&self, label: &Label, source_repo: &str) -> Result<String> {
+        let repo_directory = match label.repo() {
+            Some(Repository::Canonical(_)) => label.repo_name().unwrap().to_owned(),
+            Some(Repository::Apparent(_)) => match label.repo_name().unwrap() {
+                // `@//pkg:name` always refers to the main repository.
+                "" => self.main_repo_directory()?,
+                apparent_name => self
+                    .repo_mapping
+                    .get(&(source_repo.to_owned(), apparent_name.to_owned()))
+                    .cloned()
+                    .unwrap_or_else(|| apparent_name.to_owned()),
+            },
+            None if source_repo.is_empty() => self.main_repo_directory()?,
+            None => source_repo.to_owned(),
+        };
+
+        Ok([
+            repo_directory.as_str(),
+            label.package().unwrap_or_default(),
+            label.name(),
+        ]
+        .iter()
+        .filter(|segment| !segment.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join("/"))
+    }
+
+    /// Returns the runfiles directory name of the main repository. Bazel maps the apparent
+    /// name `__main__` to it in the repo mapping of `@bazel_tools`; without such an entry it's
+    /// taken from `TEST_WORKSPACE`, which Bazel only sets for tests.
+    fn main_repo_directory(&self) -> Result<String> {
+        let from_repo_mapping = self
+            .repo_mapping
+            .exact
+            .iter()
+            .chain(self.repo_mapping.prefixes.iter())
+            .find(|((_, apparent_name), _)| apparent_name == "__main__")
+            .map(|(_, directory)| directory.clone());
+        match from_repo_mapping {
+            Some(directory) => Ok(directory),
+            None => env::var("TEST_WORKSPACE").map_err(|_| RunfilesError::RepoMappingNotFound),
+        }
+    }
+
+    /// Applies the repo mapping of `source_repo` to the repository name at the start of a
+    /// runfiles path.
+    fn map_path(&self, path: &str, source_repo: &str) -> String {
+        let (repo_alias, repo_path): (&str, Option<&str>) = match path.split_once('/') {
             Some((name, alias)) => (name, Some(alias)),
-            None => (path_str, None),
+            None => (path, None),
         };
         let key: (String, String) = (source_repo.into(), repo_alias.into());
-        if let Some(target_repo_directory) = self.repo_mapping.get(&key) {
-            match repo_path {
-                Some(repo_path) => {
-                    raw_rlocation(&self.mode, format!("{target_repo_directory}/{repo_path}"))
-                }
-                None => raw_rlocation(&self.mode, target_repo_directory),
+        match (self.repo_mapping.get(&key), repo_path) {
+            (Some(target_repo_directory), Some(repo_path)) => {
+                format!("{target_repo_directory}/{repo_path}")
             }
-        } else {
-            raw_rlocation(&self.mode, path)
+            (Some(target_repo_directory), None) => target_repo_directory.clone(),
+            (None, _) => path.to_owned(),
+        }
+    }
+}
+
+/// A file found by [Runfiles::walk_from].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunfileEntry {
+    rlocation: String,
+    relative_path: String,
+    path: PathBuf,
+}
+
+impl RunfileEntry {
+    /// The rlocation path of the file, with repo mapping applied and `/` separators.
+    pub fn rlocation(&self) -> &str {
+        &self.rlocation
+    }
+
+    /// The path of the file relative to the walked directory, with `/` separators.
+    /// Empty if the walked path is the file itself.
+    pub fn relative_path(&self) -> &str {
+        &self.relative_path
+    }
+
+    /// The runtime path of the file. Empty for manifest entries without a target, which
+    /// don't exist on disk.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// An iterator over the files under a runfiles directory, created by [Runfiles::walk_from].
+#[derive(Debug)]
+pub struct Walk {
+    entries: std::vec::IntoIter<RunfileEntry>,
+}
+
+impl Iterator for Walk {
+    type Item = RunfileEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+#[doc(hidden)]
+pub use label::LabelSpans as __LabelSpans;
+
+/// Parses `label` at compile time, failing to compile if it can't be resolved to a runfile.
+/// Used by `rlocation_label!`.
+///
+/// ```compile_fail
+/// const _: runfiles::__LabelSpans = runfiles::__parse_label(":relative.txt");
+/// ```
+#[doc(hidden)]
+pub const fn __parse_label(label: &str) -> LabelSpans {
+    match parse_label(label) {
+        Ok(spans) => spans,
+        Err(msg) => panic!("{}", msg),
+    }
+}
+
+/// Parses `label` with the same grammar as [label::analyze], additionally requiring it to be
+/// absolute. This is a `const fn` so `rlocation_label!` can parse labels at compile time.
+const fn parse_label(label: &str) -> std::result::Result<LabelSpans, &'static str> {
+    match label::parse(label) {
+        Ok(spans) if spans.is_relative() => {
+            Err("Labels must be absolute; relative labels cannot be resolved to runfiles.")
         }
+        result => result,
+    }
+}
+
+/// Joins two `/` separated runfiles paths, either of which may be empty.
+fn join_runfiles_path(base: &str, path: &str) -> String {
+    match (base.is_empty(), path.is_empty()) {
+        (true, _) => path.to_owned(),
+        (_, true) => base.to_owned(),
+        _ => format!("{base}/{path}"),
     }
 }
 
+/// Records the files under `path` by their rlocation paths. Directories are followed through
+/// symlinks, as runfiles trees are made of them.
+fn walk_dir(
+    path: &Path,
+    rlocation: &str,
+    relative_path: &str,
+    entries: &mut BTreeMap<String, RunfileEntry>,
+) -> Result<()> {
+    walk_dir_impl(path, rlocation, relative_path, entries, &mut Vec::new())
+}
+
+/// [walk_dir], tracking the canonical paths of the directories being walked in `ancestors`
+/// so a symlink to one of them isn't followed forever.
+fn walk_dir_impl(
+    path: &Path,
+    rlocation: &str,
+    relative_path: &str,
+    entries: &mut BTreeMap<String, RunfileEntry>,
+    ancestors: &mut Vec<PathBuf>,
+) -> Result<()> {
+    if !path.is_dir() {
+        entries.insert(
+            rlocation.to_owned(),
+            RunfileEntry {
+                rlocation: rlocation.to_owned(),
+                relative_path: relative_path.to_owned(),
+                path: path.to_path_buf(),
+            },
+        );
+        return Ok(());
+    }
+
+    let canonical = path.canonicalize().map_err(RunfilesError::RunfileIoError)?;
+    if ancestors.contains(&canonical) {
+        return Ok(());
+    }
+    ancestors.push(canonical);
+
+    for entry in fs::read_dir(path).map_err(RunfilesError::RunfileIoError)? {
+        let entry = entry.map_err(RunfilesError::RunfileIoError)?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        walk_dir_impl(
+            &entry.path(),
+            &join_runfiles_path(rlocation, name),
+            &join_runfiles_path(relative_path, name),
+            entries,
+            ancestors,
+        )?;
+    }
+
+    ancestors.pop();
+    Ok(())
+}
+
 fn raw_rlocation(mode: &Mode, path: impl AsRef<Path>) -> Option<PathBuf> {
     let path = path.as_ref();
     match mode {
         Mode::DirectoryBased(runfiles_dir) => Some(runfiles_dir.join(path)),
-        Mode::ManifestBased(path_mapping) => path_mapping.get(path).cloned(),
+        Mode::ManifestBased { path_mapping, .. } => path_mapping.get(path).cloned(),
     }
 }
 
@@ -347,6 +750,19 @@ fn parse_repo_mapping(path: PathBuf) -> Result<RepoMapping> {
     Ok(RepoMapping { exact, prefixes })
 }
 
+/// Returns the runfiles directory next to a manifest file, which is either
+/// `<name>.runfiles/MANIFEST` or `<name>.runfiles_manifest`.
+fn runfiles_dir_from_manifest(manifest_file: &Path) -> Option<PathBuf> {
+    let file_name = manifest_file.file_name()?.to_str()?;
+    if file_name == "MANIFEST" {
+        return manifest_file.parent().map(Path::to_path_buf);
+    }
+    file_name
+        .strip_suffix("_manifest")
+        .filter(|name| name.ends_with(".runfiles"))
+        .map(|name| manifest_file.with_file_name(name))
+}
+
 /// Returns the .runfiles directory for the currently executing binary.
 pub fn find_runfiles_dir() -> Result<PathBuf> {
     if let Some(value) = std::env::var_os(MANIFEST_FILE_ENV_VAR) {
@@ -692,7 +1108,10 @@ mod test {
         let mut path_mapping = HashMap::new();
         path_mapping.insert("a/b".into(), "c/d".into());
         let r = Runfiles {
-            mode: Mode::ManifestBased(path_mapping),
+            mode: Mode::ManifestBased {
+                manifest_file: PathBuf::from("MANIFEST"),
+                path_mapping,
+            },
             repo_mapping: RepoMapping::new(),
         };
 
@@ -704,7 +1123,10 @@ mod test {
         let mut path_mapping = HashMap::new();
         path_mapping.insert("a/b".into(), "c/d".into());
         let r = Runfiles {
-            mode: Mode::ManifestBased(path_mapping),
+            mode: Mode::ManifestBased {
+                manifest_file: PathBuf::from("MANIFEST"),
+                path_mapping,
+            },
             repo_mapping: RepoMapping::new(),
         };
 
@@ -893,4 +1315,395 @@ mod test {
         // Should fall back to the path as-is
         assert_eq!(result, Some(runfiles_dir.join("aaa/path")));
     }
+
+    #[test]
+    fn test_labels_are_parsed_like_util_label() {
+        let r = Runfiles {
+            mode: Mode::DirectoryBased(PathBuf::from("/runfiles")),
+            repo_mapping: RepoMapping::new(),
+        };
+
+        // Labels which the label parser of this crate used to treat differently from `//util/label`.
+        for label in [
+            "@@//pkg:data.txt",
+            "@1dep//pkg:data.txt",
+            "//pkg#:data.txt",
+            "//pkg:dir/",
+            "//pkg:a:b",
+            "//pkg/./sub:data.txt",
+            "//pkg:./data.txt",
+            "@dep~1//pkg:data.txt",
+            "//pkg/$(dir) (1):data.txt",
+            "//pkg:data.txt~",
+        ] {
+            let expected = label::analyze(label).map(|parsed| !parsed.is_relative());
+            assert_eq!(
+                r.rlocation_from_label(label, "dep+").is_ok(),
+                expected == Ok(true),
+                "{}",
+                label
+            );
+        }
+
+        // `rlocation_label!` parses labels at compile time, and agrees with the runtime parser.
+        assert_eq!(
+            rlocation_label!(r, "@dep~1//pkg/$(dir) (1):data.txt~"),
+            r.rlocation_from_label("@dep~1//pkg/$(dir) (1):data.txt~", "")
+        );
+        assert_eq!(
+            r.rlocation_from_label("@dep~1//pkg/$(dir) (1):data.txt~", ""),
+            Ok(PathBuf::from("/runfiles/dep~1/pkg/$(dir) (1)/data.txt~"))
+        );
+    }
+
+    #[test]
+    fn test_rlocation_from_label() {
+        let runfiles_dir = PathBuf::from("/runfiles");
+        let r = Runfiles {
+            mode: Mode::DirectoryBased(runfiles_dir.clone()),
+            repo_mapping: RepoMapping {
+                exact: HashMap::from([
+                    (("".to_owned(), "my_module".to_owned()), "_main".to_owned()),
+                    (
+                        ("bazel_tools".to_owned(), "__main__".to_owned()),
+                        "_main".to_owned(),
+                    ),
+                    (("".to_owned(), "dep".to_owned()), "dep+".to_owned()),
+                    (("dep+".to_owned(), "dep".to_owned()), "dep+".to_owned()),
+                    (("dep+".to_owned(), "other".to_owned()), "other+".to_owned()),
+                ]),
+                prefixes: BTreeMap::new(),
+            },
+        };
+
+        let resolve = |label: &str, source_repo: &str| r.rlocation_from_label(label, source_repo);
+
+        assert_eq!(
+            resolve("@dep//pkg:data.txt", ""),
+            Ok(runfiles_dir.join("dep+/pkg/data.txt"))
+        );
+        assert_eq!(
+            resolve("@other//pkg/sub:dir/data.txt", "dep+"),
+            Ok(runfiles_dir.join("other+/pkg/sub/dir/data.txt"))
+        );
+        assert_eq!(
+            resolve("@my_module//:data.txt", ""),
+            Ok(runfiles_dir.join("_main/data.txt"))
+        );
+        assert_eq!(resolve("@dep", ""), Ok(runfiles_dir.join("dep+/dep")));
+        assert_eq!(
+            resolve("@@canonical+//pkg:data.txt", "dep+"),
+            Ok(runfiles_dir.join("canonical+/pkg/data.txt"))
+        );
+        assert_eq!(
+            rlocation_label!(r, "@@canonical+//pkg:data.txt"),
+            Ok(runfiles_dir.join("canonical+/pkg/data.txt"))
+        );
+
+        // Labels without a repository belong to the repository using them.
+        assert_eq!(
+            resolve("//pkg:data.txt", ""),
+            Ok(runfiles_dir.join("_main/pkg/data.txt"))
+        );
+        assert_eq!(
+            resolve("//pkg:data.txt", "dep+"),
+            Ok(runfiles_dir.join("dep+/pkg/data.txt"))
+        );
+        assert_eq!(
+            resolve("@//pkg:data.txt", "dep+"),
+            Ok(runfiles_dir.join("_main/pkg/data.txt"))
+        );
+
+        // Unmapped repositories are used as-is.
+        assert_eq!(
+            resolve("@unknown//pkg:data.txt", ""),
+            Ok(runfiles_dir.join("unknown/pkg/data.txt"))
+        );
+
+        assert!(matches!(
+            resolve(":data.txt", ""),
+            Err(RunfilesError::InvalidLabel(_))
+        ));
+        for invalid in [
+            "@dep//pkg//sub:data.txt",
+            "pkg:data.txt",
+            "@",
+            "@dep/pkg:data.txt",
+            "@de p//pkg:data.txt",
+            "//",
+            "//pkg:",
+            "//pkg/:data.txt",
+            "//pkg/../other:data.txt",
+            "//pkg:a:b",
+            // The main repository is `@//`; `@@` must be followed by a canonical name.
+            "@@//pkg:data.txt",
+        ] {
+            assert!(
+                matches!(resolve(invalid, ""), Err(RunfilesError::InvalidLabel(_))),
+                "{}",
+                invalid
+            );
+        }
+        assert_eq!(
+            resolve("//pkg/sub", "dep+"),
+            Ok(runfiles_dir.join("dep+/pkg/sub/sub"))
+        );
+
+        // Without a repo mapping, the main repository is the test workspace.
+        let r = Runfiles {
+            mode: Mode::ManifestBased {
+                manifest_file: PathBuf::from("/MANIFEST"),
+                path_mapping: HashMap::from([(
+                    PathBuf::from("my_workspace/pkg/data.txt"),
+                    PathBuf::from("/execroot/pkg/data.txt"),
+                )]),
+            },
+            repo_mapping: RepoMapping::new(),
+        };
+        with_mock_env([("TEST_WORKSPACE", Some("my_workspace"))], || {
+            assert_eq!(
+                r.rlocation_from_label("//pkg:data.txt", ""),
+                Ok(PathBuf::from("/execroot/pkg/data.txt"))
+            );
+            assert_eq!(
+                r.rlocation_from_label("//pkg:missing.txt", ""),
+                Err(RunfilesError::RunfileNotFound(PathBuf::from(
+                    "my_workspace/pkg/missing.txt"
+                )))
+            );
+        });
+        with_mock_env([("TEST_WORKSPACE", None::<&str>)], || {
+            assert_eq!(
+                r.rlocation_from_label("//pkg:data.txt", ""),
+                Err(RunfilesError::RepoMappingNotFound)
+            );
+        });
+    }
+
+    #[test]
+    fn test_walk_directory_and_manifest_based() {
+        let temp_dir = PathBuf::from(std::env::var("TEST_TMPDIR").unwrap());
+        let runfiles_dir = temp_dir.join("test_walk_directory_and_manifest_based.runfiles");
+        let data_dir = runfiles_dir.join("_main/pkg/data");
+        std::fs::create_dir_all(data_dir.join("sub")).unwrap();
+        std::fs::create_dir_all(data_dir.join("tree")).unwrap();
+        std::fs::write(data_dir.join("a.txt"), "a").unwrap();
+        std::fs::write(data_dir.join("sub/b.txt"), "b").unwrap();
+        std::fs::write(data_dir.join("tree/c.txt"), "c").unwrap();
+        std::fs::write(runfiles_dir.join("_main/pkg/other.txt"), "other").unwrap();
+
+        let repo_mapping = || RepoMapping {
+            exact: HashMap::from([(("".to_owned(), "my_repo".to_owned()), "_main".to_owned())]),
+            prefixes: BTreeMap::new(),
+        };
+
+        let directory_based = Runfiles {
+            mode: Mode::DirectoryBased(runfiles_dir.clone()),
+            repo_mapping: repo_mapping(),
+        };
+
+        // Manifests list files individually, but may map a path to a directory.
+        let manifest_based = Runfiles {
+            mode: Mode::ManifestBased {
+                manifest_file: runfiles_dir.join("MANIFEST"),
+                path_mapping: HashMap::from([
+                    ("_main/pkg/data/a.txt".into(), data_dir.join("a.txt")),
+                    (
+                        "_main/pkg/data/sub/b.txt".into(),
+                        data_dir.join("sub/b.txt"),
+                    ),
+                    ("_main/pkg/data/tree".into(), data_dir.join("tree")),
+                    (
+                        "_main/pkg/other.txt".into(),
+                        runfiles_dir.join("_main/pkg/other.txt"),
+                    ),
+                ]),
+            },
+            repo_mapping: repo_mapping(),
+        };
+
+        for r in [&directory_based, &manifest_based] {
+            let entries: Vec<RunfileEntry> =
+                r.walk_from("my_repo/pkg/data/", "").unwrap().collect();
+            assert_eq!(
+                entries
+                    .iter()
+                    .map(|e| (e.rlocation(), e.relative_path()))
+                    .collect::<Vec<_>>(),
+                vec![
+                    ("_main/pkg/data/a.txt", "a.txt"),
+                    ("_main/pkg/data/sub/b.txt", "sub/b.txt"),
+                    ("_main/pkg/data/tree/c.txt", "tree/c.txt"),
+                ],
+                "{:?}",
+                r.mode
+            );
+            assert_eq!(std::fs::read_to_string(entries[1].path()).unwrap(), "b");
+
+            let file: Vec<RunfileEntry> =
+                r.walk_from("my_repo/pkg/other.txt", "").unwrap().collect();
+            assert_eq!(file.len(), 1);
+            assert_eq!(file[0].rlocation(), "_main/pkg/other.txt");
+            assert_eq!(file[0].relative_path(), "");
+
+            // Paths are matched by whole segments.
+            assert_eq!(
+                r.walk_from("my_repo/pkg/dat", "").unwrap_err(),
+                RunfilesError::RunfileNotFound("my_repo/pkg/dat".into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_walk_manifest_entries_without_target() {
+        let temp_dir = PathBuf::from(std::env::var("TEST_TMPDIR").unwrap());
+        let data_dir = temp_dir.join("test_walk_manifest_entries_without_target/data");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        std::fs::write(data_dir.join("a.txt"), "a").unwrap();
+
+        let r = Runfiles {
+            mode: Mode::ManifestBased {
+                manifest_file: temp_dir.join("MANIFEST"),
+                path_mapping: HashMap::from([
+                    ("_main/pkg/__init__.py".into(), PathBuf::new()),
+                    ("_main/pkg/data/a.txt".into(), data_dir.join("a.txt")),
+                    ("_main/pkg/empty".into(), PathBuf::new()),
+                ]),
+            },
+            repo_mapping: RepoMapping::new(),
+        };
+
+        let entries: Vec<RunfileEntry> = r.walk_from("_main/pkg", "").unwrap().collect();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|e| (e.relative_path(), e.path()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("__init__.py", Path::new("")),
+                ("data/a.txt", data_dir.join("a.txt").as_path()),
+                ("empty", Path::new("")),
+            ]
+        );
+
+        let file: Vec<RunfileEntry> = r.walk_from("_main/pkg/empty", "").unwrap().collect();
+        assert_eq!(file.len(), 1);
+        assert_eq!(file[0].rlocation(), "_main/pkg/empty");
+        assert_eq!(file[0].relative_path(), "");
+        assert_eq!(file[0].path(), Path::new(""));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_skips_symlink_loops_and_non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = PathBuf::from(std::env::var("TEST_TMPDIR").unwrap());
+        let runfiles_dir =
+            temp_dir.join("test_walk_skips_symlink_loops_and_non_utf8_names.runfiles");
+        let data_dir = runfiles_dir.join("_main/pkg/data");
+        std::fs::create_dir_all(data_dir.join("sub")).unwrap();
+        std::fs::write(data_dir.join("a.txt"), "a").unwrap();
+        std::fs::write(data_dir.join(OsStr::from_bytes(b"invalid\xff.txt")), "").unwrap();
+        std::os::unix::fs::symlink(&data_dir, data_dir.join("sub/loop")).unwrap();
+
+        let r = Runfiles {
+            mode: Mode::DirectoryBased(runfiles_dir.clone()),
+            repo_mapping: RepoMapping::new(),
+        };
+
+        let entries: Vec<RunfileEntry> = r.walk_from("_main/pkg/data", "").unwrap().collect();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|e| e.relative_path())
+                .collect::<Vec<_>>(),
+            vec!["a.txt"]
+        );
+
+        let invalid = Path::new(OsStr::from_bytes(b"_main/pkg/\xff"));
+        assert_eq!(
+            r.walk_from(invalid, "").unwrap_err(),
+            RunfilesError::InvalidPath(invalid.to_path_buf())
+        );
+    }
+
+    #[test]
+    fn test_env_vars() {
+        let directory_based = Runfiles {
+            mode: Mode::DirectoryBased(PathBuf::from("/bin/foo.runfiles")),
+            repo_mapping: RepoMapping::new(),
+        };
+        assert_eq!(
+            directory_based.env_vars(),
+            vec![
+                (RUNFILES_DIR_ENV_VAR, PathBuf::from("/bin/foo.runfiles")),
+                (JAVA_RUNFILES_ENV_VAR, PathBuf::from("/bin/foo.runfiles")),
+            ]
+        );
+
+        let manifest_based = |manifest_file: &str| Runfiles {
+            mode: Mode::ManifestBased {
+                manifest_file: PathBuf::from(manifest_file),
+                path_mapping: HashMap::new(),
+            },
+            repo_mapping: RepoMapping::new(),
+        };
+        assert_eq!(
+            manifest_based("/bin/foo.runfiles/MANIFEST").env_vars(),
+            vec![
+                (
+                    MANIFEST_FILE_ENV_VAR,
+                    PathBuf::from("/bin/foo.runfiles/MANIFEST")
+                ),
+                (RUNFILES_DIR_ENV_VAR, PathBuf::from("/bin/foo.runfiles")),
+                (JAVA_RUNFILES_ENV_VAR, PathBuf::from("/bin/foo.runfiles")),
+            ]
+        );
+        assert_eq!(
+            manifest_based("/bin/foo.exe.runfiles_manifest").env_vars(),
+            vec![
+                (
+                    MANIFEST_FILE_ENV_VAR,
+                    PathBuf::from("/bin/foo.exe.runfiles_manifest")
+                ),
+                (RUNFILES_DIR_ENV_VAR, PathBuf::from("/bin/foo.exe.runfiles")),
+                (
+                    JAVA_RUNFILES_ENV_VAR,
+                    PathBuf::from("/bin/foo.exe.runfiles")
+                ),
+            ]
+        );
+        assert_eq!(
+            manifest_based("/tmp/custom_manifest.txt").env_vars(),
+            vec![(
+                MANIFEST_FILE_ENV_VAR,
+                PathBuf::from("/tmp/custom_manifest.txt")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_configure_command() {
+        let r = Runfiles {
+            mode: Mode::DirectoryBased(PathBuf::from("/bin/foo.runfiles")),
+            repo_mapping: RepoMapping::new(),
+        };
+
+        let mut command = Command::new("child");
+        r.configure_command(&mut command);
+
+        let envs: HashMap<&OsStr, Option<&OsStr>> = command.get_envs().collect();
+        assert_eq!(envs.len(), 3);
+        assert_eq!(envs[OsStr::new(MANIFEST_FILE_ENV_VAR)], None);
+        assert_eq!(
+            envs[OsStr::new(RUNFILES_DIR_ENV_VAR)],
+            Some(OsStr::new("/bin/foo.runfiles"))
+        );
+        assert_eq!(
+            envs[OsStr::new(JAVA_RUNFILES_ENV_VAR)],
+            Some(OsStr::new("/bin/foo.runfiles"))
+        );
+    }
 }
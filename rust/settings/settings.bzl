@@ -8,6 +8,7 @@ load(
     "bool_flag",
     "int_flag",
     "string_flag",
+    "string_list_flag",
 )
 load(
     "//rust/private:clippy.bzl",
@@ -130,6 +131,37 @@ def pipelined_compilation():
         build_setting_default = False,
     )
 
+def experimental_process_wrapper_worker():
+    """When set, `Rustc` and `RustcMetadata` actions may run in a persistent process_wrapper worker.
+
+    The actions advertise `supports-workers` and `supports-multiplex-workers`, so Bazel runs
+    them in a worker whenever the `worker` strategy is selected for them (it is part of
+    Bazel's default `--spawn_strategy`). The arguments of each action are passed through
+    flag files and its environment through an `--env-file`, so that a single worker can
+    serve every crate built for the same configuration.
+    """
+    bool_flag(
+        name = "experimental_process_wrapper_worker",
+        build_setting_default = False,
+    )
+
+def experimental_worker_pipelining():
+    """When set together with `pipelined_compilation` and `experimental_process_wrapper_worker`, \
+    the metadata and full actions of a library share a single rustc invocation.
+
+    Both actions run the same command, which emits the `.rmeta` file and the rlib. A
+    multiplexed process_wrapper worker starts rustc for whichever request arrives first,
+    answers the `RustcMetadata` request as soon as the `.rmeta` file is written and the
+    `Rustc` request once rustc exits. This replaces the hollow rlib, and the second rustc
+    run that produces it. Outside of a worker each action still compiles on its own.
+    Targets using `rustc_output_diagnostics`, `rustc_diagnostics_report` or `rustc_profile`
+    keep using hollow rlibs.
+    """
+    bool_flag(
+        name = "experimental_worker_pipelining",
+        build_setting_default = False,
+    )
+
 # buildifier: disable=unnamed-macro
 def experimental_use_cc_common_link():
     """A flag to control whether to link rust_binary and rust_test targets using \
@@ -408,6 +440,47 @@ def rustc_output_diagnostics():
         visibility = ["//visibility:public"],
     )
 
+def rustc_diagnostics_report():
+    """Writes a deduplicated report of the diagnostics of each `Rustc` action.
+
+    Accepts "json" (a compact schema), "sarif" or "none" (the default). Paths in the reports
+    are relative to the workspace. They are available through the `rustc_diagnostics_report`
+    output group, e.g. `bazel build //... --output_groups=+rustc_diagnostics_report`.
+    """
+    string_flag(
+        name = "rustc_diagnostics_report",
+        build_setting_default = "none",
+        values = ["json", "none", "sarif"],
+    )
+
+# buildifier: disable=unnamed-macro
+def rustc_profile():
+    """A flag to record the wall time, CPU time and peak memory usage of each `Rustc` action.
+
+    The profiles are available through the `rustc_profile` output group and can be merged
+    into a single report with `@rules_rust//util/profile_aggregator`, e.g.
+    `bazel build //... --output_groups=+rustc_profile --@rules_rust//rust/settings:rustc_profile`.
+    Pass timings are included when `-Ztime-passes-format=json` is in the rustc flags.
+    """
+    bool_flag(
+        name = "rustc_profile",
+        build_setting_default = False,
+    )
+
+# buildifier: disable=unnamed-macro
+def rustc_diagnostic_path_remap():
+    """A list of `FROM=TO` path prefixes to rewrite in the diagnostics of `Rustc` actions.
+
+    The rewrite applies to the rendered diagnostics printed by the build and to the
+    `rustc_diagnostics_report` output. `{bin_dir}` is replaced with the bin directory of the
+    target's configuration and `${pwd}` with the action's working directory, e.g.
+    `--@rules_rust//rust/settings:rustc_diagnostic_path_remap={bin_dir}/=bazel-bin/`.
+    """
+    string_list_flag(
+        name = "rustc_diagnostic_path_remap",
+        build_setting_default = [],
+    )
+
 # buildifier: disable=unnamed-macro
 def clippy_output_diagnostics():
     """A flag to enable the `clippy_output_diagnostics` setting.
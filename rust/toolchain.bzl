@@ -617,6 +617,11 @@ def _rust_toolchain_impl(ctx):
         _rename_first_party_crates = rename_first_party_crates,
         _third_party_dir = third_party_dir,
         _pipelined_compilation = pipelined_compilation,
+        _experimental_process_wrapper_worker = ctx.attr._experimental_process_wrapper_worker[BuildSettingInfo].value,
+        _experimental_worker_pipelining = ctx.attr._experimental_worker_pipelining[BuildSettingInfo].value,
+        _rustc_diagnostic_path_remap = ctx.attr._rustc_diagnostic_path_remap[BuildSettingInfo].value,
+        _rustc_diagnostics_report = ctx.attr._rustc_diagnostics_report[BuildSettingInfo].value,
+        _rustc_profile = ctx.attr._rustc_profile[BuildSettingInfo].value,
         _experimental_link_std_dylib = _experimental_link_std_dylib(ctx),
         _experimental_use_cc_common_link = _experimental_use_cc_common_link(ctx),
         _experimental_use_global_allocator = experimental_use_global_allocator,
@@ -853,6 +858,10 @@ rust_toolchain = rule(
                 "allocator libraries that mangle symbols."
             ),
         ),
+        "_experimental_process_wrapper_worker": attr.label(
+            default = Label("//rust/settings:experimental_process_wrapper_worker"),
+            doc = "Label to a boolean build setting that controls whether Rustc actions may run in a persistent process_wrapper worker.",
+        ),
         "_experimental_use_coverage_metadata_files": attr.label(
             default = Label("//rust/settings:experimental_use_coverage_metadata_files"),
         ),
@@ -863,6 +872,10 @@ rust_toolchain = rule(
                 "This flag is only relevant when used together with --@rules_rust//rust/settings:experimental_use_global_allocator."
             ),
         ),
+        "_experimental_worker_pipelining": attr.label(
+            default = Label("//rust/settings:experimental_worker_pipelining"),
+            doc = "Label to a boolean build setting that controls whether the metadata and full actions of a library share a rustc invocation in a process_wrapper worker.",
+        ),
         "_incompatible_do_not_include_data_in_compile_data": attr.label(
             default = Label("//rust/settings:incompatible_do_not_include_data_in_compile_data"),
             doc = "Label to a boolean build setting that controls whether to include data files in compile_data.",
@@ -883,6 +896,18 @@ rust_toolchain = rule(
         "_rename_first_party_crates": attr.label(
             default = Label("//rust/settings:rename_first_party_crates"),
         ),
+        "_rustc_diagnostic_path_remap": attr.label(
+            default = Label("//rust/settings:rustc_diagnostic_path_remap"),
+            doc = "Label to a string list build setting of path prefixes to rewrite in the diagnostics of Rustc actions.",
+        ),
+        "_rustc_diagnostics_report": attr.label(
+            default = Label("//rust/settings:rustc_diagnostics_report"),
+            doc = "Label to a string build setting selecting the format of the diagnostics report written by Rustc actions.",
+        ),
+        "_rustc_profile": attr.label(
+            default = Label("//rust/settings:rustc_profile"),
+            doc = "Label to a boolean build setting controlling whether Rustc actions write a resource and timing profile.",
+        ),
         "_third_party_dir": attr.label(
             default = Label("//rust/settings:third_party_dir"),
         ),
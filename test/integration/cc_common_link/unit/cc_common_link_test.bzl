@@ -3,6 +3,7 @@
 load("@bazel_skylib//lib:unittest.bzl", "analysistest", "asserts")
 load("@bazel_skylib//rules:write_file.bzl", "write_file")
 load("@rules_cc//cc:defs.bzl", "cc_library")
+load("@rules_rust//cargo:defs.bzl", "cargo_build_script")
 load(
     "@rules_rust//rust:defs.bzl",
     "rust_binary",
@@ -119,6 +120,27 @@ custom_malloc_test = analysistest.make(
     },
 )
 
+def _build_script_link_args_test(ctx):
+    env = analysistest.begin(ctx)
+    tut = analysistest.target_under_test(env)
+    registered_actions = tut[DepActionsInfo].actions
+
+    # The process wrapper writes the `cargo::rustc-*link-arg*` flags of the build script to a
+    # response file, which is passed to the link action.
+    rustc_action = [action for action in registered_actions if action.mnemonic == "Rustc"][0]
+    link_args = [output for output in rustc_action.outputs.to_list() if output.extension == "link_args"]
+    asserts.equals(env, 1, len(link_args), "Expected the Rustc action to write a .link_args file")
+    asserts.true(env, "--link-args-output" in rustc_action.argv)
+    asserts.true(env, link_args[0].path in rustc_action.argv)
+
+    link_action = [action for action in registered_actions if action.mnemonic == "CppLink"][0]
+    asserts.true(env, link_args[0] in link_action.inputs.to_list(), "Expected the response file to be an input of the link action")
+    asserts.true(env, "@" + link_args[0].path in link_action.argv, "Expected the response file in the linker invocation")
+
+    return analysistest.end(env)
+
+build_script_link_args_test = analysistest.make(_build_script_link_args_test)
+
 def _cc_common_link_test_targets():
     """Generate targets and tests."""
 
@@ -219,6 +241,58 @@ def _cc_common_link_test_targets():
         target_under_test = ":bin_with_cc_common_link",
     )
 
+    write_file(
+        name = "link_args_build_rs",
+        out = "link_args_build.rs",
+        content = [
+            "fn main() {",
+            "    println!(\"cargo::rustc-cdylib-link-arg=-Wl,--no-undefined\");",
+            "    println!(\"cargo::rustc-link-arg-bins=-Wl,--as-needed\");",
+            "}",
+            "",
+        ],
+    )
+
+    cargo_build_script(
+        name = "link_args_build_script",
+        srcs = [":link_args_build.rs"],
+        edition = "2018",
+    )
+
+    rust_binary(
+        name = "bin_with_build_script",
+        srcs = ["bin.rs"],
+        edition = "2018",
+        deps = [":link_args_build_script"],
+    )
+
+    use_cc_common_link_on_target(
+        name = "bin_with_build_script_with_cc_common_link",
+        target = ":bin_with_build_script",
+    )
+
+    rust_shared_library(
+        name = "cdylib_with_build_script",
+        srcs = ["lib.rs"],
+        edition = "2018",
+        deps = [":link_args_build_script"],
+    )
+
+    use_cc_common_link_on_target(
+        name = "cdylib_with_build_script_with_cc_common_link",
+        target = ":cdylib_with_build_script",
+    )
+
+    build_script_link_args_test(
+        name = "build_script_link_args_on_binary_test",
+        target_under_test = ":bin_with_build_script_with_cc_common_link",
+    )
+
+    build_script_link_args_test(
+        name = "build_script_link_args_on_cdylib_test",
+        target_under_test = ":cdylib_with_build_script_with_cc_common_link",
+    )
+
     return [
         "use_cc_common_link_on_binary",
         "use_cc_common_link_on_binary_with_pdb",
@@ -226,6 +300,8 @@ def _cc_common_link_test_targets():
         "use_cc_common_link_on_crate_test",
         "use_cc_common_link_on_cdylib",
         "custom_malloc_on_binary_test",
+        "build_script_link_args_on_binary_test",
+        "build_script_link_args_on_cdylib_test",
     ]
 
 _RUSTC_FLAGS_CODEGEN_UNITS = 2
@@ -0,0 +1,106 @@
+"""Unittests for the settings of `cargo_build_script` actions."""
+
+load("@bazel_skylib//lib:unittest.bzl", "analysistest", "asserts")
+load("@bazel_skylib//rules:write_file.bzl", "write_file")
+load("//cargo:defs.bzl", "cargo_build_script")
+load("//rust:rust_common.bzl", "BuildInfo")
+
+_AUDIT = str(Label("//cargo/settings:build_script_audit"))
+_CACHE_DIR = str(Label("//cargo/settings:experimental_build_script_cache_dir"))
+
+def _build_script_action(env):
+    return [action for action in analysistest.target_under_test(env).actions if action.mnemonic == "CargoBuildScriptRun"][0]
+
+def _cache_dir_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    action = _build_script_action(env)
+
+    asserts.true(env, "--rerun_cache_dir=/tmp/build_script_cache" in action.argv)
+    asserts.equals(env, "1", action.execution_info.get("no-remote-exec"))
+    asserts.equals(env, "1", action.execution_info.get("no-cache"))
+
+    return analysistest.end(env)
+
+_cache_dir_test = analysistest.make(
+    _cache_dir_test_impl,
+    config_settings = {_CACHE_DIR: "/tmp/build_script_cache"},
+)
+
+def _no_cache_dir_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    action = _build_script_action(env)
+
+    asserts.false(env, [arg for arg in action.argv if arg.startswith("--rerun_cache_dir=")])
+    asserts.false(env, "no-remote-exec" in action.execution_info)
+    asserts.false(env, "no-cache" in action.execution_info)
+
+    return analysistest.end(env)
+
+_no_cache_dir_test = analysistest.make(_no_cache_dir_test_impl)
+
+def _audit_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+    action = _build_script_action(env)
+
+    report = target[OutputGroupInfo].build_script_audit.to_list()
+    asserts.equals(env, 1, len(report))
+    asserts.true(env, report[0] in action.outputs.to_list(), "the report must be written by the build script")
+    asserts.true(env, "--audit_report=" + report[0].path in action.argv)
+
+    # The report is not an input of the crate's compile actions.
+    asserts.false(env, report[0] in target[BuildInfo].compile_data.to_list())
+
+    return analysistest.end(env)
+
+_audit_test = analysistest.make(
+    _audit_test_impl,
+    config_settings = {_AUDIT: "warn"},
+)
+
+def build_script_settings_test_suite(name):
+    """Entry-point macro called from the BUILD file.
+
+    Args:
+        name (str): The name of the test suite.
+    """
+    write_file(
+        name = "build_rs_src",
+        out = "build.rs",
+        content = [
+            "fn main() {",
+            "    println!(\"cargo::rerun-if-changed=build.rs\");",
+            "}",
+            "",
+        ],
+    )
+
+    cargo_build_script(
+        name = "build_script",
+        srcs = [":build.rs"],
+        edition = "2021",
+    )
+
+    _audit_test(
+        name = "audit_test",
+        target_under_test = ":build_script",
+    )
+
+    _cache_dir_test(
+        name = "cache_dir_test",
+        target_under_test = ":build_script",
+    )
+
+    _no_cache_dir_test(
+        name = "no_cache_dir_test",
+        target_under_test = ":build_script",
+    )
+
+    native.test_suite(
+        name = name,
+        tests = [
+            ":audit_test",
+            ":cache_dir_test",
+            ":no_cache_dir_test",
+        ],
+    )
@@ -0,0 +1,77 @@
+"""Unittests for custom target specifications in `crates_vendor`."""
+
+load("@bazel_skylib//lib:unittest.bzl", "analysistest", "asserts")
+load("@bazel_skylib//rules:write_file.bzl", "write_file")
+load("//crate_universe:defs.bzl", "crate", "crates_vendor")
+
+def _written_content(env, basename):
+    for action in analysistest.target_under_test(env).actions:
+        if action.mnemonic == "FileWrite" and action.outputs.to_list()[0].basename.endswith(basename):
+            return action.content
+    fail("No action writes {}".format(basename))
+
+def _target_specs_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    config = json.decode(_written_content(env, "cargo-bazel-config.json"))
+    asserts.true(env, "thumbv7em-acme-none" in config["supported_platform_triples"])
+    asserts.true(env, "x86_64-unknown-linux-gnu" in config["supported_platform_triples"])
+
+    # The specification is read by `cargo-bazel` from runfiles.
+    runner = target[DefaultInfo].files_to_run.executable
+    script = _written_content(env, runner.basename)
+    asserts.true(env, "--target-spec thumbv7em-acme-none=" in script, script)
+
+    spec = ctx.file.spec
+    asserts.true(env, spec in target[DefaultInfo].default_runfiles.files.to_list())
+    asserts.true(env, spec.short_path in script, script)
+
+    return analysistest.end(env)
+
+_target_specs_test = analysistest.make(
+    _target_specs_test_impl,
+    attrs = {
+        "spec": attr.label(allow_single_file = True),
+    },
+)
+
+def crates_vendor_target_specs_test_suite(name):
+    """Entry-point macro called from the BUILD file.
+
+    Args:
+        name (str): The name of the test suite.
+    """
+    write_file(
+        name = "target_spec",
+        out = "thumbv7em-acme-none.json",
+        content = [json.encode({
+            "arch": "arm",
+            "llvm-target": "thumbv7em-none-eabihf",
+            "target-pointer-width": "32",
+        })],
+    )
+
+    crates_vendor(
+        name = "crates_vendor",
+        custom_target_specs = {":thumbv7em-acme-none.json": "thumbv7em-acme-none"},
+        mode = "remote",
+        packages = {
+            "serde": crate.spec(version = "1"),
+        },
+        supported_platform_triples = ["x86_64-unknown-linux-gnu"],
+        tags = ["manual"],
+    )
+
+    _target_specs_test(
+        name = "target_specs_test",
+        spec = ":thumbv7em-acme-none.json",
+        target_under_test = ":crates_vendor",
+    )
+
+    native.test_suite(
+        name = name,
+        tests = [
+            ":target_specs_test",
+        ],
+    )
@@ -0,0 +1,416 @@
+"""Unittests for the process_wrapper settings of `Rustc` actions."""
+
+load("@bazel_skylib//lib:unittest.bzl", "analysistest", "asserts")
+load("@bazel_skylib//rules:build_test.bzl", "build_test")
+load("@bazel_skylib//rules:write_file.bzl", "write_file")
+load("//rust:defs.bzl", "rust_binary", "rust_library")
+load(":with_process_wrapper_settings.bzl", "with_process_wrapper_settings")
+
+_DIAGNOSTIC_PATH_REMAP = str(Label("//rust/settings:rustc_diagnostic_path_remap"))
+_DIAGNOSTICS_REPORT = str(Label("//rust/settings:rustc_diagnostics_report"))
+_PIPELINED_COMPILATION = str(Label("//rust/settings:pipelined_compilation"))
+_PROFILE = str(Label("//rust/settings:rustc_profile"))
+_WORKER = str(Label("//rust/settings:experimental_process_wrapper_worker"))
+_WORKER_PIPELINING = str(Label("//rust/settings:experimental_worker_pipelining"))
+
+_WORKER_REQUIREMENTS = {
+    "requires-worker-protocol": "proto",
+    "supports-multiplex-workers": "1",
+    "supports-workers": "1",
+    "worker-key-mnemonic": "Rustc",
+}
+
+def _rustc_action(env, mnemonic = "Rustc"):
+    return [action for action in analysistest.target_under_test(env).actions if action.mnemonic == mnemonic][0]
+
+def _flag_value(action, flag):
+    for index, arg in enumerate(action.argv[:-1]):
+        if arg == flag:
+            return action.argv[index + 1]
+    return None
+
+def _flag_values(action, flag):
+    return [action.argv[index + 1] for index, arg in enumerate(action.argv[:-1]) if arg == flag]
+
+def _worker_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    action = _rustc_action(env)
+
+    for key, value in _WORKER_REQUIREMENTS.items():
+        asserts.equals(env, value, action.execution_info.get(key), "missing execution requirement " + key)
+
+    # The environment of the crate is passed through an env file so that all
+    # actions share a worker.
+    env_file = _flag_value(action, "--env-file")
+    asserts.true(env, env_file and env_file.endswith(".worker_env"), "expected a worker env file, got {}".format(env_file))
+    asserts.true(env, env_file in [f.path for f in action.inputs.to_list()], "the worker env file must be an input")
+    asserts.false(env, "CARGO_CRATE_NAME" in action.env, "crate specific variables should not be in the action env")
+
+    return analysistest.end(env)
+
+_worker_test = analysistest.make(
+    _worker_test_impl,
+    config_settings = {_WORKER: True},
+)
+
+def _no_worker_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    action = _rustc_action(env)
+
+    for key in _WORKER_REQUIREMENTS:
+        asserts.false(env, key in action.execution_info, "unexpected execution requirement " + key)
+    asserts.equals(env, None, _flag_value(action, "--env-file"))
+    asserts.true(env, "CARGO_CRATE_NAME" in action.env)
+
+    return analysistest.end(env)
+
+_no_worker_test = analysistest.make(_no_worker_test_impl)
+
+def _without_pipelining_mode(argv):
+    # Param file names differ between actions, so only compare their contents.
+    args = [arg for arg in argv if not arg.startswith("--flagfile=") and not arg.startswith("@")]
+    index = args.index("--pipelining-mode")
+    return args[:index] + args[index + 2:]
+
+def _worker_pipelining_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    metadata = _rustc_action(env, "RustcMetadata")
+    full = _rustc_action(env, "Rustc")
+
+    # Both halves run the same rustc command, which emits the `.rmeta` file and the rlib.
+    asserts.equals(env, "metadata", _flag_value(metadata, "--pipelining-mode"))
+    asserts.equals(env, "full", _flag_value(full, "--pipelining-mode"))
+    asserts.equals(env, _without_pipelining_mode(full.argv), _without_pipelining_mode(metadata.argv))
+    asserts.equals(
+        env,
+        sorted([f.path for f in full.inputs.to_list()]),
+        sorted([f.path for f in metadata.inputs.to_list()]),
+    )
+
+    rmeta = metadata.outputs.to_list()[0]
+    rlib = [f for f in full.outputs.to_list() if f.extension == "rlib"][0]
+    asserts.true(env, rmeta.path.endswith(".rmeta") and "/_pipelined/" in rmeta.path, "unexpected metadata " + rmeta.path)
+    asserts.true(env, "--emit=metadata=" + rmeta.path in full.argv)
+    asserts.true(env, "--emit=link=" + rlib.path in full.argv)
+    asserts.false(env, "-Zno-codegen" in full.argv)
+    asserts.false(env, "RUSTC_BOOTSTRAP" in full.env)
+
+    return analysistest.end(env)
+
+_worker_pipelining_test = analysistest.make(
+    _worker_pipelining_test_impl,
+    config_settings = {
+        _PIPELINED_COMPILATION: True,
+        _WORKER: True,
+        _WORKER_PIPELINING: True,
+    },
+)
+
+def _hollow_rlib_without_worker_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    metadata = _rustc_action(env, "RustcMetadata")
+
+    # Worker pipelining is only used together with the worker.
+    asserts.true(env, metadata.outputs.to_list()[0].path.endswith("-hollow.rlib"))
+    asserts.equals(env, None, _flag_value(metadata, "--pipelining-mode"))
+
+    return analysistest.end(env)
+
+_hollow_rlib_without_worker_test = analysistest.make(
+    _hollow_rlib_without_worker_test_impl,
+    config_settings = {
+        _PIPELINED_COMPILATION: True,
+        _WORKER_PIPELINING: True,
+    },
+)
+
+def _diagnostics_report_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+    action = _rustc_action(env)
+
+    report = target[OutputGroupInfo].rustc_diagnostics_report.to_list()
+    asserts.equals(env, 1, len(report))
+    asserts.true(env, report[0].path.endswith(".diagnostics.sarif"))
+    asserts.true(env, report[0] in action.outputs.to_list(), "the report must be written by the Rustc action")
+    asserts.equals(env, report[0].path, _flag_value(action, "--diagnostics-output"))
+    asserts.equals(env, "sarif", _flag_value(action, "--diagnostics-format"))
+
+    # The report is built from rustc's json output.
+    asserts.true(env, _flag_value(action, "--rustc-output-format") != None)
+
+    return analysistest.end(env)
+
+_diagnostics_report_test = analysistest.make(
+    _diagnostics_report_test_impl,
+    config_settings = {_DIAGNOSTICS_REPORT: "sarif"},
+)
+
+def _no_diagnostics_report_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    asserts.false(env, OutputGroupInfo in target and hasattr(target[OutputGroupInfo], "rustc_diagnostics_report"))
+    asserts.equals(env, None, _flag_value(_rustc_action(env), "--diagnostics-output"))
+
+    return analysistest.end(env)
+
+_no_diagnostics_report_test = analysistest.make(_no_diagnostics_report_test_impl)
+
+def _diagnostic_path_remap_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    action = _rustc_action(env)
+    rlib = [f for f in action.outputs.to_list() if f.extension == "rlib"][0]
+
+    asserts.equals(
+        env,
+        [rlib.root.path + "/=bazel-bin/", "${pwd}/="],
+        _flag_values(action, "--diagnostic-path-remap"),
+    )
+
+    # Diagnostics are remapped while rendering rustc's json output.
+    asserts.true(env, _flag_value(action, "--rustc-output-format") != None)
+
+    return analysistest.end(env)
+
+_diagnostic_path_remap_test = analysistest.make(
+    _diagnostic_path_remap_test_impl,
+    config_settings = {_DIAGNOSTIC_PATH_REMAP: ["{bin_dir}/=bazel-bin/", "${pwd}/="]},
+)
+
+def _no_diagnostic_path_remap_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    action = _rustc_action(env)
+
+    asserts.equals(env, [], _flag_values(action, "--diagnostic-path-remap"))
+    asserts.equals(env, None, _flag_value(action, "--rustc-output-format"))
+
+    return analysistest.end(env)
+
+_no_diagnostic_path_remap_test = analysistest.make(_no_diagnostic_path_remap_test_impl)
+
+def _profile_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+    action = _rustc_action(env)
+
+    profile = target[OutputGroupInfo].rustc_profile.to_list()
+    asserts.equals(env, 1, len(profile))
+    asserts.true(env, profile[0].path.endswith(".profile.json"))
+    asserts.true(env, profile[0] in action.outputs.to_list(), "the profile must be written by the Rustc action")
+    asserts.equals(env, profile[0].path, _flag_value(action, "--profile-output"))
+
+    return analysistest.end(env)
+
+_profile_test = analysistest.make(
+    _profile_test_impl,
+    config_settings = {_PROFILE: True},
+)
+
+def _no_profile_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    asserts.false(env, OutputGroupInfo in target and hasattr(target[OutputGroupInfo], "rustc_profile"))
+    asserts.equals(env, None, _flag_value(_rustc_action(env), "--profile-output"))
+
+    return analysistest.end(env)
+
+_no_profile_test = analysistest.make(_no_profile_test_impl)
+
+def _profile_without_worker_pipelining_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    metadata = _rustc_action(env, "RustcMetadata")
+
+    # Only the full action writes the profile, so the actions can't share a compilation.
+    asserts.true(env, metadata.outputs.to_list()[0].path.endswith("-hollow.rlib"))
+    asserts.equals(env, None, _flag_value(metadata, "--pipelining-mode"))
+    asserts.equals(env, None, _flag_value(metadata, "--profile-output"))
+
+    return analysistest.end(env)
+
+_profile_without_worker_pipelining_test = analysistest.make(
+    _profile_without_worker_pipelining_test_impl,
+    config_settings = {
+        _PIPELINED_COMPILATION: True,
+        _PROFILE: True,
+        _WORKER: True,
+        _WORKER_PIPELINING: True,
+    },
+)
+
+def process_wrapper_settings_test_suite(name):
+    """Entry-point macro called from the BUILD file.
+
+    Args:
+        name (str): The name of the test suite.
+    """
+    write_file(
+        name = "lib_src",
+        out = "lib.rs",
+        content = [
+            "pub fn greeting() -> &'static str {",
+            "    \"Hello\"",
+            "}",
+            "",
+        ],
+    )
+
+    rust_library(
+        name = "lib",
+        srcs = [":lib.rs"],
+        edition = "2021",
+    )
+
+    write_file(
+        name = "bin_src",
+        out = "bin.rs",
+        content = [
+            "fn main() {",
+            "    println!(\"{}\", lib::greeting());",
+            "}",
+            "",
+        ],
+    )
+
+    rust_binary(
+        name = "bin",
+        srcs = [":bin.rs"],
+        edition = "2021",
+        deps = [":lib"],
+    )
+
+    _worker_test(
+        name = "worker_test",
+        target_under_test = ":lib",
+    )
+
+    _no_worker_test(
+        name = "no_worker_test",
+        target_under_test = ":lib",
+    )
+
+    _worker_pipelining_test(
+        name = "worker_pipelining_test",
+        target_under_test = ":lib",
+    )
+
+    _hollow_rlib_without_worker_test(
+        name = "hollow_rlib_without_worker_test",
+        target_under_test = ":lib",
+    )
+
+    _diagnostics_report_test(
+        name = "diagnostics_report_test",
+        target_under_test = ":lib",
+    )
+
+    _no_diagnostics_report_test(
+        name = "no_diagnostics_report_test",
+        target_under_test = ":lib",
+    )
+
+    with_process_wrapper_settings(
+        name = "lib_diagnostics_report",
+        target = ":lib",
+        diagnostics_report = "json",
+        output_group = "rustc_diagnostics_report",
+    )
+
+    build_test(
+        name = "diagnostics_report_build_test",
+        targets = [":lib_diagnostics_report"],
+    )
+
+    _diagnostic_path_remap_test(
+        name = "diagnostic_path_remap_test",
+        target_under_test = ":lib",
+    )
+
+    _no_diagnostic_path_remap_test(
+        name = "no_diagnostic_path_remap_test",
+        target_under_test = ":lib",
+    )
+
+    with_process_wrapper_settings(
+        name = "bin_diagnostic_path_remap",
+        target = ":bin",
+        diagnostic_path_remap = ["{bin_dir}/=bazel-bin/"],
+    )
+
+    build_test(
+        name = "diagnostic_path_remap_build_test",
+        targets = [":bin_diagnostic_path_remap"],
+    )
+
+    _profile_test(
+        name = "profile_test",
+        target_under_test = ":lib",
+    )
+
+    _no_profile_test(
+        name = "no_profile_test",
+        target_under_test = ":lib",
+    )
+
+    _profile_without_worker_pipelining_test(
+        name = "profile_without_worker_pipelining_test",
+        target_under_test = ":lib",
+    )
+
+    with_process_wrapper_settings(
+        name = "bin_profile",
+        target = ":bin",
+        profile = True,
+        output_group = "rustc_profile",
+    )
+
+    build_test(
+        name = "profile_build_test",
+        targets = [":bin_profile"],
+    )
+
+    with_process_wrapper_settings(
+        name = "bin_with_worker",
+        target = ":bin",
+        worker = True,
+    )
+
+    build_test(
+        name = "worker_build_test",
+        targets = [":bin_with_worker"],
+    )
+
+    with_process_wrapper_settings(
+        name = "bin_with_worker_pipelining",
+        target = ":bin",
+        worker = True,
+        worker_pipelining = True,
+    )
+
+    build_test(
+        name = "worker_pipelining_build_test",
+        targets = [":bin_with_worker_pipelining"],
+    )
+
+    native.test_suite(
+        name = name,
+        tests = [
+            ":diagnostic_path_remap_build_test",
+            ":diagnostic_path_remap_test",
+            ":diagnostics_report_build_test",
+            ":diagnostics_report_test",
+            ":hollow_rlib_without_worker_test",
+            ":no_diagnostic_path_remap_test",
+            ":no_diagnostics_report_test",
+            ":no_profile_test",
+            ":no_worker_test",
+            ":profile_build_test",
+            ":profile_test",
+            ":profile_without_worker_pipelining_test",
+            ":worker_build_test",
+            ":worker_pipelining_build_test",
+            ":worker_pipelining_test",
+            ":worker_test",
+        ],
+    )
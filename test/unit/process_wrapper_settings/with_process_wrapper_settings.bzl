@@ -0,0 +1,65 @@
+"""A rule for building targets with the process_wrapper settings enabled."""
+
+_DIAGNOSTIC_PATH_REMAP = str(Label("//rust/settings:rustc_diagnostic_path_remap"))
+_DIAGNOSTICS_REPORT = str(Label("//rust/settings:rustc_diagnostics_report"))
+_PIPELINED_COMPILATION = str(Label("//rust/settings:pipelined_compilation"))
+_PROFILE = str(Label("//rust/settings:rustc_profile"))
+_WORKER = str(Label("//rust/settings:experimental_process_wrapper_worker"))
+_WORKER_PIPELINING = str(Label("//rust/settings:experimental_worker_pipelining"))
+
+def _process_wrapper_settings_transition_impl(settings, attr):
+    return {
+        _DIAGNOSTIC_PATH_REMAP: attr.diagnostic_path_remap or settings[_DIAGNOSTIC_PATH_REMAP],
+        _DIAGNOSTICS_REPORT: attr.diagnostics_report or settings[_DIAGNOSTICS_REPORT],
+        _PIPELINED_COMPILATION: attr.worker_pipelining or settings[_PIPELINED_COMPILATION],
+        _PROFILE: attr.profile or settings[_PROFILE],
+        _WORKER: attr.worker,
+        _WORKER_PIPELINING: attr.worker_pipelining,
+    }
+
+_process_wrapper_settings_transition = transition(
+    implementation = _process_wrapper_settings_transition_impl,
+    inputs = [_DIAGNOSTIC_PATH_REMAP, _DIAGNOSTICS_REPORT, _PIPELINED_COMPILATION, _PROFILE],
+    outputs = [_DIAGNOSTIC_PATH_REMAP, _DIAGNOSTICS_REPORT, _PIPELINED_COMPILATION, _PROFILE, _WORKER, _WORKER_PIPELINING],
+)
+
+def _with_process_wrapper_settings_impl(ctx):
+    target = ctx.attr.target[0]
+    if ctx.attr.output_group:
+        files = target[OutputGroupInfo][ctx.attr.output_group]
+    else:
+        files = target[DefaultInfo].files
+    return [DefaultInfo(files = files)]
+
+with_process_wrapper_settings = rule(
+    doc = "Builds `target` with the given process_wrapper settings and exposes its outputs.",
+    implementation = _with_process_wrapper_settings_impl,
+    attrs = {
+        "diagnostic_path_remap": attr.string_list(
+            doc = "Value of `//rust/settings:rustc_diagnostic_path_remap`, if set.",
+        ),
+        "diagnostics_report": attr.string(
+            doc = "Value of `//rust/settings:rustc_diagnostics_report`, if set.",
+        ),
+        "output_group": attr.string(
+            doc = "The output group of `target` to expose. Defaults to its default outputs.",
+        ),
+        "profile": attr.bool(
+            doc = "Value of `//rust/settings:rustc_profile`, if set.",
+        ),
+        "target": attr.label(
+            doc = "The target to build.",
+            cfg = _process_wrapper_settings_transition,
+            mandatory = True,
+        ),
+        "worker": attr.bool(
+            doc = "Value of `//rust/settings:experimental_process_wrapper_worker`.",
+        ),
+        "worker_pipelining": attr.bool(
+            doc = "Value of `//rust/settings:experimental_worker_pipelining`. Also enables `pipelined_compilation`.",
+        ),
+        "_allowlist_function_transition": attr.label(
+            default = Label("//tools/allowlists/function_transition_allowlist"),
+        ),
+    },
+)
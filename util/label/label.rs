@@ -6,7 +6,6 @@ use label_error::LabelError;
 
 /// Parse and analyze given str.
 ///
-/// TODO: validate . and .. in target name
 /// TODO: validate used characters in target name
 pub fn analyze(input: &'_ str) -> Result<Label<'_>> {
     Label::analyze(input)
@@ -46,63 +45,9 @@ type Result<T, E = LabelError> = core::result::Result<T, E>;
 impl<'s> Label<'s> {
     /// Parse and analyze given str.
     pub fn analyze(input: &'s str) -> Result<Label<'s>> {
-        let label = input;
-
-        if label.is_empty() {
-            return Err(LabelError(err(
-                label,
-                "Empty string cannot be parsed into a label.",
-            )));
-        }
-
-        if label.starts_with(':') {
-            return match consume_name(input, label)? {
-                None => Err(LabelError(err(
-                    label,
-                    "Relative packages must have a name.",
-                ))),
-                Some(name) => Ok(Label::Relative { target_name: name }),
-            };
-        }
-
-        let (input, repository) = consume_repository_name(input, label)?;
-
-        // Shorthand labels such as `@repo` are expanded to `@repo//:repo`.
-        if input.is_empty() {
-            if let Some(ref repo) = repository {
-                let target_name = repo.repo_name();
-                if target_name.is_empty() {
-                    return Err(LabelError(err(
-                        label,
-                        "invalid target name: empty target name",
-                    )));
-                } else {
-                    return Ok(Label::Absolute {
-                        repository,
-                        package_name: "",
-                        target_name,
-                    });
-                };
-            }
-        }
-        let (input, package_name) = consume_package_name(input, label)?;
-        let name = consume_name(input, label)?;
-        let name = match (package_name, name) {
-            (None, None) => {
-                return Err(LabelError(err(
-                    label,
-                    "labels must have a package and/or a name.",
-                )))
-            }
-            (Some(package_name), None) => name_from_package(package_name),
-            (_, Some(name)) => name,
-        };
-
-        Ok(Label::Absolute {
-            repository,
-            package_name: package_name.unwrap_or_default(),
-            target_name: name,
-        })
+        parse(input)
+            .map(|spans| spans.label(input))
+            .map_err(|msg| LabelError(err(input, msg)))
     }
 
     pub fn is_relative(&self) -> bool {
@@ -148,191 +93,263 @@ fn err<'s>(label: &'s str, msg: &'s str) -> String {
     err_msg
 }
 
-fn consume_repository_name<'s>(
-    input: &'s str,
-    label: &'s str,
-) -> Result<(&'s str, Option<Repository<'s>>)> {
-    let at_signs = {
-        let mut count = 0;
-        for char in input.chars() {
-            if char == '@' {
-                count += 1;
-            } else {
-                break;
-            }
-        }
-        count
-    };
-    if at_signs == 0 {
-        return Ok((input, None));
-    }
-    if at_signs > 2 {
-        return Err(LabelError(err(label, "Unexpected number of leading `@`.")));
+/// The byte ranges of the parts of a label, as found by [parse].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LabelSpans {
+    /// The end of the repository name, including its leading `@`s, if the label has one.
+    repository_end: Option<usize>,
+
+    /// The range of the package name, or `None` for relative labels.
+    package: Option<(usize, usize)>,
+
+    /// The range of the target name.
+    name: (usize, usize),
+}
+
+impl LabelSpans {
+    pub const fn is_relative(&self) -> bool {
+        self.package.is_none()
     }
 
-    let slash_pos = input.find("//").unwrap_or(input.len());
-    let repository_name = &input[at_signs..slash_pos];
-
-    if !repository_name.is_empty() {
-        if !repository_name
-            .chars()
-            .next()
-            .unwrap()
-            .is_ascii_alphabetic()
-        {
-            return Err(LabelError(err(
-                label,
-                "workspace names must start with a letter.",
-            )));
-        }
-        if !repository_name
-            .chars()
-            // TODO: Disallow `~` in repository names once support for Bazel 7.2 is dropped.
-            .all(|c| {
-                c.is_ascii_alphanumeric()
-                    || c == '-'
-                    || c == '_'
-                    || c == '.'
-                    || c == '~'
-                    || c == '+'
-            })
-        {
-            return Err(LabelError(err(
-                label,
-                "workspace names \
-                may contain only A-Z, a-z, 0-9, '-', '_', '.', '+', and '~'.",
-            )));
+    /// The [Label] of the `input` these spans were parsed from.
+    pub fn label<'s>(&self, input: &'s str) -> Label<'s> {
+        let target_name = &input[self.name.0..self.name.1];
+        match self.package {
+            None => Label::Relative { target_name },
+            Some((start, end)) => Label::Absolute {
+                repository: self.repository_end.map(|repository_end| {
+                    let repository = &input[..repository_end];
+                    if repository.starts_with("@@") {
+                        Repository::Canonical(repository)
+                    } else {
+                        Repository::Apparent(repository)
+                    }
+                }),
+                package_name: &input[start..end],
+                target_name,
+            },
         }
     }
+}
 
-    let repository = if at_signs == 1 {
-        Repository::Apparent(&input[0..slash_pos])
-    } else if at_signs == 2 {
-        if repository_name.is_empty() {
-            return Err(LabelError(err(
-                label,
-                "main repository labels are only represented by a single `@`.",
-            )));
-        }
-        Repository::Canonical(&input[0..slash_pos])
-    } else {
-        return Err(LabelError(err(label, "Unexpected number of leading `@`.")));
-    };
+/// Parse given str into the ranges of its parts, returning why it isn't a legal label
+/// otherwise. Unlike [analyze], this is a `const fn`, so labels can be checked at compile time.
+pub const fn parse(input: &str) -> core::result::Result<LabelSpans, &'static str> {
+    let bytes = input.as_bytes();
+    let len = bytes.len();
 
-    Ok((&input[slash_pos..], Some(repository)))
-}
+    if len == 0 {
+        return Err("Empty string cannot be parsed into a label.");
+    }
 
-fn consume_package_name<'s>(input: &'s str, label: &'s str) -> Result<(&'s str, Option<&'s str>)> {
-    let is_absolute = match input.rfind("//") {
-        None => false,
-        Some(0) => true,
-        Some(_) => {
-            return Err(LabelError(err(
-                label,
-                "'//' cannot appear in the middle of the label.",
-            )));
+    if bytes[0] == b':' {
+        return match parse_name(bytes, 0) {
+            Ok(name) => Ok(LabelSpans {
+                repository_end: None,
+                package: None,
+                name,
+            }),
+            Err(msg) => Err(msg),
+        };
+    }
+
+    let mut at_signs = 0;
+    while at_signs < len && bytes[at_signs] == b'@' {
+        at_signs += 1;
+    }
+
+    let mut repository_end = None;
+    let mut rest = 0;
+    if at_signs > 0 {
+        if at_signs > 2 {
+            return Err("Unexpected number of leading `@`.");
         }
-    };
 
-    let (package_name, rest) = match (is_absolute, input.find(':')) {
-        (false, colon_pos) if (colon_pos != Some(0)) => {
-            return Err(LabelError(err(
-                label,
-                "relative packages are not permitted.",
-            )));
+        let slash_pos = match find_double_slash(bytes) {
+            Some(pos) => pos,
+            None => len,
+        };
+        if slash_pos > at_signs {
+            if !bytes[at_signs].is_ascii_alphabetic() {
+                return Err("workspace names must start with a letter.");
+            }
+            let mut i = at_signs;
+            while i < slash_pos {
+                let c = bytes[i];
+                // TODO: Disallow `~` in repository names once support for Bazel 7.2 is dropped.
+                if !(c.is_ascii_alphanumeric() || matches!(c, b'-' | b'_' | b'.' | b'~' | b'+')) {
+                    return Err("workspace names \
+                        may contain only A-Z, a-z, 0-9, '-', '_', '.', '+', and '~'.");
+                }
+                i += 1;
+            }
+        } else if at_signs == 2 {
+            return Err("main repository labels are only represented by a single `@`.");
         }
-        (_, colon_pos) => {
-            let (input, colon_pos) = if is_absolute {
-                (&input[2..], colon_pos.map(|cp| cp - 2))
-            } else {
-                (input, colon_pos)
-            };
-            match colon_pos {
-                Some(colon_pos) => (&input[0..colon_pos], &input[colon_pos..]),
-                None => (input, ""),
+
+        repository_end = Some(slash_pos);
+        rest = slash_pos;
+
+        // Shorthand labels such as `@repo` are expanded to `@repo//:repo`.
+        if rest == len {
+            if slash_pos == at_signs {
+                return Err("invalid target name: empty target name");
             }
+            return Ok(LabelSpans {
+                repository_end,
+                package: Some((len, len)),
+                name: (at_signs, len),
+            });
         }
+    }
+
+    let is_absolute = match rfind_double_slash(bytes, rest) {
+        None => false,
+        Some(pos) if pos == rest => true,
+        Some(_) => return Err("'//' cannot appear in the middle of the label."),
     };
 
-    if package_name.is_empty() {
-        return Ok((rest, None));
+    let colon_pos = find_colon(bytes, rest);
+    if !is_absolute && !matches!(colon_pos, Some(pos) if pos == rest) {
+        return Err("relative packages are not permitted.");
     }
 
-    if !package_name.chars().all(|c| {
-        c.is_ascii_alphanumeric()
-            || c == '/'
-            || c == '-'
-            || c == '.'
-            || c == ' '
-            || c == '$'
-            || c == '('
-            || c == ')'
-            || c == '_'
-            || c == '+'
-    }) {
-        return Err(LabelError(err(
-            label,
-            "package names may contain only A-Z, \
-        a-z, 0-9, '/', '-', '.', ' ', '$', '(', ')', '_', and '+'.",
-        )));
-    }
-    if package_name.ends_with('/') {
-        return Err(LabelError(err(
-            label,
-            "package names may not end with '/'.",
-        )));
-    }
+    let package_start = if is_absolute { rest + 2 } else { rest };
+    let package_end = match colon_pos {
+        Some(pos) => pos,
+        None => len,
+    };
+
+    if package_end > package_start {
+        let mut i = package_start;
+        while i < package_end {
+            let c = bytes[i];
+            if !(c.is_ascii_alphanumeric()
+                || matches!(
+                    c,
+                    b'/' | b'-' | b'.' | b' ' | b'$' | b'(' | b')' | b'_' | b'+'
+                ))
+            {
+                return Err("package names may contain only A-Z, \
+                    a-z, 0-9, '/', '-', '.', ' ', '$', '(', ')', '_', and '+'.");
+            }
+            i += 1;
+        }
+        if bytes[package_end - 1] == b'/' {
+            return Err("package names may not end with '/'.");
+        }
+        if has_dot_segment(bytes, package_start, package_end) {
+            return Err("package names may not contain '.' or '..' segments.");
+        }
 
-    if rest.is_empty() && is_absolute {
         // This label doesn't contain the target name, we have to use
         // last segment of the package name as target name.
-        return Ok((
-            match package_name.rfind('/') {
-                Some(pos) => &package_name[pos..],
-                None => package_name,
-            },
-            Some(package_name),
-        ));
+        if package_end == len && is_absolute {
+            let mut name_start = package_end;
+            while name_start > package_start && bytes[name_start - 1] != b'/' {
+                name_start -= 1;
+            }
+            return Ok(LabelSpans {
+                repository_end,
+                package: Some((package_start, package_end)),
+                name: (name_start, package_end),
+            });
+        }
     }
 
-    Ok((rest, Some(package_name)))
+    if package_end == len {
+        return Err("labels must have a package and/or a name.");
+    }
+
+    match parse_name(bytes, package_end) {
+        Ok(name) => Ok(LabelSpans {
+            repository_end,
+            package: Some((package_start, package_end)),
+            name,
+        }),
+        Err(msg) => Err(msg),
+    }
 }
 
-fn consume_name<'s>(input: &'s str, label: &'s str) -> Result<Option<&'s str>> {
-    if input.is_empty() {
-        return Ok(None);
+/// Parse the target name following the `:` at `bytes[colon_pos]`.
+const fn parse_name(
+    bytes: &[u8],
+    colon_pos: usize,
+) -> core::result::Result<(usize, usize), &'static str> {
+    let start = colon_pos + 1;
+    let end = bytes.len();
+    if start == end {
+        return Err("empty target name.");
     }
-    if input == ":" {
-        return Err(LabelError(err(label, "empty target name.")));
+    if bytes[start] == b'/' {
+        return Err("target names may not start with '/'.");
     }
-    let name = if let Some(stripped) = input.strip_prefix(':') {
-        stripped
-    } else if let Some(stripped) = input.strip_prefix("//") {
-        stripped
-    } else {
-        input.strip_prefix('/').unwrap_or(input)
-    };
+    if find_colon(bytes, start).is_some() {
+        return Err("target names may not contain with ':'.");
+    }
+    if bytes[end - 1] == b'/' {
+        return Err("target names may not end with '/'.");
+    }
+    if has_dot_segment(bytes, start, end) {
+        return Err("target names may not contain '.' or '..' segments.");
+    }
+    Ok((start, end))
+}
 
-    if name.starts_with('/') {
-        return Err(LabelError(err(
-            label,
-            "target names may not start with '/'.",
-        )));
+/// The position of the first `//` in `bytes`.
+const fn find_double_slash(bytes: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        if bytes[i] == b'/' && bytes[i + 1] == b'/' {
+            return Some(i);
+        }
+        i += 1;
     }
-    if name.starts_with(':') {
-        return Err(LabelError(err(
-            label,
-            "target names may not contain with ':'.",
-        )));
+    None
+}
+
+/// The position of the last `//` in `bytes[start..]`.
+const fn rfind_double_slash(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = bytes.len();
+    while i > start + 1 {
+        i -= 1;
+        if bytes[i - 1] == b'/' && bytes[i] == b'/' {
+            return Some(i - 1);
+        }
     }
-    Ok(Some(name))
+    None
 }
 
-fn name_from_package(package_name: &str) -> &str {
-    package_name
-        .rsplit_once('/')
-        .map(|tup| tup.1)
-        .unwrap_or(package_name)
+/// The position of the first `:` in `bytes[start..]`.
+const fn find_colon(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start;
+    while i < bytes.len() {
+        if bytes[i] == b':' {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Whether the `/` separated path in `bytes[start..end]` has a `.` or `..` segment.
+const fn has_dot_segment(bytes: &[u8], start: usize, end: usize) -> bool {
+    let mut segment_start = start;
+    let mut i = start;
+    while i <= end {
+        if i == end || bytes[i] == b'/' {
+            let segment_length = i - segment_start;
+            if (segment_length == 1 || segment_length == 2)
+                && bytes[segment_start] == b'.'
+                && bytes[i - 1] == b'.'
+            {
+                return true;
+            }
+            segment_start = i + 1;
+        }
+        i += 1;
+    }
+    false
 }
 
 #[cfg(test)]
@@ -606,4 +623,69 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_target_name_segments() -> Result<()> {
+        assert_eq!(
+            analyze("//foo/..:bar"),
+            Err(LabelError(
+                "//foo/..:bar must be a legal label; package names may not contain '.' or '..' segments."
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            analyze("//./foo"),
+            Err(LabelError(
+                "//./foo must be a legal label; package names may not contain '.' or '..' segments."
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            analyze("//foo:bar/../baz"),
+            Err(LabelError(
+                "//foo:bar/../baz must be a legal label; target names may not contain '.' or '..' segments."
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            analyze(":."),
+            Err(LabelError(
+                ":. must be a legal label; target names may not contain '.' or '..' segments."
+                    .to_string()
+            ))
+        );
+
+        assert_eq!(
+            analyze("//foo:bar:baz"),
+            Err(LabelError(
+                "//foo:bar:baz must be a legal label; target names may not contain with ':'."
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            analyze("//foo:bar/"),
+            Err(LabelError(
+                "//foo:bar/ must be a legal label; target names may not end with '/'.".to_string()
+            ))
+        );
+
+        assert_eq!(analyze("//.foo/bar..:...")?.package(), Some(".foo/bar.."));
+        assert_eq!(analyze("//.foo/bar..:...")?.name(), "...");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_in_const_context() {
+        const LABEL: &str = "@repo//foo/bar";
+        const SPANS: LabelSpans = match parse(LABEL) {
+            Ok(spans) => spans,
+            Err(msg) => panic!("{}", msg),
+        };
+        assert!(!SPANS.is_relative());
+        assert_eq!(SPANS.label(LABEL), analyze(LABEL).unwrap());
+
+        assert!(parse(":foo").unwrap().is_relative());
+        assert_eq!(parse("//bar:"), Err("empty target name."));
+    }
 }
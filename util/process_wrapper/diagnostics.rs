@@ -0,0 +1,493 @@
+// Copyright 2020 The Bazel Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Collects rustc json diagnostics into a structured report which can be
+//! consumed by tooling such as code review bots.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+
+use tinyjson::JsonValue;
+
+use crate::rustc::get_key;
+
+/// The version of the compact json report schema.
+const REPORT_VERSION: f64 = 1.0;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum DiagnosticsFormat {
+    // A compact json schema specific to the process wrapper.
+    #[default]
+    Json,
+    // SARIF 2.1.0, see https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+    Sarif,
+}
+
+/// A normalized rustc diagnostic, located at its primary span.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Diagnostic {
+    level: String,
+    code: Option<String>,
+    message: String,
+    file: Option<String>,
+    line_start: u64,
+    column_start: u64,
+    line_end: u64,
+    column_end: u64,
+}
+
+impl Diagnostic {
+    fn from_json(value: &JsonValue, subst_mappings: &[(String, String)]) -> Option<Self> {
+        let level = get_key(value, "level")?;
+        let message = get_key(value, "message")?;
+        let JsonValue::Object(map) = value else {
+            return None;
+        };
+        let code = match map.get("code") {
+            Some(code) => get_key(code, "code"),
+            None => None,
+        };
+        let spans = match map.get("spans") {
+            Some(JsonValue::Array(spans)) => spans.as_slice(),
+            _ => &[],
+        };
+
+        // Drop the summaries rustc emits at the end of a compilation, such as
+        // "aborting due to 2 previous errors" or "1 warning emitted".
+        if level == "failure-note"
+            || (spans.is_empty()
+                && code.is_none()
+                && (message.starts_with("aborting due to") || message.ends_with("emitted")))
+        {
+            return None;
+        }
+
+        let primary = spans.iter().find(|span| {
+            matches!(span, JsonValue::Object(span) if span.get("is_primary") == Some(&JsonValue::Boolean(true)))
+        });
+        let number = |key: &str| match primary {
+            Some(JsonValue::Object(span)) => match span.get(key) {
+                Some(JsonValue::Number(n)) => *n as u64,
+                _ => 0,
+            },
+            _ => 0,
+        };
+
+        Some(Self {
+            level,
+            code,
+            message,
+            file: primary
+                .and_then(|span| get_key(span, "file_name"))
+                .map(|file| relativize_path(&file, subst_mappings)),
+            line_start: number("line_start"),
+            column_start: number("column_start"),
+            line_end: number("line_end"),
+            column_end: number("column_end"),
+        })
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let mut map = HashMap::from([
+            ("level".to_owned(), JsonValue::String(self.level.clone())),
+            (
+                "message".to_owned(),
+                JsonValue::String(self.message.clone()),
+            ),
+            (
+                "code".to_owned(),
+                self.code.clone().map_or(JsonValue::Null, JsonValue::String),
+            ),
+        ]);
+        if let Some(file) = &self.file {
+            map.extend([
+                ("file".to_owned(), JsonValue::String(file.clone())),
+                ("line".to_owned(), JsonValue::Number(self.line_start as f64)),
+                (
+                    "column".to_owned(),
+                    JsonValue::Number(self.column_start as f64),
+                ),
+                (
+                    "end_line".to_owned(),
+                    JsonValue::Number(self.line_end as f64),
+                ),
+                (
+                    "end_column".to_owned(),
+                    JsonValue::Number(self.column_end as f64),
+                ),
+            ]);
+        }
+        JsonValue::Object(map)
+    }
+
+    fn to_sarif(&self) -> JsonValue {
+        let level = match self.level.as_str() {
+            "error" | "error: internal compiler error" => "error",
+            "warning" => "warning",
+            _ => "note",
+        };
+        let mut result = HashMap::from([
+            ("level".to_owned(), JsonValue::String(level.to_owned())),
+            (
+                "message".to_owned(),
+                JsonValue::Object(HashMap::from([(
+                    "text".to_owned(),
+                    JsonValue::String(self.message.clone()),
+                )])),
+            ),
+        ]);
+        if let Some(code) = &self.code {
+            result.insert("ruleId".to_owned(), JsonValue::String(code.clone()));
+        }
+        if let Some(file) = &self.file {
+            let region = HashMap::from([
+                (
+                    "startLine".to_owned(),
+                    JsonValue::Number(self.line_start as f64),
+                ),
+                (
+                    "startColumn".to_owned(),
+                    JsonValue::Number(self.column_start as f64),
+                ),
+                (
+                    "endLine".to_owned(),
+                    JsonValue::Number(self.line_end as f64),
+                ),
+                (
+                    "endColumn".to_owned(),
+                    JsonValue::Number(self.column_end as f64),
+                ),
+            ]);
+            let physical_location = HashMap::from([
+                (
+                    "artifactLocation".to_owned(),
+                    JsonValue::Object(HashMap::from([(
+                        "uri".to_owned(),
+                        JsonValue::String(file.clone()),
+                    )])),
+                ),
+                ("region".to_owned(), JsonValue::Object(region)),
+            ]);
+            result.insert(
+                "locations".to_owned(),
+                JsonValue::Array(vec![JsonValue::Object(HashMap::from([(
+                    "physicalLocation".to_owned(),
+                    JsonValue::Object(physical_location),
+                )]))]),
+            );
+        }
+        JsonValue::Object(result)
+    }
+}
+
+/// Rewrites absolute paths under any of the `--subst` mapped directories
+/// (e.g. `${pwd}`, the execution root) to be relative to them, so paths
+/// match the layout of the source workspace.
+pub(crate) fn relativize_path(path: &str, subst_mappings: &[(String, String)]) -> String {
+    let path = path.replace('\\', "/");
+    for (_, dir) in subst_mappings {
+        let dir = dir.replace('\\', "/");
+        if dir.is_empty() {
+            continue;
+        }
+        if let Some(relative) = path
+            .strip_prefix(dir.trim_end_matches('/'))
+            .and_then(|p| p.strip_prefix('/'))
+        {
+            return relative.to_owned();
+        }
+    }
+    path.strip_prefix("./").unwrap_or(&path).to_owned()
+}
+
+/// A deduplicated collection of the diagnostics emitted by rustc.
+#[derive(Debug, Default)]
+pub(crate) struct DiagnosticsReport {
+    subst_mappings: Vec<(String, String)>,
+    diagnostics: Vec<Diagnostic>,
+    seen: HashSet<Diagnostic>,
+}
+
+impl DiagnosticsReport {
+    pub(crate) fn new(subst_mappings: Vec<(String, String)>) -> Self {
+        Self {
+            subst_mappings,
+            ..Self::default()
+        }
+    }
+
+    /// Records a diagnostic parsed from rustc's json output.
+    pub(crate) fn record(&mut self, diagnostic: &JsonValue) {
+        if let Some(diagnostic) = Diagnostic::from_json(diagnostic, &self.subst_mappings) {
+            if self.seen.insert(diagnostic.clone()) {
+                self.diagnostics.push(diagnostic);
+            }
+        }
+    }
+
+    fn to_json(&self, format: DiagnosticsFormat) -> JsonValue {
+        match format {
+            DiagnosticsFormat::Json => JsonValue::Object(HashMap::from([
+                ("version".to_owned(), JsonValue::Number(REPORT_VERSION)),
+                (
+                    "diagnostics".to_owned(),
+                    JsonValue::Array(self.diagnostics.iter().map(Diagnostic::to_json).collect()),
+                ),
+            ])),
+            DiagnosticsFormat::Sarif => {
+                let rules = self
+                    .diagnostics
+                    .iter()
+                    .filter_map(|d| d.code.clone())
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .map(|code| {
+                        JsonValue::Object(HashMap::from([(
+                            "id".to_owned(),
+                            JsonValue::String(code),
+                        )]))
+                    })
+                    .collect();
+                let driver = HashMap::from([
+                    ("name".to_owned(), JsonValue::String("rustc".to_owned())),
+                    (
+                        "informationUri".to_owned(),
+                        JsonValue::String("https://doc.rust-lang.org/rustc/".to_owned()),
+                    ),
+                    ("rules".to_owned(), JsonValue::Array(rules)),
+                ]);
+                let run = HashMap::from([
+                    (
+                        "tool".to_owned(),
+                        JsonValue::Object(HashMap::from([(
+                            "driver".to_owned(),
+                            JsonValue::Object(driver),
+                        )])),
+                    ),
+                    (
+                        "results".to_owned(),
+                        JsonValue::Array(
+                            self.diagnostics.iter().map(Diagnostic::to_sarif).collect(),
+                        ),
+                    ),
+                ]);
+                JsonValue::Object(HashMap::from([
+                    (
+                        "$schema".to_owned(),
+                        JsonValue::String(
+                            "https://json.schemastore.org/sarif-2.1.0.json".to_owned(),
+                        ),
+                    ),
+                    ("version".to_owned(), JsonValue::String("2.1.0".to_owned())),
+                    (
+                        "runs".to_owned(),
+                        JsonValue::Array(vec![JsonValue::Object(run)]),
+                    ),
+                ]))
+            }
+        }
+    }
+
+    /// Writes the report to `path` in the requested format.
+    pub(crate) fn write(&self, path: &str, format: DiagnosticsFormat) -> Result<(), String> {
+        let mut contents = String::new();
+        stringify_sorted(&self.to_json(format), &mut contents)?;
+        fs::write(path, contents).map_err(|e| format!("{} writing path: {:?}", e, path))
+    }
+}
+
+/// Serializes `value` like `JsonValue::stringify`, but with the keys of each
+/// object in sorted order. The report is an action output, so its contents
+/// must not depend on the iteration order of a `HashMap`.
+fn stringify_sorted(value: &JsonValue, out: &mut String) -> Result<(), String> {
+    match value {
+        JsonValue::Object(map) => {
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                stringify_sorted(&JsonValue::String(key.clone()), out)?;
+                out.push(':');
+                stringify_sorted(value, out)?;
+            }
+            out.push('}');
+        }
+        JsonValue::Array(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                stringify_sorted(value, out)?;
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.stringify().map_err(|e| e.to_string())?),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_json(json_str: &str) -> JsonValue {
+        json_str.parse::<JsonValue>().unwrap()
+    }
+
+    const UNUSED_VARIABLE: &str = r#"
+        {
+            "$message_type": "diagnostic",
+            "message": "unused variable: `x`",
+            "code": {"code": "unused_variables", "explanation": null},
+            "level": "warning",
+            "spans": [
+                {
+                    "file_name": "/execroot/_main/src/lib.rs",
+                    "line_start": 1,
+                    "line_end": 1,
+                    "column_start": 25,
+                    "column_end": 26,
+                    "is_primary": true
+                }
+            ],
+            "children": [],
+            "rendered": "warning: unused variable: `x`"
+        }
+    "#;
+
+    fn subst_mappings() -> Vec<(String, String)> {
+        vec![("pwd".to_owned(), "/execroot/_main".to_owned())]
+    }
+
+    #[test]
+    fn test_relativize_path() {
+        let mappings = subst_mappings();
+        assert_eq!(
+            relativize_path("/execroot/_main/src/lib.rs", &mappings),
+            "src/lib.rs"
+        );
+        assert_eq!(relativize_path("./src/lib.rs", &mappings), "src/lib.rs");
+        assert_eq!(
+            relativize_path("/execroot/_main_other/lib.rs", &mappings),
+            "/execroot/_main_other/lib.rs"
+        );
+    }
+
+    #[test]
+    fn test_report_json_deduplicates() {
+        let mut report = DiagnosticsReport::new(subst_mappings());
+        report.record(&parse_json(UNUSED_VARIABLE));
+        report.record(&parse_json(UNUSED_VARIABLE));
+        report.record(&parse_json(
+            r#"{"message": "1 warning emitted", "code": null, "level": "warning", "spans": [], "rendered": ""}"#,
+        ));
+
+        assert_eq!(
+            report.to_json(DiagnosticsFormat::Json),
+            parse_json(
+                r#"
+                {
+                    "version": 1,
+                    "diagnostics": [
+                        {
+                            "level": "warning",
+                            "code": "unused_variables",
+                            "message": "unused variable: `x`",
+                            "file": "src/lib.rs",
+                            "line": 1,
+                            "column": 25,
+                            "end_line": 1,
+                            "end_column": 26
+                        }
+                    ]
+                }
+            "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_report_is_written_with_sorted_keys() {
+        let mut report = DiagnosticsReport::new(subst_mappings());
+        report.record(&parse_json(UNUSED_VARIABLE));
+
+        let path = std::env::temp_dir().join(format!("pw_diagnostics_{}.json", std::process::id()));
+        report
+            .write(path.to_str().unwrap(), DiagnosticsFormat::Json)
+            .unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(
+            contents,
+            concat!(
+                r#"{"diagnostics":[{"code":"unused_variables","column":25,"end_column":26,"#,
+                r#""end_line":1,"file":"src/lib.rs","level":"warning","line":1,"#,
+                r#""message":"unused variable: `x`"}],"version":1}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_report_sarif() {
+        let mut report = DiagnosticsReport::new(subst_mappings());
+        report.record(&parse_json(UNUSED_VARIABLE));
+
+        assert_eq!(
+            report.to_json(DiagnosticsFormat::Sarif),
+            parse_json(
+                r#"
+                {
+                    "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+                    "version": "2.1.0",
+                    "runs": [
+                        {
+                            "tool": {
+                                "driver": {
+                                    "name": "rustc",
+                                    "informationUri": "https://doc.rust-lang.org/rustc/",
+                                    "rules": [{"id": "unused_variables"}]
+                                }
+                            },
+                            "results": [
+                                {
+                                    "ruleId": "unused_variables",
+                                    "level": "warning",
+                                    "message": {"text": "unused variable: `x`"},
+                                    "locations": [
+                                        {
+                                            "physicalLocation": {
+                                                "artifactLocation": {"uri": "src/lib.rs"},
+                                                "region": {
+                                                    "startLine": 1,
+                                                    "startColumn": 25,
+                                                    "endLine": 1,
+                                                    "endColumn": 26
+                                                }
+                                            }
+                                        }
+                                    ]
+                                }
+                            ]
+                        }
+                    ]
+                }
+            "#
+            )
+        );
+    }
+}
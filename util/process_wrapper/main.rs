@@ -12,28 +12,38 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod diagnostics;
 mod flags;
 mod options;
 mod output;
+mod pipelining;
+mod profile;
 mod rustc;
 mod util;
+mod worker;
 
 use std::collections::HashMap;
 #[cfg(windows)]
 use std::collections::{HashSet, VecDeque};
+use std::env;
 use std::fmt;
 use std::fs::{self, copy, OpenOptions};
 use std::io;
 use std::path::PathBuf;
 use std::process::{exit, Command, Stdio};
+use std::thread;
+use std::time::Instant;
 #[cfg(windows)]
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use tinyjson::JsonValue;
 
-use crate::options::options;
+use crate::diagnostics::DiagnosticsReport;
+use crate::options::{options, Options};
 use crate::output::{process_output, LineOutput};
-use crate::rustc::ErrorFormat;
+use crate::pipelining::EmitOutputs;
+use crate::profile::Profile;
+use crate::rustc::{ErrorFormat, RustcMessage};
 #[cfg(windows)]
 use crate::util::read_file_to_array;
 
@@ -275,7 +285,15 @@ fn json_warning(line: &str) -> JsonValue {
 fn process_line(
     mut line: String,
     format: ErrorFormat,
+    path_mappings: &[(String, String)],
+    on_message: &mut dyn FnMut(RustcMessage),
 ) -> Result<LineOutput, String> {
+    // Pass timings are consumed by the process wrapper rather than forwarded,
+    // as they are not valid json diagnostics.
+    if let Some((pass, seconds)) = rustc::parse_pass_timing(&line) {
+        on_message(RustcMessage::PassTiming(&pass, seconds));
+        return Ok(LineOutput::Skip);
+    }
     // LLVM can emit lines that look like the following, and these will be interspersed
     // with the regular JSON output. Arguably, rustc should be fixed not to emit lines
     // like these (or to convert them to JSON), but for now we convert them to JSON
@@ -289,19 +307,32 @@ fn process_line(
             return Ok(LineOutput::Skip);
         }
     }
-    rustc::process_json(line, format)
+    rustc::process_json(line, format, path_mappings, on_message)
 }
 
-fn main() -> Result<(), ProcessWrapperError> {
-    let opts = options().map_err(|e| ProcessWrapperError(e.to_string()))?;
-
+/// Spawns the child process described by `opts` and waits for it to complete,
+/// returning its exit code.
+///
+/// When `captured_output` is provided (persistent worker mode), the child's
+/// stdout and processed stderr are collected into it rather than being
+/// forwarded to the wrapper's own stdout and stderr, unless explicitly
+/// redirected to files.
+///
+/// `on_metadata` is invoked as soon as rustc reports that the crate metadata
+/// was written.
+fn run(
+    opts: Options,
+    mut captured_output: Option<&mut Vec<u8>>,
+    on_metadata: &mut dyn FnMut(),
+) -> Result<i32, ProcessWrapperError> {
     let (child_arguments, dep_dir_cleanup) =
         consolidate_dependency_search_paths(&opts.child_arguments)?;
     let mut temp_dir_guard = TemporaryDirectoryGuard::new(dep_dir_cleanup);
 
-    let mut command = Command::new(opts.executable);
+    let executable = opts.executable;
+    let mut command = Command::new(&executable);
     command
-        .args(child_arguments)
+        .args(&child_arguments)
         .env_clear()
         .envs(opts.child_environment)
         .stdout(if let Some(stdout_file) = opts.stdout_file {
@@ -312,16 +343,30 @@ fn main() -> Result<(), ProcessWrapperError> {
                 .open(stdout_file)
                 .map_err(|e| ProcessWrapperError(format!("unable to open stdout file: {}", e)))?
                 .into()
+        } else if captured_output.is_some() {
+            Stdio::piped()
         } else {
             Stdio::inherit()
         })
         .stderr(Stdio::piped());
     debug_log!("{:#?}", command);
+    let start = Instant::now();
     let mut child = command
         .spawn()
         .map_err(|e| ProcessWrapperError(format!("failed to spawn child process: {}", e)))?;
 
-    let mut stderr: Box<dyn io::Write> = if let Some(stderr_file) = opts.stderr_file {
+    // Drain stdout on a separate thread so a chatty child can't block on a
+    // full pipe while stderr is being processed.
+    let stdout_reader = child.stdout.take().map(|mut child_stdout| {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = io::Read::read_to_end(&mut child_stdout, &mut buf);
+            buf
+        })
+    });
+
+    let mut stderr_buffer = Vec::new();
+    let mut stderr: Box<dyn io::Write + '_> = if let Some(stderr_file) = opts.stderr_file {
         Box::new(
             OpenOptions::new()
                 .create(true)
@@ -330,6 +375,8 @@ fn main() -> Result<(), ProcessWrapperError> {
                 .open(stderr_file)
                 .map_err(|e| ProcessWrapperError(format!("unable to open stderr file: {}", e)))?,
         )
+    } else if captured_output.is_some() {
+        Box::new(&mut stderr_buffer)
     } else {
         Box::new(io::stderr())
     };
@@ -351,12 +398,30 @@ fn main() -> Result<(), ProcessWrapperError> {
         None
     };
 
+    let mut passes = Vec::new();
+    let subst_mappings = opts.subst_mappings;
+    let diagnostic_path_mappings = opts.diagnostic_path_mappings;
+    let diagnostics_output = opts.diagnostics_output;
+    let mut diagnostics_report = diagnostics_output
+        .as_ref()
+        .map(|_| DiagnosticsReport::new(subst_mappings));
+    let mut on_message = |message: RustcMessage| match message {
+        RustcMessage::Artifact("metadata") => on_metadata(),
+        RustcMessage::Artifact(_) => {}
+        RustcMessage::Diagnostic(diagnostic) => {
+            if let Some(report) = diagnostics_report.as_mut() {
+                report.record(diagnostic);
+            }
+        }
+        RustcMessage::PassTiming(pass, seconds) => passes.push((pass.to_owned(), seconds)),
+    };
+
     let result = if let Some(format) = opts.rustc_output_format {
         process_output(
             &mut child_stderr,
             stderr.as_mut(),
             output_file.as_mut(),
-            move |line| process_line(line, format),
+            move |line| process_line(line, format, &diagnostic_path_mappings, &mut on_message),
         )
     } else {
         // Process output normally by forwarding stderr
@@ -367,12 +432,42 @@ fn main() -> Result<(), ProcessWrapperError> {
             move |line| Ok(LineOutput::Message(line)),
         )
     };
+    drop(stderr);
     result.map_err(|e| ProcessWrapperError(format!("failed to process stderr: {}", e)))?;
+    if let (Some((path, format)), Some(report)) = (&diagnostics_output, &diagnostics_report) {
+        report.write(path, *format).map_err(|e| {
+            ProcessWrapperError(format!("failed to write diagnostics output: {}", e))
+        })?;
+    }
+
+    let (status, usage) = if opts.profile_output.is_some() {
+        profile::wait_with_resource_usage(&mut child)
+    } else {
+        child.wait().map(|status| (status, None))
+    }
+    .map_err(|e| ProcessWrapperError(format!("failed to wait for child process: {}", e)))?;
+    let wall_time = start.elapsed();
+
+    if let Some(output) = captured_output.as_mut() {
+        if let Some(reader) = stdout_reader {
+            output.extend(reader.join().unwrap_or_default());
+        }
+        output.extend(stderr_buffer);
+    }
 
-    let status = child
-        .wait()
-        .map_err(|e| ProcessWrapperError(format!("failed to wait for child process: {}", e)))?;
     let code = status.code().unwrap_or(1);
+    if let Some(profile_output) = opts.profile_output {
+        Profile {
+            crate_name: profile::find_crate_name(&child_arguments),
+            executable,
+            exit_code: code,
+            wall_time,
+            usage,
+            passes,
+        }
+        .write(&profile_output)
+        .map_err(|e| ProcessWrapperError(format!("failed to write profile output: {}", e)))?;
+    }
     if code == 0 {
         if let Some(tf) = opts.touch_file {
             OpenOptions::new()
@@ -382,6 +477,14 @@ fn main() -> Result<(), ProcessWrapperError> {
                 .open(tf)
                 .map_err(|e| ProcessWrapperError(format!("failed to create touch file: {}", e)))?;
         }
+        if let Some((path, link_args)) = opts.link_args_output {
+            fs::write(&path, response_file(&link_args)).map_err(|e| {
+                ProcessWrapperError(format!(
+                    "failed to write linker arguments to {}: {}",
+                    path, e
+                ))
+            })?;
+        }
         if let Some((copy_source, copy_dest)) = opts.copy_output {
             copy(&copy_source, &copy_dest).map_err(|e| {
                 ProcessWrapperError(format!(
@@ -396,6 +499,51 @@ fn main() -> Result<(), ProcessWrapperError> {
         let _ = fs::remove_dir_all(path);
     }
 
+    Ok(code)
+}
+
+/// Formats `args` as a GCC style response file, as read by the GCC and Clang
+/// linker drivers, quoting arguments which contain whitespace, quotes or
+/// backslashes.
+fn response_file(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\')
+            {
+                format!("\"{}\"\n", arg.replace('\\', "\\\\").replace('"', "\\\""))
+            } else {
+                format!("{}\n", arg)
+            }
+        })
+        .collect()
+}
+
+fn main() -> Result<(), ProcessWrapperError> {
+    let args: Vec<String> = env::args().collect();
+    if worker::is_persistent_worker(&args) {
+        return worker::run_worker(&args);
+    }
+
+    let mut opts = options().map_err(|e| ProcessWrapperError(e.to_string()))?;
+    // Without a worker to share a rustc invocation with, each half of a
+    // pipelined compilation compiles on its own and drops the other's output.
+    let discarded = match opts.pipelining.take().and_then(|pipelining| {
+        let outputs = EmitOutputs::from_args(&opts.child_arguments)?;
+        Some(outputs.discard_other_half(pipelining.mode, &env::temp_dir()))
+    }) {
+        Some((outputs, discarded)) => {
+            outputs
+                .apply(&mut opts.child_arguments)
+                .map_err(|e| ProcessWrapperError(format!("unable to rewrite --emit: {}", e)))?;
+            Some(discarded)
+        }
+        None => None,
+    };
+    let code = run(opts, None, &mut || {})?;
+    if let Some(path) = discarded {
+        let _ = fs::remove_file(path);
+    }
+
     exit(code)
 }
 
@@ -407,6 +555,24 @@ mod test {
         json_str.parse::<JsonValue>().map_err(|e| e.to_string())
     }
 
+    #[test]
+    fn test_response_file() {
+        let args = [
+            "-lz",
+            "-Wl,-rpath,$ORIGIN/my libs",
+            "C:\\lib",
+            "say \"hi\"",
+            "",
+        ]
+        .iter()
+        .map(|&s| s.to_owned())
+        .collect::<Vec<_>>();
+        assert_eq!(
+            response_file(&args),
+            "-lz\n\"-Wl,-rpath,$ORIGIN/my libs\"\n\"C:\\\\lib\"\n\"say \\\"hi\\\"\"\n\"\"\n"
+        );
+    }
+
     #[test]
     fn test_process_line_diagnostic_json() -> Result<(), String> {
         let LineOutput::Message(msg) = process_line(
@@ -418,6 +584,8 @@ mod test {
             "#
             .to_string(),
             ErrorFormat::Json,
+            &[],
+            &mut |_| {},
         )?
         else {
             return Err("Expected a LineOutput::Message".to_string());
@@ -447,6 +615,8 @@ mod test {
             "#
             .to_string(),
             ErrorFormat::Rendered,
+            &[],
+            &mut |_| {},
         )?
         else {
             return Err("Expected a LineOutput::Message".to_string());
@@ -461,10 +631,8 @@ mod test {
             "'+zaamo' is not a recognized feature for this target (ignoring feature)",
             " WARN rustc_errors::emitter Invalid span...",
         ] {
-            let LineOutput::Message(msg) = process_line(
-                text.to_string(),
-                ErrorFormat::Json,
-            )?
+            let LineOutput::Message(msg) =
+                process_line(text.to_string(), ErrorFormat::Json, &[], &mut |_| {})?
             else {
                 return Err("Expected a LineOutput::Message".to_string());
             };
@@ -499,6 +667,8 @@ mod test {
             "#
                 .to_string(),
                 ErrorFormat::Rendered,
+                &[],
+                &mut |_| {},
             )?,
             LineOutput::Skip
         ));
@@ -517,9 +687,34 @@ mod test {
             "#
                 .to_string(),
                 ErrorFormat::Rendered,
+                &[],
+                &mut |_| {},
             )?,
             LineOutput::Skip
         ));
         Ok(())
     }
+
+    #[test]
+    fn test_process_line_emit_notifies_artifact() -> Result<(), String> {
+        let mut emitted = Vec::new();
+        let mut on_message = |message: RustcMessage| {
+            if let RustcMessage::Artifact(emit) = message {
+                emitted.push(emit.to_owned());
+            }
+        };
+        for (artifact, emit) in [("libfoo.rmeta", "metadata"), ("libfoo.rlib", "link")] {
+            process_line(
+                format!(
+                    r#"{{"$message_type": "artifact", "artifact": "{}", "emit": "{}"}}"#,
+                    artifact, emit
+                ),
+                ErrorFormat::Rendered,
+                &[],
+                &mut on_message,
+            )?;
+        }
+        assert_eq!(emitted, vec!["metadata".to_owned(), "link".to_owned()]);
+        Ok(())
+    }
 }
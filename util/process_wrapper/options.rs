@@ -1,11 +1,15 @@
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::env;
 use std::fmt;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::{self, Write};
 use std::process::exit;
 
+use crate::diagnostics::DiagnosticsFormat;
 use crate::flags::{FlagParseError, Flags, ParseOutcome};
+use crate::pipelining::{Pipelining, PipeliningMode};
 use crate::rustc;
 use crate::util::*;
 
@@ -13,6 +17,8 @@ use crate::util::*;
 pub(crate) enum OptionError {
     FlagError(FlagParseError),
     Generic(String),
+    // `--help` was passed. Contains the help text.
+    Help(String),
 }
 
 impl fmt::Display for OptionError {
@@ -20,6 +26,7 @@ impl fmt::Display for OptionError {
         match self {
             Self::FlagError(e) => write!(f, "error parsing flags: {e}"),
             Self::Generic(s) => write!(f, "{s}"),
+            Self::Help(help) => write!(f, "{help}"),
         }
     }
 }
@@ -46,9 +53,44 @@ pub(crate) struct Options {
     pub(crate) output_file: Option<String>,
     // This controls the output format of rustc messages.
     pub(crate) rustc_output_format: Option<rustc::ErrorFormat>,
+    // If set, this invocation shares a single rustc process with the other
+    // half of a pipelined compilation. Only used in persistent worker mode.
+    pub(crate) pipelining: Option<Pipelining>,
+    // If set, writes a report of the rustc diagnostics to this file in the
+    // given format. Requires --rustc-output-format.
+    pub(crate) diagnostics_output: Option<(String, DiagnosticsFormat)>,
+    // The --subst mappings, used to relativize paths in diagnostics.
+    pub(crate) subst_mappings: Vec<(String, String)>,
+    // Path prefixes to rewrite in the file names of rustc diagnostics.
+    pub(crate) diagnostic_path_mappings: Vec<(String, String)>,
+    // If set, writes the timing and resource usage of the child process to
+    // this file as json.
+    pub(crate) profile_output: Option<String>,
+    // If set to (path, args), writes the linker arguments passed via argument
+    // files to path as a linker response file.
+    pub(crate) link_args_output: Option<(String, Vec<String>)>,
 }
 
 pub(crate) fn options() -> Result<Options, OptionError> {
+    match options_from_args(env::args().collect(), &mut read_file_to_array) {
+        Err(OptionError::Help(help)) => {
+            eprintln!("{help}");
+            exit(0);
+        }
+        result => result,
+    }
+}
+
+/// Parses process wrapper options out of `argv`, using `read_file` to load the
+/// contents of `--env-file` and `--arg-file` files. This allows persistent
+/// workers to reuse file contents across requests.
+///
+/// Unlike `options`, this never exits the process, so that a persistent worker
+/// can report problems with a single request back to Bazel.
+pub(crate) fn options_from_args(
+    argv: Vec<String>,
+    read_file: &mut dyn FnMut(&str) -> Result<Vec<String>, String>,
+) -> Result<Options, OptionError> {
     // Process argument list until -- is encountered.
     // Everything after is sent to the child process.
     let mut subst_mapping_raw = None;
@@ -56,12 +98,20 @@ pub(crate) fn options() -> Result<Options, OptionError> {
     let mut volatile_status_file_raw = None;
     let mut env_file_raw = None;
     let mut arg_file_raw = None;
+    let mut keyed_arg_file_raw = None;
+    let mut arg_file_key = None;
     let mut touch_file = None;
     let mut copy_output_raw = None;
     let mut stdout_file = None;
     let mut stderr_file = None;
     let mut output_file = None;
     let mut rustc_output_format_raw = None;
+    let mut pipelining_mode_raw = None;
+    let mut diagnostics_output = None;
+    let mut diagnostics_format_raw = None;
+    let mut diagnostic_path_remap_raw = None;
+    let mut profile_output = None;
+    let mut link_args_output_raw = None;
     let mut flags = Flags::new();
     let mut require_explicit_unstable_features = None;
     flags.define_repeated_flag("--subst", "", &mut subst_mapping_raw);
@@ -77,6 +127,17 @@ pub(crate) fn options() -> Result<Options, OptionError> {
         "File(s) containing command line arguments to pass to the child process.",
         &mut arg_file_raw,
     );
+    flags.define_repeated_flag(
+        "--keyed-arg-file",
+        "File(s) containing KEY=ARG lines. ARG is passed to the child process if KEY is '*' \
+         or matches --arg-file-key.",
+        &mut keyed_arg_file_raw,
+    );
+    flags.define_flag(
+        "--arg-file-key",
+        "The key selecting which arguments of the --keyed-arg-file files to use.",
+        &mut arg_file_key,
+    );
     flags.define_flag(
         "--touch-file",
         "Create this file after the child process runs successfully.",
@@ -109,15 +170,64 @@ pub(crate) fn options() -> Result<Options, OptionError> {
          other -Zallow-features= is present in the rustc flags.",
         &mut require_explicit_unstable_features,
     );
+    flags.define_flag(
+        "--pipelining-mode",
+        "Which half of a single invocation pipelined compilation this request is. \
+         Valid values: metadata, full.",
+        &mut pipelining_mode_raw,
+    );
+    flags.define_flag(
+        "--diagnostics-output",
+        "Write a deduplicated report of the rustc diagnostics to this file. \
+         Requires --rustc-output-format.",
+        &mut diagnostics_output,
+    );
+    flags.define_flag(
+        "--diagnostics-format",
+        "The format of the --diagnostics-output report. Valid values: json (default), sarif.",
+        &mut diagnostics_format_raw,
+    );
+    flags.define_repeated_flag(
+        "--diagnostic-path-remap",
+        "FROM=TO pairs of path prefixes to rewrite in rustc diagnostics, e.g. \
+         'bazel-out/k8-fastbuild/bin/='. Substitutions such as ${pwd} are applied to both.",
+        &mut diagnostic_path_remap_raw,
+    );
+    flags.define_flag(
+        "--profile-output",
+        "Write the wall time, CPU time, peak memory usage and (with \
+         -Ztime-passes-format=json) pass timings of the subprocess to this file.",
+        &mut profile_output,
+    );
+    flags.define_flag(
+        "--link-args-output",
+        "Write the -Clink-arg and -Clink-args flags of the --arg-file and --keyed-arg-file \
+         files to this file as a linker response file, for outputs linked by another action.",
+        &mut link_args_output_raw,
+    );
 
-    let mut child_args = match flags
-        .parse(env::args().collect())
-        .map_err(OptionError::FlagError)?
-    {
-        ParseOutcome::Help(help) => {
-            eprintln!("{help}");
-            exit(0);
-        }
+    let mut param_files = argv
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .filter_map(|arg| arg.strip_prefix("--flagfile="))
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    let argv = expand_flagfiles(argv)?;
+    // Both halves of a pipelined compilation share a rustc invocation only if
+    // they agree on how to invoke rustc.
+    let pipelined_child_argv = argv
+        .iter()
+        .skip_while(|arg| *arg != "--")
+        .cloned()
+        .collect::<Vec<_>>();
+    param_files.extend(
+        pipelined_child_argv
+            .iter()
+            .filter_map(|arg| arg.strip_prefix('@'))
+            .map(str::to_owned),
+    );
+    let mut child_args = match flags.parse(argv).map_err(OptionError::FlagError)? {
+        ParseOutcome::Help(help) => return Err(OptionError::Help(help)),
         ParseOutcome::Parsed(p) => p,
     };
     let current_dir = std::env::current_dir()
@@ -141,12 +251,32 @@ pub(crate) fn options() -> Result<Options, OptionError> {
             Ok((key.to_owned(), v))
         })
         .collect::<Result<Vec<(String, String)>, OptionError>>()?;
-    let stable_stamp_mappings =
-        stable_status_file_raw.map_or_else(Vec::new, |s| read_stamp_status_to_array(s).unwrap());
-    let volatile_stamp_mappings =
-        volatile_status_file_raw.map_or_else(Vec::new, |s| read_stamp_status_to_array(s).unwrap());
-    let environment_file_block = env_from_files(env_file_raw.unwrap_or_default())?;
-    let mut file_arguments = args_from_file(arg_file_raw.unwrap_or_default())?;
+    let read_stamp_status = |path: Option<String>| {
+        path.map_or_else(
+            || Ok(Vec::new()),
+            |path| {
+                read_stamp_status_to_array(path.clone()).map_err(|e| {
+                    OptionError::Generic(format!("failed to read status file {path}: {e}"))
+                })
+            },
+        )
+    };
+    let stable_stamp_mappings = read_stamp_status(stable_status_file_raw)?;
+    let volatile_stamp_mappings = read_stamp_status(volatile_status_file_raw)?;
+    let environment_file_block = env_from_files(env_file_raw.unwrap_or_default(), read_file)?;
+    let mut file_arguments = args_from_file(arg_file_raw.unwrap_or_default(), read_file)?;
+    file_arguments.append(&mut keyed_args_from_file(
+        keyed_arg_file_raw.unwrap_or_default(),
+        arg_file_key.as_deref(),
+        read_file,
+    )?);
+    let link_args_output = link_args_output_raw.map(|path| {
+        let link_args = file_arguments
+            .iter()
+            .flat_map(|arg| linker_args(&prepare_arg(arg.clone(), &subst_mappings)))
+            .collect();
+        (path, link_args)
+    });
     // Process --copy-output
     let copy_output = copy_output_raw
         .map(|co| {
@@ -177,6 +307,65 @@ pub(crate) fn options() -> Result<Options, OptionError> {
         })
         .transpose()?;
 
+    let pipelining = pipelining_mode_raw
+        .map(|mode| match mode.as_str() {
+            "metadata" => Ok(PipeliningMode::Metadata),
+            "full" => Ok(PipeliningMode::Full),
+            _ => Err(OptionError::Generic(format!(
+                "invalid --pipelining-mode '{mode}'",
+            ))),
+        })
+        .transpose()?
+        .map(|mode| {
+            Ok(Pipelining {
+                key: pipelining_key(&pipelined_child_argv, read_file)?,
+                mode,
+                param_files,
+            })
+        })
+        .transpose()?;
+
+    let diagnostics_format = diagnostics_format_raw
+        .map(|v| match v.as_str() {
+            "json" => Ok(DiagnosticsFormat::Json),
+            "sarif" => Ok(DiagnosticsFormat::Sarif),
+            _ => Err(OptionError::Generic(format!(
+                "invalid --diagnostics-format '{v}'",
+            ))),
+        })
+        .transpose()?;
+    let diagnostics_output = match (diagnostics_output, diagnostics_format) {
+        (Some(path), format) => Some((path, format.unwrap_or_default())),
+        (None, None) => None,
+        (None, Some(_)) => {
+            return Err(OptionError::Generic(
+                "\"--diagnostics-format\" requires \"--diagnostics-output\"".to_owned(),
+            ))
+        }
+    };
+    // The report is built from the messages parsed out of rustc's json output.
+    if diagnostics_output.is_some() && rustc_output_format.is_none() {
+        return Err(OptionError::Generic(
+            "\"--diagnostics-output\" requires \"--rustc-output-format\"".to_owned(),
+        ));
+    }
+
+    let diagnostic_path_mappings = diagnostic_path_remap_raw
+        .unwrap_or_default()
+        .into_iter()
+        .map(|arg| {
+            let (from, to) = arg.split_once('=').ok_or_else(|| {
+                OptionError::Generic(format!(
+                    "\"--diagnostic-path-remap\" expects FROM=TO, got '{arg}'"
+                ))
+            })?;
+            Ok((
+                prepare_arg(from.to_owned(), &subst_mappings),
+                prepare_arg(to.to_owned(), &subst_mappings),
+            ))
+        })
+        .collect::<Result<Vec<(String, String)>, OptionError>>()?;
+
     // Prepare the environment variables, unifying those read from files with the ones
     // of the current process.
     let vars = environment_block(
@@ -215,13 +404,89 @@ pub(crate) fn options() -> Result<Options, OptionError> {
         stderr_file,
         output_file,
         rustc_output_format,
+        pipelining,
+        diagnostics_output,
+        subst_mappings,
+        diagnostic_path_mappings,
+        profile_output,
+        link_args_output,
     })
 }
 
-fn args_from_file(paths: Vec<String>) -> Result<Vec<String>, OptionError> {
+/// Returns the arguments rustc passes on to the linker for `arg`.
+fn linker_args(arg: &str) -> Vec<String> {
+    if let Some(link_arg) = arg.strip_prefix("-Clink-arg=") {
+        vec![link_arg.to_owned()]
+    } else if let Some(link_args) = arg.strip_prefix("-Clink-args=") {
+        link_args.split_whitespace().map(str::to_owned).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+/// Hashes the child arguments shared by both halves of a pipelined
+/// compilation. The process wrapper flags, which name the outputs of each half,
+/// are left out, as are rustc's `--emit` flag and the paths of `@` param files,
+/// which Bazel names after each action. The contents of param files are hashed
+/// instead.
+fn pipelining_key(
+    child_argv: &[String],
+    read_file: &mut dyn FnMut(&str) -> Result<Vec<String>, String>,
+) -> Result<u64, OptionError> {
+    let mut hasher = DefaultHasher::new();
+    let mut hash_arg = |arg: &str| {
+        if !arg.starts_with("--emit=") {
+            arg.hash(&mut hasher);
+        }
+    };
+    for arg in child_argv {
+        match arg.strip_prefix('@') {
+            Some(path) => read_file(path)
+                .map_err(|e| OptionError::Generic(format!("failed to read param file: {e}")))?
+                .iter()
+                .for_each(|arg| hash_arg(arg)),
+            None => hash_arg(arg),
+        }
+    }
+    Ok(hasher.finish())
+}
+
+/// Replaces `--flagfile=<path>` arguments preceding `--` with the lines of the
+/// file. Actions which support persistent workers pass their arguments in such
+/// files, which Bazel forwards unexpanded both to workers and to one-shot
+/// invocations of the process wrapper.
+fn expand_flagfiles(argv: Vec<String>) -> Result<Vec<String>, OptionError> {
+    let mut expanded = Vec::with_capacity(argv.len());
+    let mut argv = argv.into_iter();
+    for arg in argv.by_ref() {
+        if arg == "--" {
+            expanded.push(arg);
+            break;
+        }
+        let Some(path) = arg.strip_prefix("--flagfile=") else {
+            expanded.push(arg);
+            continue;
+        };
+        let lines = read_file_to_array(path)
+            .map_err(|e| OptionError::Generic(format!("failed to read flagfile: {e}")))?;
+        let has_child_args = lines.iter().any(|line| line == "--");
+        expanded.extend(lines);
+        if has_child_args {
+            break;
+        }
+    }
+    // Arguments after `--` belong to the child process.
+    expanded.extend(argv);
+    Ok(expanded)
+}
+
+fn args_from_file(
+    paths: Vec<String>,
+    read_file: &mut dyn FnMut(&str) -> Result<Vec<String>, String>,
+) -> Result<Vec<String>, OptionError> {
     let mut args = vec![];
     for path in paths.iter() {
-        let mut lines = read_file_to_array(path).map_err(|err| {
+        let mut lines = read_file(path).map_err(|err| {
             OptionError::Generic(format!(
                 "{} while processing args from file paths: {:?}",
                 err, &paths
@@ -232,10 +497,34 @@ fn args_from_file(paths: Vec<String>) -> Result<Vec<String>, OptionError> {
     Ok(args)
 }
 
-fn env_from_files(paths: Vec<String>) -> Result<HashMap<String, String>, OptionError> {
+/// Reads the arguments of `KEY=ARG` lines whose key is `*` or `key`.
+fn keyed_args_from_file(
+    paths: Vec<String>,
+    key: Option<&str>,
+    read_file: &mut dyn FnMut(&str) -> Result<Vec<String>, String>,
+) -> Result<Vec<String>, OptionError> {
+    let mut args = vec![];
+    for path in paths.iter() {
+        let lines = read_file(path).map_err(OptionError::Generic)?;
+        for line in lines.into_iter() {
+            let (k, arg) = line.split_once('=').ok_or_else(|| {
+                OptionError::Generic(format!("keyed arg file line {line:?} is not KEY=ARG"))
+            })?;
+            if k == "*" || Some(k) == key {
+                args.push(arg.to_owned());
+            }
+        }
+    }
+    Ok(args)
+}
+
+fn env_from_files(
+    paths: Vec<String>,
+    read_file: &mut dyn FnMut(&str) -> Result<Vec<String>, String>,
+) -> Result<HashMap<String, String>, OptionError> {
     let mut env_vars = HashMap::new();
     for path in paths.into_iter() {
-        let lines = read_file_to_array(&path).map_err(OptionError::Generic)?;
+        let lines = read_file(&path).map_err(OptionError::Generic)?;
         for line in lines.into_iter() {
             let (k, v) = line
                 .split_once('=')
@@ -464,4 +753,202 @@ mod test {
             )])
         );
     }
+
+    #[test]
+    fn test_expand_flagfiles() {
+        let dir = std::env::temp_dir().join(format!("pw_flagfiles_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let wrapper_flags = dir.join("wrapper.params");
+        std::fs::write(&wrapper_flags, "--subst\npwd=${pwd}\n").unwrap();
+        let child = dir.join("child.params");
+        std::fs::write(&child, "--\nrustc\n").unwrap();
+
+        let argv = vec![
+            "process_wrapper".to_owned(),
+            format!("--flagfile={}", wrapper_flags.display()),
+            format!("--flagfile={}", child.display()),
+            "@rustc.params".to_owned(),
+            "--flagfile=not_expanded".to_owned(),
+        ];
+        assert_eq!(
+            expand_flagfiles(argv).unwrap(),
+            vec![
+                "process_wrapper",
+                "--subst",
+                "pwd=${pwd}",
+                "--",
+                "rustc",
+                "@rustc.params",
+                "--flagfile=not_expanded",
+            ]
+        );
+
+        assert!(expand_flagfiles(vec![
+            "process_wrapper".to_owned(),
+            "--flagfile=does/not/exist".to_owned(),
+        ])
+        .is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_diagnostics_output_requires_rustc_output_format() {
+        let parse = |flags: &[&str]| {
+            let argv = std::iter::once("process_wrapper")
+                .chain(flags.iter().copied())
+                .chain(["--", "rustc"])
+                .map(|s| s.to_owned())
+                .collect();
+            options_from_args(argv, &mut |path: &str| {
+                Err(format!("unexpected read of {path}"))
+            })
+        };
+
+        let err = parse(&["--diagnostics-output", "report.json"]).unwrap_err();
+        assert!(err.to_string().contains("--rustc-output-format"), "{}", err);
+
+        let opts = parse(&[
+            "--diagnostics-output",
+            "report.sarif",
+            "--diagnostics-format",
+            "sarif",
+            "--rustc-output-format",
+            "rendered",
+        ])
+        .unwrap();
+        assert_eq!(
+            opts.diagnostics_output,
+            Some(("report.sarif".to_owned(), DiagnosticsFormat::Sarif))
+        );
+    }
+
+    #[test]
+    fn test_link_args_output() {
+        let opts = options_from_args(
+            [
+                "process_wrapper",
+                "--arg-file",
+                "build_flags",
+                "--keyed-arg-file",
+                "bin_linkflags",
+                "--arg-file-key",
+                "foo",
+                "--subst",
+                "pwd=/exec_root",
+                "--link-args-output",
+                "foo.link_args",
+                "--",
+                "rustc",
+            ]
+            .iter()
+            .map(|&s| s.to_owned())
+            .collect(),
+            &mut |path: &str| match path {
+                "build_flags" => Ok(vec![
+                    "--cfg=foo".to_owned(),
+                    "-Clink-arg=-Wl,--as-needed".to_owned(),
+                ]),
+                "bin_linkflags" => Ok(vec![
+                    "foo=-Clink-args=-L${pwd}/lib -lz".to_owned(),
+                    "bar=-Clink-arg=-lbar".to_owned(),
+                ]),
+                _ => Err(format!("file not found: {}", path)),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            opts.link_args_output,
+            Some((
+                "foo.link_args".to_owned(),
+                vec![
+                    "-Wl,--as-needed".to_owned(),
+                    "-L/exec_root/lib".to_owned(),
+                    "-lz".to_owned()
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_pipelining_key() {
+        let argv = |args: &[&str]| -> Vec<String> { args.iter().map(|&s| s.to_owned()).collect() };
+        let mut read_file = |path: &str| -> Result<Vec<String>, String> {
+            match path {
+                "libfoo.rlib-0.params" | "libfoo.rmeta-0.params" => {
+                    Ok(argv(&["lib.rs", "--emit=metadata=a.rmeta,link=a.rlib"]))
+                }
+                "libbar.rlib-0.params" => Ok(argv(&["bar.rs"])),
+                _ => Err(format!("file not found: {}", path)),
+            }
+        };
+
+        // Param file paths and `--emit` don't matter, only their contents.
+        let full = pipelining_key(
+            &argv(&["--", "rustc", "@libfoo.rlib-0.params"]),
+            &mut read_file,
+        )
+        .unwrap();
+        assert_eq!(
+            pipelining_key(
+                &argv(&["--", "rustc", "@libfoo.rmeta-0.params"]),
+                &mut read_file
+            )
+            .unwrap(),
+            full
+        );
+        assert_eq!(
+            pipelining_key(
+                &argv(&[
+                    "--",
+                    "rustc",
+                    "lib.rs",
+                    "--emit=metadata=b.rmeta,link=b.rlib"
+                ]),
+                &mut read_file
+            )
+            .unwrap(),
+            full
+        );
+        assert_ne!(
+            pipelining_key(
+                &argv(&["--", "rustc", "@libbar.rlib-0.params"]),
+                &mut read_file
+            )
+            .unwrap(),
+            full
+        );
+        assert!(
+            pipelining_key(&argv(&["--", "rustc", "@missing.params"]), &mut read_file).is_err()
+        );
+    }
+
+    #[test]
+    fn test_keyed_args_from_file() {
+        let mut read_file = |filename: &str| -> Result<Vec<String>, String> {
+            match filename {
+                "bin_linkflags" => Ok(vec![
+                    "*=-Clink-arg=-Wl,--all".to_owned(),
+                    "foo=-Clink-arg=-Wl,--foo=1".to_owned(),
+                    "bar=-Clink-arg=-Wl,--bar".to_owned(),
+                ]),
+                "malformed" => Ok(vec!["-Clink-args".to_owned()]),
+                _ => Err(format!("file not found: {}", filename)),
+            }
+        };
+
+        assert_eq!(
+            keyed_args_from_file(
+                vec!["bin_linkflags".to_owned()],
+                Some("foo"),
+                &mut read_file
+            )
+            .unwrap(),
+            vec!["-Clink-arg=-Wl,--all", "-Clink-arg=-Wl,--foo=1"]
+        );
+        assert_eq!(
+            keyed_args_from_file(vec!["bin_linkflags".to_owned()], None, &mut read_file).unwrap(),
+            vec!["-Clink-arg=-Wl,--all"]
+        );
+        assert!(keyed_args_from_file(vec!["malformed".to_owned()], None, &mut read_file).is_err());
+    }
 }
@@ -0,0 +1,690 @@
+// Copyright 2020 The Bazel Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Single invocation pipelined compilation.
+//!
+//! Rather than running rustc once to produce metadata and a second time to
+//! produce the full rlib, the metadata and full actions of a crate can share a
+//! single `--emit=metadata=<rmeta>,link=<rlib>` rustc invocation when the
+//! process wrapper runs as a persistent worker. Whichever request arrives first
+//! starts rustc. The metadata request completes as soon as rustc reports that
+//! the `.rmeta` file was written, while the full request waits for rustc to
+//! exit.
+//!
+//! Requests are matched on a hash of the rustc arguments (with param files
+//! expanded and `--emit` left out) and the digests of their inputs other than
+//! param files, so halves which don't agree on how to invoke rustc each run
+//! their own compilation. rustc writes its outputs to
+//! scratch paths, and each request moves the output it declares into place.
+//! That way a half whose counterpart never runs, e.g. because it was a cache
+//! hit, doesn't write to outputs Bazel didn't declare for it. Compilations
+//! which one half never claimed are discarded after a timeout.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a finished compilation waits for its other half to be requested.
+const ORPHAN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum PipeliningMode {
+    Metadata,
+    Full,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Pipelining {
+    // Identifies the rustc invocation shared by both requests.
+    pub(crate) key: u64,
+    pub(crate) mode: PipeliningMode,
+    // The paths of the param files of the request. Bazel names these after
+    // the action, so they differ between the two halves.
+    pub(crate) param_files: Vec<String>,
+}
+
+/// The `metadata` and `link` outputs of rustc's `--emit` flag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EmitOutputs {
+    pub(crate) metadata: PathBuf,
+    pub(crate) link: PathBuf,
+}
+
+impl EmitOutputs {
+    /// Finds the outputs in the `--emit=` flags of `args`, including those in
+    /// `@` param files. Returns `None` unless both are given explicit paths.
+    pub(crate) fn from_args(args: &[String]) -> Option<Self> {
+        let mut metadata = None;
+        let mut link = None;
+        let mut find = |arg: &str| {
+            let Some(emit) = arg.strip_prefix("--emit=") else {
+                return;
+            };
+            for kind in emit.split(',') {
+                match kind.split_once('=') {
+                    Some(("metadata", path)) => metadata = Some(PathBuf::from(path)),
+                    Some(("link", path)) => link = Some(PathBuf::from(path)),
+                    _ => {}
+                }
+            }
+        };
+        for arg in args {
+            match arg.strip_prefix('@') {
+                Some(path) => fs::read_to_string(path).ok()?.lines().for_each(&mut find),
+                None => find(arg),
+            }
+        }
+        Some(Self {
+            metadata: metadata?,
+            link: link?,
+        })
+    }
+
+    /// Rewrites the `--emit=` flags of `args` to write to these paths instead.
+    /// Flags in `@` param files are rewritten in place, so these must be
+    /// copies owned by the process wrapper, such as the `.expanded` files it
+    /// writes when applying substitutions.
+    pub(crate) fn apply(&self, args: &mut [String]) -> io::Result<()> {
+        for arg in args.iter_mut() {
+            match arg.strip_prefix('@') {
+                Some(path) => {
+                    let contents = fs::read_to_string(path)?;
+                    if contents.lines().any(|line| line.starts_with("--emit=")) {
+                        let lines = contents
+                            .lines()
+                            .map(|line| self.rewrite(line).unwrap_or_else(|| line.to_owned()))
+                            .collect::<Vec<_>>();
+                        fs::write(path, lines.join("\n") + "\n")?;
+                    }
+                }
+                None => {
+                    if let Some(rewritten) = self.rewrite(arg) {
+                        *arg = rewritten;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `arg` rewritten to emit to these paths if it is an `--emit=`
+    /// flag.
+    fn rewrite(&self, arg: &str) -> Option<String> {
+        let emit = arg.strip_prefix("--emit=")?;
+        let kinds = emit
+            .split(',')
+            .map(|kind| match kind.split_once('=') {
+                Some(("metadata", _)) => format!("metadata={}", self.metadata.display()),
+                Some(("link", _)) => format!("link={}", self.link.display()),
+                _ => kind.to_owned(),
+            })
+            .collect::<Vec<_>>();
+        Some(format!("--emit={}", kinds.join(",")))
+    }
+
+    /// Used when the process wrapper doesn't run as a worker, and each half
+    /// runs its own compilation. Returns the outputs with the one belonging to
+    /// the other half moved into `scratch_dir`, along with its path so that
+    /// it can be removed afterwards.
+    pub(crate) fn discard_other_half(
+        &self,
+        mode: PipeliningMode,
+        scratch_dir: &Path,
+    ) -> (Self, PathBuf) {
+        let scratch_path = |path: &Path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            scratch_dir.join(format!("{}-{}", std::process::id(), name))
+        };
+        let mut outputs = self.clone();
+        let discarded = match mode {
+            PipeliningMode::Metadata => &mut outputs.link,
+            PipeliningMode::Full => &mut outputs.metadata,
+        };
+        *discarded = scratch_path(discarded);
+        let discarded = discarded.clone();
+        (outputs, discarded)
+    }
+
+    fn remove(&self) {
+        let _ = fs::remove_file(&self.metadata);
+        let _ = fs::remove_file(&self.link);
+    }
+}
+
+#[derive(Debug, Default)]
+struct CompilationState {
+    metadata_ready: bool,
+    // The exit code and output of rustc once it terminated.
+    result: Option<(i32, String)>,
+    finished_at: Option<Instant>,
+    metadata_claimed: bool,
+    full_claimed: bool,
+}
+
+impl CompilationState {
+    fn claimed(&mut self, mode: PipeliningMode) -> &mut bool {
+        match mode {
+            PipeliningMode::Metadata => &mut self.metadata_claimed,
+            PipeliningMode::Full => &mut self.full_claimed,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Compilation {
+    state: Mutex<CompilationState>,
+    changed: Condvar,
+    // Where rustc writes its outputs until a request moves them into place.
+    scratch: EmitOutputs,
+}
+
+impl Compilation {
+    fn update(&self, f: impl FnOnce(&mut CompilationState)) {
+        f(&mut self.state.lock().unwrap());
+        self.changed.notify_all();
+    }
+}
+
+impl Drop for Compilation {
+    fn drop(&mut self) {
+        self.scratch.remove();
+    }
+}
+
+/// Tracks the rustc invocations shared between pipelined requests.
+#[derive(Debug)]
+pub(crate) struct PipelinedCompilations {
+    compilations: Mutex<HashMap<u64, Arc<Compilation>>>,
+    scratch_dir: PathBuf,
+    orphan_timeout: Duration,
+    next_id: AtomicU64,
+}
+
+impl PipelinedCompilations {
+    /// Creates an empty set of compilations whose outputs are written to
+    /// `scratch_dir` until they are claimed.
+    pub(crate) fn new(scratch_dir: PathBuf) -> Self {
+        Self::with_orphan_timeout(scratch_dir, ORPHAN_TIMEOUT)
+    }
+
+    fn with_orphan_timeout(scratch_dir: PathBuf, orphan_timeout: Duration) -> Self {
+        Self {
+            compilations: Mutex::default(),
+            scratch_dir,
+            orphan_timeout,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Handles one half of a pipelined compilation whose rustc invocation
+    /// writes to `outputs`, returning the exit code and output to respond
+    /// with.
+    ///
+    /// If no compilation with the same key is waiting for this half, `compile`
+    /// is started on a background thread. It is passed the scratch paths to
+    /// emit to instead of `outputs`, and a callback which must be invoked once
+    /// the crate metadata has been written.
+    pub(crate) fn run<F>(
+        &self,
+        pipelining: &Pipelining,
+        outputs: &EmitOutputs,
+        compile: F,
+    ) -> (i32, String)
+    where
+        F: FnOnce(&EmitOutputs, &mut dyn FnMut()) -> (i32, String) + Send + 'static,
+    {
+        let compilation = match self.claim(pipelining, outputs, compile) {
+            Ok(compilation) => compilation,
+            Err(e) => return (1, format!("failed to start pipelined compilation: {e}\n")),
+        };
+
+        let state = compilation.state.lock().unwrap();
+        match pipelining.mode {
+            PipeliningMode::Metadata => {
+                let state = compilation
+                    .changed
+                    .wait_while(state, |state| {
+                        !state.metadata_ready && state.result.is_none()
+                    })
+                    .unwrap();
+                match &state.result {
+                    // rustc failed before producing metadata.
+                    Some((code, output)) if *code != 0 && !state.metadata_ready => {
+                        (*code, output.clone())
+                    }
+                    // Diagnostics are reported once, by the full request.
+                    _ => move_output(&compilation.scratch.metadata, &outputs.metadata),
+                }
+            }
+            PipeliningMode::Full => {
+                let state = compilation
+                    .changed
+                    .wait_while(state, |state| state.result.is_none())
+                    .unwrap();
+                match state.result.clone().unwrap() {
+                    (0, output) => match move_output(&compilation.scratch.link, &outputs.link) {
+                        (0, _) => (0, output),
+                        error => error,
+                    },
+                    failure => failure,
+                }
+            }
+        }
+    }
+
+    /// Finds the compilation waiting for `pipelining.mode`, or starts a new
+    /// one.
+    fn claim<F>(
+        &self,
+        pipelining: &Pipelining,
+        outputs: &EmitOutputs,
+        compile: F,
+    ) -> Result<Arc<Compilation>, String>
+    where
+        F: FnOnce(&EmitOutputs, &mut dyn FnMut()) -> (i32, String) + Send + 'static,
+    {
+        let mut compilations = self.compilations.lock().unwrap();
+        self.evict_orphans(&mut compilations);
+
+        if let Some(compilation) = compilations.get(&pipelining.key) {
+            let mut state = compilation.state.lock().unwrap();
+            if !*state.claimed(pipelining.mode) {
+                *state.claimed(pipelining.mode) = true;
+                drop(state);
+                // Both halves hold on to the compilation, so it no longer
+                // needs to be tracked.
+                return Ok(compilations.remove(&pipelining.key).unwrap());
+            }
+        }
+
+        // Either this is the first half to arrive, or this half is being run
+        // again (e.g. because its output was deleted). In the latter case the
+        // previous compilation can't be trusted to match the current inputs.
+        fs::create_dir_all(&self.scratch_dir)
+            .map_err(|e| format!("unable to create {}: {}", self.scratch_dir.display(), e))?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let scratch_path = |path: &Path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            self.scratch_dir.join(format!("{id}-{name}"))
+        };
+        let compilation = Arc::new(Compilation {
+            state: Mutex::default(),
+            changed: Condvar::new(),
+            scratch: EmitOutputs {
+                metadata: scratch_path(&outputs.metadata),
+                link: scratch_path(&outputs.link),
+            },
+        });
+        *compilation.state.lock().unwrap().claimed(pipelining.mode) = true;
+
+        let background = Arc::clone(&compilation);
+        thread::spawn(move || {
+            let result = compile(&background.scratch, &mut || {
+                background.update(|state| state.metadata_ready = true)
+            });
+            background.update(|state| {
+                state.result = Some(result);
+                state.finished_at = Some(Instant::now());
+            });
+        });
+        compilations.insert(pipelining.key, Arc::clone(&compilation));
+        Ok(compilation)
+    }
+
+    /// Drops finished compilations whose other half wasn't requested in time.
+    fn evict_orphans(&self, compilations: &mut HashMap<u64, Arc<Compilation>>) {
+        compilations.retain(|_, compilation| {
+            let orphaned = compilation
+                .state
+                .lock()
+                .unwrap()
+                .finished_at
+                .is_some_and(|finished_at| finished_at.elapsed() >= self.orphan_timeout);
+            if orphaned {
+                // rustc has exited, so its outputs can be removed right away.
+                compilation.scratch.remove();
+            }
+            !orphaned
+        });
+    }
+}
+
+impl Drop for PipelinedCompilations {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.scratch_dir);
+    }
+}
+
+/// Moves a scratch output to the path declared by the request.
+fn move_output(scratch: &Path, declared: &Path) -> (i32, String) {
+    let result = fs::rename(scratch, declared).or_else(|_| {
+        // The scratch directory may be on a different file system.
+        fs::copy(scratch, declared)?;
+        fs::remove_file(scratch)
+    });
+    match result {
+        Ok(()) => (0, String::new()),
+        Err(e) => (
+            1,
+            format!(
+                "failed to move {} to {}: {}\n",
+                scratch.display(),
+                declared.display(),
+                e
+            ),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::mpsc;
+
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("pw_pipelining_{}_{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn compilations(&self, orphan_timeout: Duration) -> Arc<PipelinedCompilations> {
+            Arc::new(PipelinedCompilations::with_orphan_timeout(
+                self.0.join("scratch"),
+                orphan_timeout,
+            ))
+        }
+
+        fn outputs(&self) -> EmitOutputs {
+            EmitOutputs {
+                metadata: self.0.join("libfoo.rmeta"),
+                link: self.0.join("libfoo.rlib"),
+            }
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn pipelining(mode: PipeliningMode) -> Pipelining {
+        Pipelining {
+            key: 42,
+            mode,
+            param_files: Vec::new(),
+        }
+    }
+
+    fn write_metadata(scratch: &EmitOutputs, on_metadata: &mut dyn FnMut()) {
+        fs::write(&scratch.metadata, "rmeta").unwrap();
+        on_metadata();
+    }
+
+    fn write_link(scratch: &EmitOutputs) {
+        fs::write(&scratch.link, "rlib").unwrap();
+    }
+
+    #[test]
+    fn test_emit_outputs() {
+        let mut args = vec![
+            "--crate-name=foo".to_owned(),
+            "--emit=dep-info,metadata=out/libfoo.rmeta,link=out/libfoo.rlib".to_owned(),
+        ];
+        let outputs = EmitOutputs::from_args(&args).unwrap();
+        assert_eq!(outputs.metadata, PathBuf::from("out/libfoo.rmeta"));
+        assert_eq!(outputs.link, PathBuf::from("out/libfoo.rlib"));
+
+        EmitOutputs {
+            metadata: PathBuf::from("tmp/0-libfoo.rmeta"),
+            link: PathBuf::from("tmp/0-libfoo.rlib"),
+        }
+        .apply(&mut args)
+        .unwrap();
+        assert_eq!(
+            args[1],
+            "--emit=dep-info,metadata=tmp/0-libfoo.rmeta,link=tmp/0-libfoo.rlib"
+        );
+
+        assert_eq!(
+            EmitOutputs::from_args(&["--emit=dep-info,metadata,link".to_owned()]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_emit_outputs_in_param_file() {
+        let dir = TestDir::new("param_file");
+        let params = dir.0.join("libfoo.rlib-0.params.expanded");
+        fs::write(
+            &params,
+            "lib.rs\n--emit=dep-info,metadata=out/libfoo.rmeta,link=out/libfoo.rlib\n",
+        )
+        .unwrap();
+        let mut args = vec![format!("@{}", params.display())];
+        let outputs = EmitOutputs::from_args(&args).unwrap();
+        assert_eq!(outputs.metadata, PathBuf::from("out/libfoo.rmeta"));
+        assert_eq!(outputs.link, PathBuf::from("out/libfoo.rlib"));
+
+        EmitOutputs {
+            metadata: PathBuf::from("tmp/0-libfoo.rmeta"),
+            link: PathBuf::from("tmp/0-libfoo.rlib"),
+        }
+        .apply(&mut args)
+        .unwrap();
+        assert_eq!(args, vec![format!("@{}", params.display())]);
+        assert_eq!(
+            fs::read_to_string(&params).unwrap(),
+            "lib.rs\n--emit=dep-info,metadata=tmp/0-libfoo.rmeta,link=tmp/0-libfoo.rlib\n"
+        );
+
+        assert_eq!(
+            EmitOutputs::from_args(&["@does/not/exist".to_owned()]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_discard_other_half() {
+        let outputs = EmitOutputs {
+            metadata: PathBuf::from("out/libfoo.rmeta"),
+            link: PathBuf::from("out/libfoo.rlib"),
+        };
+        let (metadata, discarded) =
+            outputs.discard_other_half(PipeliningMode::Metadata, Path::new("tmp"));
+        assert_eq!(metadata.metadata, outputs.metadata);
+        assert_eq!(metadata.link, discarded);
+        assert!(discarded.starts_with("tmp"));
+
+        let (full, discarded) = outputs.discard_other_half(PipeliningMode::Full, Path::new("tmp"));
+        assert_eq!(full.link, outputs.link);
+        assert_eq!(full.metadata, discarded);
+    }
+
+    #[test]
+    fn test_metadata_completes_before_full() {
+        let dir = TestDir::new("metadata_first");
+        let compilations = dir.compilations(ORPHAN_TIMEOUT);
+        let outputs = dir.outputs();
+        let (finish_tx, finish_rx) = mpsc::channel::<()>();
+
+        let metadata = compilations.run(
+            &pipelining(PipeliningMode::Metadata),
+            &outputs,
+            move |scratch, on_metadata| {
+                write_metadata(scratch, on_metadata);
+                // Keep "compiling" until the test allows it to finish.
+                finish_rx.recv().unwrap();
+                write_link(scratch);
+                (0, "warning: unused".to_owned())
+            },
+        );
+        assert_eq!(metadata, (0, String::new()));
+        assert_eq!(fs::read_to_string(&outputs.metadata).unwrap(), "rmeta");
+        assert!(!outputs.link.exists());
+
+        let full = {
+            let compilations = Arc::clone(&compilations);
+            let outputs = outputs.clone();
+            thread::spawn(move || {
+                compilations.run(&pipelining(PipeliningMode::Full), &outputs, |_, _| {
+                    panic!("the full request should reuse the running compilation")
+                })
+            })
+        };
+        finish_tx.send(()).unwrap();
+        assert_eq!(full.join().unwrap(), (0, "warning: unused".to_owned()));
+        assert_eq!(fs::read_to_string(&outputs.link).unwrap(), "rlib");
+        assert!(compilations.compilations.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_full_started_first() {
+        let dir = TestDir::new("full_first");
+        let compilations = dir.compilations(ORPHAN_TIMEOUT);
+        let outputs = dir.outputs();
+        let (started_tx, started_rx) = mpsc::channel::<()>();
+        let (finish_tx, finish_rx) = mpsc::channel::<()>();
+
+        let full = {
+            let compilations = Arc::clone(&compilations);
+            let outputs = outputs.clone();
+            thread::spawn(move || {
+                compilations.run(
+                    &pipelining(PipeliningMode::Full),
+                    &outputs,
+                    move |scratch, on_metadata| {
+                        write_metadata(scratch, on_metadata);
+                        started_tx.send(()).unwrap();
+                        finish_rx.recv().unwrap();
+                        write_link(scratch);
+                        (0, String::new())
+                    },
+                )
+            })
+        };
+        started_rx.recv().unwrap();
+
+        let metadata = compilations.run(&pipelining(PipeliningMode::Metadata), &outputs, |_, _| {
+            panic!("the metadata request should reuse the running compilation")
+        });
+        assert_eq!(metadata, (0, String::new()));
+        assert_eq!(fs::read_to_string(&outputs.metadata).unwrap(), "rmeta");
+
+        finish_tx.send(()).unwrap();
+        assert_eq!(full.join().unwrap(), (0, String::new()));
+        assert_eq!(fs::read_to_string(&outputs.link).unwrap(), "rlib");
+        assert!(compilations.compilations.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_metadata_only() {
+        let dir = TestDir::new("metadata_only");
+        let compilations = dir.compilations(Duration::ZERO);
+        let outputs = dir.outputs();
+        let (finished_tx, finished_rx) = mpsc::channel::<EmitOutputs>();
+
+        let metadata = compilations.run(
+            &pipelining(PipeliningMode::Metadata),
+            &outputs,
+            move |scratch, on_metadata| {
+                write_metadata(scratch, on_metadata);
+                write_link(scratch);
+                finished_tx.send(scratch.clone()).unwrap();
+                (0, String::new())
+            },
+        );
+        assert_eq!(metadata, (0, String::new()));
+        let scratch = finished_rx.recv().unwrap();
+        // The full action was never run, so its rlib must not be written.
+        assert!(!outputs.link.exists());
+        assert!(scratch.link.exists());
+
+        // Wait for the compilation to be marked as finished, after which the
+        // next request evicts it.
+        while compilations.compilations.lock().unwrap()[&42]
+            .state
+            .lock()
+            .unwrap()
+            .finished_at
+            .is_none()
+        {
+            thread::yield_now();
+        }
+        let other = Pipelining {
+            key: 7,
+            mode: PipeliningMode::Full,
+            param_files: Vec::new(),
+        };
+        assert_eq!(
+            compilations.run(&other, &outputs, |_, _| (1, "error".to_owned())),
+            (1, "error".to_owned())
+        );
+        assert!(!compilations.compilations.lock().unwrap().contains_key(&42));
+        assert!(!scratch.link.exists());
+        assert!(!outputs.link.exists());
+    }
+
+    #[test]
+    fn test_rerun_starts_a_new_compilation() {
+        let dir = TestDir::new("rerun");
+        let compilations = dir.compilations(ORPHAN_TIMEOUT);
+        let outputs = dir.outputs();
+
+        for _ in 0..2 {
+            let (compiled_tx, compiled_rx) = mpsc::channel::<()>();
+            let metadata = compilations.run(
+                &pipelining(PipeliningMode::Metadata),
+                &outputs,
+                move |scratch, on_metadata| {
+                    compiled_tx.send(()).unwrap();
+                    write_metadata(scratch, on_metadata);
+                    (0, String::new())
+                },
+            );
+            assert_eq!(metadata, (0, String::new()));
+            compiled_rx.recv().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_metadata_failure() {
+        let dir = TestDir::new("metadata_failure");
+        let compilations = dir.compilations(ORPHAN_TIMEOUT);
+        let outputs = dir.outputs();
+        let metadata = compilations.run(&pipelining(PipeliningMode::Metadata), &outputs, |_, _| {
+            (1, "error: expected item".to_owned())
+        });
+        assert_eq!(metadata, (1, "error: expected item".to_owned()));
+
+        // The full request reports the same failure rather than compiling
+        // again.
+        let full = compilations.run(&pipelining(PipeliningMode::Full), &outputs, |_, _| {
+            panic!("the full request should reuse the failed compilation")
+        });
+        assert_eq!(full, (1, "error: expected item".to_owned()));
+        assert!(compilations.compilations.lock().unwrap().is_empty());
+    }
+}
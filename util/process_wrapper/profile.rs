@@ -0,0 +1,288 @@
+// Copyright 2020 The Bazel Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-action resource and timing profiles of the child process.
+//!
+//! Profiles are written as json and can be merged into a build-wide report
+//! with `//util/profile_aggregator`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::process::{Child, ExitStatus};
+use std::time::Duration;
+
+use tinyjson::JsonValue;
+
+use crate::util::read_file_to_array;
+
+/// The version of the profile json schema.
+pub(crate) const PROFILE_VERSION: f64 = 1.0;
+
+/// Resources consumed by a child process.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ResourceUsage {
+    pub(crate) user_time: Duration,
+    pub(crate) system_time: Duration,
+    pub(crate) max_rss_bytes: u64,
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod sys {
+    use std::io;
+    use std::os::raw::{c_int, c_long};
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{Child, ExitStatus};
+    use std::time::Duration;
+
+    use super::ResourceUsage;
+
+    #[repr(C)]
+    struct Timeval {
+        tv_sec: c_long,
+        #[cfg(target_os = "macos")]
+        tv_usec: i32,
+        #[cfg(not(target_os = "macos"))]
+        tv_usec: c_long,
+    }
+
+    #[repr(C)]
+    struct RUsage {
+        ru_utime: Timeval,
+        ru_stime: Timeval,
+        ru_maxrss: c_long,
+        // The remaining fields are unused.
+        ru_other: [c_long; 13],
+    }
+
+    extern "C" {
+        fn wait4(pid: c_int, status: *mut c_int, options: c_int, rusage: *mut RUsage) -> c_int;
+    }
+
+    fn duration(tv: &Timeval) -> Duration {
+        Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+    }
+
+    pub(super) fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<ResourceUsage>)> {
+        let mut status: c_int = 0;
+        // SAFETY: `RUsage` mirrors the C `struct rusage` layout and is only
+        // read after `wait4` successfully populated it.
+        let mut rusage: RUsage = unsafe { std::mem::zeroed() };
+        loop {
+            // SAFETY: `status` and `rusage` are valid for writes.
+            let pid = unsafe { wait4(child.id() as c_int, &mut status, 0, &mut rusage) };
+            if pid >= 0 {
+                break;
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+
+        // Linux reports the maximum resident set size in kilobytes, macOS in bytes.
+        let max_rss_bytes = if cfg!(target_os = "macos") {
+            rusage.ru_maxrss as u64
+        } else {
+            rusage.ru_maxrss as u64 * 1024
+        };
+
+        Ok((
+            ExitStatus::from_raw(status),
+            Some(ResourceUsage {
+                user_time: duration(&rusage.ru_utime),
+                system_time: duration(&rusage.ru_stime),
+                max_rss_bytes,
+            }),
+        ))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod sys {
+    use std::io;
+    use std::process::{Child, ExitStatus};
+
+    use super::ResourceUsage;
+
+    pub(super) fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<ResourceUsage>)> {
+        child.wait().map(|status| (status, None))
+    }
+}
+
+/// Waits for `child` to exit, collecting its resource usage where the
+/// platform supports it.
+pub(crate) fn wait_with_resource_usage(
+    child: &mut Child,
+) -> io::Result<(ExitStatus, Option<ResourceUsage>)> {
+    sys::wait(child)
+}
+
+/// The profile of a single process wrapper invocation.
+#[derive(Debug, Default)]
+pub(crate) struct Profile {
+    pub(crate) executable: String,
+    pub(crate) crate_name: Option<String>,
+    pub(crate) exit_code: i32,
+    pub(crate) wall_time: Duration,
+    pub(crate) usage: Option<ResourceUsage>,
+    // Per pass timings in seconds, as reported by `-Ztime-passes-format=json`.
+    pub(crate) passes: Vec<(String, f64)>,
+}
+
+impl Profile {
+    fn to_json(&self) -> JsonValue {
+        let optional_number = |value: Option<f64>| value.map_or(JsonValue::Null, JsonValue::Number);
+        JsonValue::Object(HashMap::from([
+            ("version".to_owned(), JsonValue::Number(PROFILE_VERSION)),
+            (
+                "executable".to_owned(),
+                JsonValue::String(self.executable.clone()),
+            ),
+            (
+                "crate_name".to_owned(),
+                self.crate_name
+                    .clone()
+                    .map_or(JsonValue::Null, JsonValue::String),
+            ),
+            (
+                "exit_code".to_owned(),
+                JsonValue::Number(self.exit_code as f64),
+            ),
+            (
+                "wall_time_secs".to_owned(),
+                JsonValue::Number(self.wall_time.as_secs_f64()),
+            ),
+            (
+                "user_time_secs".to_owned(),
+                optional_number(self.usage.as_ref().map(|u| u.user_time.as_secs_f64())),
+            ),
+            (
+                "system_time_secs".to_owned(),
+                optional_number(self.usage.as_ref().map(|u| u.system_time.as_secs_f64())),
+            ),
+            (
+                "max_rss_bytes".to_owned(),
+                optional_number(self.usage.as_ref().map(|u| u.max_rss_bytes as f64)),
+            ),
+            (
+                "passes".to_owned(),
+                JsonValue::Array(
+                    self.passes
+                        .iter()
+                        .map(|(pass, seconds)| {
+                            JsonValue::Object(HashMap::from([
+                                ("pass".to_owned(), JsonValue::String(pass.clone())),
+                                ("time_secs".to_owned(), JsonValue::Number(*seconds)),
+                            ]))
+                        })
+                        .collect(),
+                ),
+            ),
+        ]))
+    }
+
+    /// Writes the profile to `path` as json.
+    pub(crate) fn write(&self, path: &str) -> Result<(), String> {
+        let contents = self.to_json().stringify().map_err(|e| e.to_string())?;
+        fs::write(path, contents).map_err(|e| format!("{} writing path: {:?}", e, path))
+    }
+}
+
+/// Finds the `--crate-name` passed to rustc, looking into param files.
+pub(crate) fn find_crate_name(args: &[String]) -> Option<String> {
+    let mut args = args.iter().cloned();
+    while let Some(arg) = args.next() {
+        if let Some(name) = arg.strip_prefix("--crate-name=") {
+            return Some(name.to_owned());
+        }
+        if arg == "--crate-name" {
+            return args.next();
+        }
+        if let Some(param_file) = arg.strip_prefix('@') {
+            if let Some(name) = read_file_to_array(param_file)
+                .ok()
+                .and_then(|lines| find_crate_name(&lines))
+            {
+                return Some(name);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_crate_name() {
+        let args = |args: &[&str]| args.iter().map(|&s| s.to_owned()).collect::<Vec<_>>();
+        assert_eq!(
+            find_crate_name(&args(&["lib.rs", "--crate-name=foo"])),
+            Some("foo".to_owned())
+        );
+        assert_eq!(
+            find_crate_name(&args(&["--crate-name", "bar", "lib.rs"])),
+            Some("bar".to_owned())
+        );
+        assert_eq!(find_crate_name(&args(&["lib.rs"])), None);
+    }
+
+    #[test]
+    fn test_profile_to_json() {
+        let profile = Profile {
+            executable: "rustc".to_owned(),
+            crate_name: Some("foo".to_owned()),
+            exit_code: 0,
+            wall_time: Duration::from_millis(1500),
+            usage: Some(ResourceUsage {
+                user_time: Duration::from_secs(1),
+                system_time: Duration::from_millis(250),
+                max_rss_bytes: 1024,
+            }),
+            passes: vec![("parse_crate".to_owned(), 0.5)],
+        };
+        assert_eq!(
+            profile.to_json(),
+            r#"
+            {
+                "version": 1,
+                "executable": "rustc",
+                "crate_name": "foo",
+                "exit_code": 0,
+                "wall_time_secs": 1.5,
+                "user_time_secs": 1,
+                "system_time_secs": 0.25,
+                "max_rss_bytes": 1024,
+                "passes": [{"pass": "parse_crate", "time_secs": 0.5}]
+            }
+            "#
+            .parse::<JsonValue>()
+            .unwrap()
+        );
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_wait_with_resource_usage() {
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "exit 3"])
+            .spawn()
+            .unwrap();
+        let (status, usage) = wait_with_resource_usage(&mut child).unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert!(usage.unwrap().max_rss_bytes > 0);
+    }
+}
@@ -23,7 +23,7 @@ pub(crate) enum ErrorFormat {
     Rendered,
 }
 
-fn get_key(value: &JsonValue, key: &str) -> Option<String> {
+pub(crate) fn get_key(value: &JsonValue, key: &str) -> Option<String> {
     if let JsonValue::Object(map) = value {
         if let JsonValue::String(s) = map.get(key)? {
             Some(s.clone())
@@ -35,23 +35,186 @@ fn get_key(value: &JsonValue, key: &str) -> Option<String> {
     }
 }
 
+/// A message parsed from rustc's json output which the process wrapper may
+/// act upon, in addition to forwarding it.
+pub(crate) enum RustcMessage<'a> {
+    /// An artifact notification (emitted with --json=artifacts) for the kind
+    /// of output that was written, e.g. "metadata".
+    Artifact(&'a str),
+    /// A diagnostic, as emitted by rustc.
+    Diagnostic(&'a JsonValue),
+    /// The time in seconds spent in a compiler pass, as reported by
+    /// `-Ztime-passes -Ztime-passes-format=json`.
+    PassTiming(&'a str, f64),
+}
+
+/// Parses a pass timing line emitted by rustc with
+/// `-Ztime-passes -Ztime-passes-format=json`, such as
+/// `time: {"pass": "parse_crate", "time": 0.0001, "rss_start": 1, "rss_end": 2}`.
+pub(crate) fn parse_pass_timing(line: &str) -> Option<(String, f64)> {
+    let parsed: JsonValue = line.trim_end().strip_prefix("time: ")?.parse().ok()?;
+    let JsonValue::Object(map) = &parsed else {
+        return None;
+    };
+    match (map.get("pass"), map.get("time")) {
+        (Some(JsonValue::String(pass)), Some(JsonValue::Number(time))) => {
+            Some((pass.clone(), *time))
+        }
+        _ => None,
+    }
+}
+
 /// process_rustc_json takes an output line from rustc configured with
 /// --error-format=json, parses the json and returns the appropriate output
 /// according to the original --error-format supplied.
 /// Only diagnostics with a rendered message are returned.
+/// File names in diagnostics are rewritten according to `path_mappings`, in
+/// both the json spans and the rendered message.
+/// Diagnostics and artifact notifications are also reported to `on_message`.
 /// Returns an errors if parsing json fails.
-pub(crate) fn process_json(line: String, error_format: ErrorFormat) -> LineResult {
-    let parsed: JsonValue = line
+pub(crate) fn process_json(
+    line: String,
+    error_format: ErrorFormat,
+    path_mappings: &[(String, String)],
+    on_message: &mut dyn FnMut(RustcMessage),
+) -> LineResult {
+    let mut parsed: JsonValue = line
         .parse()
         .map_err(|_| "error parsing rustc output as json".to_owned())?;
-    Ok(if let Some(rendered) = get_key(&parsed, "rendered") {
+    Ok(if get_key(&parsed, "rendered").is_some() {
+        let line = if remap_diagnostic_paths(&mut parsed, path_mappings) {
+            let mut remapped = parsed
+                .stringify()
+                .map_err(|_| "error serializing remapped rustc output".to_owned())?;
+            remapped.push('\n');
+            remapped
+        } else {
+            line
+        };
+        on_message(RustcMessage::Diagnostic(&parsed));
+        let rendered = get_key(&parsed, "rendered").unwrap_or_default();
         output_based_on_error_format(line, rendered, error_format)
     } else {
+        if get_key(&parsed, "$message_type").as_deref() == Some("artifact") {
+            if let Some(emit) = get_key(&parsed, "emit") {
+                on_message(RustcMessage::Artifact(&emit));
+            }
+        }
         // Ignore non-diagnostic messages such as artifact notifications.
         LineOutput::Skip
     })
 }
 
+/// Returns `path` with the first matching prefix in `path_mappings` replaced.
+fn remap_path(path: &str, path_mappings: &[(String, String)]) -> Option<String> {
+    path_mappings.iter().find_map(|(from, to)| {
+        path.strip_prefix(from.as_str())
+            .map(|rest| format!("{to}{rest}"))
+    })
+}
+
+/// Rewrites the span file names of a diagnostic, including those of its
+/// children and macro expansions, recording each rename in `renamed`.
+fn remap_spans(
+    value: &mut JsonValue,
+    path_mappings: &[(String, String)],
+    renamed: &mut Vec<(String, String)>,
+) {
+    let JsonValue::Object(map) = value else {
+        return;
+    };
+    if let Some(JsonValue::String(file_name)) = map.get_mut("file_name") {
+        if let Some(new_name) = remap_path(file_name, path_mappings) {
+            let old_name = std::mem::replace(file_name, new_name.clone());
+            if !renamed.iter().any(|(old, _)| *old == old_name) {
+                renamed.push((old_name, new_name));
+            }
+        }
+    }
+    for key in ["spans", "children"] {
+        if let Some(JsonValue::Array(values)) = map.get_mut(key) {
+            for value in values {
+                remap_spans(value, path_mappings, renamed);
+            }
+        }
+    }
+    if let Some(JsonValue::Object(expansion)) = map.get_mut("expansion") {
+        for key in ["span", "def_site_span"] {
+            if let Some(span) = expansion.get_mut(key) {
+                remap_spans(span, path_mappings, renamed);
+            }
+        }
+    }
+}
+
+/// Rewrites the file names of a diagnostic's spans according to
+/// `path_mappings`, and regenerates its rendered message to match. Returns
+/// true if anything was changed.
+fn remap_diagnostic_paths(diagnostic: &mut JsonValue, path_mappings: &[(String, String)]) -> bool {
+    if path_mappings.is_empty() {
+        return false;
+    }
+    let mut renamed = Vec::new();
+    remap_spans(diagnostic, path_mappings, &mut renamed);
+    if renamed.is_empty() {
+        return false;
+    }
+
+    if let JsonValue::Object(map) = diagnostic {
+        if let Some(JsonValue::String(rendered)) = map.get_mut("rendered") {
+            for (old_name, new_name) in &renamed {
+                *rendered = replace_location_file_name(rendered, old_name, new_name);
+            }
+        }
+    }
+    true
+}
+
+/// Replaces `old_name` with `new_name` where it is the file of a span location
+/// (`file:line:col`) in a rendered diagnostic. rustc renders those after a
+/// `--> ` marker for primary spans, after `::: ` for secondary ones, and at
+/// the start of the line with `--error-format=short`. ANSI escape sequences
+/// in front of the file name, as emitted with
+/// `--json=diagnostic-rendered-ansi`, are skipped.
+fn replace_location_file_name(rendered: &str, old_name: &str, new_name: &str) -> String {
+    let mut result = String::with_capacity(rendered.len());
+    let mut rest = rendered;
+    while let Some(index) = rest.find(old_name) {
+        result.push_str(&rest[..index]);
+        rest = &rest[index + old_name.len()..];
+        let before = strip_trailing_ansi_escapes(&result);
+        let is_location = rest
+            .strip_prefix(':')
+            .is_some_and(|line| line.starts_with(|c: char| c.is_ascii_digit()))
+            && (before.is_empty()
+                || before.ends_with('\n')
+                || before.ends_with("--> ")
+                || before.ends_with("::: "));
+        result.push_str(if is_location { new_name } else { old_name });
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Removes the ANSI escape sequences (`ESC [ params letter`) at the end of
+/// `text`.
+fn strip_trailing_ansi_escapes(mut text: &str) -> &str {
+    while let Some(start) = text.rfind('\x1b') {
+        let sequence = &text.as_bytes()[start + 1..];
+        let is_escape = sequence.len() >= 2
+            && sequence[0] == b'['
+            && sequence[sequence.len() - 1].is_ascii_alphabetic()
+            && sequence[1..sequence.len() - 1]
+                .iter()
+                .all(|b| b.is_ascii_digit() || *b == b';');
+        if !is_escape {
+            break;
+        }
+        text = &text[..start];
+    }
+    text
+}
+
 fn output_based_on_error_format(
     line: String,
     rendered: String,
@@ -65,3 +228,139 @@ fn output_based_on_error_format(
         ErrorFormat::Rendered => LineOutput::Message(rendered),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_json(json_str: &str) -> JsonValue {
+        json_str.parse::<JsonValue>().unwrap()
+    }
+
+    #[test]
+    fn test_parse_pass_timing() {
+        assert_eq!(
+            parse_pass_timing(
+                "time: {\"pass\": \"parse_crate\", \"time\": 0.25, \"rss_start\": 1, \"rss_end\": 2}\n"
+            ),
+            Some(("parse_crate".to_owned(), 0.25))
+        );
+        assert_eq!(
+            parse_pass_timing("time:   0.000; rss:   30MB ->   31MB (   +1MB)\tparse_crate"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_remap_diagnostic_paths() {
+        let mut diagnostic = parse_json(
+            r#"
+            {
+                "$message_type": "diagnostic",
+                "message": "unused variable: `x`",
+                "spans": [
+                    {
+                        "file_name": "bazel-out/k8-fastbuild/bin/pkg/lib.rs",
+                        "is_primary": true,
+                        "expansion": {
+                            "span": {"file_name": "external/dep/src/macros.rs"},
+                            "def_site_span": {"file_name": "src/main.rs"}
+                        }
+                    }
+                ],
+                "children": [
+                    {"spans": [{"file_name": "external/dep/src/macros.rs"}]}
+                ],
+                "rendered": "warning: unused\n --> bazel-out/k8-fastbuild/bin/pkg/lib.rs:1:25\n  ::: external/dep/src/macros.rs:3:1\n"
+            }
+        "#,
+        );
+        let path_mappings = vec![
+            ("bazel-out/k8-fastbuild/bin/".to_owned(), "".to_owned()),
+            ("external/".to_owned(), "/output_base/external/".to_owned()),
+        ];
+
+        assert!(remap_diagnostic_paths(&mut diagnostic, &path_mappings));
+        assert_eq!(
+            diagnostic,
+            parse_json(
+                r#"
+                {
+                    "$message_type": "diagnostic",
+                    "message": "unused variable: `x`",
+                    "spans": [
+                        {
+                            "file_name": "pkg/lib.rs",
+                            "is_primary": true,
+                            "expansion": {
+                                "span": {"file_name": "/output_base/external/dep/src/macros.rs"},
+                                "def_site_span": {"file_name": "src/main.rs"}
+                            }
+                        }
+                    ],
+                    "children": [
+                        {"spans": [{"file_name": "/output_base/external/dep/src/macros.rs"}]}
+                    ],
+                    "rendered": "warning: unused\n --> pkg/lib.rs:1:25\n  ::: /output_base/external/dep/src/macros.rs:3:1\n"
+                }
+            "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_remap_diagnostic_paths_ansi() {
+        // As rendered with --json=diagnostic-rendered-ansi.
+        let mut diagnostic = parse_json(
+            r#"
+            {
+                "spans": [{"file_name": "bazel-out/k8-fastbuild/bin/pkg/lib.rs"}],
+                "rendered": "\u001b[0m\u001b[1m\u001b[33mwarning\u001b[0m\u001b[0m\u001b[1m: unused\u001b[0m\n\u001b[0m \u001b[0m\u001b[0m\u001b[1m\u001b[38;5;12m--> \u001b[0m\u001b[0mbazel-out/k8-fastbuild/bin/pkg/lib.rs:1:25\u001b[0m\n"
+            }
+        "#,
+        );
+        assert!(remap_diagnostic_paths(
+            &mut diagnostic,
+            &[("bazel-out/k8-fastbuild/bin/".to_owned(), "".to_owned())]
+        ));
+        assert_eq!(
+            get_key(&diagnostic, "rendered").unwrap(),
+            "\u{1b}[0m\u{1b}[1m\u{1b}[33mwarning\u{1b}[0m\u{1b}[0m\u{1b}[1m: unused\u{1b}[0m\n\u{1b}[0m \u{1b}[0m\u{1b}[0m\u{1b}[1m\u{1b}[38;5;12m--> \u{1b}[0m\u{1b}[0mpkg/lib.rs:1:25\u{1b}[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_remap_diagnostic_paths_short() {
+        // As rendered with --json=diagnostic-short. The file name is also
+        // mentioned in the message, which must be left alone.
+        let mut diagnostic = parse_json(
+            r#"
+            {
+                "spans": [{"file_name": "bazel-out/k8-fastbuild/bin/pkg/lib.rs"}],
+                "rendered": "bazel-out/k8-fastbuild/bin/pkg/lib.rs:1:25: warning: unused in bazel-out/k8-fastbuild/bin/pkg/lib.rs:1\n"
+            }
+        "#,
+        );
+        assert!(remap_diagnostic_paths(
+            &mut diagnostic,
+            &[("bazel-out/k8-fastbuild/bin/".to_owned(), "".to_owned())]
+        ));
+        assert_eq!(
+            get_key(&diagnostic, "rendered").unwrap(),
+            "pkg/lib.rs:1:25: warning: unused in bazel-out/k8-fastbuild/bin/pkg/lib.rs:1\n"
+        );
+    }
+
+    #[test]
+    fn test_remap_diagnostic_paths_unchanged() {
+        let mut diagnostic = parse_json(
+            r#"{"spans": [{"file_name": "src/lib.rs"}], "rendered": " --> src/lib.rs:1:1"}"#,
+        );
+        let original = diagnostic.clone();
+        assert!(!remap_diagnostic_paths(
+            &mut diagnostic,
+            &[("external/".to_owned(), "/output_base/external/".to_owned())]
+        ));
+        assert_eq!(diagnostic, original);
+    }
+}
@@ -0,0 +1,776 @@
+// Copyright 2020 The Bazel Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for running the process wrapper as a Bazel persistent worker.
+//!
+//! See https://bazel.build/remote/persistent for a description of the
+//! protocol. Both the JSON and the (default) protobuf encodings of
+//! `WorkRequest`/`WorkResponse` are supported. Requests with a non-zero
+//! `request_id` are multiplexed and handled concurrently, which allows the
+//! metadata and full requests of a pipelined compilation to share a single
+//! rustc invocation (see `pipelining.rs`).
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use tinyjson::JsonValue;
+
+use crate::options::{options_from_args, Options};
+use crate::pipelining::{EmitOutputs, PipelinedCompilations};
+use crate::util::read_file_to_array;
+use crate::ProcessWrapperError;
+
+/// The flag Bazel passes to a tool when starting it as a persistent worker.
+const PERSISTENT_WORKER_FLAG: &str = "--persistent_worker";
+
+/// The flag selecting the worker protocol encoding. This should match the
+/// `requires-worker-protocol` execution requirement of the action.
+const WORKER_PROTOCOL_FLAG: &str = "--worker-protocol=";
+
+/// Returns true if the process wrapper was started as a persistent worker.
+pub(crate) fn is_persistent_worker(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == PERSISTENT_WORKER_FLAG)
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+enum WorkerProtocol {
+    Json,
+    #[default]
+    Proto,
+}
+
+impl WorkerProtocol {
+    fn from_args(args: &[String]) -> Result<Self, ProcessWrapperError> {
+        match args
+            .iter()
+            .find_map(|arg| arg.strip_prefix(WORKER_PROTOCOL_FLAG))
+        {
+            None | Some("proto") => Ok(Self::Proto),
+            Some("json") => Ok(Self::Json),
+            Some(other) => Err(ProcessWrapperError(format!(
+                "invalid {}'{}'",
+                WORKER_PROTOCOL_FLAG, other
+            ))),
+        }
+    }
+
+    fn read_request(&self, reader: &mut impl BufRead) -> Result<Option<WorkRequest>, String> {
+        match self {
+            Self::Json => read_json_request(reader),
+            Self::Proto => read_proto_request(reader),
+        }
+    }
+
+    fn encode_response(&self, response: &WorkResponse) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Json => encode_json_response(response),
+            Self::Proto => Ok(encode_proto_response(response)),
+        }
+    }
+}
+
+/// The subset of Bazel's `WorkRequest` used by the process wrapper.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct WorkRequest {
+    arguments: Vec<String>,
+    // The (path, digest) of each input of the action.
+    inputs: Vec<(String, Vec<u8>)>,
+    request_id: i32,
+    cancel: bool,
+    sandbox_dir: String,
+}
+
+/// Bazel's `WorkResponse`.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct WorkResponse {
+    exit_code: i32,
+    output: String,
+    request_id: i32,
+}
+
+/// Caches the contents of `--env-file` and `--arg-file` files across requests.
+/// Entries are invalidated whenever the file's size or modification time
+/// changes.
+#[derive(Debug, Default)]
+struct FileCache {
+    entries: Mutex<HashMap<String, CachedFile>>,
+}
+
+#[derive(Debug)]
+struct CachedFile {
+    len: u64,
+    modified: SystemTime,
+    lines: Vec<String>,
+}
+
+impl FileCache {
+    fn read(&self, path: &str) -> Result<Vec<String>, String> {
+        // Files without a usable modification time are never cached. Errors
+        // are left to `read_file_to_array` for a consistent message.
+        let Some((len, modified)) = fs::metadata(path)
+            .ok()
+            .and_then(|metadata| Some((metadata.len(), metadata.modified().ok()?)))
+        else {
+            return read_file_to_array(path);
+        };
+
+        if let Some(cached) = self.entries.lock().unwrap().get(path) {
+            if cached.len == len && cached.modified == modified {
+                return Ok(cached.lines.clone());
+            }
+        }
+
+        let lines = read_file_to_array(path)?;
+        self.entries.lock().unwrap().insert(
+            path.to_owned(),
+            CachedFile {
+                len,
+                modified,
+                lines: lines.clone(),
+            },
+        );
+        Ok(lines)
+    }
+}
+
+/// Runs the persistent worker loop, reading requests from stdin until it is
+/// closed by Bazel.
+pub(crate) fn run_worker(args: &[String]) -> Result<(), ProcessWrapperError> {
+    let protocol = WorkerProtocol::from_args(args)?;
+    let program_name = args.first().cloned().unwrap_or_default();
+    let cache = Arc::new(FileCache::default());
+    let pipelined = Arc::new(PipelinedCompilations::new(
+        std::env::temp_dir().join(format!("process_wrapper_pipelining_{}", std::process::id())),
+    ));
+    let stdout = Arc::new(Mutex::new(io::stdout()));
+
+    let stdin = io::stdin();
+    let mut reader = stdin.lock();
+    let mut in_flight = Vec::new();
+    while let Some(request) = protocol
+        .read_request(&mut reader)
+        .map_err(|e| ProcessWrapperError(format!("failed to read work request: {}", e)))?
+    {
+        // Join the threads of requests which have already been responded to,
+        // so a long lived worker doesn't accumulate them.
+        let (finished, running) = in_flight
+            .into_iter()
+            .partition::<Vec<_>, _>(|handle: &thread::JoinHandle<_>| handle.is_finished());
+        in_flight = running;
+        for handle in finished {
+            join_request_thread(handle)?;
+        }
+
+        // Cancellation is only requested from workers which advertise
+        // `supports-worker-cancellation`, which the process wrapper doesn't.
+        if request.cancel {
+            continue;
+        }
+
+        if request.request_id == 0 {
+            let response = handle_request(&program_name, &request, &cache, &pipelined);
+            write_response(protocol, &stdout, &response)?;
+        } else {
+            let program_name = program_name.clone();
+            let cache = Arc::clone(&cache);
+            let pipelined = Arc::clone(&pipelined);
+            let stdout = Arc::clone(&stdout);
+            in_flight.push(thread::spawn(move || {
+                let response = handle_request(&program_name, &request, &cache, &pipelined);
+                write_response(protocol, &stdout, &response)
+            }));
+        }
+    }
+
+    for handle in in_flight {
+        join_request_thread(handle)?;
+    }
+
+    Ok(())
+}
+
+fn join_request_thread(
+    handle: thread::JoinHandle<Result<(), ProcessWrapperError>>,
+) -> Result<(), ProcessWrapperError> {
+    handle
+        .join()
+        .map_err(|_| ProcessWrapperError("worker thread panicked".to_owned()))?
+}
+
+fn handle_request(
+    program_name: &str,
+    request: &WorkRequest,
+    cache: &FileCache,
+    pipelined: &PipelinedCompilations,
+) -> WorkResponse {
+    let (exit_code, output) = if !request.sandbox_dir.is_empty() {
+        error_response(ProcessWrapperError(
+            "multiplex sandboxing is not supported".to_owned(),
+        ))
+    } else {
+        let argv = std::iter::once(program_name.to_owned())
+            .chain(request.arguments.iter().cloned())
+            .collect();
+        match options_from_args(argv, &mut |path: &str| cache.read(path)) {
+            Ok(mut opts) => match opts.pipelining.take() {
+                Some(mut pipelining) => match EmitOutputs::from_args(&opts.child_arguments) {
+                    Some(outputs) => {
+                        // Only share a compilation between requests for the
+                        // same inputs, so a leftover half from an earlier
+                        // build is never reused.
+                        pipelining.key =
+                            inputs_key(pipelining.key, &request.inputs, &pipelining.param_files);
+                        pipelined.run(&pipelining, &outputs, move |scratch, on_metadata| {
+                            match scratch.apply(&mut opts.child_arguments) {
+                                Ok(()) => execute(opts, on_metadata),
+                                Err(e) => error_response(ProcessWrapperError(format!(
+                                    "unable to rewrite --emit: {}",
+                                    e
+                                ))),
+                            }
+                        })
+                    }
+                    None => error_response(ProcessWrapperError(
+                        "\"--pipelining-mode\" requires rustc's --emit flag to name both the \
+                         metadata and link outputs"
+                            .to_owned(),
+                    )),
+                },
+                None => execute(opts, &mut || {}),
+            },
+            Err(e) => error_response(ProcessWrapperError(e.to_string())),
+        }
+    };
+
+    WorkResponse {
+        exit_code,
+        output,
+        request_id: request.request_id,
+    }
+}
+
+/// Combines `key` with the digests of the inputs shared by both halves of a
+/// pipelined compilation, i.e. all inputs but the param files.
+fn inputs_key(key: u64, inputs: &[(String, Vec<u8>)], param_files: &[String]) -> u64 {
+    let mut inputs = inputs
+        .iter()
+        .filter(|(path, _)| !param_files.contains(path))
+        .collect::<Vec<_>>();
+    inputs.sort();
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    inputs.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Runs the child process for a request, returning its exit code and output.
+fn execute(opts: Options, on_metadata: &mut dyn FnMut()) -> (i32, String) {
+    let mut output = Vec::new();
+    match crate::run(opts, Some(&mut output), on_metadata) {
+        Ok(code) => (code, String::from_utf8_lossy(&output).into_owned()),
+        Err(e) => {
+            let (code, message) = error_response(e);
+            (
+                code,
+                String::from_utf8_lossy(&output).into_owned() + &message,
+            )
+        }
+    }
+}
+
+fn error_response(error: ProcessWrapperError) -> (i32, String) {
+    (1, format!("{}\n", error))
+}
+
+fn write_response(
+    protocol: WorkerProtocol,
+    stdout: &Mutex<io::Stdout>,
+    response: &WorkResponse,
+) -> Result<(), ProcessWrapperError> {
+    let encoded = protocol
+        .encode_response(response)
+        .map_err(|e| ProcessWrapperError(format!("failed to encode work response: {}", e)))?;
+    let mut stdout = stdout.lock().unwrap();
+    stdout
+        .write_all(&encoded)
+        .and_then(|_| stdout.flush())
+        .map_err(|e| ProcessWrapperError(format!("failed to write work response: {}", e)))
+}
+
+fn read_json_request(reader: &mut impl BufRead) -> Result<Option<WorkRequest>, String> {
+    // Requests may be spread across several lines, so keep reading until the
+    // accumulated text forms a complete JSON value.
+    let mut text = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return if text.trim().is_empty() {
+                Ok(None)
+            } else {
+                Err("unexpected end of input".to_owned())
+            };
+        }
+        text.push_str(&line);
+        if text.trim().is_empty() {
+            text.clear();
+            continue;
+        }
+        if let Ok(value) = text.parse::<JsonValue>() {
+            return parse_json_request(&value).map(Some);
+        }
+    }
+}
+
+fn parse_json_request(value: &JsonValue) -> Result<WorkRequest, String> {
+    let JsonValue::Object(map) = value else {
+        return Err("work request is not a JSON object".to_owned());
+    };
+
+    let mut request = WorkRequest::default();
+    for (key, value) in map {
+        match (key.as_str(), value) {
+            ("arguments", JsonValue::Array(args)) => {
+                request.arguments = args
+                    .iter()
+                    .map(|arg| match arg {
+                        JsonValue::String(s) => Ok(s.clone()),
+                        _ => Err("work request arguments must be strings".to_owned()),
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            ("inputs", JsonValue::Array(inputs)) => {
+                request.inputs = inputs
+                    .iter()
+                    .map(|input| {
+                        let JsonValue::Object(input) = input else {
+                            return Err("work request inputs must be objects".to_owned());
+                        };
+                        let field = |name: &str| match input.get(name) {
+                            Some(JsonValue::String(s)) => s.clone(),
+                            _ => String::new(),
+                        };
+                        Ok((field("path"), field("digest").into_bytes()))
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            ("requestId", JsonValue::Number(id)) => request.request_id = *id as i32,
+            ("cancel", JsonValue::Boolean(cancel)) => request.cancel = *cancel,
+            ("sandboxDir", JsonValue::String(dir)) => request.sandbox_dir = dir.clone(),
+            // `verbosity` is not used.
+            _ => {}
+        }
+    }
+    Ok(request)
+}
+
+fn encode_json_response(response: &WorkResponse) -> Result<Vec<u8>, String> {
+    let value = JsonValue::Object(HashMap::from([
+        (
+            "exitCode".to_owned(),
+            JsonValue::Number(response.exit_code as f64),
+        ),
+        (
+            "output".to_owned(),
+            JsonValue::String(response.output.clone()),
+        ),
+        (
+            "requestId".to_owned(),
+            JsonValue::Number(response.request_id as f64),
+        ),
+    ]));
+    let mut encoded = value.stringify().map_err(|e| e.to_string())?;
+    encoded.push('\n');
+    Ok(encoded.into_bytes())
+}
+
+/// Reads a base 128 varint, returning `None` if the input ended before its
+/// first byte.
+fn read_varint(reader: &mut impl io::Read) -> Result<Option<u64>, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        if reader.read(&mut byte).map_err(|e| e.to_string())? == 0 {
+            return if shift == 0 {
+                Ok(None)
+            } else {
+                Err("truncated varint".to_owned())
+            };
+        }
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err("varint is too long".to_owned())
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn read_proto_request(reader: &mut impl BufRead) -> Result<Option<WorkRequest>, String> {
+    let Some(len) = read_varint(reader)? else {
+        return Ok(None);
+    };
+    let mut message = vec![0u8; len as usize];
+    reader.read_exact(&mut message).map_err(|e| e.to_string())?;
+    decode_proto_request(&message).map(Some)
+}
+
+fn decode_proto_request(mut message: &[u8]) -> Result<WorkRequest, String> {
+    let mut request = WorkRequest::default();
+    while let Some(key) = read_varint(&mut message)? {
+        let field = key >> 3;
+        let wire_type = key & 0x7;
+        match wire_type {
+            // Varint
+            0 => {
+                let value = read_varint(&mut message)?.ok_or("truncated field")?;
+                match field {
+                    3 => request.request_id = value as i32,
+                    4 => request.cancel = value != 0,
+                    _ => {}
+                }
+            }
+            // Length delimited
+            2 => {
+                let len = read_varint(&mut message)?.ok_or("truncated field")? as usize;
+                if len > message.len() {
+                    return Err("truncated field".to_owned());
+                }
+                let (bytes, rest) = message.split_at(len);
+                message = rest;
+                let as_string =
+                    || String::from_utf8(bytes.to_vec()).map_err(|_| "invalid utf-8".to_owned());
+                match field {
+                    1 => request.arguments.push(as_string()?),
+                    2 => request.inputs.push(decode_proto_input(bytes)?),
+                    6 => request.sandbox_dir = as_string()?,
+                    _ => {}
+                }
+            }
+            // Fixed 64 and 32 bit values
+            1 | 5 => {
+                let len = if wire_type == 1 { 8 } else { 4 };
+                if len > message.len() {
+                    return Err("truncated field".to_owned());
+                }
+                message = &message[len..];
+            }
+            _ => return Err(format!("unsupported wire type {}", wire_type)),
+        }
+    }
+    Ok(request)
+}
+
+/// Decodes an `Input` message into its path and digest.
+fn decode_proto_input(mut message: &[u8]) -> Result<(String, Vec<u8>), String> {
+    let mut input = (String::new(), Vec::new());
+    while let Some(key) = read_varint(&mut message)? {
+        if key & 0x7 != 2 {
+            return Err(format!("unexpected wire type {} in input", key & 0x7));
+        }
+        let len = read_varint(&mut message)?.ok_or("truncated field")? as usize;
+        if len > message.len() {
+            return Err("truncated field".to_owned());
+        }
+        let (bytes, rest) = message.split_at(len);
+        message = rest;
+        match key >> 3 {
+            1 => {
+                input.0 = String::from_utf8(bytes.to_vec()).map_err(|_| "invalid utf-8")?;
+            }
+            2 => input.1 = bytes.to_vec(),
+            _ => {}
+        }
+    }
+    Ok(input)
+}
+
+fn encode_proto_response(response: &WorkResponse) -> Vec<u8> {
+    let mut message = Vec::new();
+    if response.exit_code != 0 {
+        write_varint(&mut message, 1 << 3);
+        // Negative int32 values are sign extended to 64 bits.
+        write_varint(&mut message, response.exit_code as i64 as u64);
+    }
+    if !response.output.is_empty() {
+        write_varint(&mut message, (2 << 3) | 2);
+        write_varint(&mut message, response.output.len() as u64);
+        message.extend_from_slice(response.output.as_bytes());
+    }
+    if response.request_id != 0 {
+        write_varint(&mut message, 3 << 3);
+        write_varint(&mut message, response.request_id as i64 as u64);
+    }
+
+    let mut encoded = Vec::with_capacity(message.len() + 5);
+    write_varint(&mut encoded, message.len() as u64);
+    encoded.extend(message);
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&s| s.to_owned()).collect()
+    }
+
+    #[test]
+    fn test_is_persistent_worker() {
+        assert!(is_persistent_worker(&args(&[
+            "process_wrapper",
+            "--persistent_worker"
+        ])));
+        assert!(!is_persistent_worker(&args(&[
+            "process_wrapper",
+            "--",
+            "rustc"
+        ])));
+    }
+
+    #[test]
+    fn test_worker_protocol_from_args() {
+        assert_eq!(
+            WorkerProtocol::from_args(&args(&["pw", "--persistent_worker"])).unwrap(),
+            WorkerProtocol::Proto
+        );
+        assert_eq!(
+            WorkerProtocol::from_args(&args(&["pw", "--worker-protocol=json"])).unwrap(),
+            WorkerProtocol::Json
+        );
+        assert!(WorkerProtocol::from_args(&args(&["pw", "--worker-protocol=xml"])).is_err());
+    }
+
+    #[test]
+    fn test_read_proto_request() {
+        let mut message = Vec::new();
+        for arg in ["--", "rustc"] {
+            write_varint(&mut message, (1 << 3) | 2);
+            write_varint(&mut message, arg.len() as u64);
+            message.extend_from_slice(arg.as_bytes());
+        }
+        // An `Input` message with path "a" and digest [1, 2].
+        write_varint(&mut message, (2 << 3) | 2);
+        write_varint(&mut message, 7);
+        message.extend_from_slice(&[0x0a, 1, b'a', 0x12, 2, 1, 2]);
+        write_varint(&mut message, 3 << 3);
+        write_varint(&mut message, 42);
+
+        let mut stream = Vec::new();
+        write_varint(&mut stream, message.len() as u64);
+        stream.extend(message);
+
+        let mut reader = io::Cursor::new(stream);
+        assert_eq!(
+            read_proto_request(&mut reader).unwrap(),
+            Some(WorkRequest {
+                arguments: args(&["--", "rustc"]),
+                inputs: vec![("a".to_owned(), vec![1, 2])],
+                request_id: 42,
+                ..WorkRequest::default()
+            })
+        );
+        assert_eq!(read_proto_request(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_encode_proto_response() {
+        let encoded = encode_proto_response(&WorkResponse {
+            exit_code: 1,
+            output: "err".to_owned(),
+            request_id: 3,
+        });
+        assert_eq!(
+            encoded,
+            vec![9, 0x08, 1, 0x12, 3, b'e', b'r', b'r', 0x18, 3]
+        );
+
+        // Default values are omitted entirely.
+        assert_eq!(encode_proto_response(&WorkResponse::default()), vec![0]);
+    }
+
+    #[test]
+    fn test_read_json_request() {
+        let mut reader = io::Cursor::new(
+            r#"{"arguments": ["--", "rustc"], "inputs": [{"path": "a", "digest": "AQI="}], "requestId": 7}
+{
+  "arguments": [],
+  "cancel": true
+}
+"#,
+        );
+        assert_eq!(
+            read_json_request(&mut reader).unwrap(),
+            Some(WorkRequest {
+                arguments: args(&["--", "rustc"]),
+                inputs: vec![("a".to_owned(), b"AQI=".to_vec())],
+                request_id: 7,
+                ..WorkRequest::default()
+            })
+        );
+        assert_eq!(
+            read_json_request(&mut reader).unwrap(),
+            Some(WorkRequest {
+                cancel: true,
+                ..WorkRequest::default()
+            })
+        );
+        assert_eq!(read_json_request(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_handle_request_reports_option_errors() {
+        let cache = FileCache::default();
+        let pipelined = PipelinedCompilations::new(std::env::temp_dir().join("pw_unused"));
+        for arguments in [
+            args(&["--help"]),
+            args(&["--stable-status-file", "does/not/exist.txt", "--", "rustc"]),
+            args(&[
+                "--pipelining-mode",
+                "metadata",
+                "--",
+                "rustc",
+                "--emit=metadata,link",
+            ]),
+        ] {
+            let response = handle_request(
+                "process_wrapper",
+                &WorkRequest {
+                    arguments,
+                    request_id: 5,
+                    ..WorkRequest::default()
+                },
+                &cache,
+                &pipelined,
+            );
+            assert_eq!(response.exit_code, 1);
+            assert_eq!(response.request_id, 5);
+            assert!(!response.output.is_empty());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pipelined_requests_share_rustc() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("pw_worker_pipelined_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_owned();
+
+        // A fake rustc which records each time it is spawned, and writes the
+        // outputs named by the `--emit` flag in its param file.
+        let rustc = path("rustc.sh");
+        fs::write(
+            &rustc,
+            format!(
+                r#"#!/bin/sh
+echo spawned >> {spawns}
+emit=$(grep -e '^--emit=' "${{1#@}}")
+rmeta=${{emit#--emit=metadata=}}
+rmeta=${{rmeta%%,*}}
+echo rmeta > "$rmeta"
+echo '{{"$message_type": "artifact", "artifact": "'"$rmeta"'", "emit": "metadata"}}' >&2
+echo rlib > "${{emit##*link=}}"
+"#,
+                spawns = path("spawns"),
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (rmeta, rlib) = (path("libfoo.rmeta"), path("libfoo.rlib"));
+        let request = |mode: &str, output: &str, request_id: i32| {
+            // Bazel writes the arguments of each action to its own param
+            // files.
+            let params = path(&format!("{output}-0.params"));
+            fs::write(
+                &params,
+                format!("lib.rs\n--emit=metadata={rmeta},link={rlib}\n"),
+            )
+            .unwrap();
+            WorkRequest {
+                arguments: args(&[
+                    "--pipelining-mode",
+                    mode,
+                    "--rustc-output-format",
+                    "rendered",
+                    "--",
+                    &rustc,
+                    &format!("@{params}"),
+                ]),
+                inputs: vec![
+                    ("lib.rs".to_owned(), vec![1]),
+                    (params, output.as_bytes().to_vec()),
+                ],
+                request_id,
+                ..WorkRequest::default()
+            }
+        };
+
+        let cache = FileCache::default();
+        let pipelined = PipelinedCompilations::new(dir.join("scratch"));
+        let metadata = handle_request(
+            "process_wrapper",
+            &request("metadata", &rmeta, 1),
+            &cache,
+            &pipelined,
+        );
+        assert_eq!((metadata.exit_code, metadata.output.as_str()), (0, ""));
+        let full = handle_request(
+            "process_wrapper",
+            &request("full", &rlib, 2),
+            &cache,
+            &pipelined,
+        );
+        assert_eq!((full.exit_code, full.output.as_str()), (0, ""));
+
+        assert_eq!(fs::read_to_string(&rmeta).unwrap(), "rmeta\n");
+        assert_eq!(fs::read_to_string(&rlib).unwrap(), "rlib\n");
+        assert_eq!(fs::read_to_string(path("spawns")).unwrap(), "spawned\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_encode_json_response() -> Result<(), String> {
+        let encoded = encode_json_response(&WorkResponse {
+            exit_code: 1,
+            output: "err".to_owned(),
+            request_id: 3,
+        })?;
+        let encoded = String::from_utf8(encoded).map_err(|e| e.to_string())?;
+        assert!(encoded.ends_with('\n'));
+        assert_eq!(
+            encoded.parse::<JsonValue>().map_err(|e| e.to_string())?,
+            r#"{"exitCode": 1, "output": "err", "requestId": 3}"#
+                .parse::<JsonValue>()
+                .map_err(|e| e.to_string())?
+        );
+        Ok(())
+    }
+}
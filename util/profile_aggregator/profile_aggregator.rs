@@ -0,0 +1,380 @@
+// Copyright 2020 The Bazel Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Merges the per-action profiles written by the process wrapper's
+//! `--profile-output` flag into a single build-wide report.
+//!
+//! Usage: `profile_aggregator [--output <report.json>] <profile.json | @paramfile>...`
+//!
+//! The report lists every action sorted by wall time, the build-wide CPU time
+//! totals and peak memory usage, and the total time spent in each compiler
+//! pass across all actions (when `-Ztime-passes-format=json` was enabled).
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::process;
+
+use tinyjson::JsonValue;
+
+/// The version of the aggregated report schema.
+const REPORT_VERSION: f64 = 1.0;
+
+/// The subset of a process wrapper profile used for aggregation.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct ActionProfile {
+    // The path of the profile, which identifies the action.
+    path: String,
+    crate_name: Option<String>,
+    exit_code: f64,
+    wall_time_secs: f64,
+    user_time_secs: Option<f64>,
+    system_time_secs: Option<f64>,
+    max_rss_bytes: Option<f64>,
+    passes: Vec<(String, f64)>,
+}
+
+fn get<'a>(map: &'a HashMap<String, JsonValue>, key: &str) -> Option<&'a JsonValue> {
+    map.get(key)
+        .filter(|value| !matches!(value, JsonValue::Null))
+}
+
+fn get_number(map: &HashMap<String, JsonValue>, key: &str) -> Option<f64> {
+    match get(map, key)? {
+        JsonValue::Number(n) => Some(*n),
+        _ => None,
+    }
+}
+
+impl ActionProfile {
+    fn parse(path: &str, contents: &str) -> Result<Self, String> {
+        let parsed: JsonValue = contents
+            .parse()
+            .map_err(|e| format!("failed to parse {}: {}", path, e))?;
+        let JsonValue::Object(map) = parsed else {
+            return Err(format!("{} is not a json object", path));
+        };
+
+        let passes = match get(&map, "passes") {
+            Some(JsonValue::Array(passes)) => passes
+                .iter()
+                .filter_map(|pass| match pass {
+                    JsonValue::Object(pass) => {
+                        match (get(pass, "pass"), get_number(pass, "time_secs")) {
+                            (Some(JsonValue::String(name)), Some(time)) => {
+                                Some((name.clone(), time))
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(Self {
+            path: path.to_owned(),
+            crate_name: match get(&map, "crate_name") {
+                Some(JsonValue::String(name)) => Some(name.clone()),
+                _ => None,
+            },
+            exit_code: get_number(&map, "exit_code").unwrap_or_default(),
+            wall_time_secs: get_number(&map, "wall_time_secs").unwrap_or_default(),
+            user_time_secs: get_number(&map, "user_time_secs"),
+            system_time_secs: get_number(&map, "system_time_secs"),
+            max_rss_bytes: get_number(&map, "max_rss_bytes"),
+            passes,
+        })
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let optional_number = |value: Option<f64>| value.map_or(JsonValue::Null, JsonValue::Number);
+        JsonValue::Object(HashMap::from([
+            ("profile".to_owned(), JsonValue::String(self.path.clone())),
+            (
+                "crate_name".to_owned(),
+                self.crate_name
+                    .clone()
+                    .map_or(JsonValue::Null, JsonValue::String),
+            ),
+            ("exit_code".to_owned(), JsonValue::Number(self.exit_code)),
+            (
+                "wall_time_secs".to_owned(),
+                JsonValue::Number(self.wall_time_secs),
+            ),
+            (
+                "user_time_secs".to_owned(),
+                optional_number(self.user_time_secs),
+            ),
+            (
+                "system_time_secs".to_owned(),
+                optional_number(self.system_time_secs),
+            ),
+            (
+                "max_rss_bytes".to_owned(),
+                optional_number(self.max_rss_bytes),
+            ),
+        ]))
+    }
+}
+
+/// Merges action profiles into a build-wide report.
+fn aggregate(mut profiles: Vec<ActionProfile>) -> JsonValue {
+    profiles.sort_by(|a, b| {
+        b.wall_time_secs
+            .total_cmp(&a.wall_time_secs)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+
+    let sum = |f: fn(&ActionProfile) -> Option<f64>| profiles.iter().filter_map(f).sum::<f64>();
+    let totals = HashMap::from([
+        (
+            "wall_time_secs".to_owned(),
+            JsonValue::Number(sum(|p| Some(p.wall_time_secs))),
+        ),
+        (
+            "user_time_secs".to_owned(),
+            JsonValue::Number(sum(|p| p.user_time_secs)),
+        ),
+        (
+            "system_time_secs".to_owned(),
+            JsonValue::Number(sum(|p| p.system_time_secs)),
+        ),
+    ]);
+    let max_rss_bytes = profiles
+        .iter()
+        .filter_map(|p| p.max_rss_bytes)
+        .fold(None, |max: Option<f64>, rss| {
+            Some(max.map_or(rss, |m| m.max(rss)))
+        });
+
+    // Total time and number of occurrences of each pass.
+    let mut passes: HashMap<&str, (f64, usize)> = HashMap::new();
+    for (pass, time) in profiles.iter().flat_map(|p| p.passes.iter()) {
+        let entry = passes.entry(pass.as_str()).or_default();
+        entry.0 += time;
+        entry.1 += 1;
+    }
+    let mut passes: Vec<(&str, (f64, usize))> = passes.into_iter().collect();
+    passes.sort_by(|a, b| b.1 .0.total_cmp(&a.1 .0).then_with(|| a.0.cmp(b.0)));
+
+    JsonValue::Object(HashMap::from([
+        ("version".to_owned(), JsonValue::Number(REPORT_VERSION)),
+        (
+            "action_count".to_owned(),
+            JsonValue::Number(profiles.len() as f64),
+        ),
+        ("totals".to_owned(), JsonValue::Object(totals)),
+        (
+            "max_rss_bytes".to_owned(),
+            max_rss_bytes.map_or(JsonValue::Null, JsonValue::Number),
+        ),
+        (
+            "actions".to_owned(),
+            JsonValue::Array(profiles.iter().map(ActionProfile::to_json).collect()),
+        ),
+        (
+            "passes".to_owned(),
+            JsonValue::Array(
+                passes
+                    .into_iter()
+                    .map(|(pass, (time, count))| {
+                        JsonValue::Object(HashMap::from([
+                            ("pass".to_owned(), JsonValue::String(pass.to_owned())),
+                            ("time_secs".to_owned(), JsonValue::Number(time)),
+                            ("count".to_owned(), JsonValue::Number(count as f64)),
+                        ]))
+                    })
+                    .collect(),
+            ),
+        ),
+    ]))
+}
+
+/// Expands `@paramfile` arguments into the profile paths they list.
+fn expand_args(args: Vec<String>) -> Result<Vec<String>, String> {
+    let mut paths = Vec::new();
+    for arg in args {
+        if let Some(param_file) = arg.strip_prefix('@') {
+            let contents = fs::read_to_string(param_file)
+                .map_err(|e| format!("failed to read {}: {}", param_file, e))?;
+            paths.extend(
+                contents
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_owned),
+            );
+        } else {
+            paths.push(arg);
+        }
+    }
+    Ok(paths)
+}
+
+fn run() -> Result<(), String> {
+    let mut output = None;
+    let mut inputs = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--output" {
+            output = Some(args.next().ok_or("--output requires a value")?);
+        } else {
+            inputs.push(arg);
+        }
+    }
+
+    let profiles = expand_args(inputs)?
+        .into_iter()
+        .map(|path| {
+            let contents =
+                fs::read_to_string(&path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+            ActionProfile::parse(&path, &contents)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let report = aggregate(profiles)
+        .format()
+        .map_err(|e| format!("failed to serialize report: {}", e))?;
+    match output {
+        Some(path) => {
+            fs::write(&path, report).map_err(|e| format!("failed to write {}: {}", path, e))
+        }
+        None => {
+            println!("{}", report);
+            Ok(())
+        }
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("profile_aggregator error: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_json(json_str: &str) -> JsonValue {
+        json_str.parse::<JsonValue>().unwrap()
+    }
+
+    #[test]
+    fn test_parse_profile() {
+        let profile = ActionProfile::parse(
+            "foo.profile.json",
+            r#"
+            {
+                "version": 1,
+                "executable": "rustc",
+                "crate_name": "foo",
+                "exit_code": 0,
+                "wall_time_secs": 1.5,
+                "user_time_secs": 1,
+                "system_time_secs": null,
+                "max_rss_bytes": 1024,
+                "passes": [{"pass": "parse_crate", "time_secs": 0.5}]
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            profile,
+            ActionProfile {
+                path: "foo.profile.json".to_owned(),
+                crate_name: Some("foo".to_owned()),
+                exit_code: 0.0,
+                wall_time_secs: 1.5,
+                user_time_secs: Some(1.0),
+                system_time_secs: None,
+                max_rss_bytes: Some(1024.0),
+                passes: vec![("parse_crate".to_owned(), 0.5)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let profiles = vec![
+            ActionProfile {
+                path: "a.json".to_owned(),
+                crate_name: Some("a".to_owned()),
+                wall_time_secs: 1.0,
+                user_time_secs: Some(0.5),
+                system_time_secs: Some(0.25),
+                max_rss_bytes: Some(100.0),
+                passes: vec![("parse_crate".to_owned(), 0.5)],
+                ..ActionProfile::default()
+            },
+            ActionProfile {
+                path: "b.json".to_owned(),
+                crate_name: None,
+                wall_time_secs: 2.0,
+                user_time_secs: Some(1.5),
+                system_time_secs: None,
+                max_rss_bytes: Some(300.0),
+                passes: vec![
+                    ("parse_crate".to_owned(), 0.25),
+                    ("codegen_crate".to_owned(), 1.0),
+                ],
+                ..ActionProfile::default()
+            },
+        ];
+
+        assert_eq!(
+            aggregate(profiles),
+            parse_json(
+                r#"
+                {
+                    "version": 1,
+                    "action_count": 2,
+                    "totals": {
+                        "wall_time_secs": 3,
+                        "user_time_secs": 2,
+                        "system_time_secs": 0.25
+                    },
+                    "max_rss_bytes": 300,
+                    "actions": [
+                        {
+                            "profile": "b.json",
+                            "crate_name": null,
+                            "exit_code": 0,
+                            "wall_time_secs": 2,
+                            "user_time_secs": 1.5,
+                            "system_time_secs": null,
+                            "max_rss_bytes": 300
+                        },
+                        {
+                            "profile": "a.json",
+                            "crate_name": "a",
+                            "exit_code": 0,
+                            "wall_time_secs": 1,
+                            "user_time_secs": 0.5,
+                            "system_time_secs": 0.25,
+                            "max_rss_bytes": 100
+                        }
+                    ],
+                    "passes": [
+                        {"pass": "codegen_crate", "time_secs": 1, "count": 1},
+                        {"pass": "parse_crate", "time_secs": 0.75, "count": 2}
+                    ]
+                }
+            "#
+            )
+        );
+    }
+}